@@ -0,0 +1,208 @@
+//! Automatic alignment of components, as Glyphs.app does it for composite
+//! glyphs.
+
+use kurbo::{Affine, Point, Vec2};
+
+use crate::{Component, Font, Layer};
+
+/// The component positions and advance width that automatic alignment gives
+/// a layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alignment {
+    /// The position of each component, in the order of
+    /// [`Layer::components`]. `None` for components that aren't aligned
+    /// automatically and keep their own position.
+    pub positions: Vec<Option<Point>>,
+    /// The advance width from the automatically aligned spacing components,
+    /// or `None` if there are none and the layer keeps its own width.
+    pub width: Option<f64>,
+}
+
+impl Component {
+    /// Whether the component is aligned automatically: unless disabled for
+    /// the component or the whole font, and only in layers without paths,
+    /// unless forced for the component.
+    fn is_auto_aligned(&self, font_disabled: bool, has_paths: bool) -> bool {
+        match self.alignment {
+            Some(-1) => false,
+            Some(1) => true,
+            _ => !font_disabled && !has_paths,
+        }
+    }
+}
+
+impl Layer {
+    /// Compute where automatic alignment puts the layer's components.
+    ///
+    /// The first component without an underscore anchor sits at the origin,
+    /// and each following one is placed after the advance width of the
+    /// previous ones. A mark, a glyph with an anchor like `_top`, is moved so
+    /// that this anchor sits on the matching `top` anchor of the components
+    /// before it, or on the anchor named by the component's `anchor`. The
+    /// anchors of an attached mark replace those of the same name, so marks
+    /// stack. Marks without a matching anchor keep their position, as do
+    /// components of missing glyphs.
+    ///
+    /// Components aren't aligned automatically if the font settings disable
+    /// it, if the layer has paths, or if the component disables it, unless
+    /// the component forces it.
+    pub fn automatic_alignment(&self, font: &Font) -> Alignment {
+        let font_disabled = font
+            .settings
+            .as_ref()
+            .is_some_and(|s| s.disables_automatic_alignment);
+        let has_paths = self.paths().next().is_some();
+
+        let mut anchors: Vec<(&str, Point)> = Vec::new();
+        let mut advance = None;
+        let mut positions = Vec::new();
+        for component in self.components() {
+            let base = font
+                .get_glyph(&component.reference)
+                .and_then(|glyph| glyph.get_layer(self.matching_layer_id(glyph)));
+            let auto_aligned = component.is_auto_aligned(font_disabled, has_paths);
+            let Some(base) = base else {
+                positions.push(None);
+                continue;
+            };
+
+            let linear = component.transform().with_translation(Vec2::ZERO);
+            let is_mark = base
+                .anchors
+                .iter()
+                .flatten()
+                .any(|a| a.name.starts_with('_'));
+            let aligned = match auto_aligned {
+                false => None,
+                true if is_mark => attachment(component, base, &anchors)
+                    .map(|(target, mark)| target - (linear * mark).to_vec2()),
+                true => Some(Point::new(advance.unwrap_or(0.0), 0.0)),
+            };
+            positions.push(aligned);
+
+            let pos = aligned.unwrap_or_else(|| component.pos.unwrap_or_default());
+            let transform = Affine::translate(pos.to_vec2()) * linear;
+            for anchor in base.anchors.iter().flatten() {
+                if anchor.name.starts_with('_') {
+                    continue;
+                }
+                let anchor_pos = transform * anchor.pos;
+                match anchors.iter_mut().find(|(name, _)| *name == anchor.name) {
+                    Some((_, existing)) => *existing = anchor_pos,
+                    None => anchors.push((&anchor.name, anchor_pos)),
+                }
+            }
+            if auto_aligned && !is_mark {
+                advance = Some(pos.x + base.width);
+            }
+        }
+        Alignment {
+            positions,
+            width: advance,
+        }
+    }
+
+    /// Move the components to where automatic alignment puts them and take
+    /// the advance width it gives, see [`Layer::automatic_alignment`].
+    /// Returns whether anything changed.
+    pub fn apply_automatic_alignment(&mut self, font: &Font) -> bool {
+        let alignment = self.automatic_alignment(font);
+        let mut changed = false;
+        for (component, position) in self.components_mut().zip(alignment.positions) {
+            let Some(position) = position else {
+                continue;
+            };
+            let pos = (position != Point::ZERO).then_some(position);
+            changed |= component.pos.unwrap_or_default() != position;
+            component.pos = pos;
+        }
+        if let Some(width) = alignment.width {
+            changed |= self.width != width;
+            self.width = width;
+        }
+        changed
+    }
+}
+
+/// The anchor a mark component attaches to and the mark's own anchor to
+/// attach with, before transforming the mark.
+fn attachment(
+    component: &Component,
+    mark: &Layer,
+    anchors: &[(&str, Point)],
+) -> Option<(Point, Point)> {
+    let target = |name: &str| {
+        anchors
+            .iter()
+            .find(|(anchor, _)| *anchor == name)
+            .map(|&(_, pos)| pos)
+    };
+    let mut mark_anchors = mark
+        .anchors
+        .iter()
+        .flatten()
+        .filter_map(|a| Some((a.name.strip_prefix('_')?, a.pos)));
+    match component.anchor.as_deref() {
+        // An explicit anchor like `top.alt` attaches with `_top.alt` or
+        // `_top`.
+        Some(explicit) => {
+            let target = target(explicit)?;
+            let base_name = explicit.split('.').next().unwrap_or(explicit);
+            let mark_anchors: Vec<_> = mark_anchors.collect();
+            mark_anchors
+                .iter()
+                .find(|(name, _)| *name == explicit)
+                .or_else(|| mark_anchors.iter().find(|(name, _)| *name == base_name))
+                .map(|&(_, pos)| (target, pos))
+        }
+        None => mark_anchors.find_map(|(name, pos)| Some((target(name)?, pos))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Point;
+
+    use crate::{Font, Shape};
+
+    #[test]
+    fn automatic_alignment() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let a_dieresis = font.get_glyph("Ä").unwrap();
+        let (layer, disabled) = (&a_dieresis.layers[0], &a_dieresis.layers[1]);
+
+        // The stored positions are the ones Glyphs.app computed.
+        let alignment = layer.automatic_alignment(&font);
+        assert_eq!(
+            alignment.positions,
+            [Some(Point::ZERO), Some(Point::new(-97.0, 135.0))]
+        );
+        assert_eq!(alignment.width, Some(layer.width));
+        let mut aligned = layer.clone();
+        assert!(!aligned.apply_automatic_alignment(&font));
+        assert_eq!(&aligned, layer);
+
+        let alignment = disabled.automatic_alignment(&font);
+        assert_eq!(alignment.positions[1], None);
+
+        // Moving the anchor moves the mark.
+        let a = font.get_glyph_mut("A").unwrap();
+        a.layers[0].set_anchor("top.alt", (20.0, 635.0));
+        a.layers[0].width += 10.0;
+        let mut layer = font.get_glyph("Ä").unwrap().layers[0].clone();
+        assert!(layer.apply_automatic_alignment(&font));
+        let Shape::Component(mark) = &layer.shapes[1] else {
+            panic!("expected a component");
+        };
+        assert_eq!(mark.pos, Some(Point::new(-87.0, 135.0)));
+        assert_eq!(layer.width, 469.0);
+
+        // Unless alignment is disabled for the font.
+        font.settings
+            .get_or_insert_with(Default::default)
+            .disables_automatic_alignment = true;
+        let alignment = layer.automatic_alignment(&font);
+        assert_eq!(alignment.positions, [None, None]);
+        assert_eq!(alignment.width, None);
+    }
+}
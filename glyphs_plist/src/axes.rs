@@ -0,0 +1,287 @@
+//! Adding, removing and reordering axes while keeping the locations of the
+//! masters, instances and special layers in step.
+
+use thiserror::Error;
+
+use crate::font::AxisRules;
+use crate::{Axis, Font};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AxisError {
+    #[error("no axis named {0:?}")]
+    UnknownAxis(String),
+    #[error("an axis named {0:?} or with its tag already exists")]
+    DuplicateAxis(String),
+    #[error("the new axis order must name every axis exactly once")]
+    InvalidOrder,
+    #[error("{owner} has {found} axis values, but the font has {expected} axes")]
+    LengthMismatch {
+        owner: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Font {
+    /// Check that every master, instance, brace layer and bracket layer has
+    /// one value per axis. Bracket layers may leave out trailing axes.
+    pub fn validate_axis_values(&self) -> Result<(), AxisError> {
+        let expected = self.axes.as_ref().map_or(0, Vec::len);
+        let check = |owner: &dyn Fn() -> String, found: usize, exact: bool| {
+            if found > expected || (exact && found != expected) {
+                return Err(AxisError::LengthMismatch {
+                    owner: owner(),
+                    expected,
+                    found,
+                });
+            }
+            Ok(())
+        };
+
+        for master in &self.font_master {
+            if let Some(values) = &master.axes_values {
+                check(&|| format!("master {:?}", master.id), values.len(), true)?;
+            }
+        }
+        for instance in self.instances.iter().flatten() {
+            if let Some(values) = &instance.axes_values {
+                check(
+                    &|| format!("instance {:?}", instance.name),
+                    values.len(),
+                    true,
+                )?;
+            }
+        }
+        for glyph in &self.glyphs {
+            for layer in &glyph.layers {
+                let Some(attr) = &layer.attr else {
+                    continue;
+                };
+                let owner = || format!("layer {:?} of glyph {:?}", layer.layer_id, glyph.glyphname);
+                if let Some(coordinates) = &attr.coordinates {
+                    check(&owner, coordinates.len(), true)?;
+                }
+                if let Some(rules) = &attr.axis_rules {
+                    check(&owner, rules.len(), false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Append an axis to the font, placing every master, instance and brace
+    /// layer at `value` on it. Bracket layers get no condition on it.
+    pub fn add_axis(&mut self, axis: Axis, value: f64) -> Result<(), AxisError> {
+        if self
+            .axes
+            .iter()
+            .flatten()
+            .any(|a| a.name == axis.name || a.tag == axis.tag)
+        {
+            return Err(AxisError::DuplicateAxis(axis.name));
+        }
+        self.validate_axis_values()?;
+
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        self.for_each_location(|values| values.push(value));
+        self.for_each_axis_rules(|rules| {
+            if rules.len() == axis_count {
+                rules.push(AxisRules {
+                    min: None,
+                    max: None,
+                });
+            }
+        });
+        self.axes.get_or_insert_with(Vec::new).push(axis);
+        debug_assert_eq!(self.validate_axis_values(), Ok(()));
+        Ok(())
+    }
+
+    /// Remove an axis from the font, along with its value in every location.
+    pub fn remove_axis(&mut self, name: &str) -> Result<Axis, AxisError> {
+        let index = self.axis_index(name)?;
+        self.validate_axis_values()?;
+
+        self.for_each_location(|values| {
+            values.remove(index);
+        });
+        self.for_each_axis_rules(|rules| {
+            if index < rules.len() {
+                rules.remove(index);
+            }
+        });
+        let axis = self.axes.as_mut().unwrap().remove(index);
+        debug_assert_eq!(self.validate_axis_values(), Ok(()));
+        Ok(axis)
+    }
+
+    /// Reorder the axes of the font to the given order of names, which must
+    /// name every axis once, and every location with them.
+    pub fn reorder_axes(&mut self, order: &[&str]) -> Result<(), AxisError> {
+        let permutation = order
+            .iter()
+            .map(|name| self.axis_index(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let mut seen = permutation.clone();
+        seen.sort_unstable();
+        seen.dedup();
+        if seen.len() != order.len() || order.len() != axis_count {
+            return Err(AxisError::InvalidOrder);
+        }
+        self.validate_axis_values()?;
+
+        self.for_each_location(|values| reorder(values, &permutation));
+        self.for_each_axis_rules(|rules| {
+            rules.resize(
+                axis_count,
+                AxisRules {
+                    min: None,
+                    max: None,
+                },
+            );
+            reorder(rules, &permutation);
+        });
+        reorder(self.axes.as_mut().unwrap(), &permutation);
+        debug_assert_eq!(self.validate_axis_values(), Ok(()));
+        Ok(())
+    }
+
+    fn axis_index(&self, name: &str) -> Result<usize, AxisError> {
+        self.axes
+            .iter()
+            .flatten()
+            .position(|a| a.name == name)
+            .ok_or_else(|| AxisError::UnknownAxis(name.to_string()))
+    }
+
+    /// Call `f` with the axis values of every master and instance and the
+    /// coordinates of every brace layer.
+    fn for_each_location(&mut self, mut f: impl FnMut(&mut Vec<f64>)) {
+        let masters = self.font_master.iter_mut().map(|m| &mut m.axes_values);
+        let instances = self
+            .instances
+            .iter_mut()
+            .flatten()
+            .map(|i| &mut i.axes_values);
+        let layers = self
+            .glyphs
+            .iter_mut()
+            .flat_map(|g| &mut g.layers)
+            .filter_map(|l| l.attr.as_mut())
+            .map(|a| &mut a.coordinates);
+        masters
+            .chain(instances)
+            .chain(layers)
+            .flatten()
+            .for_each(&mut f);
+    }
+
+    fn for_each_axis_rules(&mut self, f: impl FnMut(&mut Vec<AxisRules>)) {
+        self.glyphs
+            .iter_mut()
+            .flat_map(|g| &mut g.layers)
+            .filter_map(|l| l.attr.as_mut()?.axis_rules.as_mut())
+            .for_each(f);
+    }
+}
+
+/// Reorder values so that the value at `permutation[i]` ends up at `i`.
+fn reorder<T: Clone>(values: &mut Vec<T>, permutation: &[usize]) {
+    *values = permutation.iter().map(|&i| values[i].clone()).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AxisError;
+    use crate::{Axis, Font};
+
+    fn axis(name: &str, tag: &str) -> Axis {
+        Axis {
+            name: name.into(),
+            tag: tag.into(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn add_remove_and_reorder_axes() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let original = font.clone();
+
+        font.add_axis(axis("Slant", "slnt"), -5.0).unwrap();
+        assert_eq!(font.axes.as_ref().unwrap().len(), 4);
+        assert_eq!(
+            font.font_master[0].axes_values.as_deref(),
+            Some(&[100.0, 0.0, 0.0, -5.0][..])
+        );
+        let smily = font.get_glyph("Smily").unwrap();
+        assert!(smily
+            .layers
+            .iter()
+            .filter_map(|l| l.coordinates())
+            .all(|c| c.len() == 4 && c[3] == -5.0));
+        font.remove_axis("Slant").unwrap();
+        assert_eq!(font, original);
+
+        font.reorder_axes(&["Optical size", "Weight", "Width"])
+            .unwrap();
+        let names = font
+            .axes
+            .iter()
+            .flatten()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Optical size", "Weight", "Width"]);
+        assert_eq!(
+            font.font_master[2].axes_values.as_deref(),
+            Some(&[0.0, 100.0, 150.0][..])
+        );
+        let b = font.get_glyph("B").unwrap();
+        let rules = b
+            .layers
+            .iter()
+            .find_map(|l| l.attr.as_ref()?.axis_rules.as_ref())
+            .unwrap();
+        assert_eq!(rules[1].min, Some(450.0));
+
+        font.reorder_axes(&["Weight", "Width", "Optical size"])
+            .unwrap();
+        assert_eq!(font, original);
+    }
+
+    #[test]
+    fn axis_errors() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        assert_eq!(
+            font.add_axis(axis("Weight", "XXXX"), 0.0),
+            Err(AxisError::DuplicateAxis("Weight".into()))
+        );
+        assert_eq!(
+            font.remove_axis("Slant"),
+            Err(AxisError::UnknownAxis("Slant".into()))
+        );
+        assert_eq!(
+            font.reorder_axes(&["Weight", "Weight", "Width"]),
+            Err(AxisError::InvalidOrder)
+        );
+        assert_eq!(
+            font.reorder_axes(&["Weight", "Width"]),
+            Err(AxisError::InvalidOrder)
+        );
+
+        font.instances.as_mut().unwrap()[0].axes_values = Some(vec![0.0]);
+        let name = font.instances.as_ref().unwrap()[0].name.clone();
+        let original = font.clone();
+        assert_eq!(
+            font.remove_axis("Width"),
+            Err(AxisError::LengthMismatch {
+                owner: format!("instance {name:?}"),
+                expected: 3,
+                found: 1,
+            })
+        );
+        assert_eq!(font, original);
+    }
+}
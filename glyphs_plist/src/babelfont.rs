@@ -0,0 +1,296 @@
+//! Export to the JSON format of babelfont, which web-based font tools read.
+//!
+//! Locations are keyed by axis tag. Paths are written as a string of
+//! `x y type` triples with the node types of Glyphs files, components as the
+//! referenced glyph name and an affine transform, and kerning groups without
+//! the Glyphs prefixes, as `@name` in the kerning pairs.
+
+use serde_json::{json, Map, Value};
+
+use crate::kerning::{FIRST_GROUP_PREFIX, SECOND_GROUP_PREFIX};
+use crate::{Font, FontMaster, Layer, MetricType, Path, Shape};
+
+impl Font {
+    /// The font in babelfont's JSON format.
+    pub fn to_babelfont_json(&self) -> Value {
+        let (sources, axes) = match self.design_space() {
+            Some((sources, _, axes)) => (sources, axes),
+            None => (Vec::new(), Vec::new()),
+        };
+        let tags: Vec<&str> = axes.iter().map(|a| a.tag.as_str()).collect();
+        let location = |values: &[f64]| -> Value {
+            tags.iter()
+                .zip(values)
+                .map(|(tag, value)| (tag.to_string(), json!(value)))
+                .collect::<Map<_, _>>()
+                .into()
+        };
+
+        let axes: Vec<Value> = axes
+            .iter()
+            .map(|axis| {
+                json!({
+                    "name": axis.name,
+                    "tag": axis.tag,
+                    "min": axis.min,
+                    "default": axis.default,
+                    "max": axis.max,
+                    "hidden": axis.hidden,
+                })
+            })
+            .collect();
+        let masters: Vec<Value> = self
+            .font_master
+            .iter()
+            .zip(&sources)
+            .map(|(master, source)| {
+                json!({
+                    "id": master.id.as_str(),
+                    "name": master.name,
+                    "location": location(&source.location),
+                    "metrics": self.babelfont_metrics(master),
+                    "kerning": self.babelfont_kerning(&master.id),
+                })
+            })
+            .collect();
+        let instances: Vec<Value> = self
+            .instances
+            .iter()
+            .flatten()
+            .map(|instance| {
+                json!({
+                    "name": instance.name,
+                    "location": location(instance.axes_values.as_deref().unwrap_or_default()),
+                    "exported": instance.exports,
+                })
+            })
+            .collect();
+        let glyphs: Vec<Value> = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let mut value = json!({
+                    "name": glyph.glyphname.as_str(),
+                    "codepoints": glyph
+                        .unicode
+                        .iter()
+                        .flat_map(|u| u.iter())
+                        .map(|c| c as u32)
+                        .collect::<Vec<_>>(),
+                    "exported": glyph.export,
+                    "layers": glyph
+                        .layers
+                        .iter()
+                        .map(|layer| babelfont_layer(layer, &location))
+                        .collect::<Vec<_>>(),
+                });
+                if let Some(category) = &glyph.category {
+                    value["category"] = json!(category);
+                }
+                value
+            })
+            .collect();
+
+        let mut groups = [Map::new(), Map::new()];
+        for (name, members) in self.kern_groups() {
+            if let Some(group) = name.strip_prefix(FIRST_GROUP_PREFIX) {
+                groups[0].insert(group.to_string(), json!(members));
+            } else if let Some(group) = name.strip_prefix(SECOND_GROUP_PREFIX) {
+                groups[1].insert(group.to_string(), json!(members));
+            }
+        }
+        let [first_kern_groups, second_kern_groups] = groups;
+
+        json!({
+            "upm": self.units_per_em,
+            "version": [self.version_major, self.version_minor],
+            "date": self.date,
+            "names": { "familyName": { "dflt": self.family_name } },
+            "axes": axes,
+            "masters": masters,
+            "instances": instances,
+            "glyphs": glyphs,
+            "first_kern_groups": first_kern_groups,
+            "second_kern_groups": second_kern_groups,
+            "features": self.babelfont_features(),
+        })
+    }
+
+    fn babelfont_metrics(&self, master: &FontMaster) -> Value {
+        master
+            .iter_metrics(self)
+            .filter(|(metric, _)| metric.filter.is_none())
+            .filter_map(|(metric, value)| {
+                let name = match &metric.r#type {
+                    Some(MetricType::Ascender) => "ascender".to_string(),
+                    Some(MetricType::Descender) => "descender".to_string(),
+                    Some(MetricType::XHeight) => "xHeight".to_string(),
+                    Some(MetricType::CapHeight) => "capHeight".to_string(),
+                    Some(MetricType::ItalicAngle) => "italicAngle".to_string(),
+                    Some(MetricType::Baseline) => return None,
+                    Some(other) => other.to_string(),
+                    None => metric.name.clone()?,
+                };
+                Some((name, json!(value.pos)))
+            })
+            .collect::<Map<_, _>>()
+            .into()
+    }
+
+    fn babelfont_kerning(&self, master_id: &str) -> Value {
+        let side = |side: &str| match side
+            .strip_prefix(FIRST_GROUP_PREFIX)
+            .or_else(|| side.strip_prefix(SECOND_GROUP_PREFIX))
+        {
+            Some(group) => format!("@{group}"),
+            None => side.to_string(),
+        };
+        self.kerning_pairs(master_id)
+            .map(|(first, second, value)| {
+                (format!("{}//{}", side(first), side(second)), json!(value))
+            })
+            .collect::<Map<_, _>>()
+            .into()
+    }
+
+    fn babelfont_features(&self) -> Value {
+        let code = |name: &str, code: &str| (name.to_string(), json!(code));
+        json!({
+            "prefixes": self
+                .feature_prefixes
+                .iter()
+                .flatten()
+                .filter(|p| !p.disabled)
+                .map(|p| code(&p.name, &p.code))
+                .collect::<Map<_, _>>(),
+            "classes": self
+                .classes
+                .iter()
+                .flatten()
+                .filter(|c| !c.disabled)
+                .map(|c| code(&c.name, &c.code))
+                .collect::<Map<_, _>>(),
+            "features": self
+                .features
+                .iter()
+                .flatten()
+                .filter(|f| !f.disabled)
+                .map(|f| json!([f.tag, f.code]))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn babelfont_layer(layer: &Layer, location: &impl Fn(&[f64]) -> Value) -> Value {
+    let shapes: Vec<Value> = layer
+        .shapes
+        .iter()
+        .map(|shape| match shape {
+            Shape::Path(path) => json!({
+                "nodes": babelfont_nodes(path),
+                "closed": path.closed,
+            }),
+            Shape::Component(component) => json!({
+                "ref": component.reference,
+                "transform": component.transform().as_coeffs(),
+            }),
+        })
+        .collect();
+    let anchors: Vec<Value> = layer
+        .anchors
+        .iter()
+        .flatten()
+        .map(|a| json!({ "name": a.name, "x": a.pos.x, "y": a.pos.y }))
+        .collect();
+
+    let mut value = json!({
+        "id": layer.layer_id.as_str(),
+        "width": layer.width,
+        "shapes": shapes,
+        "anchors": anchors,
+    });
+    if let Some(name) = &layer.name {
+        value["name"] = json!(name);
+    }
+    if let Some(master_id) = &layer.associated_master_id {
+        value["master"] = json!(master_id.as_str());
+    }
+    if let Some(coordinates) = layer.coordinates() {
+        value["location"] = location(coordinates);
+    }
+    value
+}
+
+fn babelfont_nodes(path: &Path) -> String {
+    path.nodes
+        .iter()
+        .map(|node| {
+            format!(
+                "{} {} {}",
+                node.pt.x,
+                node.pt.y,
+                node.node_type.glyphs_str()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let json = font.to_babelfont_json();
+
+        assert_eq!(json["upm"], font.units_per_em);
+        assert_eq!(json["names"]["familyName"]["dflt"], font.family_name);
+        let axes = json["axes"].as_array().unwrap();
+        assert_eq!(axes.len(), font.axes.as_ref().unwrap().len());
+        let tag = axes[0]["tag"].as_str().unwrap();
+        assert_eq!(
+            json["masters"][0]["location"][tag],
+            font.font_master[0].axes_values.as_ref().unwrap()[0]
+        );
+
+        let glyph = json["glyphs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|g| g["name"] == "A")
+            .unwrap();
+        assert_eq!(glyph["codepoints"], json!([65, 97]));
+        let layer = font.get_glyph("A").unwrap().master_layer("m01").unwrap();
+        let exported = glyph["layers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|l| l["id"] == "m01")
+            .unwrap();
+        assert_eq!(exported["width"], layer.width);
+        let Shape::Path(path) = &layer.shapes[0] else {
+            panic!("expected a path");
+        };
+        assert_eq!(
+            exported["shapes"][0]["nodes"]
+                .as_str()
+                .unwrap()
+                .split(' ')
+                .count(),
+            path.nodes.len() * 3
+        );
+    }
+
+    #[test]
+    fn kerning() {
+        let mut font = Font::default();
+        font.glyphs[0].kern_left = Some(norad::Name::new("space").unwrap());
+        font.set_kerning("m01", "space", "@MMK_R_space", 20.0)
+            .unwrap();
+        let json = font.to_babelfont_json();
+        assert_eq!(json["second_kern_groups"]["space"], json!(["space"]));
+        assert_eq!(json["masters"][0]["kerning"]["space//@space"], 20.0);
+    }
+}
@@ -0,0 +1,243 @@
+//! Typed access to userData and custom parameter values, with conversions
+//! that applications register for the keys they know.
+
+use std::any::Any;
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{CustomParameter, Plist};
+
+/// Conversions between plists and typed values, registered by key.
+///
+/// Keys are matched exactly, or by prefix with a pattern ending in `*`, like
+/// `com.github.googlefonts.*`; the longest matching pattern wins. Values are
+/// only converted when accessed through the registry, so the model keeps the
+/// plists of all keys and the ones without a codec are written back as they
+/// were read.
+#[derive(Default)]
+pub struct Codecs {
+    codecs: HashMap<String, Box<dyn Any + Send + Sync>>,
+}
+
+type DecodeFn<T> = dyn Fn(&Plist) -> Result<T, String> + Send + Sync;
+type EncodeFn<T> = dyn Fn(&T) -> Plist + Send + Sync;
+
+struct Codec<T> {
+    decode: Box<DecodeFn<T>>,
+    encode: Box<EncodeFn<T>>,
+}
+
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("no codec for {0} with the requested type")]
+    NoCodec(String),
+    #[error("bad value for {key}: {message}")]
+    Invalid { key: String, message: String },
+}
+
+impl Codecs {
+    pub fn new() -> Codecs {
+        Codecs::default()
+    }
+
+    /// Register the conversions for the values of `key`, replacing any
+    /// registered for the same key before.
+    pub fn register<T: 'static>(
+        &mut self,
+        key: impl Into<String>,
+        decode: impl Fn(&Plist) -> Result<T, String> + Send + Sync + 'static,
+        encode: impl Fn(&T) -> Plist + Send + Sync + 'static,
+    ) {
+        let codec = Codec {
+            decode: Box::new(decode),
+            encode: Box::new(encode),
+        };
+        self.codecs.insert(key.into(), Box::new(codec));
+    }
+
+    /// Convert the value of `key` to the type it was registered with.
+    pub fn decode<T: 'static>(&self, key: &str, value: &Plist) -> Result<T, CodecError> {
+        (self.codec::<T>(key)?.decode)(value).map_err(|message| CodecError::Invalid {
+            key: key.to_string(),
+            message,
+        })
+    }
+
+    /// Convert a typed value of `key` to a plist.
+    pub fn encode<T: 'static>(&self, key: &str, value: &T) -> Result<Plist, CodecError> {
+        Ok((self.codec::<T>(key)?.encode)(value))
+    }
+
+    /// The typed value of `key` in a userData map, `None` if it has no value.
+    pub fn get<T: 'static>(
+        &self,
+        user_data: &HashMap<String, Plist>,
+        key: &str,
+    ) -> Option<Result<T, CodecError>> {
+        user_data.get(key).map(|value| self.decode(key, value))
+    }
+
+    /// Set the value of `key` in a userData map from a typed value.
+    pub fn set<T: 'static>(
+        &self,
+        user_data: &mut HashMap<String, Plist>,
+        key: &str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        user_data.insert(key.to_string(), self.encode(key, value)?);
+        Ok(())
+    }
+
+    /// The typed value of the first enabled custom parameter called `name`,
+    /// `None` if there is none.
+    pub fn parameter<'a, T: 'static>(
+        &self,
+        parameters: impl IntoIterator<Item = &'a CustomParameter>,
+        name: &str,
+    ) -> Option<Result<T, CodecError>> {
+        parameters
+            .into_iter()
+            .find(|p| p.name == name && !p.disabled)
+            .map(|p| self.decode(name, &p.value))
+    }
+
+    fn codec<T: 'static>(&self, key: &str) -> Result<&Codec<T>, CodecError> {
+        let codec = match self.codecs.get(key) {
+            Some(codec) => Some(codec),
+            None => self
+                .codecs
+                .iter()
+                .filter_map(|(pattern, codec)| {
+                    let prefix = pattern.strip_suffix('*')?;
+                    key.starts_with(prefix).then_some((prefix.len(), codec))
+                })
+                .max_by_key(|(len, _)| *len)
+                .map(|(_, codec)| codec),
+        };
+        codec
+            .and_then(|codec| codec.downcast_ref())
+            .ok_or_else(|| CodecError::NoCodec(key.to_string()))
+    }
+}
+
+impl std::fmt::Debug for Codecs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.codecs.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Font;
+
+    #[derive(Debug, PartialEq)]
+    struct Ratio(f64);
+
+    fn codecs() -> Codecs {
+        let mut codecs = Codecs::new();
+        codecs.register(
+            "com.example.*",
+            |plist| plist.as_f64().map(Ratio).ok_or("expected a number".into()),
+            |ratio: &Ratio| Plist::Float(ratio.0),
+        );
+        codecs.register(
+            "com.example.name",
+            |plist| {
+                plist
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or("expected text".into())
+            },
+            |name: &String| name.clone().into(),
+        );
+        codecs
+    }
+
+    #[test]
+    fn user_data() {
+        let codecs = codecs();
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let glyph = &mut font.glyphs[0];
+        glyph
+            .user_data
+            .insert("org.other".into(), Plist::Integer(1));
+
+        codecs
+            .set(&mut glyph.user_data, "com.example.ratio", &Ratio(0.5))
+            .unwrap();
+        codecs
+            .set(
+                &mut glyph.user_data,
+                "com.example.name",
+                &"wide".to_string(),
+            )
+            .unwrap();
+        assert!(matches!(
+            codecs.set(&mut glyph.user_data, "org.other", &Ratio(1.0)),
+            Err(CodecError::NoCodec(_))
+        ));
+
+        let saved: Font = font.to_plist_string().parse().unwrap();
+        let user_data = &saved.glyphs[0].user_data;
+        assert_eq!(
+            codecs
+                .get::<Ratio>(user_data, "com.example.ratio")
+                .unwrap()
+                .unwrap(),
+            Ratio(0.5)
+        );
+        assert_eq!(
+            codecs
+                .get::<String>(user_data, "com.example.name")
+                .unwrap()
+                .unwrap(),
+            "wide"
+        );
+        assert!(codecs
+            .get::<Ratio>(user_data, "com.example.other")
+            .is_none());
+        // The exact key is registered with another type.
+        assert!(matches!(
+            codecs.get::<Ratio>(user_data, "com.example.name"),
+            Some(Err(CodecError::NoCodec(_)))
+        ));
+        assert!(matches!(
+            codecs.decode::<Ratio>("com.example.ratio", &Plist::String("x".into())),
+            Err(CodecError::Invalid { .. })
+        ));
+        assert!(matches!(
+            codecs.get::<Ratio>(user_data, "org.other"),
+            Some(Err(CodecError::NoCodec(_)))
+        ));
+        assert_eq!(user_data["org.other"], Plist::Integer(1));
+    }
+
+    #[test]
+    fn custom_parameters() {
+        let codecs = codecs();
+        let parameters = vec![
+            CustomParameter {
+                name: "com.example.ratio".into(),
+                value: Plist::Float(2.0),
+                disabled: true,
+            },
+            CustomParameter {
+                name: "com.example.ratio".into(),
+                value: Plist::Float(1.5),
+                disabled: false,
+            },
+        ];
+        assert_eq!(
+            codecs
+                .parameter::<Ratio>(&parameters, "com.example.ratio")
+                .unwrap()
+                .unwrap(),
+            Ratio(1.5)
+        );
+        assert!(codecs
+            .parameter::<Ratio>(&parameters, "com.example.missing")
+            .is_none());
+    }
+}
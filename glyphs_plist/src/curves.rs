@@ -0,0 +1,551 @@
+//! Conversion between cubic and quadratic curves.
+
+use kurbo::{cubics_to_quadratic_splines, CubicBez, Point, QuadBez};
+
+use crate::outline::{is_smooth, segment_node_type};
+use crate::{Font, Glyph, Layer, Node, NodeType, Path};
+
+/// A run of off-curve points followed by the on-curve node ending it.
+pub(crate) struct Segment {
+    pub(crate) off_curves: Vec<Point>,
+    pub(crate) end: Node,
+}
+
+impl Segment {
+    fn is_cubic(&self) -> bool {
+        matches!(self.end.node_type, NodeType::Curve | NodeType::CurveSmooth)
+            && self.off_curves.len() >= 2
+    }
+
+    /// Whether the segment is a quadratic curve. Like in
+    /// [`Path::to_bezpath`], a curve with a single off-curve node counts as
+    /// quadratic.
+    fn is_quadratic(&self) -> bool {
+        match self.end.node_type {
+            NodeType::QCurve | NodeType::QCurveSmooth => !self.off_curves.is_empty(),
+            NodeType::Curve | NodeType::CurveSmooth => self.off_curves.len() == 1,
+            _ => false,
+        }
+    }
+}
+
+/// The nodes of a path split into segments, in drawing order.
+pub(crate) struct Segments {
+    /// The first node of an open path.
+    pub(crate) start: Option<Node>,
+    pub(crate) segments: Vec<Segment>,
+}
+
+impl Segments {
+    pub(crate) fn new(path: &Path) -> Self {
+        let len = path.nodes.len();
+        let (start, order): (Option<Node>, Vec<usize>) = if !path.closed {
+            (path.nodes.first().cloned(), (1..len).collect())
+        } else {
+            match path
+                .nodes
+                .iter()
+                .rposition(|n| n.node_type != NodeType::OffCurve)
+            {
+                Some(i) => (None, (i + 1..i + 1 + len).map(|j| j % len).collect()),
+                None if len > 0 => {
+                    // A closed quadratic path without on-curve nodes; make the
+                    // implied point between the last and first node explicit.
+                    let segment = Segment {
+                        off_curves: path.nodes.iter().map(|n| n.pt).collect(),
+                        end: Node {
+                            pt: path.nodes[len - 1].pt.midpoint(path.nodes[0].pt),
+                            node_type: NodeType::QCurveSmooth,
+                        },
+                    };
+                    return Segments {
+                        start: None,
+                        segments: vec![segment],
+                    };
+                }
+                None => (None, Vec::new()),
+            }
+        };
+
+        let mut segments = Vec::new();
+        let mut off_curves = Vec::new();
+        for node in order.into_iter().map(|i| &path.nodes[i]) {
+            if node.node_type == NodeType::OffCurve {
+                off_curves.push(node.pt);
+            } else {
+                segments.push(Segment {
+                    off_curves: std::mem::take(&mut off_curves),
+                    end: node.clone(),
+                });
+            }
+        }
+        Segments { start, segments }
+    }
+
+    /// The on-curve point the segment at `index` starts from.
+    pub(crate) fn start_of(&self, index: usize) -> Point {
+        match index {
+            0 => match &self.start {
+                Some(start) => start.pt,
+                None => self.segments[self.segments.len() - 1].end.pt,
+            },
+            _ => self.segments[index - 1].end.pt,
+        }
+    }
+
+    /// Split cubic curves with more than two off-curve nodes into curves
+    /// with two, see [`split_super_bezier`].
+    fn split_super_beziers(&mut self) {
+        if !self
+            .segments
+            .iter()
+            .any(|s| s.is_cubic() && s.off_curves.len() > 2)
+        {
+            return;
+        }
+        let mut segments = Vec::with_capacity(self.segments.len());
+        for i in 0..self.segments.len() {
+            let segment = &self.segments[i];
+            if !segment.is_cubic() || segment.off_curves.len() == 2 {
+                segments.push(Segment {
+                    off_curves: segment.off_curves.clone(),
+                    end: segment.end.clone(),
+                });
+                continue;
+            }
+            let cubics = split_super_bezier(self.start_of(i), &segment.off_curves, segment.end.pt);
+            let last = cubics.len() - 1;
+            for (j, cubic) in cubics.into_iter().enumerate() {
+                let end = match j == last {
+                    true => segment.end.clone(),
+                    // The curves meet smoothly.
+                    false => Node {
+                        pt: cubic.p3,
+                        node_type: NodeType::CurveSmooth,
+                    },
+                };
+                segments.push(Segment {
+                    off_curves: vec![cubic.p1, cubic.p2],
+                    end,
+                });
+            }
+        }
+        self.segments = segments;
+    }
+
+    /// The nodes of the segments, with a closed path ending on its starting
+    /// node like Glyphs.app stores it.
+    pub(crate) fn into_nodes(self) -> Vec<Node> {
+        let mut nodes: Vec<Node> = self.start.into_iter().collect();
+        for segment in self.segments {
+            push_off_curves(&mut nodes, &segment.off_curves);
+            nodes.push(segment.end);
+        }
+        nodes
+    }
+}
+
+impl Path {
+    /// Convert the cubic curves of the path into quadratic ones, deviating
+    /// by at most `max_err` font units from the original.
+    ///
+    /// Each cubic curve becomes a `QCurve` node, or `QCurveSmooth` if it was
+    /// smooth, preceded by as many off-curve nodes as the approximation
+    /// needs. Lines and quadratic curves are kept as they are.
+    pub fn to_quadratic(&mut self, max_err: f64) {
+        paths_to_quadratic(&mut [self], max_err);
+    }
+
+    /// Convert the quadratic curves of the path into cubic ones.
+    ///
+    /// The conversion is exact: a quadratic curve with several off-curve
+    /// nodes becomes a run of cubic curves joined by smooth nodes at its
+    /// implied on-curve points. Lines and cubic curves are kept as they are.
+    pub fn to_cubic(&mut self) {
+        let segments = Segments::new(self);
+        if !segments.segments.iter().any(Segment::is_quadratic) {
+            return;
+        }
+
+        let mut nodes: Vec<Node> = segments.start.clone().into_iter().collect();
+        for (i, segment) in segments.segments.iter().enumerate() {
+            if !segment.is_quadratic() {
+                push_off_curves(&mut nodes, &segment.off_curves);
+                nodes.push(segment.end.clone());
+                continue;
+            }
+            let mut start = segments.start_of(i);
+            let off_curves = &segment.off_curves;
+            for (j, &control) in off_curves.iter().enumerate() {
+                let (end, node_type) = match off_curves.get(j + 1) {
+                    Some(next) => (control.midpoint(*next), NodeType::CurveSmooth),
+                    None => (
+                        segment.end.pt,
+                        segment_node_type(NodeType::Curve, is_smooth(segment.end.node_type)),
+                    ),
+                };
+                let cubic = QuadBez::new(start, control, end).raise();
+                push_off_curves(&mut nodes, &[cubic.p1, cubic.p2]);
+                nodes.push(Node { pt: end, node_type });
+                start = end;
+            }
+        }
+        self.nodes = nodes;
+    }
+}
+
+impl Layer {
+    /// Convert the cubic curves of the layer's paths into quadratic ones, see
+    /// [`Path::to_quadratic`].
+    pub fn to_quadratic(&mut self, max_err: f64) {
+        for path in self.paths_mut() {
+            path.to_quadratic(max_err);
+        }
+    }
+
+    /// Convert the quadratic curves of the layer's paths into cubic ones,
+    /// see [`Path::to_cubic`].
+    pub fn to_cubic(&mut self) {
+        for path in self.paths_mut() {
+            path.to_cubic();
+        }
+    }
+}
+
+impl Font {
+    /// Convert the cubic curves of all glyphs into quadratic ones, deviating
+    /// by at most `max_err` font units from the original.
+    ///
+    /// Unlike converting layer by layer, the layers of a glyph stay
+    /// compatible for interpolation: each curve is split into the same
+    /// number of quadratic segments in every layer whose matching path has
+    /// the same structure.
+    pub fn to_quadratic(&mut self, max_err: f64) {
+        for glyph in &mut self.glyphs {
+            glyph_to_quadratic(glyph, max_err);
+        }
+    }
+
+    /// Convert the quadratic curves of all glyphs into cubic ones, see
+    /// [`Path::to_cubic`].
+    pub fn to_cubic(&mut self) {
+        for layer in self.glyphs.iter_mut().flat_map(|g| &mut g.layers) {
+            layer.to_cubic();
+        }
+    }
+}
+
+fn glyph_to_quadratic(glyph: &mut Glyph, max_err: f64) {
+    let path_count = glyph
+        .layers
+        .iter()
+        .map(|l| l.paths().count())
+        .max()
+        .unwrap_or(0);
+    for i in 0..path_count {
+        let mut groups: Vec<Vec<&mut Path>> = Vec::new();
+        for path in glyph.layers.iter_mut().filter_map(|l| l.paths_mut().nth(i)) {
+            match groups
+                .iter_mut()
+                .find(|group| is_compatible(group[0], path))
+            {
+                Some(group) => group.push(path),
+                None => groups.push(vec![path]),
+            }
+        }
+        for mut group in groups {
+            paths_to_quadratic(&mut group, max_err);
+        }
+    }
+}
+
+/// Whether two paths have the same sequence of node types, ignoring
+/// smoothness.
+fn is_compatible(a: &Path, b: &Path) -> bool {
+    a.closed == b.closed
+        && a.nodes.len() == b.nodes.len()
+        && a.nodes.iter().zip(&b.nodes).all(|(a, b)| {
+            segment_node_type(a.node_type, false) == segment_node_type(b.node_type, false)
+        })
+}
+
+/// Convert compatible paths to quadratic curves together, so that each cubic
+/// curve is split the same way in all of them.
+///
+/// A curve that can't be approximated within `max_err` is left cubic.
+fn paths_to_quadratic(paths: &mut [&mut Path], max_err: f64) {
+    let mut all_segments: Vec<Segments> = paths.iter().map(|p| Segments::new(p)).collect();
+    // Compatible paths split the same way, as the number of curves only
+    // depends on the number of off-curve nodes.
+    for segments in &mut all_segments {
+        segments.split_super_beziers();
+    }
+    let Some(first) = all_segments.first() else {
+        return;
+    };
+    let has_curves = first
+        .segments
+        .iter()
+        .any(|s| matches!(s.end.node_type, NodeType::Curve | NodeType::CurveSmooth));
+    if !has_curves {
+        return;
+    }
+
+    for i in 0..first.segments.len() {
+        let cubics: Option<Vec<CubicBez>> = all_segments
+            .iter()
+            .map(|segments| {
+                let segment = segments.segments.get(i).filter(|s| s.is_cubic())?;
+                let [c1, c2] = segment.off_curves[..] else {
+                    return None;
+                };
+                Some(CubicBez::new(segments.start_of(i), c1, c2, segment.end.pt))
+            })
+            .collect();
+        let splines = cubics.and_then(|cubics| cubics_to_quadratic_splines(&cubics, max_err));
+        for (j, segments) in all_segments.iter_mut().enumerate() {
+            let Some(segment) = segments.segments.get_mut(i) else {
+                continue;
+            };
+            if let Some(splines) = &splines {
+                let points = splines[j].points();
+                segment.off_curves = points[1..points.len() - 1].to_vec();
+            } else if !segment.is_quadratic() {
+                continue;
+            }
+            let smooth = is_smooth(segment.end.node_type);
+            segment.end.node_type = segment_node_type(NodeType::QCurve, smooth);
+        }
+    }
+
+    for (path, segments) in paths.iter_mut().zip(all_segments) {
+        path.nodes = segments.into_nodes();
+    }
+}
+
+/// Split a cubic curve with any number of off-curve points, like Glyphs.app
+/// allows, into curves with two each, the way fontTools decomposes such
+/// "super Bézier" curves. The curves meet smoothly.
+///
+/// There have to be at least two off-curve points.
+pub(crate) fn split_super_bezier(start: Point, off_curves: &[Point], end: Point) -> Vec<CubicBez> {
+    let n = off_curves.len();
+    let mut cubics = Vec::new();
+    let (mut from, mut c1, mut c2) = (start, off_curves[0], None);
+    for i in 2..=n {
+        let divisions = i.min(3).min(n + 2 - i);
+        for j in 1..divisions {
+            let point = off_curves[i - 2].lerp(off_curves[i - 1], j as f64 / divisions as f64);
+            match c2.take() {
+                None => c2 = Some(point),
+                Some(c2) => {
+                    let on = c2.midpoint(point);
+                    cubics.push(CubicBez::new(from, c1, c2, on));
+                    (from, c1) = (on, point);
+                }
+            }
+        }
+    }
+    cubics.push(CubicBez::new(from, c1, off_curves[n - 1], end));
+    cubics
+}
+
+fn push_off_curves(nodes: &mut Vec<Node>, off_curves: &[Point]) {
+    nodes.extend(off_curves.iter().map(|&pt| Node {
+        pt,
+        node_type: NodeType::OffCurve,
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Point, Shape as _};
+
+    use crate::{Font, Glyph, Layer, NodeType, Path};
+
+    fn path(closed: bool, nodes: &[(f64, f64, NodeType)]) -> Path {
+        let mut path = Path::new(closed);
+        for &(x, y, node_type) in nodes {
+            path.add((x, y), node_type);
+        }
+        path
+    }
+
+    fn node_types(path: &Path) -> Vec<NodeType> {
+        path.nodes.iter().map(|n| n.node_type).collect()
+    }
+
+    #[test]
+    fn to_quadratic() {
+        use NodeType::*;
+        let mut circle = path(
+            true,
+            &[
+                (100.0, 55.0, OffCurve),
+                (55.0, 100.0, OffCurve),
+                (0.0, 100.0, CurveSmooth),
+                (-55.0, 100.0, OffCurve),
+                (-100.0, 55.0, OffCurve),
+                (-100.0, 0.0, CurveSmooth),
+                (-100.0, -100.0, Line),
+                (100.0, -100.0, Line),
+                (100.0, 0.0, Line),
+            ],
+        );
+        let original = circle.clone();
+        circle.to_quadratic(0.5);
+
+        let types = node_types(&circle);
+        assert!(!types.iter().any(|t| matches!(t, Curve | CurveSmooth)));
+        assert_eq!(
+            types.iter().filter(|t| **t == QCurveSmooth).count(),
+            2,
+            "{types:?}"
+        );
+        assert!(types.len() > original.nodes.len());
+        // The starting node stays put.
+        assert_eq!(circle.nodes.last(), original.nodes.last());
+
+        let (bounds, original_bounds) = (
+            circle.to_bezpath().bounding_box(),
+            original.to_bezpath().bounding_box(),
+        );
+        assert!((bounds.y1 - original_bounds.y1).abs() <= 0.5);
+        assert!((circle.to_bezpath().area() - original.to_bezpath().area()).abs() < 100.0);
+    }
+
+    #[test]
+    fn super_bezier_to_quadratic() {
+        use NodeType::*;
+        let mut hump = path(
+            false,
+            &[
+                (0.0, 0.0, Line),
+                (0.0, 100.0, OffCurve),
+                (100.0, 200.0, OffCurve),
+                (200.0, 100.0, OffCurve),
+                (200.0, 0.0, Curve),
+            ],
+        );
+        let original = hump.to_bezpath();
+        hump.to_quadratic(0.5);
+
+        assert!(!node_types(&hump).contains(&Curve));
+        // The joins of the split curves stay on the curve.
+        assert!(hump.nodes.iter().any(|n| n.pt == Point::new(100.0, 150.0)));
+        assert!((hump.to_bezpath().area() - original.area()).abs() < 100.0);
+    }
+
+    #[test]
+    fn to_cubic() {
+        use NodeType::*;
+        let mut quadratic = path(
+            false,
+            &[
+                (0.0, 0.0, Line),
+                (0.0, 100.0, OffCurve),
+                (100.0, 100.0, OffCurve),
+                (100.0, 0.0, QCurve),
+                (50.0, -50.0, OffCurve),
+                (0.0, 0.0, Curve),
+            ],
+        );
+        let original = quadratic.clone();
+        quadratic.to_cubic();
+        assert_eq!(
+            node_types(&quadratic),
+            [
+                Line,
+                OffCurve,
+                OffCurve,
+                CurveSmooth,
+                OffCurve,
+                OffCurve,
+                Curve,
+                OffCurve,
+                OffCurve,
+                Curve
+            ]
+        );
+        assert_eq!(quadratic.nodes[3].pt, Point::new(50.0, 100.0));
+        let (bez, original_bez) = (quadratic.to_bezpath(), original.to_bezpath());
+        assert!((bez.area() - original_bez.area()).abs() < 1e-9);
+        let (bounds, original_bounds) = (bez.bounding_box(), original_bez.bounding_box());
+        assert!((bounds.y0 - original_bounds.y0).abs() < 1e-9);
+        assert_eq!(bounds.y1, original_bounds.y1);
+
+        // Converting back finds the quadratic curves again.
+        quadratic.to_quadratic(0.01);
+        assert_eq!(
+            node_types(&quadratic),
+            [
+                Line,
+                OffCurve,
+                QCurveSmooth,
+                OffCurve,
+                QCurve,
+                OffCurve,
+                QCurve
+            ]
+        );
+    }
+
+    #[test]
+    fn to_cubic_without_on_curves() {
+        use NodeType::*;
+        let mut diamond = path(
+            true,
+            &[
+                (0.0, 0.0, OffCurve),
+                (100.0, 0.0, OffCurve),
+                (100.0, 100.0, OffCurve),
+                (0.0, 100.0, OffCurve),
+            ],
+        );
+        let original = diamond.to_bezpath();
+        diamond.to_cubic();
+        assert_eq!(diamond.nodes.len(), 12);
+        assert_eq!(diamond.nodes[11].pt, Point::new(0.0, 50.0));
+        assert!((diamond.to_bezpath().area() - original.area()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn font_to_quadratic_keeps_layers_compatible() {
+        use NodeType::*;
+        let bowl = |inset: f64, depth: f64| {
+            path(
+                false,
+                &[
+                    (0.0, 0.0, Line),
+                    (inset, depth, OffCurve),
+                    (1000.0 - inset, depth, OffCurve),
+                    (1000.0, 0.0, Curve),
+                ],
+            )
+        };
+        let mut glyph = Glyph::new(norad::Name::new("u").unwrap(), None);
+        for (id, inset, depth) in [("m01", 0.0, 1000.0), ("m02", 400.0, 10.0)] {
+            let mut layer = Layer::new(id);
+            layer.push_path(bowl(inset, depth));
+            glyph.layers.push(layer);
+        }
+        let mut layer = Layer::new("m03");
+        layer.push_path(path(false, &[(0.0, 0.0, Line), (10.0, 10.0, Line)]));
+        glyph.layers.push(layer);
+        let mut font = Font::new();
+        font.glyphs.push(glyph);
+
+        let mut flat = bowl(400.0, 10.0);
+        flat.to_quadratic(0.1);
+        let mut deep = bowl(0.0, 1000.0);
+        deep.to_quadratic(0.1);
+        assert!(flat.nodes.len() < deep.nodes.len());
+
+        font.to_quadratic(0.1);
+        let layers = &font.get_glyph("u").unwrap().layers;
+        let paths: Vec<&Path> = layers.iter().flat_map(|l| l.paths()).collect();
+        assert_eq!(*paths[0], deep);
+        assert_eq!(node_types(paths[1]), node_types(&deep));
+        assert_eq!(node_types(paths[2]), [Line, Line]);
+    }
+}
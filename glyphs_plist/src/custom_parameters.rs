@@ -0,0 +1,204 @@
+//! Typed access to custom parameters.
+//!
+//! The font keeps its custom parameters as [`CustomParameter`]s, masters and
+//! instances as plist dictionaries under `customParameters` in their
+//! `other_stuff`. Either way a parameter is looked up by name, skipping
+//! disabled ones, and read as the type asked for with
+//! [`Font::custom_parameter_as`] and its counterparts.
+//!
+//! [`CustomParameter`]: crate::CustomParameter
+
+use std::collections::HashMap;
+
+use crate::{Font, FontMaster, Instance, Plist};
+
+const CUSTOM_PARAMETERS: &str = "customParameters";
+
+/// A type the value of a custom parameter can be read as.
+///
+/// Numbers are also read from strings, as Glyphs.app writes some numeric
+/// parameters that way, and booleans from the integers 0 and 1.
+pub trait ParameterValue<'a>: Sized {
+    fn from_parameter(value: &'a Plist) -> Option<Self>;
+}
+
+impl<'a> ParameterValue<'a> for &'a Plist {
+    fn from_parameter(value: &'a Plist) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl<'a> ParameterValue<'a> for &'a str {
+    fn from_parameter(value: &'a Plist) -> Option<Self> {
+        value.as_str()
+    }
+}
+
+impl<'a> ParameterValue<'a> for &'a [Plist] {
+    fn from_parameter(value: &'a Plist) -> Option<Self> {
+        value.as_array()
+    }
+}
+
+impl<'a> ParameterValue<'a> for &'a HashMap<String, Plist> {
+    fn from_parameter(value: &'a Plist) -> Option<Self> {
+        value.as_dict()
+    }
+}
+
+impl ParameterValue<'_> for f64 {
+    fn from_parameter(value: &Plist) -> Option<Self> {
+        value.as_f64().or_else(|| value.as_str()?.parse().ok())
+    }
+}
+
+impl ParameterValue<'_> for i64 {
+    fn from_parameter(value: &Plist) -> Option<Self> {
+        value.as_i64().or_else(|| value.as_str()?.parse().ok())
+    }
+}
+
+impl ParameterValue<'_> for bool {
+    fn from_parameter(value: &Plist) -> Option<Self> {
+        i64::from_parameter(value).map(|value| value != 0)
+    }
+}
+
+impl Font {
+    /// Look up the value of an enabled font-level custom parameter.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        self.custom_parameters
+            .iter()
+            .flatten()
+            .find(|p| p.name == name && !p.disabled)
+            .map(|p| &p.value)
+    }
+
+    /// The value of an enabled font-level custom parameter, if it can be
+    /// read as `T`.
+    pub fn custom_parameter_as<'a, T: ParameterValue<'a>>(&'a self, name: &str) -> Option<T> {
+        T::from_parameter(self.custom_parameter(name)?)
+    }
+}
+
+impl FontMaster {
+    /// The value of an enabled custom parameter of the master.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        dict_parameter(&self.other_stuff, name)
+    }
+
+    /// The value of an enabled custom parameter of the master, if it can be
+    /// read as `T`.
+    pub fn custom_parameter_as<'a, T: ParameterValue<'a>>(&'a self, name: &str) -> Option<T> {
+        T::from_parameter(self.custom_parameter(name)?)
+    }
+}
+
+impl Instance {
+    /// The value of an enabled custom parameter of the instance.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        dict_parameter(&self.other_stuff, name)
+    }
+
+    /// The value of an enabled custom parameter of the instance, if it can
+    /// be read as `T`.
+    pub fn custom_parameter_as<'a, T: ParameterValue<'a>>(&'a self, name: &str) -> Option<T> {
+        T::from_parameter(self.custom_parameter(name)?)
+    }
+}
+
+/// An enabled custom parameter among those stored as dictionaries.
+fn dict_parameter<'a>(other_stuff: &'a HashMap<String, Plist>, name: &str) -> Option<&'a Plist> {
+    other_stuff
+        .get(CUSTOM_PARAMETERS)?
+        .as_array()?
+        .iter()
+        .filter_map(Plist::as_dict)
+        .filter(|p| {
+            !p.get("disabled")
+                .and_then(bool::from_parameter)
+                .unwrap_or(false)
+        })
+        .find(|p| p.get("name").and_then(Plist::as_str) == Some(name))?
+        .get("value")
+}
+
+/// The names and values of all custom parameters stored as dictionaries,
+/// including disabled ones, for changing the values.
+pub(crate) fn dict_parameters_mut(
+    other_stuff: &mut HashMap<String, Plist>,
+) -> impl Iterator<Item = (String, &mut Plist)> {
+    let parameters = match other_stuff.get_mut(CUSTOM_PARAMETERS) {
+        Some(Plist::Array(parameters)) => parameters.as_mut_slice(),
+        _ => &mut [],
+    };
+    parameters.iter_mut().filter_map(|parameter| {
+        let Plist::Dictionary(parameter) = parameter else {
+            return None;
+        };
+        let name = parameter.get("name")?.as_str()?.to_string();
+        Some((name, parameter.get_mut("value")?))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CustomParameter;
+
+    #[test]
+    fn typed_values() {
+        let mut font = Font {
+            custom_parameters: Some(vec![
+                CustomParameter {
+                    name: "winAscent".into(),
+                    value: Plist::String("900".into()),
+                    disabled: false,
+                },
+                CustomParameter {
+                    name: "Use Typo Metrics".into(),
+                    value: Plist::Integer(1),
+                    disabled: false,
+                },
+                CustomParameter {
+                    name: "Disabled".into(),
+                    value: Plist::Integer(1),
+                    disabled: true,
+                },
+            ]),
+            ..Font::default()
+        };
+        assert_eq!(font.custom_parameter_as::<f64>("winAscent"), Some(900.0));
+        assert_eq!(font.custom_parameter_as::<&str>("winAscent"), Some("900"));
+        assert_eq!(
+            font.custom_parameter_as::<bool>("Use Typo Metrics"),
+            Some(true)
+        );
+        assert_eq!(font.custom_parameter_as::<&str>("Use Typo Metrics"), None);
+        assert_eq!(font.custom_parameter_as::<i64>("Disabled"), None);
+
+        let master = &mut font.font_master[0];
+        let parameter = |name: &str, value: Plist, disabled: i64| {
+            Plist::Dictionary(HashMap::from([
+                ("name".to_string(), Plist::String(name.into())),
+                ("value".to_string(), value),
+                ("disabled".to_string(), Plist::Integer(disabled)),
+            ]))
+        };
+        master.other_stuff.insert(
+            CUSTOM_PARAMETERS.into(),
+            Plist::Array(vec![
+                parameter("typoAscender", Plist::Integer(800), 1),
+                parameter("typoAscender", Plist::Float(750.5), 0),
+            ]),
+        );
+        assert_eq!(
+            master.custom_parameter_as::<f64>("typoAscender"),
+            Some(750.5)
+        );
+        for (_, value) in dict_parameters_mut(&mut master.other_stuff) {
+            *value = Plist::Integer(0);
+        }
+        assert_eq!(master.custom_parameter_as::<i64>("typoAscender"), Some(0));
+    }
+}
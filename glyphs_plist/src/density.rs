@@ -0,0 +1,169 @@
+//! The area and density of outlines, for optical spacing and for comparing
+//! the weight of glyphs across masters.
+
+use kurbo::PathEl;
+
+use crate::outline::MAX_COMPONENT_DEPTH;
+use crate::{Font, Glyph, Layer, Path, Shape};
+
+impl Path {
+    /// The area enclosed by the path, positive when it runs
+    /// counter-clockwise in the y-up coordinates of a font.
+    ///
+    /// An open path encloses the area it would with a straight line from its
+    /// last node back to its first.
+    pub fn signed_area(&self) -> f64 {
+        use kurbo::Shape as _;
+        let mut bez = self.to_bezpath();
+        if !matches!(bez.elements().last(), None | Some(PathEl::ClosePath)) {
+            bez.close_path();
+        }
+        bez.area()
+    }
+}
+
+impl Layer {
+    /// The area covered by the outline of the layer, with components
+    /// resolved as in [`Layer::flattened_bezpath`].
+    ///
+    /// The areas of counters are subtracted from the paths around them, by
+    /// the direction of the paths, so they should be oriented like
+    /// [`Layer::correct_path_direction`] does. Overlapping paths count twice;
+    /// remove overlaps first for the exact area.
+    pub fn ink_area(&self, font: &Font) -> f64 {
+        self.signed_ink_area(font, 0).abs()
+    }
+
+    /// The share of the layer's box, from the descender to the ascender and
+    /// across its advance width, covered by its outline: a rough measure of
+    /// how dark the glyph is. `None` for layers without width or height.
+    ///
+    /// The ascender and descender are those of the layer's master, or 80% and
+    /// -20% of the em without them.
+    pub fn density(&self, font: &Font) -> Option<f64> {
+        let master_id = self
+            .associated_master_id
+            .as_deref()
+            .unwrap_or(&self.layer_id);
+        let metrics = font
+            .font_master
+            .iter()
+            .find(|master| master.id == master_id)
+            .map(|master| master.resolved_metrics(font));
+        let upm = f64::from(font.units_per_em);
+        let ascender = metrics
+            .as_ref()
+            .and_then(|m| m.ascender())
+            .unwrap_or(upm * 0.8);
+        let descender = metrics
+            .as_ref()
+            .and_then(|m| m.descender())
+            .unwrap_or(upm * -0.2);
+        let box_area = self.width * (ascender - descender);
+        (box_area > 0.0).then(|| self.ink_area(font) / box_area)
+    }
+
+    /// The summed signed areas of the paths of the layer and its components,
+    /// with mirrored components counted the right way round.
+    fn signed_ink_area(&self, font: &Font, depth: usize) -> f64 {
+        self.shapes
+            .iter()
+            .map(|shape| match shape {
+                Shape::Path(path) => path.signed_area(),
+                Shape::Component(component) if depth < MAX_COMPONENT_DEPTH => font
+                    .get_glyph(&component.reference)
+                    .and_then(|glyph| glyph.get_layer(self.matching_layer_id(glyph)))
+                    .map_or(0.0, |layer| {
+                        layer.signed_ink_area(font, depth + 1)
+                            * component.transform().determinant().abs()
+                    }),
+                Shape::Component(_) => 0.0,
+            })
+            .sum()
+    }
+}
+
+impl Glyph {
+    /// The density of the glyph's master layers, keyed by master ID in
+    /// master order, to compare its weight across masters. Masters the glyph
+    /// has no layer for are left out.
+    pub fn density_per_master<'a>(&self, font: &'a Font) -> Vec<(&'a str, Option<f64>)> {
+        font.font_master
+            .iter()
+            .filter_map(|master| {
+                let layer = self.master_layer(&master.id)?;
+                Some((master.id.as_str(), layer.density(font)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Scale;
+    use crate::{Component, NodeType};
+
+    fn square(x: f64, y: f64, size: f64) -> Path {
+        let mut path = Path::new(true);
+        for (dx, dy) in [(size, 0.0), (size, size), (0.0, size), (0.0, 0.0)] {
+            path.add((x + dx, y + dy), NodeType::Line);
+        }
+        path
+    }
+
+    #[test]
+    fn open_path_area() {
+        for offset in [0.0, 1000.0] {
+            let mut open = Path::new(false);
+            for (dx, dy) in [(0.0, 0.0), (0.0, 100.0), (100.0, 0.0)] {
+                open.add((offset + dx, offset + dy), NodeType::Line);
+            }
+            assert_eq!(open.signed_area(), -5000.0);
+        }
+    }
+
+    #[test]
+    fn ink_area() {
+        let mut outer = square(0.0, 0.0, 100.0);
+        assert_eq!(outer.signed_area(), 100.0 * 100.0);
+        let mut counter = square(25.0, 25.0, 50.0);
+        counter.reverse_direction();
+        assert_eq!(counter.signed_area(), -50.0 * 50.0);
+
+        let mut font = Font::default();
+        let layer = &mut font.glyphs[0].layers[0];
+        layer.width = 200.0;
+        layer.shapes.push(Shape::Path(Box::new(outer.clone())));
+        layer.shapes.push(Shape::Path(Box::new(counter)));
+        let area = 100.0 * 100.0 - 50.0 * 50.0;
+        assert_eq!(font.glyphs[0].layers[0].ink_area(&font), area);
+        assert_eq!(
+            font.glyphs[0].density_per_master(&font),
+            [("m01", Some(area / (200.0 * 1000.0)))]
+        );
+
+        // A mirrored component covers as much as the glyph it refers to.
+        let mut glyph = Glyph::new(norad::Name::new("mirrored").unwrap(), None);
+        let mut layer = Layer::new("m01");
+        outer.apply_affine(kurbo::Affine::scale(0.5));
+        layer.shapes.push(Shape::Path(Box::new(outer)));
+        layer.shapes.push(Shape::Component(Component {
+            reference: "space".into(),
+            rotation: None,
+            pos: None,
+            scale: Some(Scale {
+                horizontal: -1.0,
+                vertical: 1.0,
+            }),
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        }));
+        glyph.layers.push(layer);
+        assert_eq!(glyph.layers[0].ink_area(&font), 50.0 * 50.0 + area);
+        glyph.layers[0].width = 0.0;
+        assert_eq!(glyph.layers[0].density(&font), None);
+    }
+}
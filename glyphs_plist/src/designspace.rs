@@ -0,0 +1,477 @@
+//! Designspace concepts built from the special layers of a font: bracket
+//! (alternate) layers become substitution rules, brace (intermediate) layers
+//! sparse sources.
+
+use thiserror::Error;
+
+use crate::font::AxisRules;
+use crate::{Font, Glyph, Layer};
+
+/// A designspace `<rule>`: while the location matches all conditions, each
+/// glyph is substituted with its alternate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubstitutionRule {
+    pub name: String,
+    pub conditions: Vec<AxisCondition>,
+    /// Pairs of the glyph name and the name of the glyph replacing it.
+    pub substitutions: Vec<(String, String)>,
+}
+
+/// A range on one axis, in design coordinates. A missing bound is open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisCondition {
+    pub axis: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// The alternate of a glyph for feature variations: while the location
+/// matches all conditions, the glyph is substituted with its alternate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlternateRule {
+    pub glyph: String,
+    /// The glyph made from the alternate layers by [`Font::bracket_glyphs`].
+    pub substitute: String,
+    pub conditions: Vec<FeatureCondition>,
+}
+
+/// A range on one axis, given by tag, in design coordinates. A missing bound
+/// is open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureCondition {
+    pub tag: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// A location with brace layers, which acts as a master for only the glyphs
+/// that have a layer there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseSource<'a> {
+    /// The design coordinates, one per axis.
+    pub location: Vec<f64>,
+    /// The glyphs with a brace layer at the location, in glyph order.
+    pub layers: Vec<(&'a Glyph, &'a Layer)>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SparseSourceError {
+    #[error("glyph {glyph:?} has more than one brace layer at {location:?}")]
+    DuplicateLayer { glyph: String, location: Vec<f64> },
+    #[error(
+        "glyph {glyph:?} has a brace layer at {location:?} but its component {component:?} doesn't"
+    )]
+    MissingComponentLayer {
+        glyph: String,
+        component: String,
+        location: Vec<f64>,
+    },
+}
+
+impl Font {
+    /// Group the brace layers of the font by location into sparse sources,
+    /// in the order the locations first appear.
+    ///
+    /// Each glyph may have one brace layer per location, and every glyph used
+    /// as a component at a location needs a brace layer there too, or the
+    /// composite couldn't be interpolated.
+    pub fn sparse_sources(&self) -> Result<Vec<SparseSource<'_>>, SparseSourceError> {
+        let mut sources: Vec<SparseSource> = Vec::new();
+        for glyph in &self.glyphs {
+            for layer in &glyph.layers {
+                let Some(location) = layer.coordinates() else {
+                    continue;
+                };
+                let source = match sources.iter_mut().position(|s| s.location == location) {
+                    Some(index) => &mut sources[index],
+                    None => {
+                        sources.push(SparseSource {
+                            location: location.to_vec(),
+                            layers: Vec::new(),
+                        });
+                        sources.last_mut().unwrap()
+                    }
+                };
+                if source
+                    .layers
+                    .iter()
+                    .any(|(g, _)| g.glyphname == glyph.glyphname)
+                {
+                    return Err(SparseSourceError::DuplicateLayer {
+                        glyph: glyph.glyphname.to_string(),
+                        location: location.to_vec(),
+                    });
+                }
+                source.layers.push((glyph, layer));
+            }
+        }
+
+        for source in &sources {
+            for (glyph, layer) in &source.layers {
+                for component in layer.components() {
+                    if !source
+                        .layers
+                        .iter()
+                        .any(|(g, _)| g.glyphname.as_str() == component.reference)
+                    {
+                        return Err(SparseSourceError::MissingComponentLayer {
+                            glyph: glyph.glyphname.to_string(),
+                            component: component.reference.clone(),
+                            location: source.location.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(sources)
+    }
+
+    /// The distinct axis rules of the alternate layers in the font, in the
+    /// order they first appear.
+    fn distinct_axis_rules(&self) -> Vec<&[AxisRules]> {
+        let mut distinct: Vec<&[AxisRules]> = Vec::new();
+        for rules in self
+            .glyphs
+            .iter()
+            .flat_map(|g| &g.layers)
+            .filter_map(Layer::axis_rules)
+        {
+            if !distinct.contains(&rules) {
+                distinct.push(rules);
+            }
+        }
+        distinct
+    }
+
+    /// Designspace substitution rules for the alternate layers in the font.
+    ///
+    /// There is one rule per distinct set of axis ranges. Glyphs are
+    /// substituted with the glyphs made by [`Font::bracket_glyphs`], named
+    /// `<glyphname>.BRACKET.varAlt<nn>` after the rule, like glyphsLib does.
+    pub fn substitution_rules(&self) -> Vec<SubstitutionRule> {
+        self.distinct_axis_rules()
+            .into_iter()
+            .enumerate()
+            .map(|(i, rules)| {
+                let conditions = self
+                    .axes
+                    .iter()
+                    .flatten()
+                    .zip(rules)
+                    .filter(|(_, rule)| rule.min.is_some() || rule.max.is_some())
+                    .map(|(axis, rule)| AxisCondition {
+                        axis: axis.name.clone(),
+                        min: rule.min,
+                        max: rule.max,
+                    })
+                    .collect();
+                let substitutions = self
+                    .glyphs
+                    .iter()
+                    .filter(|g| g.layers.iter().any(|l| l.axis_rules() == Some(rules)))
+                    .map(|g| (g.glyphname.to_string(), bracket_glyph_name(g, i)))
+                    .collect();
+                SubstitutionRule {
+                    name: format!("BRACKET.varAlt{:02}", i + 1),
+                    conditions,
+                    substitutions,
+                }
+            })
+            .collect()
+    }
+
+    /// The alternate layers of the font as rules for feature variations, one
+    /// per glyph and distinct set of axis ranges, in glyph order.
+    ///
+    /// The rules apply in the feature given by the "Feature for Feature
+    /// Variations" custom parameter, see
+    /// [`Font::feature_variations_feature`].
+    pub fn alternate_rules(&self) -> Vec<AlternateRule> {
+        let distinct = self.distinct_axis_rules();
+        let mut alternate_rules = Vec::new();
+        for glyph in &self.glyphs {
+            for (i, rules) in distinct.iter().enumerate() {
+                if !glyph.layers.iter().any(|l| l.axis_rules() == Some(rules)) {
+                    continue;
+                }
+                let conditions = self
+                    .axes
+                    .iter()
+                    .flatten()
+                    .zip(*rules)
+                    .filter(|(_, rule)| rule.min.is_some() || rule.max.is_some())
+                    .map(|(axis, rule)| FeatureCondition {
+                        tag: axis.tag.clone(),
+                        min: rule.min,
+                        max: rule.max,
+                    })
+                    .collect();
+                alternate_rules.push(AlternateRule {
+                    glyph: glyph.glyphname.to_string(),
+                    substitute: bracket_glyph_name(glyph, i),
+                    conditions,
+                });
+            }
+        }
+        alternate_rules
+    }
+
+    /// The tag of the feature that alternate layers are substituted in:
+    /// `rvrn`, or another one like `rclt` given by the "Feature for Feature
+    /// Variations" custom parameter.
+    pub fn feature_variations_feature(&self) -> &str {
+        self.custom_parameter_as("Feature for Feature Variations")
+            .unwrap_or("rvrn")
+    }
+
+    /// The substitute glyphs for the alternate layers in the font, as named
+    /// by [`Font::substitution_rules`].
+    ///
+    /// The alternate layers become the master layers of the new glyphs.
+    /// Masters without an alternate layer for a rule use a copy of the
+    /// master layer of the original glyph instead. The glyphs have no
+    /// Unicode values of their own.
+    pub fn bracket_glyphs(&self) -> Vec<Glyph> {
+        let distinct = self.distinct_axis_rules();
+        let mut bracket_glyphs = Vec::new();
+        for glyph in &self.glyphs {
+            for (i, rules) in distinct.iter().enumerate() {
+                let alternates: Vec<&Layer> = glyph
+                    .layers
+                    .iter()
+                    .filter(|l| l.axis_rules() == Some(rules))
+                    .collect();
+                if alternates.is_empty() {
+                    continue;
+                }
+                let layers = self
+                    .font_master
+                    .iter()
+                    .filter_map(|master| {
+                        let layer = alternates
+                            .iter()
+                            .find(|l| l.associated_master_id.as_deref() == Some(&master.id))
+                            .copied()
+                            .or_else(|| glyph.master_layer(&master.id))?;
+                        Some(Layer {
+                            attr: None,
+                            name: None,
+                            associated_master_id: None,
+                            layer_id: master.id.clone(),
+                            ..layer.clone()
+                        })
+                    })
+                    .collect();
+                let name = norad::Name::new(&bracket_glyph_name(glyph, i))
+                    .expect("suffixing a valid glyph name keeps it valid");
+                bracket_glyphs.push(Glyph {
+                    layers,
+                    export: glyph.export,
+                    ..Glyph::new(name, None)
+                });
+            }
+        }
+        bracket_glyphs
+    }
+}
+
+impl Layer {
+    fn axis_rules(&self) -> Option<&[AxisRules]> {
+        self.attr.as_ref()?.axis_rules.as_deref()
+    }
+}
+
+fn bracket_glyph_name(glyph: &Glyph, rule_index: usize) -> String {
+    format!("{}.BRACKET.varAlt{:02}", glyph.glyphname, rule_index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AlternateRule, AxisCondition, FeatureCondition, SparseSourceError, SubstitutionRule,
+    };
+    use crate::{Font, Layer, LayerAttr, Shape};
+
+    #[test]
+    fn substitution_rules() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        assert_eq!(
+            font.substitution_rules(),
+            vec![
+                SubstitutionRule {
+                    name: "BRACKET.varAlt01".into(),
+                    conditions: vec![AxisCondition {
+                        axis: "Weight".into(),
+                        min: Some(450.0),
+                        max: None,
+                    }],
+                    substitutions: vec![("B".into(), "B.BRACKET.varAlt01".into())],
+                },
+                SubstitutionRule {
+                    name: "BRACKET.varAlt02".into(),
+                    conditions: vec![AxisCondition {
+                        axis: "Weight".into(),
+                        min: None,
+                        max: Some(450.0),
+                    }],
+                    substitutions: vec![("C".into(), "C.BRACKET.varAlt02".into())],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn alternate_rules() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let tag = font.axes.as_ref().unwrap()[0].tag.clone();
+
+        assert_eq!(
+            font.alternate_rules(),
+            vec![
+                AlternateRule {
+                    glyph: "B".into(),
+                    substitute: "B.BRACKET.varAlt01".into(),
+                    conditions: vec![FeatureCondition {
+                        tag: tag.clone(),
+                        min: Some(450.0),
+                        max: None,
+                    }],
+                },
+                AlternateRule {
+                    glyph: "C".into(),
+                    substitute: "C.BRACKET.varAlt02".into(),
+                    conditions: vec![FeatureCondition {
+                        tag,
+                        min: None,
+                        max: Some(450.0),
+                    }],
+                },
+            ]
+        );
+
+        assert_eq!(font.feature_variations_feature(), "rvrn");
+        font.custom_parameters
+            .get_or_insert_with(Vec::new)
+            .push(crate::CustomParameter {
+                name: "Feature for Feature Variations".into(),
+                value: crate::Plist::String("rclt".into()),
+                disabled: false,
+            });
+        assert_eq!(font.feature_variations_feature(), "rclt");
+    }
+
+    #[test]
+    fn bracket_glyphs() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let bracket_glyphs = font.bracket_glyphs();
+
+        let names = bracket_glyphs
+            .iter()
+            .map(|g| g.glyphname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["B.BRACKET.varAlt01", "C.BRACKET.varAlt02"]);
+        assert_eq!(bracket_glyphs[0].layers.len(), 2);
+
+        let b = font.get_glyph("B").unwrap();
+        let bracket_b = &bracket_glyphs[0];
+        // The test file only has layers for the first two masters.
+        for master in &font.font_master[..2] {
+            let layer = bracket_b.get_layer(&master.id).unwrap();
+            assert!(layer.is_master_layer());
+            let alternate = b
+                .layers
+                .iter()
+                .find(|l| {
+                    l.is_alternate_layer() && l.associated_master_id.as_ref() == Some(&master.id)
+                })
+                .unwrap();
+            assert_eq!(layer.shapes, alternate.shapes);
+        }
+
+        // Without an alternate layer, the master layer is used.
+        let mut font = font;
+        let other_master = font.font_master[1].id.clone();
+        let c = font.get_glyph_mut("C").unwrap();
+        c.layers.retain(|l| {
+            !(l.is_alternate_layer() && l.associated_master_id.as_ref() == Some(&other_master))
+        });
+        let c = font.get_glyph("C").unwrap();
+        assert_eq!(
+            font.bracket_glyphs()[1]
+                .get_layer(&other_master)
+                .unwrap()
+                .shapes,
+            c.get_layer(&other_master).unwrap().shapes
+        );
+    }
+
+    #[test]
+    fn sparse_sources() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let sources = font.sparse_sources().unwrap();
+
+        let summary = sources
+            .iter()
+            .map(|s| {
+                let glyphs = s
+                    .layers
+                    .iter()
+                    .map(|(g, l)| (g.glyphname.as_str(), l.layer_id.as_str()))
+                    .collect::<Vec<_>>();
+                (s.location.clone(), glyphs)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    vec![450.0, 0.0, 0.0],
+                    vec![("Smily", "403102FA-569E-44B5-9F7D-836F0E7D2DAD")]
+                ),
+                (
+                    vec![6.0, 0.123, 0.0],
+                    vec![("Smily", "89B02DA8-2AE3-4DCE-B67F-1B5963597B41")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sparse_source_errors() {
+        let brace_layer = |id: &str| Layer {
+            attr: Some(LayerAttr {
+                axis_rules: None,
+                coordinates: Some(vec![450.0, 0.0, 0.0]),
+                other_stuff: Default::default(),
+            }),
+            ..Layer::new_associated(id, "m01")
+        };
+
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let smily = font.get_glyph_mut("Smily").unwrap();
+        smily.layers.push(brace_layer("duplicate"));
+        assert_eq!(
+            font.sparse_sources(),
+            Err(SparseSourceError::DuplicateLayer {
+                glyph: "Smily".into(),
+                location: vec![450.0, 0.0, 0.0],
+            })
+        );
+
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let a_dieresis = font.get_glyph_mut("Ä").unwrap();
+        let mut layer = brace_layer("composite");
+        layer.shapes = a_dieresis.layers[0].shapes.clone();
+        assert!(matches!(layer.shapes[0], Shape::Component(_)));
+        a_dieresis.layers.push(layer);
+        assert_eq!(
+            font.sparse_sources(),
+            Err(SparseSourceError::MissingComponentLayer {
+                glyph: "Ä".into(),
+                component: "A".into(),
+                location: vec![450.0, 0.0, 0.0],
+            })
+        );
+    }
+}
@@ -0,0 +1,659 @@
+//! Comparing two fonts at the model level, for showing the changes between
+//! revisions of a font in terms of glyphs, outlines, kerning and metrics.
+
+use std::collections::{BTreeSet, HashMap};
+
+use kurbo::Point;
+
+use crate::{Component, Font, Glyph, Layer, MasterMetric, Metric, Path, Plist, Shape};
+
+/// The differences between two fonts, from [`Font::diff`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontDiff {
+    /// The font's own attributes that changed, by their plist key, like
+    /// `familyName` or `unitsPerEm`. The app version isn't compared.
+    pub attributes: Vec<&'static str>,
+    /// The glyphs only in the new font, in its order.
+    pub added_glyphs: Vec<String>,
+    /// The glyphs only in the old font, in its order.
+    pub removed_glyphs: Vec<String>,
+    /// The glyphs in both fonts that differ, in the new font's order.
+    pub changed_glyphs: Vec<GlyphDiff>,
+    /// The IDs of the masters only in the new font.
+    pub added_masters: Vec<String>,
+    /// The IDs of the masters only in the old font.
+    pub removed_masters: Vec<String>,
+    /// The changed metric values of the masters in both fonts.
+    pub metrics: Vec<MetricChange>,
+    /// The kerning pairs added, removed or changed, sorted.
+    pub kerning: Vec<KerningChange>,
+    /// Where custom parameters, user data or data the model doesn't know
+    /// about changed, like `glyphs["A"].userData`.
+    pub custom_data: Vec<String>,
+}
+
+/// How a glyph in both fonts differs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlyphDiff {
+    pub name: String,
+    /// The glyph's own attributes that changed, by their plist key, like
+    /// `unicode` or `kernLeft`.
+    pub attributes: Vec<&'static str>,
+    /// The IDs of the layers only in the new glyph.
+    pub added_layers: Vec<String>,
+    /// The IDs of the layers only in the old glyph.
+    pub removed_layers: Vec<String>,
+    /// The layers in both glyphs that differ.
+    pub changed_layers: Vec<LayerDiff>,
+}
+
+/// How a layer in both glyphs differs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayerDiff {
+    pub layer_id: String,
+    /// The old and new advance width, if it changed.
+    pub width: Option<(f64, f64)>,
+    /// The shapes that changed, by index.
+    pub shapes: Vec<ShapeChange>,
+    /// The names of the anchors added, removed or moved.
+    pub anchors: Vec<String>,
+    /// The other attributes that changed, by their plist key, like `guides`
+    /// or `background`.
+    pub attributes: Vec<&'static str>,
+}
+
+/// A change to a shape of a layer, which are matched by index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShapeChange {
+    /// A shape only in the new layer.
+    Added(usize),
+    /// A shape only in the old layer.
+    Removed(usize),
+    /// A path with the same structure whose nodes moved, with the index and
+    /// old and new position of each node that moved.
+    NodesMoved {
+        index: usize,
+        nodes: Vec<(usize, Point, Point)>,
+    },
+    /// A shape that changed in any other way: a path whose nodes were added,
+    /// removed or changed type, or a component that changed.
+    Changed(usize),
+}
+
+/// A changed metric value of a master.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricChange {
+    pub master_id: String,
+    /// The metric's type, or its name for metrics without one, with its
+    /// filter if it has one.
+    pub metric: String,
+    /// The old value, or `None` if the metric is new.
+    pub old: Option<MasterMetric>,
+    /// The new value, or `None` if the metric was removed.
+    pub new: Option<MasterMetric>,
+}
+
+/// A kerning pair added, removed or changed.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct KerningChange {
+    /// `kerningLTR`, `kerningRTL` or `kerningVertical`.
+    pub direction: &'static str,
+    pub master_id: String,
+    pub first: String,
+    pub second: String,
+    /// The old value, or `None` if the pair was added.
+    pub old: Option<f64>,
+    /// The new value, or `None` if the pair was removed.
+    pub new: Option<f64>,
+}
+
+impl FontDiff {
+    /// Whether the fonts are the same, as far as the comparison goes.
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+            && self.added_glyphs.is_empty()
+            && self.removed_glyphs.is_empty()
+            && self.changed_glyphs.is_empty()
+            && self.added_masters.is_empty()
+            && self.removed_masters.is_empty()
+            && self.metrics.is_empty()
+            && self.kerning.is_empty()
+            && self.custom_data.is_empty()
+    }
+}
+
+impl Font {
+    /// Compare the font, as the old version, to `new`.
+    ///
+    /// Glyphs are matched by name, layers by ID, masters by ID, metrics by
+    /// type, name and filter, and shapes and nodes by index. Coordinates,
+    /// widths and metric values within `tolerance` of each other count as
+    /// unchanged. Font-level data not listed in [`FontDiff`], like features
+    /// or instances, is compared as custom data.
+    pub fn diff(&self, new: &Font, tolerance: f64) -> FontDiff {
+        let mut diff = FontDiff::default();
+        let attributes = [
+            (".formatVersion", self.format_version != new.format_version),
+            ("date", self.date != new.date),
+            ("familyName", self.family_name != new.family_name),
+            ("versionMajor", self.version_major != new.version_major),
+            ("versionMinor", self.version_minor != new.version_minor),
+            ("unitsPerEm", self.units_per_em != new.units_per_em),
+        ];
+        diff.attributes = changed_keys(attributes);
+
+        let old_glyphs: HashMap<&str, &Glyph> = self
+            .glyphs
+            .iter()
+            .map(|g| (g.glyphname.as_str(), g))
+            .collect();
+        let new_glyphs: HashMap<&str, &Glyph> = new
+            .glyphs
+            .iter()
+            .map(|g| (g.glyphname.as_str(), g))
+            .collect();
+        for glyph in &new.glyphs {
+            match old_glyphs.get(glyph.glyphname.as_str()) {
+                Some(old) => {
+                    let glyph_diff = diff_glyph(old, glyph, tolerance, &mut diff.custom_data);
+                    if glyph_diff != GlyphDiff::default() {
+                        diff.changed_glyphs.push(GlyphDiff {
+                            name: glyph.glyphname.to_string(),
+                            ..glyph_diff
+                        });
+                    }
+                }
+                None => diff.added_glyphs.push(glyph.glyphname.to_string()),
+            }
+        }
+        diff.removed_glyphs = self
+            .glyphs
+            .iter()
+            .filter(|g| !new_glyphs.contains_key(g.glyphname.as_str()))
+            .map(|g| g.glyphname.to_string())
+            .collect();
+
+        self.diff_masters(new, tolerance, &mut diff);
+        self.diff_kerning(new, tolerance, &mut diff);
+
+        let custom = [
+            (
+                "customParameters",
+                self.custom_parameters != new.custom_parameters,
+            ),
+            ("axes", self.axes != new.axes),
+            ("classes", self.classes != new.classes),
+            (
+                "featurePrefixes",
+                self.feature_prefixes != new.feature_prefixes,
+            ),
+            ("features", self.features != new.features),
+            ("instances", self.instances != new.instances),
+            ("numbers", self.numbers != new.numbers),
+            ("stems", self.stems != new.stems),
+            ("settings", self.settings != new.settings),
+        ];
+        for (key, changed) in custom {
+            if changed {
+                diff.custom_data.push(key.to_string());
+            }
+        }
+        diff_other_stuff(
+            "",
+            &self.other_stuff,
+            &new.other_stuff,
+            &mut diff.custom_data,
+        );
+        diff
+    }
+
+    fn diff_masters(&self, new: &Font, tolerance: f64, diff: &mut FontDiff) {
+        diff.added_masters = new
+            .font_master
+            .iter()
+            .filter(|m| !self.font_master.iter().any(|old| old.id == m.id))
+            .map(|m| m.id.to_string())
+            .collect();
+        diff.removed_masters = self
+            .font_master
+            .iter()
+            .filter(|m| !new.font_master.iter().any(|n| n.id == m.id))
+            .map(|m| m.id.to_string())
+            .collect();
+
+        // Every metric of either font, with its position in each.
+        let mut metrics: Vec<(&Metric, Option<usize>, Option<usize>)> = Vec::new();
+        for (i, metric) in self.metrics.iter().enumerate() {
+            let j = new.metrics.iter().position(|m| m == metric);
+            metrics.push((metric, Some(i), j));
+        }
+        for (j, metric) in new.metrics.iter().enumerate() {
+            if !self.metrics.contains(metric) {
+                metrics.push((metric, None, Some(j)));
+            }
+        }
+
+        for master in &new.font_master {
+            let Some(old_master) = self.font_master.iter().find(|m| m.id == master.id) else {
+                continue;
+            };
+            for &(metric, i, j) in &metrics {
+                let old = i.and_then(|i| old_master.metric_values.get(i));
+                let new = j.and_then(|j| master.metric_values.get(j));
+                let unchanged = match (old, new) {
+                    (Some(old), Some(new)) => {
+                        close(old.pos, new.pos, tolerance) && close(old.over, new.over, tolerance)
+                    }
+                    (None, None) => true,
+                    _ => false,
+                };
+                if !unchanged {
+                    diff.metrics.push(MetricChange {
+                        master_id: master.id.to_string(),
+                        metric: metric_name(metric),
+                        old: old.cloned(),
+                        new: new.cloned(),
+                    });
+                }
+            }
+
+            let path = format!("fontMaster[{:?}]", master.id);
+            if old_master.user_data != master.user_data {
+                diff.custom_data.push(format!("{path}.userData"));
+            }
+            let others = [
+                ("axesValues", old_master.axes_values != master.axes_values),
+                ("name", old_master.name != master.name),
+                (
+                    "numberValues",
+                    old_master.number_values != master.number_values,
+                ),
+                ("stemValues", old_master.stem_values != master.stem_values),
+                ("visible", old_master.visible != master.visible),
+            ];
+            for (key, changed) in others {
+                if changed {
+                    diff.custom_data.push(format!("{path}.{key}"));
+                }
+            }
+            diff_other_stuff(
+                &path,
+                &old_master.other_stuff,
+                &master.other_stuff,
+                &mut diff.custom_data,
+            );
+        }
+    }
+
+    fn diff_kerning(&self, new: &Font, tolerance: f64, diff: &mut FontDiff) {
+        let directions = [
+            ("kerningLTR", &self.kerning_ltr, &new.kerning_ltr),
+            ("kerningRTL", &self.kerning_rtl, &new.kerning_rtl),
+            (
+                "kerningVertical",
+                &self.kerning_vertical,
+                &new.kerning_vertical,
+            ),
+        ];
+        for (direction, old, new) in directions {
+            let pairs = |kerning: &Option<HashMap<String, norad::Kerning>>| {
+                let mut pairs = HashMap::new();
+                for (master_id, master_kerning) in kerning.iter().flatten() {
+                    for (first, seconds) in master_kerning {
+                        for (second, &value) in seconds {
+                            let key = (master_id.clone(), first.to_string(), second.to_string());
+                            pairs.insert(key, value);
+                        }
+                    }
+                }
+                pairs
+            };
+            let (old, new) = (pairs(old), pairs(new));
+            let keys: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let (old_value, new_value) = (old.get(key).copied(), new.get(key).copied());
+                let unchanged = match (old_value, new_value) {
+                    (Some(old), Some(new)) => close(old, new, tolerance),
+                    _ => false,
+                };
+                if !unchanged {
+                    let (master_id, first, second) = key.clone();
+                    diff.kerning.push(KerningChange {
+                        direction,
+                        master_id,
+                        first,
+                        second,
+                        old: old_value,
+                        new: new_value,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn diff_glyph(
+    old: &Glyph,
+    new: &Glyph,
+    tolerance: f64,
+    custom_data: &mut Vec<String>,
+) -> GlyphDiff {
+    let mut diff = GlyphDiff::default();
+    let attributes = [
+        ("unicode", old.unicode != new.unicode),
+        ("production", old.production != new.production),
+        ("script", old.script != new.script),
+        ("direction", old.direction != new.direction),
+        ("case", old.case != new.case),
+        ("category", old.category != new.category),
+        ("subCategory", old.sub_category != new.sub_category),
+        ("tags", old.tags != new.tags),
+        ("kernLeft", old.kern_left != new.kern_left),
+        ("kernRight", old.kern_right != new.kern_right),
+        ("kernTop", old.kern_top != new.kern_top),
+        ("kernBottom", old.kern_bottom != new.kern_bottom),
+        ("metricTop", old.metric_top != new.metric_top),
+        ("metricBottom", old.metric_bottom != new.metric_bottom),
+        ("metricLeft", old.metric_left != new.metric_left),
+        ("metricRight", old.metric_right != new.metric_right),
+        ("metricWidth", old.metric_width != new.metric_width),
+        ("export", old.export != new.export),
+        ("color", old.color != new.color),
+        ("note", old.note != new.note),
+        ("locked", old.locked != new.locked),
+    ];
+    diff.attributes = changed_keys(attributes);
+
+    let path = format!("glyphs[{:?}]", new.glyphname.as_str());
+    if old.user_data != new.user_data {
+        custom_data.push(format!("{path}.userData"));
+    }
+    diff_other_stuff(&path, &old.other_stuff, &new.other_stuff, custom_data);
+
+    for layer in &new.layers {
+        match old.get_layer(&layer.layer_id) {
+            Some(old_layer) => {
+                let layer_path = format!("{path}.layers[{:?}]", layer.layer_id);
+                let layer_diff = diff_layer(old_layer, layer, tolerance, &layer_path, custom_data);
+                if layer_diff != LayerDiff::default() {
+                    diff.changed_layers.push(LayerDiff {
+                        layer_id: layer.layer_id.to_string(),
+                        ..layer_diff
+                    });
+                }
+            }
+            None => diff.added_layers.push(layer.layer_id.to_string()),
+        }
+    }
+    diff.removed_layers = old
+        .layers
+        .iter()
+        .filter(|l| new.get_layer(&l.layer_id).is_none())
+        .map(|l| l.layer_id.to_string())
+        .collect();
+    diff
+}
+
+fn diff_layer(
+    old: &Layer,
+    new: &Layer,
+    tolerance: f64,
+    path: &str,
+    custom_data: &mut Vec<String>,
+) -> LayerDiff {
+    let mut diff = LayerDiff::default();
+    if !close(old.width, new.width, tolerance) {
+        diff.width = Some((old.width, new.width));
+    }
+
+    for (i, (old_shape, new_shape)) in old.shapes.iter().zip(&new.shapes).enumerate() {
+        if let Some(change) = diff_shape(i, old_shape, new_shape, tolerance) {
+            diff.shapes.push(change);
+        }
+    }
+    let common = old.shapes.len().min(new.shapes.len());
+    diff.shapes
+        .extend((common..new.shapes.len()).map(ShapeChange::Added));
+    diff.shapes
+        .extend((common..old.shapes.len()).map(ShapeChange::Removed));
+
+    let old_anchors = old.anchors.as_deref().unwrap_or_default();
+    let new_anchors = new.anchors.as_deref().unwrap_or_default();
+    for anchor in new_anchors {
+        let unchanged = old_anchors
+            .iter()
+            .find(|a| a.name == anchor.name)
+            .is_some_and(|old| {
+                close_points(old.pos, anchor.pos, tolerance)
+                    && old.orientation == anchor.orientation
+            });
+        if !unchanged {
+            diff.anchors.push(anchor.name.clone());
+        }
+    }
+    for anchor in old_anchors {
+        if !new_anchors.iter().any(|a| a.name == anchor.name) {
+            diff.anchors.push(anchor.name.clone());
+        }
+    }
+
+    let attributes = [
+        (
+            "associatedMasterId",
+            old.associated_master_id != new.associated_master_id,
+        ),
+        ("attr", old.attr != new.attr),
+        ("name", old.name != new.name),
+        ("background", old.background != new.background),
+        (
+            "backgroundImage",
+            old.background_image != new.background_image,
+        ),
+        ("vertWidth", old.vert_width != new.vert_width),
+        ("vertOrigin", old.vert_origin != new.vert_origin),
+        ("guides", old.guides != new.guides),
+        ("metricTop", old.metric_top != new.metric_top),
+        ("metricBottom", old.metric_bottom != new.metric_bottom),
+        ("metricLeft", old.metric_left != new.metric_left),
+        ("metricRight", old.metric_right != new.metric_right),
+        ("metricWidth", old.metric_width != new.metric_width),
+        (
+            "metricVertWidth",
+            old.metric_vert_width != new.metric_vert_width,
+        ),
+        ("color", old.color != new.color),
+    ];
+    diff.attributes = changed_keys(attributes);
+
+    if old.user_data != new.user_data {
+        custom_data.push(format!("{path}.userData"));
+    }
+    diff_other_stuff(path, &old.other_stuff, &new.other_stuff, custom_data);
+    diff
+}
+
+fn diff_shape(index: usize, old: &Shape, new: &Shape, tolerance: f64) -> Option<ShapeChange> {
+    match (old, new) {
+        (Shape::Path(old), Shape::Path(new)) => diff_path(index, old, new, tolerance),
+        (Shape::Component(old), Shape::Component(new)) => {
+            (!same_component(old, new, tolerance)).then_some(ShapeChange::Changed(index))
+        }
+        _ => Some(ShapeChange::Changed(index)),
+    }
+}
+
+fn diff_path(index: usize, old: &Path, new: &Path, tolerance: f64) -> Option<ShapeChange> {
+    let same_structure = old.closed == new.closed
+        && old.attr == new.attr
+        && old.nodes.len() == new.nodes.len()
+        && old
+            .nodes
+            .iter()
+            .zip(&new.nodes)
+            .all(|(a, b)| a.node_type == b.node_type);
+    if !same_structure {
+        return Some(ShapeChange::Changed(index));
+    }
+    let nodes: Vec<(usize, Point, Point)> = old
+        .nodes
+        .iter()
+        .zip(&new.nodes)
+        .enumerate()
+        .filter(|(_, (a, b))| !close_points(a.pt, b.pt, tolerance))
+        .map(|(i, (a, b))| (i, a.pt, b.pt))
+        .collect();
+    (!nodes.is_empty()).then_some(ShapeChange::NodesMoved { index, nodes })
+}
+
+fn same_component(old: &Component, new: &Component, tolerance: f64) -> bool {
+    let (old_coeffs, new_coeffs) = (old.transform().as_coeffs(), new.transform().as_coeffs());
+    old.reference == new.reference
+        && old.alignment == new.alignment
+        && old.anchor == new.anchor
+        && old.other_stuff == new.other_stuff
+        && old_coeffs
+            .iter()
+            .zip(new_coeffs)
+            .all(|(&a, b)| close(a, b, tolerance))
+}
+
+/// Record the keys of the data the model doesn't know about that changed.
+fn diff_other_stuff(
+    path: &str,
+    old: &HashMap<String, Plist>,
+    new: &HashMap<String, Plist>,
+    custom_data: &mut Vec<String>,
+) {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        if old.get(key) != new.get(key) {
+            custom_data.push(match path {
+                "" => key.clone(),
+                path => format!("{path}.{key}"),
+            });
+        }
+    }
+}
+
+fn changed_keys<const N: usize>(attributes: [(&'static str, bool); N]) -> Vec<&'static str> {
+    attributes
+        .into_iter()
+        .filter(|&(_, changed)| changed)
+        .map(|(key, _)| key)
+        .collect()
+}
+
+fn metric_name(metric: &Metric) -> String {
+    let name = match (&metric.r#type, &metric.name) {
+        (Some(metric_type), _) => metric_type.to_string(),
+        (None, Some(name)) => name.clone(),
+        (None, None) => String::new(),
+    };
+    match &metric.filter {
+        Some(filter) => format!("{name} ({filter})"),
+        None => name,
+    }
+}
+
+fn close(a: f64, b: f64, tolerance: f64) -> bool {
+    (a - b).abs() <= tolerance
+}
+
+fn close_points(a: Point, b: Point, tolerance: f64) -> bool {
+    close(a.x, b.x, tolerance) && close(a.y, b.y, tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KerningChange, ShapeChange};
+    use crate::{Font, Plist, Shape};
+
+    #[test]
+    fn identical() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        assert!(font.diff(&font.clone(), 0.0).is_empty());
+    }
+
+    #[test]
+    fn diff() {
+        let old = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut new = old.clone();
+
+        let a = new.get_glyph_mut("A").unwrap();
+        a.category = Some("Letter".into());
+        let layer = &mut a.layers[0];
+        layer.width += 10.0;
+        layer.set_anchor("top", (240.0, 700.0));
+        let Shape::Path(path) = &mut layer.shapes[0] else {
+            panic!("expected a path");
+        };
+        path.nodes[1].pt.x += 0.5;
+        path.nodes[2].pt.x += 5.0;
+        layer.user_data.insert("key".into(), Plist::Integer(1));
+        let removed = new.glyphs.pop().unwrap().glyphname.to_string();
+        new.font_master[0].metric_values[0].pos += 2.0;
+        new.set_kerning("m01", "A", "B", 40.0).unwrap();
+
+        let diff = old.diff(&new, 1.0);
+        assert_eq!(diff.removed_glyphs, [removed]);
+        assert!(diff.added_glyphs.is_empty());
+        assert_eq!(diff.changed_glyphs.len(), 1);
+        let a = &diff.changed_glyphs[0];
+        assert_eq!(
+            (a.name.as_str(), a.attributes.as_slice()),
+            ("A", &["category"][..])
+        );
+        assert_eq!(a.changed_layers.len(), 1);
+        let layer = &a.changed_layers[0];
+        assert_eq!(layer.layer_id, "m01");
+        assert_eq!(layer.width.map(|(old, new)| new - old), Some(10.0));
+        assert_eq!(layer.anchors, ["top"]);
+        let old_pt = match &old.get_glyph("A").unwrap().layers[0].shapes[0] {
+            Shape::Path(path) => path.nodes[2].pt,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            layer.shapes,
+            [ShapeChange::NodesMoved {
+                index: 0,
+                nodes: vec![(2, old_pt, old_pt + (5.0, 0.0))],
+            }]
+        );
+        assert_eq!(diff.custom_data, [r#"glyphs["A"].layers["m01"].userData"#]);
+
+        assert!(diff.attributes.is_empty());
+        assert_eq!(diff.metrics.len(), 1);
+        assert_eq!(diff.metrics[0].master_id, "m01");
+        assert_eq!(diff.metrics[0].metric, "ascender");
+        let kerning_value = old.kern_value("m01", "A", "B");
+        assert_eq!(
+            diff.kerning,
+            [KerningChange {
+                direction: "kerningLTR",
+                master_id: "m01".into(),
+                first: "A".into(),
+                second: "B".into(),
+                old: kerning_value,
+                new: Some(40.0),
+            }]
+        );
+    }
+
+    #[test]
+    fn font_attributes() {
+        let old = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut new = old.clone();
+        new.family_name = "Other".into();
+        new.units_per_em = 2048;
+        new.version_minor += 1;
+        new.app_version = "0".into();
+
+        let diff = old.diff(&new, 0.0);
+        assert_eq!(
+            diff.attributes,
+            ["familyName", "versionMinor", "unitsPerEm"]
+        );
+        assert!(diff.custom_data.is_empty());
+        assert!(!diff.is_empty());
+    }
+}
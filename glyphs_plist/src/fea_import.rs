@@ -0,0 +1,485 @@
+//! Importing class definitions and flat kerning from feature files, for
+//! fonts whose features are maintained outside of Glyphs.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::kerning::{FIRST_GROUP_PREFIX, SECOND_GROUP_PREFIX};
+use crate::{FeatureClass, Font};
+
+/// Prefixes of class names that stand for kerning groups rather than
+/// feature classes, for the first and the second side of a pair.
+const FIRST_CLASS_PREFIXES: &[&str] = &["MMK_L_", "public.kern1."];
+const SECOND_CLASS_PREFIXES: &[&str] = &["MMK_R_", "public.kern2."];
+
+/// What [`Font::import_fea`] took from a feature file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeaImportReport {
+    /// The classes that were added to or replaced in [`Font::classes`].
+    pub classes: Vec<String>,
+    /// The kerning groups that glyphs were put in, with their prefix.
+    pub groups: Vec<String>,
+    /// The number of kerning pairs that were imported.
+    pub kerning_pairs: usize,
+    /// Positioning statements that weren't imported, because they aren't
+    /// pair kerning or name glyphs the font doesn't have.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FeaImportError {
+    #[error("no master with id {0:?}")]
+    UnknownMaster(String),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("line {line}: class @{class} is used before it is defined")]
+    UnknownClass { line: usize, class: String },
+    #[error("glyph {glyph:?} would be in both kerning groups {first:?} and {second:?}")]
+    GroupConflict {
+        glyph: String,
+        first: String,
+        second: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// One side of a kerning pair.
+enum Side {
+    Glyph(String),
+    Class(String),
+}
+
+impl Font {
+    /// Import the class definitions and the pair kerning of a feature file
+    /// into a master.
+    ///
+    /// Classes named like kerning groups (`@MMK_L_A`, `@public.kern1.A`)
+    /// put their glyphs in that group; the other classes are added to
+    /// [`Font::classes`], replacing classes of the same name. Pair positioning
+    /// rules, with a single value or a value record, become left-to-right
+    /// kerning of the master; a class side becomes a kerning group named
+    /// after the class, and a glyph list side is split into glyph pairs.
+    /// `enum pos` rules are read as exceptions, not enumerated. Other
+    /// statements, such as substitutions, are ignored, and positioning rules
+    /// that aren't pair kerning are reported as skipped.
+    pub fn import_fea(
+        &mut self,
+        fea: &str,
+        master_id: &str,
+    ) -> Result<FeaImportReport, FeaImportError> {
+        if !self.font_master.iter().any(|m| m.id.as_str() == master_id) {
+            return Err(FeaImportError::UnknownMaster(master_id.to_string()));
+        }
+        let mut report = FeaImportReport::default();
+        let mut classes: HashMap<String, Vec<String>> = HashMap::new();
+        let mut class_order = Vec::new();
+        // The kerning groups to set, per glyph and side.
+        let mut groups: [HashMap<String, String>; 2] = Default::default();
+        let mut pairs = Vec::new();
+
+        for statement in statements(&tokenize(fea))? {
+            let line = statement[0].line;
+            let words: Vec<&str> = statement.iter().map(|t| t.text.as_str()).collect();
+            match words.as_slice() {
+                [class, "=", "[", members @ .., "]"] if class.starts_with('@') => {
+                    let mut glyphs = Vec::new();
+                    for member in members {
+                        match member.strip_prefix('@') {
+                            Some(name) => glyphs.extend(class_members(&classes, name, line)?),
+                            None => glyphs.push(member.to_string()),
+                        }
+                    }
+                    if classes.insert(class[1..].to_string(), glyphs).is_none() {
+                        class_order.push(class[1..].to_string());
+                    }
+                }
+                ["enum" | "enumerate", "pos" | "position", rest @ ..]
+                | ["pos" | "position", rest @ ..] => {
+                    let Some((first, second, value)) = parse_pair(rest, line)? else {
+                        report.skipped.push(words.join(" "));
+                        continue;
+                    };
+                    let sides = [first, second].map(|side| side_names(&classes, side, line));
+                    let [firsts, seconds] = sides;
+                    let (firsts, seconds) = (firsts?, seconds?);
+                    let known = |names: &[(String, bool)]| {
+                        names
+                            .iter()
+                            .all(|(name, is_class)| *is_class || self.contains_glyph(name))
+                    };
+                    if !known(&firsts) || !known(&seconds) {
+                        report.skipped.push(words.join(" "));
+                        continue;
+                    }
+                    for (first, first_is_class) in &firsts {
+                        for (second, second_is_class) in &seconds {
+                            let first = match first_is_class {
+                                true => group_side(&classes, &mut groups[0], first, 0)?,
+                                false => first.clone(),
+                            };
+                            let second = match second_is_class {
+                                true => group_side(&classes, &mut groups[1], second, 1)?,
+                                false => second.clone(),
+                            };
+                            pairs.push((first, second, value));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for name in class_order {
+            let members = &classes[&name];
+            let is_group = FIRST_CLASS_PREFIXES
+                .iter()
+                .chain(SECOND_CLASS_PREFIXES)
+                .any(|prefix| name.starts_with(prefix));
+            if is_group {
+                continue;
+            }
+            let class = FeatureClass {
+                name: name.clone(),
+                code: members.join(" "),
+                automatic: false,
+                disabled: false,
+                notes: None,
+                other_stuff: Default::default(),
+            };
+            let existing = self.classes.get_or_insert_with(Vec::new);
+            match existing.iter_mut().find(|c| c.name == name) {
+                Some(existing) => *existing = class,
+                None => existing.push(class),
+            }
+            report.classes.push(name);
+        }
+
+        for (side, groups) in groups.iter().enumerate() {
+            for (glyph, group) in groups {
+                let Some(glyph) = self.get_glyph_mut(glyph) else {
+                    continue;
+                };
+                let Ok(name) = norad::Name::new(group) else {
+                    continue;
+                };
+                match side {
+                    0 => glyph.kern_right = Some(name),
+                    _ => glyph.kern_left = Some(name),
+                }
+            }
+            let prefix = [FIRST_GROUP_PREFIX, SECOND_GROUP_PREFIX][side];
+            report
+                .groups
+                .extend(groups.values().map(|group| format!("{prefix}{group}")));
+        }
+        report.groups.sort();
+        report.groups.dedup();
+
+        for (first, second, value) in pairs {
+            if self.set_kerning(master_id, &first, &second, value).is_ok() {
+                report.kerning_pairs += 1;
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn tokenize(fea: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (i, line) in fea.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or_default();
+        let mut word = String::new();
+        for c in code.chars() {
+            if c.is_whitespace() || "[]<>{};=".contains(c) {
+                if !word.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut word),
+                        line: i + 1,
+                    });
+                }
+                if !c.is_whitespace() {
+                    tokens.push(Token {
+                        text: c.to_string(),
+                        line: i + 1,
+                    });
+                }
+            } else {
+                word.push(c);
+            }
+        }
+        if !word.is_empty() {
+            tokens.push(Token {
+                text: word,
+                line: i + 1,
+            });
+        }
+    }
+    tokens
+}
+
+/// Split the tokens into statements, leaving out the headers and ends of
+/// blocks like `feature kern { ... } kern;`.
+fn statements(tokens: &[Token]) -> Result<Vec<Vec<Token>>, FeaImportError> {
+    let mut statements = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0usize;
+    let mut in_block_end = false;
+    for token in tokens {
+        match token.text.as_str() {
+            "{" => {
+                current.clear();
+                depth += 1;
+            }
+            "}" => {
+                if depth == 0 {
+                    return Err(FeaImportError::Syntax {
+                        line: token.line,
+                        message: "unexpected }".into(),
+                    });
+                }
+                depth -= 1;
+                current.clear();
+                in_block_end = true;
+            }
+            ";" => {
+                if !in_block_end && !current.is_empty() {
+                    statements.push(std::mem::take(&mut current));
+                }
+                current.clear();
+                in_block_end = false;
+            }
+            _ => current.push(token.clone()),
+        }
+    }
+    if depth != 0 || !current.is_empty() {
+        return Err(FeaImportError::Syntax {
+            line: tokens.last().map_or(0, |t| t.line),
+            message: "unexpected end of file".into(),
+        });
+    }
+    Ok(statements)
+}
+
+/// The sides and the advance adjustment of a pair positioning rule, without
+/// its keyword. `None` for other positioning rules.
+fn parse_pair(tokens: &[&str], line: usize) -> Result<Option<(Side, Side, f64)>, FeaImportError> {
+    let (first, rest) = match parse_side(tokens) {
+        Some(parsed) => parsed,
+        None => return Ok(None),
+    };
+    let (second, rest) = match parse_side(rest) {
+        Some(parsed) => parsed,
+        None => return Ok(None),
+    };
+    let number = |text: &str| {
+        text.parse::<f64>().map_err(|_| FeaImportError::Syntax {
+            line,
+            message: format!("expected a number, got {text:?}"),
+        })
+    };
+    let value = match rest {
+        [value] => number(value)?,
+        ["<", _, _, x_advance, _, ">"] => number(x_advance)?,
+        _ => return Ok(None),
+    };
+    Ok(Some((first, second, value)))
+}
+
+fn parse_side<'a>(tokens: &'a [&'a str]) -> Option<(Side, &'a [&'a str])> {
+    match tokens {
+        ["[", ..] => {
+            let end = tokens.iter().position(|&t| t == "]")?;
+            Some((Side::Glyph(tokens[1..end].join(" ")), &tokens[end + 1..]))
+        }
+        [name, rest @ ..] if name.starts_with('@') => {
+            Some((Side::Class(name[1..].to_string()), rest))
+        }
+        [name, rest @ ..] => {
+            let is_keyword = ["cursive", "base", "ligature", "mark", "<"].contains(name);
+            // Marked glyphs are contextual positioning.
+            (!is_keyword && !name.ends_with('\'')).then(|| (Side::Glyph(name.to_string()), rest))
+        }
+        [] => None,
+    }
+}
+
+/// The names of a side, each with whether it is a class. Glyph lists are
+/// split into their glyphs.
+fn side_names(
+    classes: &HashMap<String, Vec<String>>,
+    side: Side,
+    line: usize,
+) -> Result<Vec<(String, bool)>, FeaImportError> {
+    match side {
+        Side::Class(name) => {
+            class_members(classes, &name, line)?;
+            Ok(vec![(name, true)])
+        }
+        Side::Glyph(names) => {
+            let mut glyphs = Vec::new();
+            for name in names.split_whitespace() {
+                match name.strip_prefix('@') {
+                    Some(class) => glyphs.extend(
+                        class_members(classes, class, line)?
+                            .into_iter()
+                            .map(|g| (g, false)),
+                    ),
+                    None => glyphs.push((name.to_string(), false)),
+                }
+            }
+            Ok(glyphs)
+        }
+    }
+}
+
+fn class_members(
+    classes: &HashMap<String, Vec<String>>,
+    name: &str,
+    line: usize,
+) -> Result<Vec<String>, FeaImportError> {
+    classes
+        .get(name)
+        .cloned()
+        .ok_or_else(|| FeaImportError::UnknownClass {
+            line,
+            class: name.to_string(),
+        })
+}
+
+/// The prefixed kerning group for a class on one side of a pair, recording
+/// the group of its glyphs.
+fn group_side(
+    classes: &HashMap<String, Vec<String>>,
+    groups: &mut HashMap<String, String>,
+    class: &str,
+    side: usize,
+) -> Result<String, FeaImportError> {
+    let prefixes = [FIRST_CLASS_PREFIXES, SECOND_CLASS_PREFIXES][side];
+    let group = prefixes
+        .iter()
+        .find_map(|prefix| class.strip_prefix(prefix))
+        .unwrap_or(class);
+    for glyph in &classes[class] {
+        match groups.get(glyph) {
+            Some(existing) if existing != group => {
+                return Err(FeaImportError::GroupConflict {
+                    glyph: glyph.clone(),
+                    first: existing.clone(),
+                    second: group.to_string(),
+                })
+            }
+            _ => {
+                groups.insert(glyph.clone(), group.to_string());
+            }
+        }
+    }
+    let prefix = [FIRST_GROUP_PREFIX, SECOND_GROUP_PREFIX][side];
+    Ok(format!("{prefix}{group}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Glyph;
+
+    fn font() -> Font {
+        let mut font = Font::default();
+        for name in ["A", "Aacute", "V", "W", "T", "o"] {
+            font.glyphs
+                .push(Glyph::new(norad::Name::new(name).unwrap(), None));
+        }
+        font
+    }
+
+    #[test]
+    fn classes_and_kerning() {
+        let mut font = font();
+        let fea = "
+            # Kerning maintained by hand.
+            @MMK_L_A = [A Aacute];
+            @MMK_R_V = [V W];
+            @round = [o];
+            languagesystem DFLT dflt;
+
+            feature kern {
+                pos A W -60;
+                enum pos Aacute @MMK_R_V -20;
+                pos @MMK_L_A @MMK_R_V <0 0 -40 0>;
+                pos [T V] @round -30;
+                pos A missing -10;
+                pos cursive A <anchor 0 0> <anchor NULL>;
+                sub A by V;
+            } kern;
+        ";
+        let report = font.import_fea(fea, "m01").unwrap();
+
+        assert_eq!(report.classes, ["round"]);
+        assert_eq!(report.groups, ["@MMK_L_A", "@MMK_R_V", "@MMK_R_round"]);
+        assert_eq!(report.kerning_pairs, 5);
+        assert_eq!(report.skipped.len(), 2);
+
+        assert_eq!(font.kern_value("m01", "A", "W"), Some(-60.0));
+        assert_eq!(font.kern_value("m01", "Aacute", "V"), Some(-20.0));
+        assert_eq!(font.kern_value("m01", "A", "V"), Some(-40.0));
+        assert_eq!(font.kern_value("m01", "T", "o"), Some(-30.0));
+        assert_eq!(font.kern_value("m01", "V", "o"), Some(-30.0));
+        assert_eq!(
+            font.get_glyph("Aacute").unwrap().kern_right.as_deref(),
+            Some("A")
+        );
+        assert_eq!(
+            font.get_glyph("o").unwrap().kern_left.as_deref(),
+            Some("round")
+        );
+        assert_eq!(font.classes.as_ref().unwrap()[0].code, "o");
+    }
+
+    #[test]
+    fn round_trip_through_kern_feature() {
+        let mut font = font();
+        let fea = "
+            @MMK_L_A = [A Aacute];
+            @MMK_R_V = [V W];
+            feature kern {
+                enum pos Aacute @MMK_R_V -20;
+                pos @MMK_L_A @MMK_R_V -40;
+            } kern;
+        ";
+        font.import_fea(fea, "m01").unwrap();
+        let written = font.kerning_to_fea("m01").unwrap();
+
+        let mut reimported = self::font();
+        reimported.import_fea(&written, "m01").unwrap();
+        assert_eq!(reimported.kerning_ltr, font.kerning_ltr);
+    }
+
+    #[test]
+    fn errors() {
+        let mut font = font();
+        assert_eq!(
+            font.import_fea("pos @missing A -10;", "m01"),
+            Err(FeaImportError::UnknownClass {
+                line: 1,
+                class: "missing".into()
+            })
+        );
+        assert!(matches!(
+            font.import_fea("@a = [A];\n@b = [A];\npos @a V -1;\npos @b V -2;", "m01"),
+            Err(FeaImportError::GroupConflict { .. })
+        ));
+        assert!(matches!(
+            font.import_fea("feature kern {\npos A V -10;", "m01"),
+            Err(FeaImportError::Syntax { .. })
+        ));
+        assert_eq!(
+            font.import_fea("", "m02"),
+            Err(FeaImportError::UnknownMaster("m02".into()))
+        );
+    }
+}
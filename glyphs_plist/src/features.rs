@@ -0,0 +1,133 @@
+//! Assembly of a complete `features.fea` from the prefixes, classes and
+//! features stored in a font.
+
+use crate::Font;
+
+impl Font {
+    /// Assemble the font's feature code into a single `features.fea` string.
+    ///
+    /// The order matches Glyphs.app: prefixes, then classes, then features.
+    /// Automatic and disabled entries are marked with comments, and the code
+    /// of disabled entries is commented out.
+    pub fn features_fea(&self) -> String {
+        let prefixes = self.feature_prefixes.iter().flatten().map(|prefix| {
+            let mut lines = Vec::new();
+            if !prefix.name.is_empty() {
+                lines.push(format!("# Prefix: {}", prefix.name));
+            }
+            push_code(
+                &mut lines,
+                &prefix.code,
+                prefix.notes.as_deref(),
+                prefix.automatic,
+                prefix.disabled,
+            );
+            lines.join("\n")
+        });
+
+        let classes = self.classes.iter().flatten().map(|class| {
+            let name = class.name.strip_prefix('@').unwrap_or(&class.name);
+            let code = format!("@{name} = [ {} ];", class.code.trim());
+            let mut lines = Vec::new();
+            push_code(
+                &mut lines,
+                &code,
+                class.notes.as_deref(),
+                class.automatic,
+                class.disabled,
+            );
+            lines.join("\n")
+        });
+
+        let features = self.features.iter().flatten().map(|feature| {
+            let mut lines = vec![format!("feature {} {{", feature.tag)];
+            push_code(
+                &mut lines,
+                &feature.code,
+                feature.notes.as_deref(),
+                feature.automatic,
+                feature.disabled,
+            );
+            lines.push(format!("}} {};", feature.tag));
+            lines.join("\n")
+        });
+
+        let blocks: Vec<String> = prefixes.chain(classes).chain(features).collect();
+        if blocks.is_empty() {
+            String::new()
+        } else {
+            blocks.join("\n\n") + "\n"
+        }
+    }
+}
+
+fn push_code(
+    lines: &mut Vec<String>,
+    code: &str,
+    notes: Option<&str>,
+    automatic: bool,
+    disabled: bool,
+) {
+    if let Some(notes) = notes {
+        lines.push("# notes:".to_string());
+        lines.extend(notes.lines().map(|line| format!("# {line}")));
+    }
+    if automatic {
+        lines.push("# automatic".to_string());
+    }
+    if disabled {
+        lines.push("# disabled".to_string());
+        lines.extend(code.lines().map(|line| format!("#{line}")));
+    } else {
+        lines.extend(code.lines().map(String::from));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Font;
+
+    #[test]
+    fn assemble_features() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        assert_eq!(
+            font.features_fea(),
+            "\
+# Prefix: Languagesystems
+# notes:
+# a prefix note
+# automatic
+languagesystem DFLT dflt;
+
+# Prefix: somePrefix
+# disabled
+
+# notes:
+# a class note.
+@Uppercase = [ A B C D E F G H I J K L M N O P Q R S T U V W X Y Z ];
+
+# automatic
+# disabled
+#@AllLetters = [ A B C D ];
+
+feature test {
+# notes:
+# a feature note.
+sub C by D;
+} test;
+
+feature ss01 {
+# automatic
+# disabled
+#sub A by A.ss01;
+} ss01;
+"
+        );
+    }
+
+    #[test]
+    fn assemble_no_features() {
+        assert_eq!(Font::new().features_fea(), "");
+    }
+}
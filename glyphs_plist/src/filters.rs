@@ -0,0 +1,600 @@
+//! Outline filters like those of Glyphs.app: rounding corners, offsetting
+//! paths and expanding strokes to outlines.
+
+use std::f64::consts::PI;
+
+use kurbo::offset::CubicOffset;
+use kurbo::{Affine, Arc, BezPath, ParamCurve, PathEl, PathSeg, Point, Vec2};
+
+use crate::font::PathAttrs;
+use crate::{Layer, Node, NodeType, Path, Shape};
+
+/// How closely offset curves follow the exact parallel curve, in units.
+const ACCURACY: f64 = 0.1;
+/// Corners are extended to a point only while it is no further from the
+/// corner than this many times the offset distance, and beveled otherwise.
+const MITER_LIMIT: f64 = 4.0;
+/// Segments shorter than this don't have a direction to offset along.
+const EPSILON: f64 = 1e-9;
+
+/// The shape of the ends of an open stroke.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineCap {
+    /// Flat, ending at the end of the path.
+    #[default]
+    Butt,
+    /// A half circle around the end of the path.
+    Round,
+    /// Flat, extended past the end of the path by half the stroke width.
+    Square,
+}
+
+/// Which side of a path a stroke is drawn on, looking along its direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StrokePosition {
+    #[default]
+    Center,
+    Left,
+    Right,
+}
+
+/// How to expand a path into the outline of its stroke.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    /// The thickness of the stroke, across vertical parts of the path.
+    pub width: f64,
+    /// The thickness across horizontal parts, if different from the width,
+    /// as if drawn with an elliptical pen.
+    pub height: Option<f64>,
+    pub start_cap: LineCap,
+    pub end_cap: LineCap,
+    pub position: StrokePosition,
+}
+
+impl StrokeStyle {
+    /// A stroke of the given width centered on the path, with butt caps.
+    pub fn new(width: f64) -> Self {
+        Self {
+            width,
+            height: None,
+            start_cap: LineCap::Butt,
+            end_cap: LineCap::Butt,
+            position: StrokePosition::Center,
+        }
+    }
+}
+
+impl PathAttrs {
+    /// The stroke the attributes give a path, if it has a stroke width.
+    ///
+    /// Caps are stored like Cocoa's line cap styles: 0 for butt, 1 for round
+    /// and 2 for square caps. The stroke position is left of the path for
+    /// negative values, right for positive ones and centered for 0.
+    pub fn stroke_style(&self) -> Option<StrokeStyle> {
+        let width = self.stroke_width.filter(|&w| w > 0.0)?;
+        let cap = |cap: Option<f64>| match cap.map(|c| c.round() as i64) {
+            Some(1) => LineCap::Round,
+            Some(2) => LineCap::Square,
+            _ => LineCap::Butt,
+        };
+        let position = match self.stroke_pos.unwrap_or(0) {
+            0 => StrokePosition::Center,
+            pos if pos < 0 => StrokePosition::Left,
+            _ => StrokePosition::Right,
+        };
+        Some(StrokeStyle {
+            width,
+            height: self.stroke_height.filter(|&h| h > 0.0 && h != width),
+            start_cap: cap(self.line_cap_start),
+            end_cap: cap(self.line_cap_end),
+            position,
+        })
+    }
+}
+
+impl Layer {
+    /// A copy of the layer with the paths that have a stroke replaced by the
+    /// outlines of their strokes, as Glyphs.app exports them.
+    ///
+    /// The outlines keep the other attributes of the path, like its mask
+    /// and fill settings.
+    pub fn with_strokes_expanded(&self) -> Layer {
+        let shapes = self
+            .shapes
+            .iter()
+            .flat_map(|shape| match shape.expanded_stroke() {
+                Some(paths) => paths
+                    .into_iter()
+                    .map(|p| Shape::Path(Box::new(p)))
+                    .collect(),
+                None => vec![shape.clone()],
+            })
+            .collect();
+        Layer {
+            shapes,
+            ..self.clone()
+        }
+    }
+}
+
+impl Shape {
+    /// The outlines of the stroke of a stroked path, without the stroke
+    /// attributes. `None` for other shapes.
+    pub(crate) fn expanded_stroke(&self) -> Option<Vec<Path>> {
+        let Shape::Path(path) = self else {
+            return None;
+        };
+        let attr = path.attr.as_ref()?;
+        let style = attr.stroke_style()?;
+        let attr = PathAttrs {
+            line_cap_start: None,
+            line_cap_end: None,
+            stroke_pos: None,
+            stroke_height: None,
+            stroke_width: None,
+            stroke_color: None,
+            ..attr.clone()
+        };
+        let mut paths = path.stroke(&style);
+        for outline in &mut paths {
+            outline.attr = Some(attr.clone());
+        }
+        Some(paths)
+    }
+}
+
+impl Path {
+    /// Round the corners between two straight segments with an arc of the
+    /// given radius, like the Round Corner filter of Glyphs.app. The radius
+    /// is reduced where the segments are too short for it, so that arcs
+    /// take at most half of each. Returns the number of corners rounded.
+    ///
+    /// Smooth nodes, corners next to curves and the ends of open paths are
+    /// left alone.
+    pub fn round_corners(&mut self, radius: f64) -> usize {
+        let count = self.nodes.len();
+        if radius <= 0.0 || count < 3 {
+            return 0;
+        }
+        let mut nodes = Vec::with_capacity(count);
+        let mut rounded = 0;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let corner = match self.closed {
+                true => Some(((i + count - 1) % count, (i + 1) % count)),
+                false => (i > 0 && i + 1 < count).then(|| (i - 1, i + 1)),
+            };
+            let arc = corner.and_then(|(prev, next)| {
+                let (prev, next) = (&self.nodes[prev], &self.nodes[next]);
+                let is_line = |n: &Node| n.node_type == NodeType::Line;
+                if !is_line(node) || !is_line(next) || prev.node_type == NodeType::OffCurve {
+                    return None;
+                }
+                corner_arc(prev.pt, node.pt, next.pt, radius)
+            });
+            match arc {
+                Some(arc) => {
+                    nodes.extend(arc);
+                    rounded += 1;
+                }
+                None => nodes.push(node.clone()),
+            }
+        }
+        self.nodes = nodes;
+        rounded
+    }
+
+    /// The parallel curve at a distance from the path, to its left looking
+    /// along its direction for positive distances, and to its right for
+    /// negative ones. Counter-clockwise paths, like outer contours in Glyphs,
+    /// have their inside on the left.
+    ///
+    /// Straight segments are extended or cut to meet at corners. Elsewhere,
+    /// outer corners are extended to a point, or beveled if that would be
+    /// far off, and at inner corners the offset segments are connected as
+    /// they are, which can leave small overlaps to be removed.
+    pub fn offset(&self, distance: f64) -> Path {
+        let bez = offset_bezpath(&self.to_bezpath(), distance, self.closed);
+        Path::from_bezpath(&bez)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| Path::new(self.closed))
+    }
+
+    /// The outline of the stroke of the path, like Glyphs.app draws paths
+    /// with a stroke width set: one path for an open path, and an outer and
+    /// an inner path for a closed one.
+    pub fn stroke(&self, style: &StrokeStyle) -> Vec<Path> {
+        let width = style.width;
+        let (left, right) = match style.position {
+            StrokePosition::Center => (width / 2.0, -width / 2.0),
+            StrokePosition::Left => (width, 0.0),
+            StrokePosition::Right => (0.0, -width),
+        };
+        // An elliptical pen is a round one in a space squashed vertically.
+        let squash = match style.height {
+            Some(height) if height > 0.0 && width > 0.0 => {
+                Affine::scale_non_uniform(1.0, width / height)
+            }
+            _ => Affine::IDENTITY,
+        };
+        let bez = squash * self.to_bezpath();
+
+        let left_side = offset_bezpath(&bez, left, self.closed);
+        let right_side = offset_bezpath(&bez, right, self.closed);
+        let outline = if self.closed {
+            let mut outline = left_side;
+            outline.extend(right_side.reverse_subpaths());
+            outline
+        } else {
+            open_stroke(&bez, &left_side, &right_side, left - right, style)
+        };
+        Path::from_bezpath(&(squash.inverse() * outline))
+    }
+}
+
+/// The nodes replacing a corner at `corner` between straight segments from
+/// `prev` and to `next`: a line ending where the arc starts, the two
+/// handles of the arc and its end.
+fn corner_arc(prev: Point, corner: Point, next: Point, radius: f64) -> Option<[Node; 4]> {
+    let (incoming, outgoing) = (corner - prev, next - corner);
+    let (in_len, out_len) = (incoming.hypot(), outgoing.hypot());
+    if in_len < EPSILON || out_len < EPSILON {
+        return None;
+    }
+    let (u_in, u_out) = (incoming / in_len, outgoing / out_len);
+    let turn = u_in.cross(u_out).atan2(u_in.dot(u_out)).abs();
+    if turn < 1f64.to_radians() || turn > 179f64.to_radians() {
+        return None;
+    }
+    let cut = (radius * (turn / 2.0).tan())
+        .min(in_len / 2.0)
+        .min(out_len / 2.0);
+    let radius = cut / (turn / 2.0).tan();
+    let handle = 4.0 / 3.0 * (turn / 4.0).tan() * radius;
+    let (start, end) = (corner - u_in * cut, corner + u_out * cut);
+    let node = |pt: Point, node_type| Node { pt, node_type };
+    Some([
+        node(start, NodeType::LineSmooth),
+        node(start + u_in * handle, NodeType::OffCurve),
+        node(end - u_out * handle, NodeType::OffCurve),
+        node(end, NodeType::CurveSmooth),
+    ])
+}
+
+/// Offset the first subpath of a Bézier path, joining the offset segments.
+fn offset_bezpath(bez: &BezPath, distance: f64, closed: bool) -> BezPath {
+    let segments: Vec<PathSeg> = bez
+        .segments()
+        .filter(|seg| start_tangent(seg).hypot() > EPSILON)
+        .collect();
+    let mut result = BezPath::new();
+    let Some(first) = segments.first() else {
+        return result;
+    };
+    for (i, seg) in segments.iter().enumerate() {
+        let piece = offset_segment(seg, distance);
+        match i {
+            0 => result.extend(piece.elements().iter().copied()),
+            _ => {
+                join(&mut result, &segments[i - 1], seg, distance);
+                result.extend(piece.elements().iter().skip(1).copied());
+            }
+        }
+    }
+    if closed {
+        if let Some(corner) = join(&mut result, &segments[segments.len() - 1], first, distance) {
+            // The path now starts and ends at the corner.
+            result.elements_mut()[0] = PathEl::MoveTo(corner);
+            result.pop();
+        }
+        result.close_path();
+    }
+    result
+}
+
+/// The parallel curve of one segment, starting with a move.
+fn offset_segment(seg: &PathSeg, distance: f64) -> BezPath {
+    let cubic = match *seg {
+        PathSeg::Line(line) => {
+            let normal = left_normal(line.p1 - line.p0) * distance;
+            let mut bez = BezPath::new();
+            bez.move_to(line.p0 + normal);
+            bez.line_to(line.p1 + normal);
+            return bez;
+        }
+        PathSeg::Quad(quad) => quad.raise(),
+        PathSeg::Cubic(cubic) => cubic,
+    };
+    kurbo::fit_to_bezpath(
+        &CubicOffset::new_regularized(cubic, distance, EPSILON),
+        ACCURACY,
+    )
+}
+
+/// Connect the offset of `prev` drawn so far to the start of the offset of
+/// `next`, through their intersection at outer corners.
+///
+/// Between two lines, the offset lines are cut or extended to meet instead:
+/// the end of the path is moved to their intersection, which is returned,
+/// and the offset of `next` continues from there.
+fn join(bez: &mut BezPath, prev: &PathSeg, next: &PathSeg, distance: f64) -> Option<Point> {
+    let from = bez.elements().last().and_then(PathEl::end_point)?;
+    let (t_prev, t_next) = (end_tangent(prev), start_tangent(next));
+    let to = next.start() + left_normal(t_next) * distance;
+    if from.distance(to) < EPSILON {
+        return None;
+    }
+    let cross = t_prev.cross(t_next);
+    if cross.abs() > EPSILON {
+        // Where the tangent lines at the ends of the offset segments meet.
+        let along = (to - from).cross(t_next) / cross;
+        let miter = from + t_prev * along;
+        let near = miter.distance(next.start()) <= MITER_LIMIT * distance.abs();
+        let lines = matches!(prev, PathSeg::Line(_)) && matches!(next, PathSeg::Line(_));
+        if lines && near {
+            let last = bez.elements().len() - 1;
+            bez.elements_mut()[last] = PathEl::LineTo(miter);
+            return Some(miter);
+        }
+        let outer = along > 0.0 && (to - miter).dot(t_next) > 0.0;
+        if outer && near {
+            bez.line_to(miter);
+        }
+    }
+    bez.line_to(to);
+    None
+}
+
+/// Close the two sides of the stroke of an open path with its caps.
+fn open_stroke(
+    bez: &BezPath,
+    left_side: &BezPath,
+    right_side: &BezPath,
+    width: f64,
+    style: &StrokeStyle,
+) -> BezPath {
+    let segments: Vec<PathSeg> = bez
+        .segments()
+        .filter(|seg| start_tangent(seg).hypot() > EPSILON)
+        .collect();
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return BezPath::new();
+    };
+    let mut outline = left_side.clone();
+    let right_reversed = right_side.reverse_subpaths();
+    let end = right_reversed
+        .elements()
+        .first()
+        .and_then(PathEl::end_point);
+    let start = left_side.elements().first().and_then(PathEl::end_point);
+    let (Some(end), Some(start)) = (end, start) else {
+        return BezPath::new();
+    };
+    cap(&mut outline, end, end_tangent(last), width, style.end_cap);
+    outline.extend(right_reversed.elements().iter().skip(1).copied());
+    cap(
+        &mut outline,
+        start,
+        -start_tangent(first),
+        width,
+        style.start_cap,
+    );
+    outline.close_path();
+    outline
+}
+
+/// Draw a cap from the current point to `to`, across an end of the path
+/// whose outward direction is `direction`.
+fn cap(bez: &mut BezPath, to: Point, direction: Vec2, width: f64, cap: LineCap) {
+    let Some(from) = bez.elements().last().and_then(PathEl::end_point) else {
+        return;
+    };
+    let direction = direction.normalize();
+    match cap {
+        LineCap::Butt => bez.line_to(to),
+        LineCap::Square => {
+            let extension = direction * width.abs() / 2.0;
+            bez.line_to(from + extension);
+            bez.line_to(to + extension);
+            bez.line_to(to);
+        }
+        LineCap::Round => {
+            let center = from.midpoint(to);
+            let radius = from.distance(to) / 2.0;
+            let start_angle = (from - center).atan2();
+            // Sweep through the outward direction.
+            let sweep = if (from - center).cross(direction) < 0.0 {
+                -PI
+            } else {
+                PI
+            };
+            let arc = Arc {
+                center,
+                radii: Vec2::new(radius, radius),
+                start_angle,
+                sweep_angle: sweep,
+                x_rotation: 0.0,
+            };
+            arc.to_cubic_beziers(ACCURACY, |c1, c2, p| bez.curve_to(c1, c2, p));
+        }
+    }
+}
+
+fn left_normal(direction: Vec2) -> Vec2 {
+    Vec2::new(-direction.y, direction.x).normalize()
+}
+
+/// The direction a segment leaves its start in, skipping coincident
+/// control points.
+fn start_tangent(seg: &PathSeg) -> Vec2 {
+    let points = match *seg {
+        PathSeg::Line(l) => vec![l.p0, l.p1],
+        PathSeg::Quad(q) => vec![q.p0, q.p1, q.p2],
+        PathSeg::Cubic(c) => vec![c.p0, c.p1, c.p2, c.p3],
+    };
+    points[1..]
+        .iter()
+        .map(|&p| p - points[0])
+        .find(|v| v.hypot() > EPSILON)
+        .unwrap_or(Vec2::ZERO)
+}
+
+fn end_tangent(seg: &PathSeg) -> Vec2 {
+    -start_tangent(&seg.reverse())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurbo::{Rect, Shape as _};
+
+    fn square(size: f64) -> Path {
+        let mut path = Path::new(true);
+        for (x, y) in [(size, 0.0), (size, size), (0.0, size), (0.0, 0.0)] {
+            path.add((x, y), NodeType::Line);
+        }
+        path
+    }
+
+    fn assert_rect_close(a: Rect, b: Rect) {
+        let close = [a.x0 - b.x0, a.y0 - b.y0, a.x1 - b.x1, a.y1 - b.y1]
+            .iter()
+            .all(|d| d.abs() < 1e-6);
+        assert!(close, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn round_corners() {
+        let mut path = square(100.0);
+        assert_eq!(path.round_corners(10.0), 4);
+        assert_eq!(path.nodes.len(), 16);
+        assert_rect_close(path.bounds().unwrap(), Rect::new(0.0, 0.0, 100.0, 100.0));
+        let on_curves: Vec<Point> = path
+            .nodes
+            .iter()
+            .filter(|n| n.node_type != NodeType::OffCurve)
+            .map(|n| n.pt)
+            .collect();
+        assert!(on_curves.contains(&Point::new(100.0, 90.0)));
+        assert!(on_curves.contains(&Point::new(90.0, 100.0)));
+        // The rounded corner is a quarter circle: its middle is a radius
+        // away from the center.
+        let area = path.to_bezpath().area();
+        assert!((area - (100.0 * 100.0 - (400.0 - 100.0 * PI))).abs() < 1.0);
+
+        // The radius shrinks to fit short segments.
+        let mut small = square(10.0);
+        small.round_corners(20.0);
+        assert_rect_close(small.bounds().unwrap(), Rect::new(0.0, 0.0, 10.0, 10.0));
+
+        // Ends of open paths and corners at curves stay.
+        let mut open = Path::new(false);
+        for (x, y) in [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)] {
+            open.add((x, y), NodeType::Line);
+        }
+        assert_eq!(open.round_corners(10.0), 1);
+        assert_eq!(open.nodes.len(), 6);
+    }
+
+    #[test]
+    fn offset() {
+        // Counter-clockwise, so the inside is on the left.
+        let path = square(100.0);
+        assert!(!path.is_clockwise());
+        let outside = path.offset(-10.0);
+        assert!(outside.closed);
+        assert_rect_close(
+            outside.bounds().unwrap(),
+            Rect::new(-10.0, -10.0, 110.0, 110.0),
+        );
+        assert_eq!(outside.nodes.len(), 4);
+        let inside = path.offset(10.0);
+        assert_rect_close(inside.bounds().unwrap(), Rect::new(10.0, 10.0, 90.0, 90.0));
+        assert_eq!(inside.nodes.len(), 4);
+
+        let mut circle = Path::new(true);
+        let bez = kurbo::Circle::new((0.0, 0.0), 100.0).to_path(0.01);
+        circle.nodes = Path::from_bezpath(&bez)[0].nodes.clone();
+        let offset = circle.offset(-20.0).to_bezpath();
+        let bounds = offset.bounding_box();
+        assert!((bounds.width() - 240.0).abs() < 1.0, "{bounds:?}");
+    }
+
+    #[test]
+    fn expand_stroke_attributes() {
+        let mut line = Path::new(false);
+        line.add((0.0, 0.0), NodeType::Line);
+        line.add((0.0, 100.0), NodeType::Line);
+        line.attr = Some(PathAttrs {
+            stroke_width: Some(30.0),
+            line_cap_end: Some(2.0),
+            stroke_pos: Some(1),
+            mask: Some(1),
+            ..Default::default()
+        });
+        let style = line.attr.as_ref().unwrap().stroke_style().unwrap();
+        assert_eq!(
+            style,
+            StrokeStyle {
+                width: 30.0,
+                height: None,
+                start_cap: LineCap::Butt,
+                end_cap: LineCap::Square,
+                position: StrokePosition::Right,
+            }
+        );
+        assert_eq!(PathAttrs::default().stroke_style(), None);
+
+        let mut layer = Layer::new("m01");
+        layer.shapes.push(Shape::Path(Box::new(line)));
+        layer.shapes.push(Shape::Path(Box::new(square(10.0))));
+        let expanded = layer.with_strokes_expanded();
+        assert_eq!(expanded.shapes.len(), 2);
+        let outline = expanded.paths().next().unwrap();
+        assert!(outline.closed);
+        // Going up, the right side is at positive x.
+        assert_rect_close(outline.bounds().unwrap(), Rect::new(0.0, 0.0, 30.0, 115.0));
+        assert_eq!(
+            outline.attr,
+            Some(PathAttrs {
+                mask: Some(1),
+                ..Default::default()
+            })
+        );
+        assert_eq!(expanded.shapes[1], layer.shapes[1]);
+    }
+
+    #[test]
+    fn stroke() {
+        let mut line = Path::new(false);
+        line.add((0.0, 0.0), NodeType::Line);
+        line.add((100.0, 0.0), NodeType::Line);
+        let bounds = |style: &StrokeStyle| {
+            let paths = line.stroke(style);
+            assert_eq!(paths.len(), 1);
+            assert!(paths[0].closed);
+            paths[0].bounds().unwrap()
+        };
+
+        let mut style = StrokeStyle::new(20.0);
+        assert_rect_close(bounds(&style), Rect::new(0.0, -10.0, 100.0, 10.0));
+        style.start_cap = LineCap::Square;
+        style.end_cap = LineCap::Round;
+        assert_rect_close(bounds(&style), Rect::new(-10.0, -10.0, 110.0, 10.0));
+        style.start_cap = LineCap::Butt;
+        style.end_cap = LineCap::Butt;
+        style.height = Some(40.0);
+        assert_rect_close(bounds(&style), Rect::new(0.0, -20.0, 100.0, 20.0));
+        style.height = None;
+        style.position = StrokePosition::Left;
+        assert_rect_close(bounds(&style), Rect::new(0.0, 0.0, 100.0, 20.0));
+
+        let paths = square(100.0).stroke(&StrokeStyle::new(10.0));
+        assert_eq!(paths.len(), 2);
+        assert_rect_close(
+            paths[1].bounds().unwrap(),
+            Rect::new(-5.0, -5.0, 105.0, 105.0),
+        );
+        assert_ne!(paths[0].is_clockwise(), paths[1].is_clockwise());
+    }
+}
@@ -4,8 +4,14 @@
 //! There are lots of other ways this could go, including something serde-like
 //! where it gets serialized to more Rust-native structures, proc macros, etc.
 
+use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
 use std::convert::Infallible;
+use std::hash::Hash;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{PoisonError, RwLock};
 use std::{fs, io};
 
 use kurbo::Point;
@@ -14,10 +20,16 @@ use thiserror::Error;
 use crate::from_plist::{
     ArrayConversionError, BoolConversionError, DownsizeToU16Error, FromPlist, VariantError,
 };
-use crate::plist::Plist;
+use crate::glyph_list::GlyphList;
+use crate::glyphs2::ConversionReport;
+use crate::id::Id;
+use crate::load::{GlyphError, LoadOptions};
+use crate::plist::{format_float, Plist};
 use crate::to_plist::ToPlist;
+use crate::validate::{Diagnostic, Severity};
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Font {
     #[plist(rename = ".appVersion", always_serialise)]
     pub app_version: String,
@@ -33,16 +45,21 @@ pub struct Font {
     pub version_minor: i64,
     #[plist(always_serialise)]
     pub units_per_em: u16, // Glyphs UI only allows for 16-16384 inclusive
+    /// The glyphs, shared between clones of the font until changed.
     #[plist(always_serialise)]
-    pub glyphs: Vec<Glyph>,
+    pub glyphs: GlyphList,
     #[plist(always_serialise)]
     pub font_master: Vec<FontMaster>,
     #[plist(always_serialise)]
     pub metrics: Vec<Metric>,
+    pub custom_parameters: Option<Vec<CustomParameter>>,
     pub axes: Option<Vec<Axis>>,
     pub numbers: Option<Vec<FontNumbers>>,
     pub stems: Option<Vec<FontStems>>,
     pub settings: Option<Settings>,
+    pub classes: Option<Vec<FeatureClass>>,
+    pub feature_prefixes: Option<Vec<FeaturePrefix>>,
+    pub features: Option<Vec<Feature>>,
     pub instances: Option<Vec<Instance>>,
     #[plist(rename = "kerningLTR")]
     pub kerning_ltr: Option<HashMap<String, norad::Kerning>>,
@@ -52,9 +69,122 @@ pub struct Font {
 
     #[plist(rest)]
     pub other_stuff: HashMap<String, Plist>,
+
+    /// The format the font was read from, if it was upgraded to the format
+    /// the model follows when loading.
+    #[plist(skip)]
+    pub upgraded_from: Option<FormatVersion>,
+    /// The format [`Font::save`] writes, Glyphs 3 unless the font was
+    /// converted with [`Font::convert_to`].
+    #[plist(skip)]
+    pub save_format: FormatVersion,
+    /// The text encoding the font was read in. Fonts are always saved in
+    /// UTF-8.
+    #[plist(skip)]
+    pub encoding: Encoding,
+    #[plist(skip)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) glyph_index: GlyphIndex,
+}
+
+/// Lazily built maps from glyph names, codepoints and production names to
+/// positions in [`Font::glyphs`].
+///
+/// Each map remembers the [`GlyphList`] version it was built for and is
+/// rebuilt once glyphs have been added, removed or moved. Editing glyphs in
+/// place keeps the map: a hit is checked against the glyph it points to, and
+/// only a stale hit or a miss after an edit rebuilds it.
+#[derive(Debug, Default)]
+pub(crate) struct GlyphIndex {
+    names: LookupCache<String>,
+    codepoints: LookupCache<char>,
+    production_names: LookupCache<String>,
+}
+
+#[derive(Debug, Default)]
+struct LookupCache<K>(RwLock<Option<(ListVersion, HashMap<K, usize>)>>);
+
+/// The version of a [`GlyphList`] and of the edits to its glyphs.
+type ListVersion = (u64, u64);
+
+impl<K: Eq + Hash> LookupCache<K> {
+    /// Find the first glyph with the key, where `keys` lists the keys of a
+    /// glyph for rebuilding the map.
+    fn find<Q, I>(&self, glyphs: &GlyphList, key: &Q, keys: impl Fn(&Glyph) -> I) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        I: IntoIterator<Item = K>,
+    {
+        let version = (glyphs.version(), glyphs.edits());
+        if let Some((built, map)) = &*self.0.read().unwrap_or_else(PoisonError::into_inner) {
+            if built.0 == version.0 {
+                match map.get(key) {
+                    Some(&i) if keys(&glyphs[i]).into_iter().any(|k| k.borrow() == key) => {
+                        return Some(i);
+                    }
+                    None if built.1 == version.1 => return None,
+                    _ => (),
+                }
+            }
+        }
+
+        let mut map = HashMap::with_capacity(glyphs.len());
+        for (i, glyph) in glyphs.iter().enumerate() {
+            for key in keys(glyph) {
+                map.entry(key).or_insert(i);
+            }
+        }
+        let i = map.get(key).copied();
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some((version, map));
+        i
+    }
+}
+
+impl GlyphIndex {
+    fn find(&self, glyphs: &GlyphList, glyphname: &str) -> Option<usize> {
+        self.names
+            .find(glyphs, glyphname, |g| [g.glyphname.to_string()])
+    }
+
+    fn find_codepoint(&self, glyphs: &GlyphList, codepoint: char) -> Option<usize> {
+        let codepoints = |g: &Glyph| g.unicode.iter().flat_map(|u| u.iter()).collect::<Vec<_>>();
+        self.codepoints.find(glyphs, &codepoint, codepoints)
+    }
+
+    fn find_production_name(&self, glyphs: &GlyphList, production_name: &str) -> Option<usize> {
+        self.production_names
+            .find(glyphs, production_name, |g| g.production.clone())
+    }
+}
+
+// The index is a cache: copies start out empty and it doesn't take part in
+// comparisons.
+impl Clone for GlyphIndex {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for GlyphIndex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CustomParameter {
+    #[plist(always_serialise)]
+    pub name: String,
+    #[plist(always_serialise)]
+    pub value: Plist,
+    #[plist(default)]
+    pub disabled: bool,
+}
+
+#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Axis {
     #[plist(always_serialise)]
     pub name: String,
@@ -65,6 +195,7 @@ pub struct Axis {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metric {
     pub filter: Option<String>,
     pub name: Option<String>,
@@ -72,6 +203,7 @@ pub struct Metric {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MetricType {
     Ascender,
     Baseline,
@@ -86,11 +218,13 @@ pub enum MetricType {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FontNumbers {
     pub name: String,
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FontStems {
     pub name: String,
     pub filter: Option<String>,
@@ -99,21 +233,77 @@ pub struct FontStems {
 }
 
 #[derive(Clone, Debug, Default, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Settings {
     #[plist(default)]
     pub disables_automatic_alignment: bool,
     #[plist(default)]
     pub disables_nice_names: bool,
+    pub grid_length: Option<u16>,
+    pub grid_sub_division: Option<u16>,
+
+    #[plist(rest)]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeatureClass {
+    #[plist(always_serialise)]
+    pub name: String,
+    #[plist(default, always_serialise)]
+    pub code: String,
+    #[plist(default)]
+    pub automatic: bool,
+    #[plist(default)]
+    pub disabled: bool,
+    pub notes: Option<String>,
+
+    #[plist(rest)]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeaturePrefix {
+    #[plist(default)]
+    pub name: String,
+    #[plist(default, always_serialise)]
+    pub code: String,
+    #[plist(default)]
+    pub automatic: bool,
+    #[plist(default)]
+    pub disabled: bool,
+    pub notes: Option<String>,
+
+    #[plist(rest)]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Feature {
+    #[plist(always_serialise)]
+    pub tag: String,
+    #[plist(default, always_serialise)]
+    pub code: String,
+    #[plist(default)]
+    pub automatic: bool,
+    #[plist(default)]
+    pub disabled: bool,
+    pub notes: Option<String>,
 
     #[plist(rest)]
     pub other_stuff: HashMap<String, Plist>,
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Glyph {
     #[plist(always_serialise)]
     pub glyphname: norad::Name,
     // The Unicode values(s) for the glyph.
+    #[cfg_attr(feature = "serde", serde(with = "serde_codepoints"))]
     pub unicode: Option<norad::Codepoints>,
     #[plist(always_serialise)]
     pub layers: Vec<Layer>,
@@ -151,6 +341,7 @@ pub struct Glyph {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     Bidi,
     Ltr,
@@ -160,6 +351,7 @@ pub enum Direction {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Case {
     None,
     Upper,
@@ -169,13 +361,15 @@ pub enum Case {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Layer {
     pub attr: Option<LayerAttr>,
     pub name: Option<String>,
     pub background: Option<BackgroundLayer>,
-    pub associated_master_id: Option<String>,
+    pub background_image: Option<BackgroundImage>,
+    pub associated_master_id: Option<Id>,
     #[plist(always_serialise)]
-    pub layer_id: String,
+    pub layer_id: Id,
     #[plist(always_serialise)]
     pub width: f64,
     pub vert_width: Option<f64>,
@@ -199,6 +393,7 @@ pub struct Layer {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Index(i64),
     GreyAlpha(u8, u8),
@@ -206,7 +401,17 @@ pub enum Color {
     Cmyka(u8, u8, u8, u8, u8),
 }
 
+/// One layer of a COLRv0 color glyph: the glyph drawing the layer and the
+/// palette entry it is filled with.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorLayerGlyph {
+    pub glyph_name: String,
+    pub palette_index: u16,
+}
+
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LayerAttr {
     pub axis_rules: Option<Vec<AxisRules>>,
     pub coordinates: Option<Vec<f64>>,
@@ -216,12 +421,14 @@ pub struct LayerAttr {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AxisRules {
     pub min: Option<f64>,
     pub max: Option<f64>,
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BackgroundLayer {
     pub anchors: Option<Vec<Anchor>>,
     #[plist(default)]
@@ -231,20 +438,47 @@ pub struct BackgroundLayer {
     pub other_stuff: HashMap<String, Plist>,
 }
 
+/// An image shown behind a layer for tracing, placed by its own transform.
+#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackgroundImage {
+    /// The image file, relative to the Glyphs file or absolute.
+    #[plist(always_serialise)]
+    pub image_path: String,
+    /// The rotation in degrees.
+    #[plist(default)]
+    pub angle: f64,
+    /// The visible part of the image as x, y, width and height, in pixels.
+    pub crop: Option<Vec<f64>>,
+    #[plist(default)]
+    pub pos: Point,
+    pub scale: Option<Scale>,
+    #[plist(default)]
+    pub locked: bool,
+
+    #[plist(rest)]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shape {
     Path(Box<Path>),
     Component(Component),
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Path {
     pub attr: Option<PathAttrs>,
+    #[plist(always_serialise)]
     pub closed: bool,
+    #[plist(default, always_serialise)]
     pub nodes: Vec<Node>,
 }
 
-#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[derive(Clone, Debug, Default, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathAttrs {
     pub line_cap_start: Option<f64>,
     pub line_cap_end: Option<f64>,
@@ -260,6 +494,7 @@ pub struct PathAttrs {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathShadow {
     pub blur: String,
     pub color: Vec<i64>,
@@ -268,20 +503,25 @@ pub struct PathShadow {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathGradient {
     pub colors: Vec<Vec<Color>>, // TODO: Destructure this once relevant.
+    #[plist(always_serialise)]
     pub start: Point,
+    #[plist(always_serialise)]
     pub end: Point,
     pub r#type: String, // TODO: Make enum once relevant.
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub pt: Point,
     pub node_type: NodeType,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeType {
     Line,
     LineSmooth,
@@ -293,6 +533,7 @@ pub enum NodeType {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Component {
     #[plist(rename = "ref", always_serialise)]
     pub reference: String,
@@ -301,17 +542,25 @@ pub struct Component {
     pub pos: Option<Point>,
     pub scale: Option<Scale>,
     pub slant: Option<Scale>,
+    /// -1 when automatic alignment is disabled for the component, 1 when it
+    /// is forced.
+    pub alignment: Option<i64>,
+    /// The anchor of the preceding components that the component attaches
+    /// to, when not the default one.
+    pub anchor: Option<String>,
     #[plist(rest)]
     pub other_stuff: HashMap<String, Plist>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scale {
     pub horizontal: f64,
     pub vertical: f64,
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Anchor {
     #[plist(always_serialise)]
     pub name: String,
@@ -323,12 +572,14 @@ pub struct Anchor {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AnchorOrientation {
     Center,
     Right,
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GuideLine {
     pub name: Option<String>,
     #[plist(default)]
@@ -346,9 +597,10 @@ pub struct GuideLine {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FontMaster {
     #[plist(always_serialise)]
-    pub id: String,
+    pub id: Id,
     #[plist(always_serialise)]
     pub name: String,
     #[plist(always_serialise)]
@@ -364,7 +616,8 @@ pub struct FontMaster {
     pub other_stuff: HashMap<String, Plist>,
 }
 
-#[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[derive(Clone, Debug, Default, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MasterMetric {
     #[plist(default)]
     pub pos: f64,
@@ -373,6 +626,7 @@ pub struct MasterMetric {
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instance {
     #[plist(always_serialise)]
     pub name: String,
@@ -399,6 +653,7 @@ pub struct Instance {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InstanceType {
     Variable,
 }
@@ -416,13 +671,14 @@ impl Default for Font {
             glyphs: vec![Glyph {
                 layers: vec![Layer {
                     width: 200.0,
-                    ..Layer::new("m01", None)
+                    ..Layer::new("m01")
                 }],
                 ..Glyph::new(
                     norad::Name::new("space").unwrap(),
                     Some(norad::Codepoints::new(vec![' '])),
                 )
-            }],
+            }]
+            .into(),
             font_master: vec![FontMaster {
                 metric_values: vec![
                     MasterMetric {
@@ -440,6 +696,7 @@ impl Default for Font {
                 ],
                 ..FontMaster::new("m01", "Regular")
             }],
+            custom_parameters: Default::default(),
             metrics: vec![
                 Metric {
                     filter: None,
@@ -461,25 +718,300 @@ impl Default for Font {
             numbers: Default::default(),
             stems: Default::default(),
             settings: Default::default(),
+            classes: Default::default(),
+            feature_prefixes: Default::default(),
+            features: Default::default(),
             instances: Default::default(),
             kerning_ltr: Default::default(),
             kerning_rtl: Default::default(),
             kerning_vertical: Default::default(),
             other_stuff: Default::default(),
+            upgraded_from: None,
+            save_format: FormatVersion::Glyphs3,
+            encoding: Encoding::Utf8,
+            glyph_index: Default::default(),
         }
     }
 }
 
+/// A version of the Glyphs file format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FormatVersion {
+    /// Glyphs 2, which files without a `.formatVersion` are in.
+    Glyphs2,
+    /// Glyphs 3, the format the model follows.
+    #[default]
+    Glyphs3,
+}
+
+/// The text encoding of a font file.
+///
+/// Glyphs.app writes UTF-8, but older files and other tools may use a byte
+/// order mark, UTF-16 or, from classic Mac OS, MacRoman. Files that aren't
+/// valid UTF-8 are only read as MacRoman if they look like it, so for other
+/// encodings pass one with [`Font::from_bytes_with_encoding`] or
+/// [`LoadOptions::encoding`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark.
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    MacRoman,
+}
+
+/// A font file read into a plist, upgraded to the format the model follows.
+pub(crate) struct FontFile {
+    pub(crate) plist: Plist,
+    /// The format the file was upgraded from, if it was.
+    pub(crate) upgraded_from: Option<FormatVersion>,
+    pub(crate) encoding: Encoding,
+}
+
+/// Options controlling how [`Font::save_with_options`] saves a font.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SaveOptions {
+    /// Keep the previous version of the file, with `.bak` appended to its
+    /// name.
+    pub backup: bool,
+    /// Refuse to save fonts that [`Font::validate`] finds errors in.
+    pub validate: bool,
+    /// Leave out the editor's UI state, see [`Font::strip_ui_state`].
+    pub strip_ui_state: bool,
+}
+
+/// The settings that only concern the editor.
+const UI_SETTINGS: &[&str] = &[
+    "keyboardIncrement",
+    "keyboardIncrementBig",
+    "keyboardIncrementHuge",
+    "previewRemoveOverlap",
+];
+
+/// The number of decimal places [`Font::normalize`] rounds numbers to.
+const NORMALIZED_DECIMALS: i32 = 5;
+
+/// The date [`Font::normalize`] gives fonts.
+const NORMALIZED_DATE: &str = "1970-01-01 00:00:00 +0000";
+
+/// Round the numbers of a plist to [`NORMALIZED_DECIMALS`], making those that
+/// end up whole integers.
+fn normalize_numbers(plist: &mut Plist) {
+    match plist {
+        Plist::Dictionary(dict) => dict.values_mut().for_each(normalize_numbers),
+        Plist::Array(array) => array.iter_mut().for_each(normalize_numbers),
+        Plist::Float(f) => {
+            let scale = 10f64.powi(NORMALIZED_DECIMALS);
+            let rounded = (*f * scale).round() / scale;
+            *plist = match rounded.fract() == 0.0 {
+                true => Plist::Integer(rounded as i64),
+                false => Plist::Float(rounded),
+            };
+        }
+        Plist::String(_) | Plist::Integer(_) => (),
+    }
+}
+
+/// Write a file by writing a temporary file in the same directory and
+/// renaming it over the target, optionally copying the previous version to
+/// a `.bak` file first.
+///
+/// The temporary file is named after the process and a counter, and only
+/// created if it doesn't exist yet, so concurrent saves of the same file
+/// never write to the same temporary file.
+pub(crate) fn write_atomic(path: &std::path::Path, content: &str, backup: bool) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let with_suffix = |suffix: &str| {
+        let mut name = file_name.to_os_string();
+        name.push(suffix);
+        path.with_file_name(name)
+    };
+    static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+    let (temp_path, file) = loop {
+        let n = TEMP_FILES.fetch_add(1, Ordering::Relaxed);
+        let temp_path = with_suffix(&format!(".{}-{n}.tmp", std::process::id()));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => break (temp_path, file),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    };
+
+    let write = |mut file: fs::File| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        if backup && path.exists() {
+            fs::copy(path, with_suffix(".bak"))?;
+        }
+        fs::rename(&temp_path, path)
+    };
+    write(file).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum FontSaveError {
+    #[error("failed to write file: {0}")]
+    Io(#[from] io::Error),
+    #[error("glyphs {0:?} and {1:?} would be saved to the same file")]
+    GlyphFileClash(String, String),
+    #[error("refused to save invalid font: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Invalid(Vec<Diagnostic>),
+}
+
+impl std::str::FromStr for Font {
+    type Err = FontLoadError;
+
+    /// Load a font from the contents of a `.glyphs` file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (plist, upgraded_from) = Self::parse_plist(s)?;
+        let mut font = Self::from_font_plist(plist)?;
+        font.upgraded_from = upgraded_from;
+        Ok(font)
+    }
+}
+
+/// The characters of the bytes from 0x80 up in MacRoman, with 0xDB as the
+/// euro sign and 0xF0 as the Apple logo in the private use area.
+const MAC_ROMAN: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è', //
+    'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü', //
+    '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø', //
+    '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø', //
+    '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{A0}', 'À', 'Ã', 'Õ', 'Œ', 'œ', //
+    '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ', //
+    '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô', //
+    '\u{F8FF}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ', //
+];
+
+/// Decode the contents of a font file, in the given encoding or else the one
+/// detected: UTF-16 or UTF-8 by their byte order mark, then UTF-8, and
+/// MacRoman if the contents aren't valid UTF-8 but look like MacRoman.
+pub(crate) fn decode(
+    bytes: &[u8],
+    encoding: Option<Encoding>,
+) -> Result<(Cow<'_, str>, Encoding), FontLoadError> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let chunks = bytes.chunks_exact(2);
+        if !chunks.remainder().is_empty() {
+            return Err(FontLoadError::Encoding);
+        }
+        let units = chunks.map(|c| from_bytes([c[0], c[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map(Cow::Owned)
+            .map_err(|_| FontLoadError::Encoding)
+    };
+    let utf8 = |bytes| {
+        std::str::from_utf8(bytes)
+            .map(Cow::Borrowed)
+            .map_err(|_| FontLoadError::Encoding)
+    };
+    let mac_roman = |bytes: &[u8]| {
+        let text = bytes
+            .iter()
+            .map(|&b| match b {
+                0..=0x7F => b as char,
+                _ => MAC_ROMAN[b as usize - 0x80],
+            })
+            .collect();
+        Cow::Owned(text)
+    };
+    let strip = |bom: &[u8]| bytes.strip_prefix(bom).unwrap_or(bytes);
+
+    let encoding = encoding.unwrap_or(match bytes {
+        [0xFF, 0xFE, ..] => Encoding::Utf16Le,
+        [0xFE, 0xFF, ..] => Encoding::Utf16Be,
+        [0xEF, 0xBB, 0xBF, ..] => Encoding::Utf8Bom,
+        _ if looks_like_mac_roman(bytes) => Encoding::MacRoman,
+        _ => Encoding::Utf8,
+    });
+    let text = match encoding {
+        Encoding::Utf8 | Encoding::Utf8Bom => utf8(strip(&[0xEF, 0xBB, 0xBF]))?,
+        Encoding::Utf16Le => utf16(strip(&[0xFF, 0xFE]), u16::from_le_bytes)?,
+        Encoding::Utf16Be => utf16(strip(&[0xFE, 0xFF]), u16::from_be_bytes)?,
+        Encoding::MacRoman => mac_roman(bytes),
+    };
+    Ok((text, encoding))
+}
+
+/// Whether bytes that aren't valid UTF-8 look like MacRoman text: no control
+/// characters but line breaks and tabs, and none of the multi-byte sequences
+/// of UTF-8, which would rather make it broken UTF-8.
+fn looks_like_mac_roman(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).is_err()
+        && bytes
+            .iter()
+            .all(|&b| !b.is_ascii_control() || matches!(b, b'\t' | b'\n' | b'\r'))
+        && bytes.utf8_chunks().all(|chunk| chunk.valid().is_ascii())
+}
+
 #[derive(Debug, Error)]
 pub enum FontLoadError {
-    #[error("failed to read file: {0}")]
+    #[error("failed to read file")]
     Io(#[from] io::Error),
-    #[error("failed to parse file as plist: {0}")]
+    #[error("file is not valid text in its encoding; if it isn't UTF-8, pass the encoding")]
+    Encoding,
+    #[error("failed to parse file as plist")]
     ParsePlist(#[from] crate::plist::Error),
-    #[error("Glyphs 2 files are not supported")]
-    Glyphs2,
     #[error(transparent)]
     ParseGlyphs(#[from] GlyphsFromPlistError),
+    #[error("failed to convert glyphs: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Glyphs(Vec<GlyphError>),
+    #[error("invalid font: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Invalid(Vec<Diagnostic>),
+    /// An error loading the given file, which for packages is the file in
+    /// the package that failed if the error is about a single file. The
+    /// error itself is its source, so reports that walk the sources name it
+    /// once.
+    #[error("failed to load {}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: Box<FontLoadError>,
+    },
+}
+
+impl FontLoadError {
+    /// The file the error happened in, if known.
+    pub fn path(&self) -> Option<&std::path::Path> {
+        match self {
+            FontLoadError::File { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The error without the file it happened in.
+    pub fn into_inner(self) -> FontLoadError {
+        match self {
+            FontLoadError::File { source, .. } => *source,
+            error => error,
+        }
+    }
+
+    /// Record the file the error happened in, unless a more specific one is
+    /// known already.
+    pub(crate) fn in_file(self, path: &std::path::Path) -> FontLoadError {
+        match self {
+            FontLoadError::File { .. } => self,
+            error => FontLoadError::File {
+                path: path.to_path_buf(),
+                source: Box::new(error),
+            },
+        }
+    }
 }
 
 impl Font {
@@ -488,29 +1020,325 @@ impl Font {
         Self::default()
     }
 
+    /// Load a font from a `.glyphs` file or a `.glyphspackage` directory.
+    ///
+    /// Errors are [`FontLoadError::File`], naming the file that failed.
     pub fn load(path: impl AsRef<std::path::Path>) -> Result<Font, FontLoadError> {
-        let contents = fs::read_to_string(path)?;
-        let plist = Plist::parse(&contents)?;
+        let path = path.as_ref();
+        Self::load_file(path).map_err(|e| e.in_file(path))
+    }
+
+    fn load_file(path: &std::path::Path) -> Result<Font, FontLoadError> {
+        let file = Self::load_plist(path)?;
+        let mut font = Self::from_font_plist(file.plist)?;
+        font.upgraded_from = file.upgraded_from;
+        font.encoding = file.encoding;
+        Ok(font)
+    }
+
+    /// Convert the plist of a font.
+    #[cfg(not(feature = "rayon"))]
+    pub(crate) fn from_font_plist(plist: Plist) -> Result<Font, GlyphsFromPlistError> {
+        let mut font: Font = plist.try_into()?;
+        font.share_ids();
+        Ok(font)
+    }
+
+    /// Convert the plist of a font, converting the glyphs in parallel. They
+    /// keep their order, and the first glyph that fails to convert gives the
+    /// error.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn from_font_plist(plist: Plist) -> Result<Font, GlyphsFromPlistError> {
+        use rayon::prelude::*;
 
+        let Plist::Dictionary(mut dict) = plist else {
+            return plist.try_into();
+        };
+        let glyphs = match dict.remove("glyphs") {
+            Some(Plist::Array(glyphs)) => glyphs,
+            other => {
+                dict.extend(other.map(|glyphs| ("glyphs".to_string(), glyphs)));
+                return Plist::from(dict).try_into();
+            }
+        };
+        dict.insert("glyphs".into(), Plist::Array(Vec::new()));
+        let mut font: Font = Plist::from(dict).try_into()?;
+        let glyphs: Vec<Result<Glyph, _>> = glyphs.into_par_iter().map(Glyph::try_from).collect();
+        font.glyphs = glyphs
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .map_err(ArrayConversionError::Element)?;
+        font.share_ids();
+        Ok(font)
+    }
+
+    /// Load a font from the contents of a `.glyphs` file, in the encoding
+    /// detected as described for [`Encoding`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Font, FontLoadError> {
+        Self::from_decoded(decode(bytes, None)?)
+    }
+
+    /// Load a font from the contents of a `.glyphs` file in the given
+    /// encoding.
+    pub fn from_bytes_with_encoding(
+        bytes: &[u8],
+        encoding: Encoding,
+    ) -> Result<Font, FontLoadError> {
+        Self::from_decoded(decode(bytes, Some(encoding))?)
+    }
+
+    fn from_decoded((text, encoding): (Cow<str>, Encoding)) -> Result<Font, FontLoadError> {
+        let mut font: Font = text.parse()?;
+        font.encoding = encoding;
+        Ok(font)
+    }
+
+    /// Read the plist of a font file or package, upgraded to the format the
+    /// model follows.
+    pub(crate) fn load_plist(path: &std::path::Path) -> Result<FontFile, FontLoadError> {
+        Self::load_plist_with(path, &LoadOptions::default())
+    }
+
+    /// Read the plist of a font like [`Font::load_plist`], in the encoding
+    /// the options give, and parsing files straight from a memory mapping if
+    /// they say so and the `mmap` feature is enabled.
+    pub(crate) fn load_plist_with(
+        path: &std::path::Path,
+        options: &LoadOptions,
+    ) -> Result<FontFile, FontLoadError> {
+        if path.is_dir() {
+            return Ok(FontFile {
+                plist: Self::read_package(path)?,
+                upgraded_from: None,
+                encoding: Encoding::Utf8,
+            });
+        }
+        let parse = |(text, encoding): (Cow<str>, Encoding)| {
+            let (plist, upgraded_from) = Self::parse_plist(&text)?;
+            Ok(FontFile {
+                plist,
+                upgraded_from,
+                encoding,
+            })
+        };
+        #[cfg(feature = "mmap")]
+        if options.memory_map {
+            let file = fs::File::open(path)?;
+            // SAFETY: the mapping is only read while parsing. Another process
+            // changing the file meanwhile can garble what is parsed, as with
+            // any concurrent write, but the mapping stays valid until dropped.
+            let map = unsafe { memmap2::Mmap::map(&file)? };
+            return parse(decode(&map, options.encoding)?);
+        }
+        parse(decode(&fs::read(path)?, options.encoding)?)
+    }
+
+    /// Parse the plist of a font file, upgraded to the format the model
+    /// follows, and the format it was upgraded from.
+    fn parse_plist(contents: &str) -> Result<(Plist, Option<FormatVersion>), FontLoadError> {
+        let mut plist = Plist::parse(contents)?;
+        let upgraded_from = Self::upgrade_plist(&mut plist);
+        Ok((plist, upgraded_from))
+    }
+
+    /// Upgrade the plist of a font file to the format the model follows,
+    /// returning the format it was upgraded from.
+    pub(crate) fn upgrade_plist(plist: &mut Plist) -> Option<FormatVersion> {
         // The formatVersion key is only present in Glyphs 3+ files.
-        if plist.get(".formatVersion").is_none() {
-            return Err(FontLoadError::Glyphs2);
+        match plist {
+            Plist::Dictionary(dict) if !dict.contains_key(".formatVersion") => {
+                crate::glyphs2::upgrade(dict);
+                Some(FormatVersion::Glyphs2)
+            }
+            _ => None,
         }
+    }
 
-        Ok(plist.try_into()?)
+    /// Save the font, in the Glyphs 2 format if it was converted to that with
+    /// [`Font::convert_to`].
+    ///
+    /// The file is written next to the target and then renamed over it, so
+    /// a failed save leaves the previous version intact.
+    pub fn save(&self, path: &std::path::Path) -> Result<(), FontSaveError> {
+        self.save_with_options(path, &SaveOptions::default())
+    }
+
+    /// Save the font like [`Font::save`], with options.
+    pub fn save_with_options(
+        &self,
+        path: &std::path::Path,
+        options: &SaveOptions,
+    ) -> Result<(), FontSaveError> {
+        if options.validate {
+            self.check_valid()?;
+        }
+        let plist = match options.strip_ui_state {
+            true => {
+                let mut font = self.clone();
+                font.strip_ui_state();
+                font.to_file_plist()
+            }
+            false => self.to_file_plist(),
+        };
+        write_atomic(path, &plist.to_string(), options.backup)?;
+        Ok(())
+    }
+
+    /// Write the font to `writer` as [`Font::save`] writes it to a file.
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), FontSaveError> {
+        writer.write_all(self.to_plist_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// The font as [`Font::save`] writes it to a file.
+    pub fn to_plist_string(&self) -> String {
+        self.to_file_plist().to_string()
     }
 
-    pub fn save(self, path: &std::path::Path) -> Result<(), String> {
-        let plist = self.to_plist();
-        fs::write(path, plist.to_string()).map_err(|e| format!("{:?}", e))
+    /// Save the font in the Glyphs 2 format, for older tools, returning what
+    /// the format can't represent and was left out.
+    pub fn save_v2(&self, path: &std::path::Path) -> Result<ConversionReport, FontSaveError> {
+        let mut plist = self.to_plist().into_hashmap();
+        let report = crate::glyphs2::downgrade(&mut plist);
+        write_atomic(path, &Plist::from(plist).to_string(), false)?;
+        Ok(report)
     }
 
+    /// The plist of the font as it's saved, in the Glyphs 2 format if it was
+    /// converted to that.
+    pub(crate) fn to_file_plist(&self) -> Plist {
+        match self.save_format {
+            FormatVersion::Glyphs3 => self.to_plist(),
+            FormatVersion::Glyphs2 => {
+                let mut plist = self.to_plist().into_hashmap();
+                crate::glyphs2::downgrade(&mut plist);
+                plist.into()
+            }
+        }
+    }
+
+    /// Refuse fonts with validation errors.
+    fn check_valid(&self) -> Result<(), FontSaveError> {
+        let errors: Vec<Diagnostic> = self
+            .validate()
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(FontSaveError::Invalid(errors)),
+        }
+    }
+
+    /// Remove the editor's UI state, which changes as the font is worked on
+    /// without changing the font: the font's display strings, the keyboard
+    /// increments and preview settings, the masters' icons and visibility,
+    /// the instances' visibility, when glyphs were last changed, and which
+    /// layers are shown.
+    pub fn strip_ui_state(&mut self) {
+        self.other_stuff.remove("DisplayStrings");
+        if let Some(settings) = &mut self.settings {
+            for key in UI_SETTINGS {
+                settings.other_stuff.remove(*key);
+            }
+        }
+        for master in &mut self.font_master {
+            master.visible = true;
+            master.other_stuff.remove("iconName");
+        }
+        for instance in self.instances.iter_mut().flatten() {
+            instance.visible = true;
+        }
+        for glyph in &mut self.glyphs {
+            glyph.other_stuff.remove("lastChange");
+            for layer in &mut glyph.layers {
+                layer.other_stuff.remove("visible");
+            }
+        }
+    }
+
+    /// Make the font write the same bytes whenever its contents are the same,
+    /// for keeping sources in version control, like ufonormalizer does for
+    /// UFOs.
+    ///
+    /// Numbers are rounded to five decimal places, dropping floating point
+    /// noise and negative zeros, the date is set to the start of 1970, and the
+    /// display strings are removed. Dictionaries such as the kerning and user
+    /// data are always written with sorted keys. See also
+    /// [`Font::strip_ui_state`].
+    pub fn normalize(&mut self) -> Result<(), GlyphsFromPlistError> {
+        self.date = NORMALIZED_DATE.into();
+        self.other_stuff.remove("DisplayStrings");
+
+        let mut plist = self.to_plist();
+        normalize_numbers(&mut plist);
+        let (upgraded_from, save_format, encoding) =
+            (self.upgraded_from, self.save_format, self.encoding);
+        *self = Font::from_font_plist(plist)?;
+        (self.upgraded_from, self.save_format, self.encoding) =
+            (upgraded_from, save_format, encoding);
+        Ok(())
+    }
+
+    /// Look up a glyph by name, using an index of the glyph list that is
+    /// kept up to date on demand.
     pub fn get_glyph(&self, glyphname: &str) -> Option<&Glyph> {
-        self.glyphs.iter().find(|g| g.glyphname == glyphname)
+        let i = self.glyph_index.find(&self.glyphs, glyphname)?;
+        Some(&self.glyphs[i])
     }
 
     pub fn get_glyph_mut(&mut self, glyphname: &str) -> Option<&mut Glyph> {
-        self.glyphs.iter_mut().find(|g| g.glyphname == glyphname)
+        let i = self.glyph_index.find(&self.glyphs, glyphname)?;
+        Some(&mut self.glyphs[i])
+    }
+
+    /// Iterate over the glyphs that have a master layer for the master with
+    /// the given ID, together with that layer.
+    pub fn layers_for_master<'a>(
+        &'a self,
+        master_id: &'a str,
+    ) -> impl Iterator<Item = (&'a Glyph, &'a Layer)> + 'a {
+        self.glyphs
+            .iter()
+            .filter_map(move |glyph| Some((glyph, glyph.master_layer(master_id)?)))
+    }
+
+    pub fn contains_glyph(&self, glyphname: &str) -> bool {
+        self.glyph_index.find(&self.glyphs, glyphname).is_some()
+    }
+
+    /// The first glyph encoding the codepoint.
+    pub fn glyph_for_codepoint(&self, codepoint: char) -> Option<&Glyph> {
+        let i = self.glyph_index.find_codepoint(&self.glyphs, codepoint)?;
+        Some(&self.glyphs[i])
+    }
+
+    /// The first glyph with the given production name. Glyphs without an
+    /// explicit production name aren't considered.
+    pub fn glyph_by_production_name(&self, production_name: &str) -> Option<&Glyph> {
+        let i = self
+            .glyph_index
+            .find_production_name(&self.glyphs, production_name)?;
+        Some(&self.glyphs[i])
+    }
+
+    /// The palettes from the "Color Palettes" custom parameter.
+    ///
+    /// Colors that can't be parsed are skipped.
+    pub fn color_palettes(&self) -> Vec<Vec<Color>> {
+        let Some(palettes) = self.custom_parameter_as::<&[Plist]>("Color Palettes") else {
+            return Vec::new();
+        };
+        palettes
+            .iter()
+            .filter_map(Plist::as_array)
+            .map(|palette| {
+                palette
+                    .iter()
+                    .filter_map(|color| color.clone().try_into().ok())
+                    .collect()
+            })
+            .collect()
     }
 }
 
@@ -548,15 +1376,55 @@ impl Glyph {
     pub fn get_layer(&self, layer_id: &str) -> Option<&Layer> {
         self.layers.iter().find(|l| l.layer_id == layer_id)
     }
+
+    /// The master layer for the master with the given ID.
+    ///
+    /// Unlike [`Glyph::get_layer`], this never returns a special layer whose
+    /// ID happens to match, and accepts master layers that name their own
+    /// master as the associated one.
+    pub fn master_layer(&self, master_id: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| {
+            l.layer_id == master_id
+                && l.associated_master_id.as_deref().unwrap_or(master_id) == master_id
+        })
+    }
+
+    /// Iterate over the color palette layers associated with a master, in
+    /// drawing order.
+    pub fn color_palette_layers<'a>(
+        &'a self,
+        master_id: &'a str,
+    ) -> impl Iterator<Item = &'a Layer> {
+        self.layers.iter().filter(move |l| {
+            l.associated_master_id.as_deref() == Some(master_id)
+                && l.color_palette_index().is_some()
+        })
+    }
+
+    /// The COLRv0 layer list of this glyph in a master.
+    ///
+    /// Layer glyphs are named `<glyphname>.color<i>`, like glyphsLib does.
+    pub fn color_layer_glyphs(&self, master_id: &str) -> Vec<ColorLayerGlyph> {
+        self.color_palette_layers(master_id)
+            .filter_map(Layer::color_palette_index)
+            .enumerate()
+            .map(|(i, palette_index)| ColorLayerGlyph {
+                glyph_name: format!("{}.color{i}", self.glyphname),
+                palette_index,
+            })
+            .collect()
+    }
 }
 
 impl Layer {
-    pub fn new(layer_id: impl Into<String>, associated_master_id: Option<String>) -> Self {
+    /// A master layer, with the ID of the master it belongs to.
+    pub fn new(layer_id: impl Into<Id>) -> Self {
         Self {
             attr: Default::default(),
             name: Default::default(),
             background: Default::default(),
-            associated_master_id: associated_master_id.map(Into::into),
+            background_image: Default::default(),
+            associated_master_id: None,
             layer_id: layer_id.into(),
             width: 600.0,
             vert_width: Default::default(),
@@ -576,6 +1444,15 @@ impl Layer {
         }
     }
 
+    /// A layer other than a master layer, like a brace or backup layer,
+    /// associated with the master with the given ID.
+    pub fn new_associated(layer_id: impl Into<Id>, master_id: impl Into<Id>) -> Self {
+        Self {
+            associated_master_id: Some(master_id.into()),
+            ..Self::new(layer_id)
+        }
+    }
+
     pub fn is_master_layer(&self) -> bool {
         self.associated_master_id.is_none()
     }
@@ -608,6 +1485,18 @@ impl Layer {
             .unwrap_or(false)
     }
 
+    /// The palette entry a color palette layer is filled with.
+    ///
+    /// The foreground color (`*`) is returned as `0xFFFF`, as in the COLR
+    /// table. Returns `None` for other layers.
+    pub fn color_palette_index(&self) -> Option<u16> {
+        match self.attr.as_ref()?.other_stuff.get("colorPalette")? {
+            Plist::Integer(index) => (*index).try_into().ok(),
+            Plist::String(s) if s == "*" => Some(0xFFFF),
+            _ => None,
+        }
+    }
+
     pub fn is_svg_layer(&self) -> bool {
         self.attr
             .as_ref()
@@ -625,10 +1514,125 @@ impl Layer {
     pub fn coordinates(&self) -> Option<&[f64]> {
         self.attr.as_ref().and_then(|a| a.coordinates.as_deref())
     }
+
+    /// Iterate over the paths of the layer, skipping components.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.shapes.iter().filter_map(|shape| match shape {
+            Shape::Path(path) => Some(path.as_ref()),
+            Shape::Component(_) => None,
+        })
+    }
+
+    /// Iterate over the paths of the layer mutably, skipping components.
+    pub fn paths_mut(&mut self) -> impl Iterator<Item = &mut Path> {
+        self.shapes.iter_mut().filter_map(|shape| match shape {
+            Shape::Path(path) => Some(path.as_mut()),
+            Shape::Component(_) => None,
+        })
+    }
+
+    /// Iterate over the components of the layer, skipping paths.
+    pub fn components(&self) -> impl Iterator<Item = &Component> {
+        self.shapes.iter().filter_map(|shape| match shape {
+            Shape::Component(component) => Some(component),
+            Shape::Path(_) => None,
+        })
+    }
+
+    /// Iterate over the components of the layer mutably, skipping paths.
+    pub fn components_mut(&mut self) -> impl Iterator<Item = &mut Component> {
+        self.shapes.iter_mut().filter_map(|shape| match shape {
+            Shape::Component(component) => Some(component),
+            Shape::Path(_) => None,
+        })
+    }
+
+    /// Add a path after the existing shapes.
+    pub fn push_path(&mut self, path: Path) {
+        self.shapes.push(Shape::Path(Box::new(path)));
+    }
+
+    /// Add a component after the existing shapes.
+    pub fn push_component(&mut self, component: Component) {
+        self.shapes.push(Shape::Component(component));
+    }
+
+    /// The anchor with the given name.
+    pub fn anchor(&self, name: &str) -> Option<&Anchor> {
+        self.anchors.iter().flatten().find(|a| a.name == name)
+    }
+
+    /// Move the anchor with the given name, or add it if there is none.
+    pub fn set_anchor(&mut self, name: &str, pos: impl Into<Point>) {
+        let anchors = self.anchors.get_or_insert_with(Vec::new);
+        match anchors.iter_mut().find(|a| a.name == name) {
+            Some(anchor) => anchor.pos = pos.into(),
+            None => anchors.push(Anchor {
+                name: name.to_string(),
+                orientation: None,
+                pos: pos.into(),
+                user_data: Default::default(),
+            }),
+        }
+    }
+
+    /// Remove the anchor with the given name. The anchor list is dropped
+    /// once it is empty.
+    pub fn remove_anchor(&mut self, name: &str) -> Option<Anchor> {
+        let anchors = self.anchors.as_mut()?;
+        let anchor = anchors.remove(anchors.iter().position(|a| a.name == name)?);
+        if anchors.is_empty() {
+            self.anchors = None;
+        }
+        Some(anchor)
+    }
+
+    /// Exchange the shapes and anchors of the layer with those of its
+    /// background. Anything else stored in the background stays there.
+    pub fn swap_with_background(&mut self) {
+        let background = self.background.get_or_insert_with(|| BackgroundLayer {
+            anchors: None,
+            shapes: Vec::new(),
+            other_stuff: HashMap::new(),
+        });
+        std::mem::swap(&mut self.shapes, &mut background.shapes);
+        std::mem::swap(&mut self.anchors, &mut background.anchors);
+        if background.shapes.is_empty()
+            && background.anchors.is_none()
+            && background.other_stuff.is_empty()
+        {
+            self.background = None;
+        }
+    }
+
+    /// Replace the shapes and anchors of the background with copies of the
+    /// layer's.
+    pub fn copy_to_background(&mut self) {
+        let shapes = self.shapes.clone();
+        let anchors = self.anchors.clone();
+        match &mut self.background {
+            Some(background) => {
+                background.shapes = shapes;
+                background.anchors = anchors;
+            }
+            None => {
+                self.background = Some(BackgroundLayer {
+                    anchors,
+                    shapes,
+                    other_stuff: HashMap::new(),
+                })
+            }
+        }
+    }
+
+    /// Remove the background, returning it.
+    pub fn clear_background(&mut self) -> Option<BackgroundLayer> {
+        self.background.take()
+    }
 }
 
 impl FontMaster {
-    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+    pub fn new(id: impl Into<Id>, name: impl Into<String>) -> Self {
         Self {
             id: id.into(),
             name: name.into(),
@@ -652,6 +1656,78 @@ impl FontMaster {
     ) -> impl Iterator<Item = (&Metric, &MasterMetric)> {
         font.metrics.iter().zip(self.metric_values.iter())
     }
+
+    /// The master's metric values, looked up by the metric they belong to.
+    pub fn resolved_metrics<'a>(&'a self, font: &'a Font) -> ResolvedMetrics<'a> {
+        ResolvedMetrics {
+            metrics: self.iter_metrics(font).collect(),
+        }
+    }
+}
+
+/// The metric values of a master, paired with the font's metric definitions.
+///
+/// Metrics of a type come in two kinds: one that applies to all glyphs and
+/// any number scoped to the glyphs matching a filter. The typed accessors
+/// return the former.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedMetrics<'a> {
+    metrics: Vec<(&'a Metric, &'a MasterMetric)>,
+}
+
+impl<'a> ResolvedMetrics<'a> {
+    /// The value of the unfiltered metric of the given type.
+    pub fn get(&self, metric_type: &MetricType) -> Option<&'a MasterMetric> {
+        self.metrics
+            .iter()
+            .find(|(m, _)| m.filter.is_none() && m.r#type.as_ref() == Some(metric_type))
+            .map(|(_, value)| *value)
+    }
+
+    /// The values of the metrics of the given type that are scoped to a
+    /// filter, with the filter.
+    pub fn filtered(
+        &self,
+        metric_type: &'a MetricType,
+    ) -> impl Iterator<Item = (&'a str, &'a MasterMetric)> + '_ {
+        self.metrics.iter().filter_map(move |(m, value)| {
+            let filter = m.filter.as_deref()?;
+            (m.r#type.as_ref() == Some(metric_type)).then_some((filter, *value))
+        })
+    }
+
+    /// The value of a custom metric, identified by name rather than type.
+    pub fn named(&self, name: &str) -> Option<&'a MasterMetric> {
+        self.metrics
+            .iter()
+            .find(|(m, _)| m.r#type.is_none() && m.name.as_deref() == Some(name))
+            .map(|(_, value)| *value)
+    }
+
+    pub fn ascender(&self) -> Option<f64> {
+        self.get(&MetricType::Ascender).map(|m| m.pos)
+    }
+
+    pub fn descender(&self) -> Option<f64> {
+        self.get(&MetricType::Descender).map(|m| m.pos)
+    }
+
+    pub fn baseline(&self) -> Option<f64> {
+        self.get(&MetricType::Baseline).map(|m| m.pos)
+    }
+
+    pub fn x_height(&self) -> Option<f64> {
+        self.get(&MetricType::XHeight).map(|m| m.pos)
+    }
+
+    pub fn cap_height(&self) -> Option<f64> {
+        self.get(&MetricType::CapHeight).map(|m| m.pos)
+    }
+
+    /// The italic angle in degrees, as entered in Glyphs.
+    pub fn italic_angle(&self) -> Option<f64> {
+        self.get(&MetricType::ItalicAngle).map(|m| m.pos)
+    }
 }
 
 impl Settings {
@@ -680,7 +1756,7 @@ impl Instance {
 }
 
 #[derive(Debug, Error)]
-#[error("name must be a string or a float with value infinite/NaN")]
+#[error("name must be a string or a number")]
 pub struct NameConversionError;
 
 impl TryFrom<Plist> for norad::Name {
@@ -694,6 +1770,9 @@ impl TryFrom<Plist> for norad::Name {
             // it is parsed as a float instead.
             Plist::Float(f) if f.is_infinite() => Ok(Self::new("infinity").unwrap()),
             Plist::Float(f) if f.is_nan() => Ok(Self::new("nan").unwrap()),
+            // Glyphs with numbers for names, written unquoted.
+            Plist::Integer(i) => Ok(Self::new(&i.to_string()).unwrap()),
+            Plist::Float(f) => Ok(Self::new(&format_float(f)).unwrap()),
             _ => Err(NameConversionError),
         }
     }
@@ -723,7 +1802,7 @@ impl TryFrom<Plist> for AnchorOrientation {
 }
 
 impl ToPlist for AnchorOrientation {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         match self {
             AnchorOrientation::Center => Plist::String("center".into()),
             AnchorOrientation::Right => Plist::String("right".into()),
@@ -769,14 +1848,20 @@ impl TryFrom<Plist> for Color {
 }
 
 impl ToPlist for Color {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         match self {
-            Color::Index(int) => int.into(),
-            Color::GreyAlpha(g, a) => Plist::Array(vec![g.into(), a.into()]),
-            Color::Rgba(r, g, b, a) => Plist::Array(vec![r.into(), g.into(), b.into(), a.into()]),
-            Color::Cmyka(c, m, y, k, a) => {
-                Plist::Array(vec![c.into(), m.into(), y.into(), k.into(), a.into()])
+            Color::Index(int) => (*int).into(),
+            Color::GreyAlpha(g, a) => Plist::Array(vec![(*g).into(), (*a).into()]),
+            Color::Rgba(r, g, b, a) => {
+                Plist::Array(vec![(*r).into(), (*g).into(), (*b).into(), (*a).into()])
             }
+            Color::Cmyka(c, m, y, k, a) => Plist::Array(vec![
+                (*c).into(),
+                (*m).into(),
+                (*y).into(),
+                (*k).into(),
+                (*a).into(),
+            ]),
         }
     }
 }
@@ -804,7 +1889,7 @@ impl TryFrom<Plist> for Direction {
 }
 
 impl ToPlist for Direction {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         match self {
             Direction::Bidi => "BIDI".to_string().into(),
             Direction::Ltr => "LTR".to_string().into(),
@@ -840,7 +1925,7 @@ impl TryFrom<Plist> for Case {
 }
 
 impl ToPlist for Case {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         match self {
             Case::None => "noCase".to_string().into(),
             Case::Upper => "upper".to_string().into(),
@@ -898,7 +1983,7 @@ impl std::fmt::Display for MetricType {
 }
 
 impl ToPlist for MetricType {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         self.to_string().into()
     }
 }
@@ -921,7 +2006,7 @@ impl TryFrom<Plist> for InstanceType {
 }
 
 impl ToPlist for InstanceType {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         match self {
             InstanceType::Variable => "variable".to_string().into(),
         }
@@ -966,16 +2051,16 @@ impl TryFrom<Plist> for Shape {
 }
 
 impl ToPlist for Shape {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         match self {
-            Shape::Path(path) => ToPlist::to_plist(*path),
+            Shape::Path(path) => ToPlist::to_plist(&**path),
             Shape::Component(component) => ToPlist::to_plist(component),
         }
     }
 }
 
 impl ToPlist for norad::Name {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         self.to_string().into()
     }
 }
@@ -1019,7 +2104,7 @@ impl TryFrom<Plist> for norad::Codepoints {
 }
 
 impl ToPlist for norad::Codepoints {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         assert!(!self.is_empty());
         if self.len() == 1 {
             Plist::Integer(self.iter().next().unwrap() as i64)
@@ -1029,6 +2114,27 @@ impl ToPlist for norad::Codepoints {
     }
 }
 
+/// Serializing codepoints as a list of characters, as norad doesn't.
+#[cfg(feature = "serde")]
+mod serde_codepoints {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        codepoints: &Option<norad::Codepoints>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let codepoints: Option<Vec<char>> = codepoints.as_ref().map(|cps| cps.iter().collect());
+        codepoints.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<norad::Codepoints>, D::Error> {
+        let codepoints = Option::<Vec<char>>::deserialize(deserializer)?;
+        Ok(codepoints.map(norad::Codepoints::new))
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum NodeConversionError {
     #[error("nodes can only be parsed from an array of length 3")]
@@ -1106,7 +2212,7 @@ impl std::str::FromStr for NodeType {
 }
 
 impl NodeType {
-    fn glyphs_str(&self) -> &'static str {
+    pub(crate) fn glyphs_str(&self) -> &'static str {
         match self {
             NodeType::Line => "l",
             NodeType::LineSmooth => "ls",
@@ -1120,7 +2226,7 @@ impl NodeType {
 }
 
 impl ToPlist for Node {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         Plist::Array(vec![
             self.pt.x.into(),
             self.pt.y.into(),
@@ -1170,7 +2276,7 @@ impl TryFrom<Plist> for Point {
 }
 
 impl ToPlist for Point {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         Plist::Array(vec![self.x.into(), self.y.into()])
     }
 }
@@ -1219,7 +2325,7 @@ impl TryFrom<Plist> for Scale {
 }
 
 impl ToPlist for Scale {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         Plist::Array(vec![self.horizontal.into(), self.vertical.into()])
     }
 }
@@ -1247,8 +2353,55 @@ impl Path {
     }
 }
 
+impl Component {
+    /// The transformation applied to the referenced glyph, composed in the
+    /// same order glyphsLib uses: translate, rotate, scale, then slant.
+    pub fn transform(&self) -> kurbo::Affine {
+        let offset = self.pos.unwrap_or_default().to_vec2();
+        let rotation = self.rotation.unwrap_or(0.0).to_radians();
+        let (scale_x, scale_y) = self
+            .scale
+            .as_ref()
+            .map(|s| (s.horizontal, s.vertical))
+            .unwrap_or((1.0, 1.0));
+        // Slant is stored in degrees, the skew matrix wants tangents.
+        let (skew_x, skew_y) = self
+            .slant
+            .as_ref()
+            .map(|s| {
+                (
+                    s.horizontal.to_radians().tan(),
+                    s.vertical.to_radians().tan(),
+                )
+            })
+            .unwrap_or((0.0, 0.0));
+
+        // Warning: Don't use kurbo's .then_* methods because they apply the ops
+        // in the wrong order!
+        kurbo::Affine::translate(offset)
+            * kurbo::Affine::rotate(rotation)
+            * kurbo::Affine::scale_non_uniform(scale_x, scale_y)
+            * kurbo::Affine::skew(skew_x, skew_y)
+    }
+}
+
+impl BackgroundImage {
+    /// The transformation placing the image in the glyph: translate, rotate,
+    /// then scale.
+    pub fn transform(&self) -> kurbo::Affine {
+        let (scale_x, scale_y) = self
+            .scale
+            .as_ref()
+            .map(|s| (s.horizontal, s.vertical))
+            .unwrap_or((1.0, 1.0));
+        kurbo::Affine::translate(self.pos.to_vec2())
+            * kurbo::Affine::rotate(self.angle.to_radians())
+            * kurbo::Affine::scale_non_uniform(scale_x, scale_y)
+    }
+}
+
 impl ToPlist for HashMap<String, norad::Kerning> {
-    fn to_plist(self) -> Plist {
+    fn to_plist(&self) -> Plist {
         let mut kerning = HashMap::new();
 
         for (master_id, master_kerning) in self {
@@ -1256,7 +2409,7 @@ impl ToPlist for HashMap<String, norad::Kerning> {
             for (first, second_map) in master_kerning {
                 let mut second_dict = HashMap::new();
                 for (second, value) in second_map {
-                    second_dict.insert(second.to_string(), value.into());
+                    second_dict.insert(second.to_string(), (*value).into());
                 }
                 first_dict.insert(first.to_string(), second_dict.into());
             }
@@ -1389,7 +2542,18 @@ mod tests {
 
     #[test]
     fn parse_empty_font_glyphs2() {
-        Font::load("testdata/NewFont.glyphs").unwrap_err();
+        let font = Font::load("testdata/NewFont.glyphs").unwrap();
+        assert_eq!(font.upgraded_from, Some(FormatVersion::Glyphs2));
+        assert_eq!(font.format_version, Some(3));
+        assert_eq!(font.glyphs.len(), 66);
+        assert_eq!(font.font_master[0].name, "Regular");
+        assert_eq!(
+            font.glyph_for_codepoint('J').map(|g| g.glyphname.as_str()),
+            Some("J")
+        );
+
+        let glyphs3 = Font::load("testdata/NewFontG3.glyphs").unwrap();
+        assert_eq!(glyphs3.upgraded_from, None);
     }
 
     #[test]
@@ -1424,7 +2588,7 @@ mod tests {
         let contents = fs::read_to_string("testdata/NewFontG3.glyphs").unwrap();
         let plist = Plist::parse(&contents).unwrap();
         let font: Font = plist.clone().try_into().unwrap();
-        let plist_roundtrip = ToPlist::to_plist(font);
+        let plist_roundtrip = ToPlist::to_plist(&font);
 
         assert_eq!(plist, plist_roundtrip);
     }
@@ -1438,17 +2602,12 @@ mod tests {
 
         let disallowed = other_keys
             .difference(&HashSet::from([
-                // Explicitly unhandled:
-                "features".to_owned(),
-                "featurePrefixes".to_owned(),
                 // Potentially should be handled:
                 // TODO: Evaluate these.
                 "numbers".to_owned(),
                 "kerningVertical".to_owned(),
-                "customParameters".to_owned(),
                 "properties".to_owned(),
                 "DisplayStrings".to_owned(),
-                "classes".to_owned(),
                 "userData".to_owned(),
                 "stems".to_owned(),
                 "metrics".to_owned(),
@@ -1465,6 +2624,37 @@ mod tests {
         // TODO: Implement for nested structs.
     }
 
+    #[test]
+    fn color_layer_glyphs() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let glyph = font.get_glyph("C").unwrap();
+
+        assert_eq!(
+            glyph.color_layer_glyphs("m01"),
+            vec![
+                ColorLayerGlyph {
+                    glyph_name: "C.color0".into(),
+                    palette_index: 1,
+                },
+                ColorLayerGlyph {
+                    glyph_name: "C.color1".into(),
+                    palette_index: 0xFFFF,
+                },
+            ]
+        );
+        assert!(glyph
+            .color_layer_glyphs("C2ECF50A-02EF-4989-A14C-AF8E838D1105")
+            .is_empty());
+
+        assert_eq!(
+            font.color_palettes(),
+            vec![vec![
+                Color::Rgba(255, 0, 0, 255),
+                Color::Rgba(255, 128, 0, 255)
+            ]]
+        );
+    }
+
     #[test]
     fn error_on_unexpected_fields() {
         #[derive(Debug, FromPlist)]
@@ -1484,4 +2674,445 @@ mod tests {
         };
         assert_eq!(fields, vec![String::from("bar")]);
     }
+
+    #[test]
+    fn resolved_metrics() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let metrics = font.font_master[0].resolved_metrics(&font);
+
+        assert_eq!(metrics.ascender(), Some(800.0));
+        assert_eq!(metrics.cap_height(), Some(700.0));
+        assert_eq!(metrics.x_height(), Some(500.0));
+        assert_eq!(metrics.baseline(), Some(0.0));
+        assert_eq!(metrics.descender(), Some(-200.0));
+        assert_eq!(metrics.italic_angle(), Some(0.0));
+        let filtered = metrics
+            .filtered(&MetricType::XHeight)
+            .map(|(filter, _)| filter)
+            .collect::<Vec<_>>();
+        assert_eq!(filtered, ["case == 3"]);
+        assert!(metrics.named("A custom metric").is_some());
+    }
+
+    #[test]
+    fn master_layers() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let master_id = font.font_master[1].id.clone();
+        let layers = font.layers_for_master(&master_id).collect::<Vec<_>>();
+        assert_eq!(layers.len(), font.glyphs.len());
+        assert!(layers
+            .iter()
+            .all(|(_, l)| l.layer_id == master_id && l.is_master_layer()));
+
+        // A special layer with the master's ID isn't a master layer.
+        let glyph = font.get_glyph_mut("B").unwrap();
+        glyph.layers.retain(|l| l.layer_id != master_id);
+        glyph.layers.push(Layer::new_associated(&master_id, "m01"));
+        assert!(glyph.get_layer(&master_id).is_some());
+        assert!(glyph.master_layer(&master_id).is_none());
+        assert_eq!(
+            font.layers_for_master(&master_id).count(),
+            font.glyphs.len() - 1
+        );
+    }
+
+    #[test]
+    fn glyph_lookup() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        assert_eq!(font.get_glyph("B").unwrap().glyphname, "B");
+        assert!(font.contains_glyph("Smily"));
+        assert!(!font.contains_glyph("Z"));
+
+        // The index follows changes made through the public glyph list.
+        font.get_glyph_mut("B").unwrap().glyphname = norad::Name::new("Z").unwrap();
+        assert!(font.get_glyph("B").is_none());
+        assert_eq!(font.get_glyph("Z").unwrap().glyphname, "Z");
+        font.glyphs.remove(0);
+        assert!(!font.contains_glyph("A"));
+        assert_eq!(font.get_glyph("Z").unwrap().glyphname, "Z");
+        font.glyphs
+            .push(Glyph::new(norad::Name::new("B").unwrap(), None));
+        assert_eq!(font.get_glyph("B").unwrap().glyphname, "B");
+        assert_eq!(font.clone(), font);
+
+        // A glyph of the same name inserted earlier is found first.
+        let mut b = Glyph::new(norad::Name::new("B").unwrap(), None);
+        b.note = Some("first".into());
+        font.glyphs.insert(0, b);
+        assert_eq!(font.get_glyph("B").unwrap().note.as_deref(), Some("first"));
+
+        // A miss is answered from the index until the list changes.
+        let version = font.glyphs.version();
+        assert!(!font.contains_glyph("Y"));
+        assert!(!font.contains_glyph("Y"));
+        assert_eq!(font.glyphs.version(), version);
+        font.glyphs[0].glyphname = norad::Name::new("Y").unwrap();
+        assert!(font.contains_glyph("Y"));
+    }
+
+    #[test]
+    fn editing_glyphs_keeps_the_index() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let built = |font: &Font| {
+            let names = font.glyph_index.names.0.read().unwrap();
+            names.as_ref().map(|(version, _)| *version)
+        };
+        assert!(font.contains_glyph("A"));
+        let version = built(&font);
+        for note in ["one", "two", "three"] {
+            font.get_glyph_mut("B").unwrap().note = Some(note.into());
+            font.get_glyph_mut("A").unwrap().note = Some(note.into());
+        }
+        assert_eq!(built(&font), version);
+        assert_eq!(font.get_glyph("B").unwrap().note.as_deref(), Some("three"));
+
+        // A renamed glyph is noticed on the next lookup.
+        font.get_glyph_mut("B").unwrap().glyphname = norad::Name::new("Z").unwrap();
+        assert!(font.get_glyph("B").is_none());
+        assert_ne!(built(&font), version);
+        assert_eq!(font.get_glyph("Z").unwrap().glyphname, "Z");
+    }
+
+    #[test]
+    fn glyph_lookup_by_codepoint_and_production_name() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        assert_eq!(font.glyph_for_codepoint('Ä').unwrap().glyphname, "Ä");
+        assert!(font.glyph_for_codepoint('Z').is_none());
+        assert_eq!(
+            font.glyph_by_production_name("someSmily")
+                .unwrap()
+                .glyphname,
+            "Smily"
+        );
+        assert!(font.glyph_by_production_name("Smily").is_none());
+
+        let b = font.get_glyph_mut("B").unwrap();
+        b.unicode = Some(norad::Codepoints::new(['Z']));
+        b.production = Some("uni0042".into());
+        assert_eq!(font.glyph_for_codepoint('Z').unwrap().glyphname, "B");
+        assert!(font.glyph_for_codepoint('B').is_none());
+        assert_eq!(
+            font.glyph_by_production_name("uni0042").unwrap().glyphname,
+            "B"
+        );
+    }
+
+    #[test]
+    fn shape_accessors() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut layer = font.get_glyph("Ä").unwrap().layers[0].clone();
+        assert_eq!(layer.paths().count(), 0);
+        let references = layer
+            .components()
+            .map(|c| c.reference.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(references, ["A", "dieresiscomb"]);
+
+        for component in layer.components_mut() {
+            component.reference.push_str(".alt");
+        }
+        layer.push_path(Path::new(true));
+        layer.push_component(Component {
+            reference: "B".into(),
+            rotation: None,
+            pos: None,
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        });
+        for path in layer.paths_mut() {
+            path.add((1.0, 2.0), NodeType::Line);
+        }
+        assert_eq!(layer.shapes.len(), 4);
+        assert_eq!(layer.paths().next().unwrap().nodes.len(), 1);
+        let references = layer
+            .components()
+            .map(|c| c.reference.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(references, ["A.alt", "dieresiscomb.alt", "B"]);
+    }
+
+    #[test]
+    fn anchor_accessors() {
+        let mut layer = Layer::new("m01");
+        assert!(layer.anchor("top").is_none());
+        assert!(layer.remove_anchor("top").is_none());
+
+        layer.set_anchor("top", (100.0, 700.0));
+        layer.set_anchor("bottom", (100.0, 0.0));
+        layer.set_anchor("top", (120.0, 710.0));
+        assert_eq!(layer.anchors.as_ref().unwrap().len(), 2);
+        assert_eq!(layer.anchor("top").unwrap().pos, Point::new(120.0, 710.0));
+
+        assert_eq!(layer.remove_anchor("top").unwrap().name, "top");
+        assert!(layer.anchor("top").is_none());
+        layer.remove_anchor("bottom").unwrap();
+        assert_eq!(layer.anchors, None);
+    }
+
+    #[test]
+    fn background_operations() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let original = font.get_glyph("A").unwrap().layers[0].clone();
+        assert!(original.background.is_none());
+
+        let mut layer = original.clone();
+        layer.copy_to_background();
+        let background = layer.background.as_ref().unwrap();
+        assert_eq!(background.shapes, original.shapes);
+        assert_eq!(background.anchors, original.anchors);
+        assert_eq!(layer.shapes, original.shapes);
+
+        layer.shapes.clear();
+        layer.anchors = None;
+        layer.set_anchor("new", (1.0, 2.0));
+        layer.swap_with_background();
+        assert_eq!(layer.shapes, original.shapes);
+        assert_eq!(layer.anchors, original.anchors);
+        let background = layer.background.as_ref().unwrap();
+        assert!(background.shapes.is_empty());
+        assert_eq!(background.anchors.as_ref().unwrap().len(), 1);
+
+        assert!(layer.clear_background().is_some());
+        assert!(layer.background.is_none());
+        // Swapping with an empty background leaves no background behind.
+        layer.swap_with_background();
+        layer.swap_with_background();
+        assert_eq!(layer, original);
+    }
+
+    #[test]
+    fn from_font_plist() {
+        let contents = fs::read_to_string("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let plist = Plist::parse(&contents).unwrap();
+        let font = Font::from_font_plist(plist.clone()).unwrap();
+        assert_eq!(font, Font::try_from(plist.clone()).unwrap());
+
+        // The first glyph that fails to convert gives the error.
+        let mut dict = plist.into_hashmap();
+        let Some(Plist::Array(glyphs)) = dict.get_mut("glyphs") else {
+            panic!("expected glyphs");
+        };
+        glyphs[1] = Plist::Dictionary(HashMap::new());
+        if let Plist::Dictionary(glyph) = &mut glyphs[3] {
+            glyph.remove("layers");
+        }
+        let plist = Plist::from(dict);
+        let error = Font::from_font_plist(plist.clone()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            Font::try_from(plist).unwrap_err().to_string()
+        );
+        assert!(error.to_string().contains("glyphname"));
+    }
+
+    #[test]
+    fn save_atomically() {
+        let dir = std::env::temp_dir().join("glyphs_plist_save_atomically");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Font.glyphs");
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        font.save(&path).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        font.family_name = "Changed".into();
+        let options = SaveOptions {
+            backup: true,
+            ..Default::default()
+        };
+        font.save_with_options(&path, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("Font.glyphs.bak")).unwrap(),
+            saved
+        );
+        assert_eq!(Font::load(&path).unwrap(), font);
+        let mut written = Vec::new();
+        font.write_to(&mut written).unwrap();
+        assert_eq!(written, fs::read(&path).unwrap());
+        assert_eq!(font.to_plist_string().parse::<Font>().unwrap(), font);
+        // Only the font and its backup are left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // A failed save leaves nothing behind.
+        let error = font
+            .clone()
+            .save(&dir.join("missing").join("Font.glyphs"))
+            .unwrap_err();
+        assert!(matches!(error, FontSaveError::Io(e) if e.kind() == io::ErrorKind::NotFound));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Saves from several threads at once don't share a temporary file.
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| font.save(&path).unwrap());
+            }
+        });
+        assert_eq!(Font::load(&path).unwrap(), font);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Invalid fonts are refused if asked to.
+        font.font_master.clear();
+        let options = SaveOptions {
+            validate: true,
+            ..Default::default()
+        };
+        let error = font.save_with_options(&path, &options).unwrap_err();
+        assert!(matches!(error, FontSaveError::Invalid(errors) if !errors.is_empty()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn strip_ui_state() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let original = font.clone();
+        font.strip_ui_state();
+
+        assert!(!font.other_stuff.contains_key("DisplayStrings"));
+        let settings = font.settings.as_ref().unwrap();
+        assert!(!settings.other_stuff.contains_key("keyboardIncrement"));
+        assert!(settings.other_stuff.contains_key("keepAlternatesTogether"));
+        assert!(font.font_master.iter().all(|m| m.visible));
+        assert!(font.instances.iter().flatten().all(|i| i.visible));
+        for glyph in &font.glyphs {
+            assert!(!glyph.other_stuff.contains_key("lastChange"));
+            for layer in &glyph.layers {
+                assert!(!layer.other_stuff.contains_key("visible"));
+                // Smart component part selections aren't UI state.
+                let original = original.get_glyph(&glyph.glyphname).unwrap();
+                let original = original.get_layer(&layer.layer_id).unwrap();
+                assert_eq!(
+                    layer.other_stuff.get("partSelection"),
+                    original.other_stuff.get("partSelection")
+                );
+            }
+        }
+        assert_eq!(font.glyphs.len(), original.glyphs.len());
+    }
+
+    #[test]
+    fn normalize() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.date = "2024-05-06 07:08:09 +0000".into();
+        let layer = &mut font.get_glyph_mut("A").unwrap().layers[0];
+        layer.width = 600.1 + 0.2;
+        layer.user_data.insert("zero".into(), Plist::Float(-0.0));
+        let mut other = font.clone();
+        other.get_glyph_mut("A").unwrap().layers[0].width = 600.3;
+        other.date = "2025-01-01 00:00:00 +0000".into();
+
+        font.normalize().unwrap();
+        other.normalize().unwrap();
+        assert_eq!(font.to_plist_string(), other.to_plist_string());
+        assert_eq!(font.date, NORMALIZED_DATE);
+        assert!(!font.other_stuff.contains_key("DisplayStrings"));
+        let layer = &font.get_glyph("A").unwrap().layers[0];
+        assert_eq!(layer.width, 600.3);
+        assert_eq!(layer.user_data["zero"], Plist::Integer(0));
+        assert_eq!(font.format_version, other.format_version);
+
+        let normalized = font.clone();
+        font.normalize().unwrap();
+        assert_eq!(font, normalized);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let json = serde_json::to_string(&font).unwrap();
+        let from_json: Font = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json, font);
+        assert_eq!(
+            serde_json::to_value(Plist::from(vec![Plist::Integer(1), "a".to_string().into()]))
+                .unwrap(),
+            serde_json::json!([1, "a"])
+        );
+    }
+
+    #[test]
+    fn from_str_and_bytes() {
+        let path = "testdata/GlyphsFileFormatv3.glyphs";
+        let font = Font::load(path).unwrap();
+        let contents = fs::read_to_string(path).unwrap();
+        assert_eq!(contents.parse::<Font>().unwrap(), font);
+
+        let in_encoding = |encoding| Font {
+            encoding,
+            ..font.clone()
+        };
+        let mut bytes = vec![0xEF, 0xBB, 0xBF];
+        bytes.extend(contents.as_bytes());
+        assert_eq!(
+            Font::from_bytes(&bytes).unwrap(),
+            in_encoding(Encoding::Utf8Bom)
+        );
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(contents.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(
+            Font::from_bytes(&bytes).unwrap(),
+            in_encoding(Encoding::Utf16Le)
+        );
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(contents.encode_utf16().flat_map(u16::to_be_bytes));
+        assert_eq!(
+            Font::from_bytes(&bytes).unwrap(),
+            in_encoding(Encoding::Utf16Be)
+        );
+
+        assert!(matches!(
+            Font::from_bytes(&[0xFF, 0xFE, 0x7B]),
+            Err(FontLoadError::Encoding)
+        ));
+        assert!(matches!(
+            Font::from_bytes_with_encoding(&[0x7B, 0xFF, 0x7D], Encoding::Utf8),
+            Err(FontLoadError::Encoding)
+        ));
+        let glyphs2 = fs::read_to_string("testdata/NewFont.glyphs").unwrap();
+        let font: Font = glyphs2.parse().unwrap();
+        assert_eq!(font.upgraded_from, Some(FormatVersion::Glyphs2));
+    }
+
+    #[test]
+    fn mac_roman() {
+        let font = Font {
+            family_name: "Café “Spécial” €".into(),
+            ..Font::default()
+        };
+        let bytes: Vec<u8> = font
+            .to_plist_string()
+            .chars()
+            .map(|c| match MAC_ROMAN.iter().position(|&m| m == c) {
+                Some(i) => 0x80 + i as u8,
+                None => c as u8,
+            })
+            .collect();
+
+        let loaded = Font::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.encoding, Encoding::MacRoman);
+        assert_eq!(loaded.family_name, font.family_name);
+        // Forcing an encoding skips the detection.
+        let forced = Font::from_bytes_with_encoding(&bytes, Encoding::MacRoman).unwrap();
+        assert_eq!(forced, loaded);
+
+        // Broken UTF-8 isn't taken for MacRoman, unless asked to.
+        let mut broken = font.to_plist_string().into_bytes();
+        broken.push(0xFF);
+        assert!(matches!(
+            Font::from_bytes(&broken),
+            Err(FontLoadError::Encoding)
+        ));
+        assert!(Font::from_bytes_with_encoding(&broken, Encoding::MacRoman).is_ok());
+        assert!(matches!(
+            Font::from_bytes(&[b'{', 0x01, 0xFF, b'}']),
+            Err(FontLoadError::Encoding)
+        ));
+
+        let path = std::env::temp_dir().join("glyphs_plist_mac_roman.glyphs");
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(Font::load(&path).unwrap(), loaded);
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -3,11 +3,17 @@ use thiserror::Error;
 
 pub use glyphs_plist_derive::FromPlist;
 
-use crate::plist::Plist;
+use crate::plist::{format_float, Plist};
 
 impl From<Plist> for String {
+    /// Numbers are read as text, as files sometimes have unquoted numbers
+    /// for text, like a family named `2024`.
     fn from(plist: Plist) -> Self {
-        plist.into_string()
+        match plist {
+            Plist::Integer(i) => i.to_string(),
+            Plist::Float(f) => format_float(f),
+            plist => plist.into_string(),
+        }
     }
 }
 
@@ -0,0 +1,367 @@
+//! Glyph info from GlyphData XML files, like Glyphs' "Update Glyph Info".
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use quick_xml::events::Event;
+use thiserror::Error;
+
+use crate::{Case, Direction, Font, Glyph, Plist};
+
+const BUNDLED_GLYPH_DATA: &str = include_str!("../data/GlyphData.xml");
+
+#[derive(Debug, Error)]
+pub enum GlyphDataError {
+    #[error("failed to read glyph data: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse glyph data: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("bad attribute in glyph data: {0}")]
+    Attribute(#[from] quick_xml::events::attributes::AttrError),
+    #[error("glyph {0:?} has a bad Unicode value")]
+    BadUnicode(String),
+    #[error("glyph entry without a name")]
+    MissingName,
+}
+
+/// What GlyphData knows about a glyph name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlyphInfo {
+    pub name: String,
+    pub unicode: Option<char>,
+    pub category: Option<String>,
+    pub sub_category: Option<String>,
+    pub case: Option<Case>,
+    pub script: Option<String>,
+    pub direction: Option<Direction>,
+    /// The production name, if it differs from the name.
+    pub production: Option<String>,
+    pub alt_names: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// A glyph info database, loaded from GlyphData XML files.
+#[derive(Clone, Debug, Default)]
+pub struct GlyphData {
+    entries: Vec<GlyphInfo>,
+    names: HashMap<String, usize>,
+    codepoints: HashMap<char, usize>,
+}
+
+impl GlyphData {
+    /// The glyph data bundled with the crate.
+    ///
+    /// It only covers Basic Latin, Latin-1 and the common combining accents;
+    /// load the complete `GlyphData.xml` of Glyphs or glyphsLib with
+    /// [`GlyphData::from_xml`] for anything else.
+    pub fn bundled() -> &'static GlyphData {
+        static BUNDLED: OnceLock<GlyphData> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            GlyphData::from_xml(BUNDLED_GLYPH_DATA).expect("bundled glyph data is valid")
+        })
+    }
+
+    /// Parse a GlyphData XML file, made of `<glyph>` elements with the glyph
+    /// info as attributes.
+    pub fn from_xml(xml: &str) -> Result<GlyphData, GlyphDataError> {
+        let mut data = GlyphData::default();
+        let mut reader = quick_xml::Reader::from_str(xml);
+        loop {
+            match reader.read_event()? {
+                Event::Start(element) | Event::Empty(element)
+                    if element.name().as_ref() == b"glyph" =>
+                {
+                    let mut info = GlyphInfo::default();
+                    let mut unicode = None;
+                    for attribute in element.attributes() {
+                        let attribute = attribute?;
+                        let value = attribute.unescape_value()?.into_owned();
+                        match attribute.key.as_ref() {
+                            b"name" => info.name = value,
+                            b"unicode" => unicode = Some(value),
+                            b"category" => info.category = Some(value),
+                            b"subCategory" => info.sub_category = Some(value),
+                            b"case" => info.case = Some(parse_case(value)),
+                            b"script" => info.script = Some(value),
+                            b"direction" => {
+                                info.direction = Direction::try_from(Plist::String(value)).ok()
+                            }
+                            b"production" => info.production = Some(value),
+                            b"altNames" => {
+                                info.alt_names =
+                                    value.split(',').map(|n| n.trim().to_string()).collect()
+                            }
+                            b"description" => info.description = Some(value),
+                            _ => (),
+                        }
+                    }
+                    if info.name.is_empty() {
+                        return Err(GlyphDataError::MissingName);
+                    }
+                    if let Some(unicode) = unicode {
+                        info.unicode = Some(
+                            u32::from_str_radix(&unicode, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| GlyphDataError::BadUnicode(info.name.clone()))?,
+                        );
+                    }
+                    data.insert(info);
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+        Ok(data)
+    }
+
+    /// Parse a GlyphData XML file from disk.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<GlyphData, GlyphDataError> {
+        GlyphData::from_xml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Add the entries of other glyph data, replacing the entries for the
+    /// same names and Unicode values, like a custom GlyphData file does.
+    pub fn extend(&mut self, other: GlyphData) {
+        for info in other.entries {
+            self.insert(info);
+        }
+    }
+
+    /// The entry for a glyph name or one of its alternative names.
+    pub fn get(&self, name: &str) -> Option<&GlyphInfo> {
+        self.names.get(name).map(|&i| &self.entries[i])
+    }
+
+    /// The entry for a Unicode value.
+    pub fn for_codepoint(&self, codepoint: char) -> Option<&GlyphInfo> {
+        self.codepoints.get(&codepoint).map(|&i| &self.entries[i])
+    }
+
+    /// The info for any glyph name, including names the data doesn't list:
+    /// `uniXXXX` and `uXXXXX` names take the info of their Unicode value,
+    /// suffixed names like `a.sc` the info of their base glyph without its
+    /// Unicode value, and ligatures like `f_i` the info of their first part.
+    pub fn info(&self, name: &str) -> Option<GlyphInfo> {
+        if let Some(info) = self.get(name) {
+            return Some(info.clone());
+        }
+        if let Some(codepoint) = parse_uni_name(name) {
+            return Some(GlyphInfo {
+                name: name.to_string(),
+                unicode: Some(codepoint),
+                production: None,
+                alt_names: Vec::new(),
+                ..self.for_codepoint(codepoint)?.clone()
+            });
+        }
+        if let Some((base, suffix)) = name.split_once('.') {
+            let base = self.info(base)?;
+            let is_small_caps = matches!(suffix, "sc" | "smcp" | "c2sc");
+            return Some(GlyphInfo {
+                name: name.to_string(),
+                unicode: None,
+                case: if is_small_caps {
+                    Some(Case::SmallCaps)
+                } else {
+                    base.case
+                },
+                production: base.production.map(|p| format!("{p}.{suffix}")),
+                alt_names: Vec::new(),
+                description: None,
+                ..base
+            });
+        }
+        if !name.starts_with('_') && name.contains('_') {
+            let parts = name
+                .split('_')
+                .map(|part| self.info(part))
+                .collect::<Option<Vec<_>>>()?;
+            let production = parts.iter().any(|p| p.production.is_some()).then(|| {
+                let names = parts
+                    .iter()
+                    .map(|p| p.production.as_ref().unwrap_or(&p.name));
+                names.cloned().collect::<Vec<_>>().join("_")
+            });
+            let first = parts.into_iter().next()?;
+            return Some(GlyphInfo {
+                name: name.to_string(),
+                unicode: None,
+                sub_category: Some("Ligature".into()),
+                production,
+                alt_names: Vec::new(),
+                description: None,
+                ..first
+            });
+        }
+        None
+    }
+
+    fn insert(&mut self, info: GlyphInfo) {
+        let index = match self.names.get(&info.name) {
+            Some(&index) => index,
+            None => {
+                self.entries.push(GlyphInfo::default());
+                self.entries.len() - 1
+            }
+        };
+        for name in std::iter::once(&info.name).chain(&info.alt_names) {
+            self.names.entry(name.clone()).or_insert(index);
+        }
+        if let Some(codepoint) = info.unicode {
+            self.codepoints.insert(codepoint, index);
+        }
+        self.entries[index] = info;
+    }
+}
+
+impl Glyph {
+    /// Fill in the Unicode value, category, subcategory, case, script,
+    /// direction and production name from the glyph data, based on the glyph
+    /// name. Returns `false`, leaving the glyph alone, if the data knows
+    /// nothing about the name.
+    ///
+    /// Existing Unicode values are kept if they include the glyph data's.
+    pub fn assign_info(&mut self, db: &GlyphData) -> bool {
+        let Some(info) = db.info(self.glyphname.as_str()) else {
+            return false;
+        };
+        let has_codepoint = |c: char| {
+            self.unicode
+                .as_ref()
+                .is_some_and(|u| u.iter().any(|u| u == c))
+        };
+        match info.unicode {
+            Some(codepoint) if has_codepoint(codepoint) => (),
+            Some(codepoint) => self.unicode = Some(norad::Codepoints::new([codepoint])),
+            None => self.unicode = None,
+        }
+        self.category = info.category;
+        self.sub_category = info.sub_category;
+        self.case = info.case;
+        self.script = info.script;
+        self.direction = info.direction;
+        self.production = info.production;
+        true
+    }
+}
+
+impl Font {
+    /// Assign the info of the bundled glyph data to every glyph, returning
+    /// the number of glyphs it knew about. See [`Glyph::assign_info`].
+    pub fn update_glyph_info(&mut self) -> usize {
+        self.update_glyph_info_with(GlyphData::bundled())
+    }
+
+    /// Assign the info of the given glyph data to every glyph, returning the
+    /// number of glyphs it knew about. See [`Glyph::assign_info`].
+    pub fn update_glyph_info_with(&mut self, db: &GlyphData) -> usize {
+        self.glyphs
+            .iter_mut()
+            .map(|glyph| glyph.assign_info(db))
+            .filter(|&assigned| assigned)
+            .count()
+    }
+}
+
+fn parse_case(value: String) -> Case {
+    Case::try_from(Plist::String(value)).unwrap_or(Case::Other)
+}
+
+/// The Unicode value of a `uniXXXX` or `uXXXX[X[X]]` glyph name.
+fn parse_uni_name(name: &str) -> Option<char> {
+    let hex = match name.strip_prefix("uni") {
+        Some(hex) if hex.len() == 4 => hex,
+        _ => name
+            .strip_prefix('u')
+            .filter(|hex| (4..=6).contains(&hex.len()))?,
+    };
+    if !hex
+        .chars()
+        .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_lowercase())
+    {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GlyphData, GlyphDataError};
+    use crate::{Case, Font};
+
+    #[test]
+    fn glyph_info() {
+        let db = GlyphData::bundled();
+
+        let a = db.get("A").unwrap();
+        assert_eq!(a.unicode, Some('A'));
+        assert_eq!(a.category.as_deref(), Some("Letter"));
+        assert_eq!(a.case, Some(Case::Upper));
+        assert_eq!(db.get("guillemotleft").unwrap().name, "guillemetleft");
+        assert_eq!(db.for_codepoint('\u{308}').unwrap().name, "dieresiscomb");
+
+        let uni = db.info("uni00E9").unwrap();
+        assert_eq!(uni.name, "uni00E9");
+        assert_eq!(uni.unicode, Some('é'));
+        assert_eq!(uni.case, Some(Case::Lower));
+
+        let small_cap = db.info("a.sc").unwrap();
+        assert_eq!(small_cap.unicode, None);
+        assert_eq!(small_cap.case, Some(Case::SmallCaps));
+        let comb = db.info("dieresiscomb.case").unwrap();
+        assert_eq!(comb.production.as_deref(), Some("uni0308.case"));
+
+        let ligature = db.info("f_i").unwrap();
+        assert_eq!(ligature.sub_category.as_deref(), Some("Ligature"));
+        assert_eq!(ligature.production, None);
+
+        assert_eq!(db.info("notaglyph"), None);
+        assert_eq!(db.info("uni00e9"), None);
+    }
+
+    #[test]
+    fn custom_glyph_data() {
+        let mut db = GlyphData::bundled().clone();
+        let custom = GlyphData::from_xml(
+            r#"<glyphData>
+                <glyph name="A" unicode="0041" category="Letter" case="minor" production="Aprod" />
+                <glyph name="smiley" unicode="263A" category="Symbol" />
+            </glyphData>"#,
+        )
+        .unwrap();
+        db.extend(custom);
+        assert_eq!(db.get("A").unwrap().production.as_deref(), Some("Aprod"));
+        assert_eq!(db.get("A").unwrap().case, Some(Case::Other));
+        assert_eq!(db.for_codepoint('☺').unwrap().name, "smiley");
+        assert_eq!(db.get("B").unwrap().unicode, Some('B'));
+
+        assert!(matches!(
+            GlyphData::from_xml(r#"<glyph name="x" unicode="nothex" />"#),
+            Err(GlyphDataError::BadUnicode(name)) if name == "x"
+        ));
+    }
+
+    #[test]
+    fn update_glyph_info() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let count = font.update_glyph_info();
+        assert!(count >= 6);
+
+        let a = font.get_glyph("A").unwrap();
+        // The extra Unicode value is kept.
+        assert_eq!(a.unicode.as_ref().unwrap().len(), 2);
+        assert_eq!(a.category.as_deref(), Some("Letter"));
+        assert_eq!(a.script.as_deref(), Some("latin"));
+        let alternate = font.get_glyph("A.ss01").unwrap();
+        assert_eq!(alternate.unicode, None);
+        assert_eq!(alternate.case, Some(Case::Upper));
+        let alef = font.get_glyph("alef-ar").unwrap();
+        assert_eq!(alef.production.as_deref(), Some("uni0627"));
+        assert_eq!(
+            font.get_glyph("Smily").unwrap().category.as_deref(),
+            Some("Icon"),
+            "unknown glyphs are left alone"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Glyph storage shared between clones of a font, so that cloning a font for
+//! reading it elsewhere doesn't copy all of its outlines.
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::from_plist::ArrayConversionError;
+use crate::to_plist::ToPlist;
+use crate::{Glyph, GlyphsFromPlistError, Plist};
+
+/// The glyphs of a font, each copied on write.
+///
+/// Works like a `Vec<Glyph>`, so the glyphs are read and edited as usual.
+/// Clones share their glyphs until one is changed through a mutable
+/// reference, which copies just that glyph first.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct GlyphList {
+    glyphs: Vec<Arc<Glyph>>,
+    /// Changes whenever glyphs are added, removed or moved, so caches of the
+    /// list know when to rebuild. No two different lists share a version.
+    #[cfg_attr(feature = "serde", serde(skip, default = "next_version"))]
+    version: u64,
+    /// Changes whenever a glyph is handed out for editing, which may change
+    /// what caches know about it without moving it.
+    #[cfg_attr(feature = "serde", serde(skip, default = "next_version"))]
+    edits: u64,
+}
+
+fn next_version() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+pub(crate) type Iter<'a> =
+    std::iter::Map<std::slice::Iter<'a, Arc<Glyph>>, fn(&Arc<Glyph>) -> &Glyph>;
+pub(crate) type IterMut<'a> =
+    std::iter::Map<std::slice::IterMut<'a, Arc<Glyph>>, fn(&mut Arc<Glyph>) -> &mut Glyph>;
+
+impl GlyphList {
+    fn new(glyphs: Vec<Arc<Glyph>>) -> GlyphList {
+        GlyphList {
+            glyphs,
+            version: next_version(),
+            edits: next_version(),
+        }
+    }
+
+    /// The version of the list, which changes whenever glyphs are added,
+    /// removed or moved, but not when they are edited in place.
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Changes whenever any of the glyphs might have been edited in place.
+    pub(crate) fn edits(&self) -> u64 {
+        self.edits
+    }
+
+    /// The glyphs for adding, removing or moving, marking the list as
+    /// changed.
+    fn changed(&mut self) -> &mut Vec<Arc<Glyph>> {
+        self.version = next_version();
+        &mut self.glyphs
+    }
+
+    /// The glyphs for editing in place.
+    fn edited(&mut self) -> &mut Vec<Arc<Glyph>> {
+        self.edits = next_version();
+        &mut self.glyphs
+    }
+
+    /// Whether the two lists share all their glyphs, i.e. neither was
+    /// changed since one was cloned from the other.
+    pub fn ptr_eq(&self, other: &GlyphList) -> bool {
+        self.glyphs.len() == other.glyphs.len()
+            && self
+                .glyphs
+                .iter()
+                .zip(&other.glyphs)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+
+    /// Whether the glyph at `index` is shared with the same glyph of
+    /// `other`, i.e. neither was changed since one list was cloned from the
+    /// other.
+    pub fn shares_glyph(&self, other: &GlyphList, index: usize) -> bool {
+        match (self.glyphs.get(index), other.glyphs.get(index)) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.glyphs.iter().map(|glyph| &**glyph)
+    }
+
+    /// Iterate over the glyphs mutably, copying each shared glyph as it is
+    /// reached.
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        self.edited().iter_mut().map(Arc::make_mut)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Glyph> {
+        self.glyphs.get(index).map(|glyph| &**glyph)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Glyph> {
+        self.edited().get_mut(index).map(Arc::make_mut)
+    }
+
+    pub fn first(&self) -> Option<&Glyph> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&Glyph> {
+        self.glyphs.last().map(|glyph| &**glyph)
+    }
+
+    pub fn push(&mut self, glyph: Glyph) {
+        self.changed().push(Arc::new(glyph));
+    }
+
+    pub fn pop(&mut self) -> Option<Glyph> {
+        self.changed().pop().map(Arc::unwrap_or_clone)
+    }
+
+    pub fn insert(&mut self, index: usize, glyph: Glyph) {
+        self.changed().insert(index, Arc::new(glyph));
+    }
+
+    pub fn remove(&mut self, index: usize) -> Glyph {
+        Arc::unwrap_or_clone(self.changed().remove(index))
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.changed().swap(a, b);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.changed().truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.changed().clear();
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&Glyph) -> bool) {
+        self.changed().retain(|glyph| f(glyph));
+    }
+
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&Glyph, &Glyph) -> std::cmp::Ordering) {
+        self.changed().sort_by(|a, b| compare(a, b));
+    }
+
+    pub fn sort_by_key<K: Ord>(&mut self, mut f: impl FnMut(&Glyph) -> K) {
+        self.changed().sort_by_key(|glyph| f(glyph));
+    }
+}
+
+impl Default for GlyphList {
+    fn default() -> GlyphList {
+        GlyphList::new(Vec::new())
+    }
+}
+
+impl Index<usize> for GlyphList {
+    type Output = Glyph;
+
+    fn index(&self, index: usize) -> &Glyph {
+        &self.glyphs[index]
+    }
+}
+
+impl IndexMut<usize> for GlyphList {
+    fn index_mut(&mut self, index: usize) -> &mut Glyph {
+        Arc::make_mut(&mut self.edited()[index])
+    }
+}
+
+impl PartialEq for GlyphList {
+    fn eq(&self, other: &GlyphList) -> bool {
+        self.ptr_eq(other) || self.glyphs == other.glyphs
+    }
+}
+
+impl PartialEq<Vec<Glyph>> for GlyphList {
+    fn eq(&self, other: &Vec<Glyph>) -> bool {
+        self.iter().eq(other)
+    }
+}
+
+impl fmt::Debug for GlyphList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl From<Vec<Glyph>> for GlyphList {
+    fn from(glyphs: Vec<Glyph>) -> GlyphList {
+        glyphs.into_iter().collect()
+    }
+}
+
+impl From<GlyphList> for Vec<Glyph> {
+    fn from(glyphs: GlyphList) -> Vec<Glyph> {
+        glyphs.into_iter().collect()
+    }
+}
+
+impl FromIterator<Glyph> for GlyphList {
+    fn from_iter<I: IntoIterator<Item = Glyph>>(iter: I) -> GlyphList {
+        GlyphList::new(iter.into_iter().map(Arc::new).collect())
+    }
+}
+
+impl Extend<Glyph> for GlyphList {
+    fn extend<I: IntoIterator<Item = Glyph>>(&mut self, iter: I) {
+        self.changed().extend(iter.into_iter().map(Arc::new));
+    }
+}
+
+impl IntoIterator for GlyphList {
+    type Item = Glyph;
+    type IntoIter = std::iter::Map<std::vec::IntoIter<Arc<Glyph>>, fn(Arc<Glyph>) -> Glyph>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.glyphs.into_iter().map(Arc::unwrap_or_clone)
+    }
+}
+
+impl<'a> IntoIterator for &'a GlyphList {
+    type Item = &'a Glyph;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut GlyphList {
+    type Item = &'a mut Glyph;
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl TryFrom<Plist> for GlyphList {
+    type Error = ArrayConversionError<GlyphsFromPlistError>;
+
+    fn try_from(plist: Plist) -> Result<Self, Self::Error> {
+        Vec::try_from(plist).map(GlyphList::from)
+    }
+}
+
+impl ToPlist for GlyphList {
+    fn to_plist(&self) -> Plist {
+        Plist::Array(self.iter().map(ToPlist::to_plist).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Font;
+
+    #[test]
+    fn copy_on_write() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut copy = font.clone();
+        assert!(copy.glyphs.ptr_eq(&font.glyphs));
+
+        let note = font.glyphs[0].note.clone();
+        copy.glyphs[0].note = Some("changed".into());
+        assert!(!copy.glyphs.ptr_eq(&font.glyphs));
+        assert_eq!(font.glyphs[0].note, note);
+        assert_ne!(copy, font);
+        // Only the changed glyph was copied.
+        assert!(!copy.glyphs.shares_glyph(&font.glyphs, 0));
+        assert!(copy.glyphs.shares_glyph(&font.glyphs, 1));
+    }
+}
@@ -0,0 +1,214 @@
+//! The order glyphs are exported in, from the `glyphOrder` custom parameter
+//! or the default Glyphs sorting.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Case, CustomParameter, Font, Glyph, Plist};
+
+const GLYPH_ORDER: &str = "glyphOrder";
+
+/// Categories in the order Glyphs sorts them; anything else comes after.
+const CATEGORY_ORDER: &[&str] = &[
+    "Letter",
+    "Number",
+    "Punctuation",
+    "Symbol",
+    "Separator",
+    "Mark",
+    "Other",
+];
+
+/// Scripts in the order Glyphs sorts them, after glyphs without a script;
+/// other scripts follow alphabetically.
+const SCRIPT_ORDER: &[&str] = &["latin", "greek", "cyrillic"];
+
+impl Font {
+    /// The names in the `glyphOrder` custom parameter, if there is one.
+    pub fn custom_glyph_order(&self) -> Option<Vec<&str>> {
+        let names = self.custom_parameter_as::<&[Plist]>(GLYPH_ORDER)?;
+        Some(names.iter().filter_map(Plist::as_str).collect())
+    }
+
+    /// Set the `glyphOrder` custom parameter, replacing any existing one.
+    pub fn set_custom_glyph_order(&mut self, order: &[&str]) {
+        let value = Plist::Array(order.iter().map(|&name| name.to_string().into()).collect());
+        let parameters = self.custom_parameters.get_or_insert_with(Vec::new);
+        parameters.retain(|p| p.name != GLYPH_ORDER);
+        parameters.push(CustomParameter {
+            name: GLYPH_ORDER.into(),
+            value,
+            disabled: false,
+        });
+    }
+
+    /// The export order of the glyphs.
+    ///
+    /// Glyphs named in the `glyphOrder` custom parameter come first, in its
+    /// order, followed by the remaining glyphs in font order. Without the
+    /// parameter, this is the font order.
+    pub fn glyph_order(&self) -> Vec<&str> {
+        let custom = self.custom_glyph_order().unwrap_or_default();
+        ordered_names(self, &custom)
+    }
+
+    /// Sort the glyphs of the font.
+    ///
+    /// Glyphs named in `order` come first, in its order. Without `order`, the
+    /// `glyphOrder` custom parameter is used, if present. The remaining
+    /// glyphs follow in the default Glyphs sorting: by category, script and
+    /// case, then by Unicode value, with suffixed glyphs like `a.sc` right
+    /// after their base glyph. The sorting relies on the stored glyph info;
+    /// glyphs without a category come last.
+    pub fn sort_glyphs(&mut self, order: Option<&[&str]>) {
+        let order: Vec<String> = match order {
+            Some(order) => order.iter().map(|name| name.to_string()).collect(),
+            None => self
+                .custom_glyph_order()
+                .unwrap_or_default()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        };
+        let positions: HashMap<&str, usize> = order
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
+        let codepoints: HashMap<&str, u32> = self
+            .glyphs
+            .iter()
+            .filter_map(|g| {
+                let codepoint = g.unicode.as_ref()?.iter().next()?;
+                Some((g.glyphname.as_str(), codepoint as u32))
+            })
+            .collect();
+
+        let keys: HashMap<String, (Option<usize>, DefaultSortKey)> = self
+            .glyphs
+            .iter()
+            .map(|g| {
+                let name = g.glyphname.as_str();
+                let key = (
+                    positions.get(name).copied(),
+                    default_sort_key(g, &codepoints),
+                );
+                (name.to_string(), key)
+            })
+            .collect();
+        // Explicitly ordered glyphs first: `None` sorts before `Some`.
+        self.glyphs.sort_by(|a, b| {
+            let (a, b) = (&keys[a.glyphname.as_str()], &keys[b.glyphname.as_str()]);
+            (a.0.is_none(), a.0, &a.1).cmp(&(b.0.is_none(), b.0, &b.1))
+        });
+    }
+}
+
+/// The font's glyph names, with the given names first.
+fn ordered_names<'a>(font: &'a Font, order: &[&str]) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    order
+        .iter()
+        .filter_map(|name| font.get_glyph(name))
+        .chain(&font.glyphs)
+        .map(|g| g.glyphname.as_str())
+        .filter(|name| seen.insert(*name))
+        .collect()
+}
+
+type DefaultSortKey = (usize, (usize, String), usize, u32, String);
+
+fn default_sort_key(glyph: &Glyph, codepoints: &HashMap<&str, u32>) -> DefaultSortKey {
+    let category = glyph
+        .category
+        .as_deref()
+        .and_then(|c| CATEGORY_ORDER.iter().position(|&o| o == c))
+        .unwrap_or(CATEGORY_ORDER.len());
+    let script = match glyph.script.as_deref() {
+        None => (0, String::new()),
+        Some(script) => match SCRIPT_ORDER.iter().position(|&o| o == script) {
+            Some(i) => (i + 1, String::new()),
+            None => (SCRIPT_ORDER.len() + 1, script.to_string()),
+        },
+    };
+    let case = match glyph.case {
+        Some(Case::Upper) => 0,
+        Some(Case::Lower) => 1,
+        Some(Case::SmallCaps) => 2,
+        Some(Case::Other) => 3,
+        Some(Case::None) | None => 4,
+    };
+    let name = glyph.glyphname.as_str();
+    let base = name.split('.').next().unwrap_or(name);
+    let codepoint = codepoints
+        .get(name)
+        .or_else(|| codepoints.get(base))
+        .copied()
+        .unwrap_or(u32::MAX);
+    (category, script, case, codepoint, name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Font;
+
+    #[test]
+    fn custom_glyph_order() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let font_order = font
+            .glyphs
+            .iter()
+            .map(|g| g.glyphname.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(font.custom_glyph_order(), None);
+        assert_eq!(font.glyph_order(), font_order);
+
+        font.set_custom_glyph_order(&["B", "missing", "A"]);
+        font.set_custom_glyph_order(&["C", "missing", "A"]);
+        assert_eq!(font.custom_glyph_order().unwrap(), ["C", "missing", "A"]);
+        let order = font.glyph_order();
+        assert_eq!(order[..2], ["C", "A"]);
+        assert_eq!(order.len(), font_order.len());
+        // The font itself is left alone.
+        assert_eq!(font.glyphs[0].glyphname.as_str(), font_order[0]);
+
+        font.sort_glyphs(None);
+        let sorted = font
+            .glyphs
+            .iter()
+            .map(|g| g.glyphname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(sorted[..2], ["C", "A"]);
+        assert!(font.get_glyph("B").is_some());
+    }
+
+    #[test]
+    fn default_sorting() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        for (name, category) in [
+            ("one", "Number"),
+            ("A", "Letter"),
+            ("A.ss01", "Letter"),
+            ("B", "Letter"),
+            ("alef-ar", "Letter"),
+            ("dieresiscomb", "Mark"),
+        ] {
+            font.get_glyph_mut(name).unwrap().category = Some(category.into());
+        }
+        font.get_glyph_mut("alef-ar").unwrap().script = Some("arabic".into());
+        font.get_glyph_mut("A").unwrap().script = Some("latin".into());
+        font.get_glyph_mut("A.ss01").unwrap().script = Some("latin".into());
+        font.get_glyph_mut("B").unwrap().script = Some("latin".into());
+
+        font.sort_glyphs(Some(&["B"]));
+        let sorted = font
+            .glyphs
+            .iter()
+            .map(|g| g.glyphname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sorted[..6],
+            ["B", "A", "A.ss01", "alef-ar", "one", "dieresiscomb"]
+        );
+    }
+}
@@ -0,0 +1,1599 @@
+//! Reading and writing Glyphs 2 files, by converting their plist from and to
+//! the Glyphs 3 format the model follows.
+//!
+//! The conversion works on the plist rather than the model, so that everything
+//! the model doesn't know about is carried over as it is.
+
+use std::collections::HashMap;
+
+use crate::{Font, FormatVersion, GlyphsFromPlistError, Plist, ToPlist};
+
+type Dict = HashMap<String, Plist>;
+
+/// The keys holding the location of a master on each of the up to six axes
+/// of a Glyphs 2 font, with their defaults.
+const MASTER_AXIS_KEYS: [(&str, f64); 6] = [
+    ("weightValue", 100.0),
+    ("widthValue", 100.0),
+    ("customValue", 0.0),
+    ("customValue1", 0.0),
+    ("customValue2", 0.0),
+    ("customValue3", 0.0),
+];
+
+/// The keys holding the location of an instance, as for masters.
+const INSTANCE_AXIS_KEYS: [(&str, f64); 6] = [
+    ("interpolationWeight", 100.0),
+    ("interpolationWidth", 100.0),
+    ("interpolationCustom", 0.0),
+    ("interpolationCustom1", 0.0),
+    ("interpolationCustom2", 0.0),
+    ("interpolationCustom3", 0.0),
+];
+
+/// The axes of a Glyphs 2 font without an `Axes` custom parameter, by the
+/// index of their location keys.
+const DEFAULT_AXES: [(&str, &str); 3] = [("Weight", "wght"), ("Width", "wdth"), ("Custom", "XXXX")];
+
+/// The vertical metrics of a Glyphs 2 master, with their Glyphs 3 metric type
+/// and the default Glyphs 2 leaves out.
+const METRICS: [(&str, &str, f64); 5] = [
+    ("ascender", "ascender", 800.0),
+    ("capHeight", "cap height", 700.0),
+    ("xHeight", "x-height", 500.0),
+    ("baseline", "baseline", 0.0),
+    ("descender", "descender", -200.0),
+];
+
+/// Font keys that became `properties`, with the property key and whether the
+/// property is localised.
+const PROPERTIES: [(&str, &str, bool); 5] = [
+    ("copyright", "copyrights", true),
+    ("designer", "designers", true),
+    ("designerURL", "designerURL", false),
+    ("manufacturer", "manufacturers", true),
+    ("manufacturerURL", "manufacturerURL", false),
+];
+
+/// Font keys that moved into `settings`.
+const SETTINGS: [&str; 6] = [
+    "disablesAutomaticAlignment",
+    "disablesNiceNames",
+    "gridLength",
+    "gridSubDivision",
+    "keepAlternatesTogether",
+    "keyboardIncrement",
+];
+
+/// Glyph and layer keys that were renamed.
+const RENAMED_KEYS: [(&str, &str); 10] = [
+    ("leftKerningGroup", "kernLeft"),
+    ("rightKerningGroup", "kernRight"),
+    ("topKerningGroup", "kernTop"),
+    ("bottomKerningGroup", "kernBottom"),
+    ("leftMetricsKey", "metricLeft"),
+    ("rightMetricsKey", "metricRight"),
+    ("topMetricsKey", "metricTop"),
+    ("bottomMetricsKey", "metricBottom"),
+    ("widthMetricsKey", "metricWidth"),
+    ("vertWidthMetricsKey", "metricVertWidth"),
+];
+
+/// The weight classes Glyphs 2 stores by name, the preferred name for a
+/// class first.
+const WEIGHT_CLASSES: [(&str, i64); 14] = [
+    ("Thin", 100),
+    ("ExtraLight", 200),
+    ("UltraLight", 200),
+    ("Light", 300),
+    ("Regular", 400),
+    ("Normal", 400),
+    ("Medium", 500),
+    ("SemiBold", 600),
+    ("DemiBold", 600),
+    ("Bold", 700),
+    ("ExtraBold", 800),
+    ("UltraBold", 800),
+    ("Black", 900),
+    ("Heavy", 900),
+];
+
+/// The width classes Glyphs 2 stores by name.
+const WIDTH_CLASSES: [(&str, i64); 9] = [
+    ("Ultra Condensed", 1),
+    ("Extra Condensed", 2),
+    ("Condensed", 3),
+    ("SemiCondensed", 4),
+    ("Medium (normal)", 5),
+    ("Semi Expanded", 6),
+    ("Expanded", 7),
+    ("Extra Expanded", 8),
+    ("Ultra Expanded", 9),
+];
+
+/// Rewrite the top-level dictionary of a Glyphs 2 file in the Glyphs 3
+/// format.
+///
+/// Masters get their metrics, stems and axis locations as lists matching the
+/// font's `metrics`, `stems` and `axes`, with overshoots taken from the
+/// alignment zones at the same height. Hexadecimal Unicode values become
+/// numbers, paths and components become `shapes` with nodes and transforms in
+/// the new encoding, and brace and bracket layers get their location from the
+/// layer name. Renamed keys, like `kerning` and `leftKerningGroup`, get their
+/// new names.
+pub(crate) fn upgrade(font: &mut Dict) {
+    rename(font, "kerning", "kerningLTR");
+    rename(font, "vertKerning", "kerningVertical");
+    upgrade_settings(font);
+    upgrade_properties(font);
+    let axes = upgrade_axes(font);
+    upgrade_masters(font, &axes);
+    for instance in dicts_mut(font, "instances") {
+        upgrade_instance(instance, &axes);
+    }
+    for feature in dicts_mut(font, "features") {
+        rename(feature, "name", "tag");
+    }
+    for glyph in dicts_mut(font, "glyphs") {
+        upgrade_glyph(glyph);
+    }
+    font.insert(".formatVersion".into(), Plist::Integer(3));
+}
+
+fn upgrade_settings(font: &mut Dict) {
+    let settings: Dict = SETTINGS
+        .iter()
+        .filter_map(|&key| Some((key.to_string(), font.remove(key)?)))
+        .collect();
+    if !settings.is_empty() {
+        font.insert("settings".into(), Plist::Dictionary(settings));
+    }
+}
+
+fn upgrade_properties(font: &mut Dict) {
+    let mut properties = Vec::new();
+    for (key, property_key, localised) in PROPERTIES {
+        let Some(value) = font.remove(key) else {
+            continue;
+        };
+        let mut property = Dict::from([("key".to_string(), Plist::from(property_key.to_string()))]);
+        if localised {
+            let localised_value = Dict::from([
+                ("language".to_string(), Plist::from("dflt".to_string())),
+                ("value".to_string(), value),
+            ]);
+            property.insert(
+                "values".into(),
+                Plist::Array(vec![Plist::Dictionary(localised_value)]),
+            );
+        } else {
+            property.insert("value".into(), value);
+        }
+        properties.push(Plist::Dictionary(property));
+    }
+    if !properties.is_empty() {
+        font.insert("properties".into(), Plist::Array(properties));
+    }
+}
+
+/// Build the font's `axes`, returning for each the index of the keys holding
+/// the master and instance locations on it.
+///
+/// Without an `Axes` custom parameter the font has a weight axis, and a width
+/// and custom axis if any master is off their default.
+fn upgrade_axes(font: &mut Dict) -> Vec<usize> {
+    let (axes, indices): (Vec<Plist>, Vec<usize>) = match take_custom_parameter(font, "Axes") {
+        Some(Plist::Array(axes)) => axes
+            .iter()
+            .filter_map(Plist::as_dict)
+            .take(MASTER_AXIS_KEYS.len())
+            .enumerate()
+            .map(|(i, axis)| {
+                let mut new = Dict::new();
+                for (old_key, new_key) in [("Name", "name"), ("Tag", "tag"), ("Hidden", "hidden")] {
+                    if let Some(value) = axis.get(old_key) {
+                        new.insert(new_key.into(), value.clone());
+                    }
+                }
+                (Plist::Dictionary(new), i)
+            })
+            .unzip(),
+        _ => {
+            let is_used = |i: usize| {
+                let (key, default) = MASTER_AXIS_KEYS[i];
+                let off_default = |master: &Dict| {
+                    master
+                        .get(key)
+                        .and_then(Plist::as_f64)
+                        .is_some_and(|value| value != default)
+                };
+                i == 0 || dicts(font, "fontMaster").any(off_default)
+            };
+            DEFAULT_AXES
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| is_used(i))
+                .map(|(i, (name, tag))| {
+                    let axis = Dict::from([
+                        ("name".to_string(), Plist::from(name.to_string())),
+                        ("tag".to_string(), Plist::from(tag.to_string())),
+                    ]);
+                    (Plist::Dictionary(axis), i)
+                })
+                .unzip()
+        }
+    };
+    font.insert("axes".into(), Plist::Array(axes));
+    indices
+}
+
+fn upgrade_masters(font: &mut Dict, axes: &[usize]) {
+    let has_italic_angle = dicts(font, "fontMaster").any(|m| m.contains_key("italicAngle"));
+    let stem_count = |key: &str| {
+        dicts(font, "fontMaster")
+            .filter_map(|m| m.get(key)?.as_array().map(<[Plist]>::len))
+            .max()
+            .unwrap_or(0)
+    };
+    let (horizontal_stems, vertical_stems) =
+        (stem_count("horizontalStems"), stem_count("verticalStems"));
+
+    for master in dicts_mut(font, "fontMaster") {
+        let name = master_name(master);
+        master.insert("name".into(), Plist::from(name));
+        for key in ["weight", "width", "custom"] {
+            master.remove(key);
+        }
+
+        let zones: Vec<Vec<f64>> = match master.remove("alignmentZones") {
+            Some(Plist::Array(zones)) => zones
+                .iter()
+                .filter_map(|zone| parse_numbers(zone.as_str()?))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut metric_values: Vec<Plist> = METRICS
+            .iter()
+            .map(|&(key, _, default)| {
+                let pos = master
+                    .remove(key)
+                    .and_then(|pos| pos.as_f64())
+                    .unwrap_or(default);
+                let over = zones
+                    .iter()
+                    .find(|zone| zone.first() == Some(&pos))
+                    .and_then(|zone| zone.get(1).copied())
+                    .unwrap_or(0.0);
+                metric_value(pos, over)
+            })
+            .collect();
+        if has_italic_angle {
+            let angle = master
+                .remove("italicAngle")
+                .and_then(|angle| angle.as_f64())
+                .unwrap_or(0.0);
+            metric_values.push(metric_value(angle, 0.0));
+        }
+        master.insert("metricValues".into(), Plist::Array(metric_values));
+
+        let mut stem_values = Vec::new();
+        for (key, count) in [
+            ("horizontalStems", horizontal_stems),
+            ("verticalStems", vertical_stems),
+        ] {
+            let stems = master.remove(key);
+            let stems = stems.as_ref().and_then(Plist::as_array).unwrap_or_default();
+            stem_values
+                .extend((0..count).map(|i| stems.get(i).cloned().unwrap_or(Plist::Integer(0))));
+        }
+        if !stem_values.is_empty() {
+            master.insert("stemValues".into(), Plist::Array(stem_values));
+        }
+
+        let axes_values: Vec<Plist> = axes
+            .iter()
+            .map(|&i| {
+                let (key, default) = MASTER_AXIS_KEYS[i];
+                master.get(key).cloned().unwrap_or(number(default))
+            })
+            .collect();
+        for (key, _) in MASTER_AXIS_KEYS {
+            master.remove(key);
+        }
+        master.insert("axesValues".into(), Plist::Array(axes_values));
+    }
+
+    let mut metrics: Vec<Plist> = METRICS
+        .iter()
+        .map(|(_, metric_type, _)| metric(metric_type))
+        .collect();
+    if has_italic_angle {
+        metrics.push(metric("italic angle"));
+    }
+    font.insert("metrics".into(), Plist::Array(metrics));
+
+    let stems: Vec<Plist> = (0..horizontal_stems)
+        .map(|i| stem(format!("hStem{i}"), true))
+        .chain((0..vertical_stems).map(|i| stem(format!("vStem{i}"), false)))
+        .collect();
+    if !stems.is_empty() {
+        font.insert("stems".into(), Plist::Array(stems));
+    }
+}
+
+/// The name of a master: its `name`, its `Master Name` custom parameter, or
+/// else its width, weight and custom names leaving out `Regular`.
+fn master_name(master: &mut Dict) -> String {
+    if let Some(Plist::String(name)) = master.get("name") {
+        return name.clone();
+    }
+    if let Some(Plist::String(name)) = take_custom_parameter(master, "Master Name") {
+        return name;
+    }
+    let parts: Vec<&str> = ["width", "weight", "custom"]
+        .iter()
+        .filter_map(|key| master.get(*key)?.as_str())
+        .filter(|&part| part != "Regular" && part != "Medium (normal)")
+        .collect();
+    match parts.is_empty() {
+        true => "Regular".to_string(),
+        false => parts.join(" "),
+    }
+}
+
+fn upgrade_instance(instance: &mut Dict, axes: &[usize]) {
+    let axes_values: Vec<Plist> = axes
+        .iter()
+        .map(|&i| {
+            let (key, default) = INSTANCE_AXIS_KEYS[i];
+            instance.get(key).cloned().unwrap_or(number(default))
+        })
+        .collect();
+    for (key, _) in INSTANCE_AXIS_KEYS {
+        instance.remove(key);
+    }
+    instance.insert("axesValues".into(), Plist::Array(axes_values));
+
+    for (key, classes) in [
+        ("weightClass", &WEIGHT_CLASSES[..]),
+        ("widthClass", &WIDTH_CLASSES[..]),
+    ] {
+        if let Some(Plist::String(name)) = instance.get(key) {
+            match classes.iter().find(|(class, _)| class == name) {
+                Some(&(_, value)) => instance.insert(key.into(), Plist::Integer(value)),
+                None => instance.remove(key),
+            };
+        }
+    }
+}
+
+fn upgrade_glyph(glyph: &mut Dict) {
+    for (old_key, new_key) in RENAMED_KEYS {
+        rename(glyph, old_key, new_key);
+    }
+
+    let hex = match glyph.get("unicode") {
+        Some(Plist::String(hex)) => hex.clone(),
+        // All-digit values parse as decimal numbers.
+        Some(Plist::Integer(digits)) => digits.to_string(),
+        _ => String::new(),
+    };
+    let codepoints: Option<Vec<Plist>> = hex
+        .split(',')
+        .map(|hex| i64::from_str_radix(hex.trim(), 16).ok().map(Plist::Integer))
+        .collect();
+    match codepoints {
+        Some(mut codepoints) if codepoints.len() == 1 => {
+            glyph.insert("unicode".into(), codepoints.remove(0));
+        }
+        Some(codepoints) => {
+            glyph.insert("unicode".into(), Plist::Array(codepoints));
+        }
+        None => (),
+    }
+
+    for layer in dicts_mut(glyph, "layers") {
+        upgrade_layer(layer);
+    }
+}
+
+fn upgrade_layer(layer: &mut Dict) {
+    for (old_key, new_key) in RENAMED_KEYS {
+        rename(layer, old_key, new_key);
+    }
+    upgrade_shapes(layer);
+    if let Some(Plist::Dictionary(background)) = layer.get_mut("background") {
+        upgrade_shapes(background);
+    }
+    rename(layer, "guideLines", "guides");
+    for guide in dicts_mut(layer, "guides") {
+        rename(guide, "position", "pos");
+        convert_point(guide, "pos");
+    }
+    if let Some(Plist::Dictionary(image)) = layer.get_mut("backgroundImage") {
+        upgrade_transform(image);
+        if let Some(crop) = image.get("crop").and_then(Plist::as_str) {
+            let crop = parse_numbers(crop).map(|crop| crop.into_iter().map(number).collect());
+            match crop {
+                Some(crop) => image.insert("crop".into(), Plist::Array(crop)),
+                None => image.remove("crop"),
+            };
+        }
+    }
+
+    let location = match layer.get("name").and_then(Plist::as_str) {
+        Some(name) if !layer.contains_key("attr") => layer_location(name),
+        _ => None,
+    };
+    if let Some(location) = location {
+        layer.insert("attr".into(), Plist::Dictionary(Dict::from([location])));
+    }
+}
+
+/// The `attr` entry for a brace layer named like `{100, 50}` or a bracket
+/// layer named like `[100]` or `]100]`, the latter for the first axis only.
+fn layer_location(name: &str) -> Option<(String, Plist)> {
+    if let (Some(start), Some(end)) = (name.find('{'), name.rfind('}')) {
+        let coordinates = parse_numbers(name.get(start..=end)?)?;
+        let coordinates = coordinates.into_iter().map(number).collect();
+        return Some(("coordinates".into(), Plist::Array(coordinates)));
+    }
+    let start = name.find(['[', ']'])?;
+    let end = start + 1 + name[start + 1..].find(']')?;
+    let value = name[start + 1..end].trim().parse().ok()?;
+    let bound = match &name[start..=start] {
+        "[" => "min",
+        _ => "max",
+    };
+    let rule = Dict::from([(bound.to_string(), number(value))]);
+    Some((
+        "axisRules".into(),
+        Plist::Array(vec![Plist::Dictionary(rule)]),
+    ))
+}
+
+/// Merge the `components` and `paths` of a layer or background into its
+/// `shapes`, in the order they're listed in the file, and convert its anchors.
+fn upgrade_shapes(layer: &mut Dict) {
+    let mut shapes = Vec::new();
+    if let Some(Plist::Array(components)) = layer.remove("components") {
+        for mut component in components {
+            if let Plist::Dictionary(component) = &mut component {
+                rename(component, "name", "ref");
+                upgrade_transform(component);
+            }
+            shapes.push(component);
+        }
+    }
+    if let Some(Plist::Array(paths)) = layer.remove("paths") {
+        for mut path in paths {
+            if let Some(Plist::Array(nodes)) = path_nodes(&mut path) {
+                for node in nodes {
+                    if let Some(upgraded) = node.as_str().and_then(upgrade_node) {
+                        *node = upgraded;
+                    }
+                }
+            }
+            shapes.push(path);
+        }
+    }
+    if !shapes.is_empty() {
+        layer.insert("shapes".into(), Plist::Array(shapes));
+    }
+
+    for anchor in dicts_mut(layer, "anchors") {
+        rename(anchor, "position", "pos");
+        convert_point(anchor, "pos");
+    }
+}
+
+fn path_nodes(path: &mut Plist) -> Option<&mut Plist> {
+    match path {
+        Plist::Dictionary(path) => path.get_mut("nodes"),
+        _ => None,
+    }
+}
+
+/// Convert a node like `"100 200 CURVE SMOOTH"` to `(100, 200, cs)`.
+fn upgrade_node(node: &str) -> Option<Plist> {
+    let mut parts = node.split_whitespace();
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    let node_type = match parts.next()? {
+        "LINE" => "l",
+        "CURVE" => "c",
+        "QCURVE" => "q",
+        "OFFCURVE" => "o",
+        _ => return None,
+    };
+    let smooth = match (parts.next() == Some("SMOOTH"), node_type) {
+        (true, "l" | "c" | "q") => "s",
+        _ => "",
+    };
+    Some(Plist::Array(vec![
+        number(x),
+        number(y),
+        Plist::from(format!("{node_type}{smooth}")),
+    ]))
+}
+
+/// Replace a `transform` like `"{1, 0, 0, 1, 50, 0}"` with the `pos`,
+/// `scale` and `angle` of Glyphs 3.
+fn upgrade_transform(dict: &mut Dict) {
+    let transform = dict.remove("transform");
+    let Some([a, b, c, d, tx, ty]) = transform
+        .as_ref()
+        .and_then(Plist::as_str)
+        .and_then(parse_numbers)
+        .and_then(|numbers| <[f64; 6]>::try_from(numbers).ok())
+    else {
+        return;
+    };
+
+    // Without rotation, the signs of the scale carry any mirroring.
+    let (angle, scale) = if b == 0.0 && c == 0.0 {
+        (0.0, (a, d))
+    } else {
+        let sx = a.hypot(b);
+        (b.atan2(a).to_degrees(), (sx, (a * d - b * c) / sx))
+    };
+    if (tx, ty) != (0.0, 0.0) {
+        dict.insert("pos".into(), Plist::Array(vec![number(tx), number(ty)]));
+    }
+    if scale != (1.0, 1.0) {
+        let scale = vec![number(scale.0), number(scale.1)];
+        dict.insert("scale".into(), Plist::Array(scale));
+    }
+    if angle != 0.0 {
+        dict.insert("angle".into(), number(angle));
+    }
+}
+
+/// Convert a point like `"{10, 20}"` to `(10, 20)`.
+fn convert_point(dict: &mut Dict, key: &str) {
+    let point = dict
+        .get(key)
+        .and_then(Plist::as_str)
+        .and_then(parse_numbers);
+    if let Some(point) = point {
+        dict.insert(
+            key.into(),
+            Plist::Array(point.into_iter().map(number).collect()),
+        );
+    }
+}
+
+/// The numbers of a string like `"{1, 2}"` or `"{{1, 2}, {3, 4}}"`.
+fn parse_numbers(s: &str) -> Option<Vec<f64>> {
+    s.split(['{', '}', ','])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// A number as Glyphs writes it, without a fraction if it has none.
+fn number(value: f64) -> Plist {
+    match value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        true => Plist::Integer(value as i64),
+        false => Plist::Float(value),
+    }
+}
+
+fn metric(metric_type: &str) -> Plist {
+    Plist::Dictionary(Dict::from([(
+        "type".to_string(),
+        Plist::from(metric_type.to_string()),
+    )]))
+}
+
+fn metric_value(pos: f64, over: f64) -> Plist {
+    let mut value = Dict::new();
+    if pos != 0.0 {
+        value.insert("pos".into(), number(pos));
+    }
+    if over != 0.0 {
+        value.insert("over".into(), number(over));
+    }
+    Plist::Dictionary(value)
+}
+
+fn stem(name: String, horizontal: bool) -> Plist {
+    let mut stem = Dict::from([("name".to_string(), Plist::from(name))]);
+    if horizontal {
+        stem.insert("horizontal".into(), Plist::Integer(1));
+    }
+    Plist::Dictionary(stem)
+}
+
+/// What a conversion between format versions couldn't carry over.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConversionReport {
+    /// The data that was dropped, each entry saying where in the font it was,
+    /// like `glyph "A", layer "m01": path attributes`.
+    pub dropped: Vec<String>,
+}
+
+impl ConversionReport {
+    /// Whether nothing was dropped.
+    pub fn is_lossless(&self) -> bool {
+        self.dropped.is_empty()
+    }
+
+    fn record(&mut self, context: &str, what: impl std::fmt::Display) {
+        self.dropped.push(format!("{context}: {what}"));
+    }
+}
+
+impl Font {
+    /// Convert the font to what the given format version can represent,
+    /// returning what was dropped. [`Font::save`] then writes that version.
+    ///
+    /// Converting to Glyphs 2 drops what [`Font::save_v2`] would leave out,
+    /// and converts the rest as saving and reading back a Glyphs 2 file
+    /// would. Converting to Glyphs 3 loses nothing, as the model follows it.
+    pub fn convert_to(
+        &mut self,
+        version: FormatVersion,
+    ) -> Result<ConversionReport, GlyphsFromPlistError> {
+        match version {
+            FormatVersion::Glyphs2 => {
+                let mut plist = self.to_plist().into_hashmap();
+                let report = downgrade(&mut plist);
+                upgrade(&mut plist);
+                let (upgraded_from, encoding) = (self.upgraded_from, self.encoding);
+                *self = Plist::Dictionary(plist).try_into()?;
+                (self.upgraded_from, self.encoding) = (upgraded_from, encoding);
+                self.save_format = version;
+                Ok(report)
+            }
+            FormatVersion::Glyphs3 => {
+                self.save_format = version;
+                Ok(ConversionReport::default())
+            }
+        }
+    }
+}
+
+/// Rewrite the top-level dictionary of a font in the Glyphs 3 format in the
+/// Glyphs 2 format, reversing [`upgrade`], and report what Glyphs 2 can't
+/// represent and was left out.
+///
+/// Metrics other than the ones Glyphs 2 masters have, axes beyond the sixth,
+/// variable font settings, localised and other properties without a Glyphs 2
+/// key, and attributes of paths, layers and glyphs new in Glyphs 3 are
+/// dropped.
+pub(crate) fn downgrade(font: &mut Dict) -> ConversionReport {
+    let mut report = ConversionReport::default();
+    font.remove(".formatVersion");
+    rename(font, "kerningLTR", "kerning");
+    rename(font, "kerningVertical", "vertKerning");
+    if font.remove("kerningRTL").is_some() {
+        report.record("font", "right-to-left kerning");
+    }
+    downgrade_settings(font, &mut report);
+    downgrade_properties(font, &mut report);
+    let axis_count = downgrade_axes(font, &mut report);
+    downgrade_masters(font, axis_count, &mut report);
+
+    if let Some(Plist::Array(instances)) = font.get_mut("instances") {
+        instances.retain_mut(|instance| match instance {
+            Plist::Dictionary(instance) => downgrade_instance(instance, axis_count, &mut report),
+            _ => true,
+        });
+    }
+    for feature in dicts_mut(font, "features") {
+        rename(feature, "tag", "name");
+    }
+    for glyph in dicts_mut(font, "glyphs") {
+        downgrade_glyph(glyph, &mut report);
+    }
+    report
+}
+
+fn downgrade_settings(font: &mut Dict, report: &mut ConversionReport) {
+    let Some(Plist::Dictionary(settings)) = font.remove("settings") else {
+        return;
+    };
+    for (key, value) in settings {
+        match SETTINGS.contains(&key.as_str()) {
+            true => font.insert(key, value),
+            false => {
+                report.record("font", format_args!("setting {key:?}"));
+                None
+            }
+        };
+    }
+}
+
+fn downgrade_properties(font: &mut Dict, report: &mut ConversionReport) {
+    let Some(Plist::Array(properties)) = font.remove("properties") else {
+        return;
+    };
+    for property in properties.iter().filter_map(Plist::as_dict) {
+        let property_key = property.get("key").and_then(Plist::as_str).unwrap_or("");
+        let Some(&(key, _, _)) = PROPERTIES.iter().find(|(_, p, _)| *p == property_key) else {
+            report.record("font", format_args!("property {property_key:?}"));
+            continue;
+        };
+        let value = match property.get("values").and_then(Plist::as_array) {
+            Some(values) => {
+                if values.len() > 1 {
+                    report.record("font", format_args!("localisations of {property_key:?}"));
+                }
+                localised_value(values)
+            }
+            None => property.get("value"),
+        };
+        if let Some(value) = value {
+            font.insert(key.into(), value.clone());
+        }
+    }
+}
+
+/// The default, English, or else first value of a localised property.
+fn localised_value(values: &[Plist]) -> Option<&Plist> {
+    let value = |language: &str| {
+        values
+            .iter()
+            .find(|v| v.get("language").and_then(Plist::as_str) == Some(language))
+    };
+    value("dflt")
+        .or_else(|| value("ENG"))
+        .or(values.first())?
+        .get("value")
+}
+
+/// Replace the font's `axes` with an `Axes` custom parameter, unless they
+/// are the ones Glyphs 2 assumes without it, returning how many axes are
+/// kept.
+fn downgrade_axes(font: &mut Dict, report: &mut ConversionReport) -> usize {
+    let Some(Plist::Array(mut axes)) = font.remove("axes") else {
+        return 0;
+    };
+    if axes.len() > MASTER_AXIS_KEYS.len() {
+        report.record("font", "axes after the sixth");
+        axes.truncate(MASTER_AXIS_KEYS.len());
+    }
+
+    let is_default = axes.iter().zip(DEFAULT_AXES).all(|(axis, (name, tag))| {
+        axis.get("name").and_then(Plist::as_str) == Some(name)
+            && axis.get("tag").and_then(Plist::as_str) == Some(tag)
+            && axis.get("hidden").is_none()
+    });
+    if is_default && axes.len() <= DEFAULT_AXES.len() {
+        return axes.len();
+    }
+
+    let axes: Vec<Plist> = axes
+        .iter()
+        .filter_map(Plist::as_dict)
+        .map(|axis| {
+            let mut old = Dict::new();
+            for (new_key, old_key) in [("name", "Name"), ("tag", "Tag"), ("hidden", "Hidden")] {
+                if let Some(value) = axis.get(new_key) {
+                    old.insert(old_key.into(), value.clone());
+                }
+            }
+            Plist::Dictionary(old)
+        })
+        .collect();
+    let count = axes.len();
+    push_custom_parameter(font, "Axes", Plist::Array(axes));
+    count
+}
+
+fn downgrade_masters(font: &mut Dict, axis_count: usize, report: &mut ConversionReport) {
+    // The Glyphs 2 key for each of the font's metrics, if there is one.
+    let metrics: Vec<Option<(&str, f64)>> = match font.remove("metrics") {
+        Some(Plist::Array(metrics)) => metrics
+            .iter()
+            .map(|metric| {
+                let metric_type = metric.get("type").and_then(Plist::as_str);
+                let key = match metric_type {
+                    Some("italic angle") => Some(("italicAngle", 0.0)),
+                    Some(metric_type) => METRICS
+                        .iter()
+                        .find(|(_, t, _)| *t == metric_type)
+                        .map(|&(key, _, default)| (key, default)),
+                    None => None,
+                };
+                let name = metric.get("name").and_then(Plist::as_str);
+                let name = name.or(metric_type).unwrap_or("");
+                match (key, metric.get("filter").and_then(Plist::as_str)) {
+                    (Some(key), None) => Some(key),
+                    (_, Some(filter)) => {
+                        let what = format_args!("metric {name:?} for {filter:?}");
+                        report.record("font", what);
+                        None
+                    }
+                    (None, None) => {
+                        report.record("font", format_args!("metric {name:?}"));
+                        None
+                    }
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    // Whether each stem is horizontal.
+    let stems: Vec<bool> = match font.remove("stems") {
+        Some(Plist::Array(stems)) => stems
+            .iter()
+            .map(|stem| stem.get("horizontal").and_then(Plist::as_i64) == Some(1))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    for master in dicts_mut(font, "fontMaster") {
+        let name = match master.remove("name") {
+            Some(Plist::String(name)) => name,
+            _ => String::new(),
+        };
+        let context = format!("master {name:?}");
+        if !name.is_empty() && name != "Regular" {
+            push_custom_parameter(master, "Master Name", Plist::from(name));
+        }
+
+        let metric_values = master.remove("metricValues");
+        let metric_values = metric_values.as_ref().and_then(Plist::as_array);
+        let mut zones = Vec::new();
+        for (metric, value) in metrics.iter().zip(metric_values.unwrap_or_default()) {
+            let Some((key, _)) = metric else {
+                continue;
+            };
+            let pos = value.get("pos").and_then(Plist::as_f64).unwrap_or(0.0);
+            let over = value.get("over").and_then(Plist::as_f64).unwrap_or(0.0);
+            if *key != "baseline" && (*key != "italicAngle" || pos != 0.0) {
+                master.insert(key.to_string(), number(pos));
+            }
+            if over != 0.0 {
+                zones.push(Plist::from(format!(
+                    "{{{}, {}}}",
+                    format_number(pos),
+                    format_number(over)
+                )));
+            }
+        }
+        if !zones.is_empty() {
+            master.insert("alignmentZones".into(), Plist::Array(zones));
+        }
+
+        if let Some(Plist::Array(stem_values)) = master.remove("stemValues") {
+            let (horizontal, vertical): (Vec<_>, Vec<_>) = stems
+                .iter()
+                .zip(stem_values)
+                .partition(|(horizontal, _)| **horizontal);
+            for (key, stems) in [("horizontalStems", horizontal), ("verticalStems", vertical)] {
+                if !stems.is_empty() {
+                    let stems = stems.into_iter().map(|(_, value)| value).collect();
+                    master.insert(key.into(), Plist::Array(stems));
+                }
+            }
+        }
+
+        downgrade_axes_values(master, &MASTER_AXIS_KEYS[..axis_count]);
+        if master.remove("numberValues").is_some() {
+            report.record(&context, "number values");
+        }
+    }
+}
+
+/// Downgrade an instance, returning whether Glyphs 2 can represent it at all.
+fn downgrade_instance(
+    instance: &mut Dict,
+    axis_count: usize,
+    report: &mut ConversionReport,
+) -> bool {
+    let name = instance.get("name").and_then(Plist::as_str).unwrap_or("");
+    let context = format!("instance {name:?}");
+    if instance.get("type").and_then(Plist::as_str) == Some("variable") {
+        report.record(&context, "variable font setting");
+        return false;
+    }
+    if instance.remove("properties").is_some() {
+        report.record(&context, "properties");
+    }
+    downgrade_axes_values(instance, &INSTANCE_AXIS_KEYS[..axis_count]);
+    for (key, classes) in [
+        ("weightClass", &WEIGHT_CLASSES[..]),
+        ("widthClass", &WIDTH_CLASSES[..]),
+    ] {
+        let Some(value) = instance.get(key).and_then(Plist::as_i64) else {
+            continue;
+        };
+        match classes.iter().find(|(_, class)| *class == value) {
+            Some(&(name, _)) => instance.insert(key.into(), Plist::from(name.to_string())),
+            None => {
+                report.record(&context, format_args!("{key} {value}"));
+                instance.remove(key)
+            }
+        };
+    }
+    true
+}
+
+/// Replace `axesValues` with the Glyphs 2 keys, leaving out defaults.
+fn downgrade_axes_values(dict: &mut Dict, keys: &[(&str, f64)]) {
+    let Some(Plist::Array(values)) = dict.remove("axesValues") else {
+        return;
+    };
+    for (&(key, default), value) in keys.iter().zip(values) {
+        if value.as_f64() != Some(default) {
+            dict.insert(key.into(), value);
+        }
+    }
+}
+
+fn downgrade_glyph(glyph: &mut Dict, report: &mut ConversionReport) {
+    for (old_key, new_key) in RENAMED_KEYS {
+        rename(glyph, new_key, old_key);
+    }
+    let name = glyph.get("glyphname").and_then(Plist::as_str).unwrap_or("");
+    let context = format!("glyph {name:?}");
+    for key in ["case", "tags"] {
+        if glyph.remove(key).is_some() {
+            report.record(&context, key);
+        }
+    }
+
+    let codepoints = match glyph.get("unicode") {
+        Some(Plist::Integer(codepoint)) => vec![*codepoint],
+        Some(Plist::Array(codepoints)) => codepoints.iter().filter_map(Plist::as_i64).collect(),
+        _ => Vec::new(),
+    };
+    if !codepoints.is_empty() {
+        let hex: Vec<String> = codepoints.iter().map(|cp| format!("{cp:04X}")).collect();
+        glyph.insert("unicode".into(), Plist::from(hex.join(",")));
+    }
+
+    for layer in dicts_mut(glyph, "layers") {
+        let layer_id = layer.get("layerId").and_then(Plist::as_str).unwrap_or("");
+        let context = format!("{context}, layer {layer_id:?}");
+        downgrade_layer(layer, &context, report);
+    }
+}
+
+fn downgrade_layer(layer: &mut Dict, context: &str, report: &mut ConversionReport) {
+    for (old_key, new_key) in RENAMED_KEYS {
+        rename(layer, new_key, old_key);
+    }
+    downgrade_shapes(layer, context, report);
+    if let Some(Plist::Dictionary(background)) = layer.get_mut("background") {
+        downgrade_shapes(background, &format!("{context}, background"), report);
+    }
+    rename(layer, "guides", "guideLines");
+    for guide in dicts_mut(layer, "guideLines") {
+        rename(guide, "pos", "position");
+        format_point(guide, "position");
+    }
+    if let Some(Plist::Dictionary(image)) = layer.get_mut("backgroundImage") {
+        downgrade_transform(image);
+        let crop = image.get("crop").and_then(numbers);
+        if let Some(&[x, y, width, height]) = crop.as_deref() {
+            let crop = format!(
+                "{{{{{}, {}}}, {{{}, {}}}}}",
+                format_number(x),
+                format_number(y),
+                format_number(width),
+                format_number(height)
+            );
+            image.insert("crop".into(), Plist::from(crop));
+        }
+    }
+
+    let Some(Plist::Dictionary(mut attr)) = layer.remove("attr") else {
+        return;
+    };
+    let location = match (attr.remove("coordinates"), attr.remove("axisRules")) {
+        (Some(coordinates), _) => {
+            let coordinates = numbers(&coordinates).unwrap_or_default();
+            let coordinates: Vec<String> = coordinates.into_iter().map(format_number).collect();
+            Some(format!("{{{}}}", coordinates.join(", ")))
+        }
+        (None, Some(Plist::Array(rules))) => {
+            if rules
+                .iter()
+                .skip(1)
+                .any(|rule| rule.as_dict().is_some_and(|r| !r.is_empty()))
+            {
+                report.record(context, "bracket layer rules beyond the first axis");
+            }
+            let rule = rules.first();
+            let min = rule.and_then(|r| r.get("min")).and_then(Plist::as_f64);
+            let max = rule.and_then(|r| r.get("max")).and_then(Plist::as_f64);
+            match (min, max) {
+                (Some(min), max) => {
+                    if max.is_some() {
+                        report.record(context, "bracket layer maximum");
+                    }
+                    Some(format!("[{}]", format_number(min)))
+                }
+                (None, Some(max)) => Some(format!("]{}]", format_number(max))),
+                (None, None) => None,
+            }
+        }
+        _ => None,
+    };
+    if let Some(location) = location {
+        let name = layer.get("name").and_then(Plist::as_str).unwrap_or("");
+        let has_location = layer_location(name).is_some();
+        if !has_location {
+            let name = match name {
+                "" => location,
+                name => format!("{name} {location}"),
+            };
+            layer.insert("name".into(), Plist::from(name));
+        }
+    }
+    for key in attr.keys() {
+        report.record(context, format_args!("layer attribute {key:?}"));
+    }
+}
+
+/// Split the `shapes` of a layer or background into `components` and
+/// `paths`, and convert its anchors.
+fn downgrade_shapes(layer: &mut Dict, context: &str, report: &mut ConversionReport) {
+    let mut components = Vec::new();
+    let mut paths = Vec::new();
+    if let Some(Plist::Array(shapes)) = layer.remove("shapes") {
+        for shape in shapes {
+            let Plist::Dictionary(mut shape) = shape else {
+                continue;
+            };
+            if shape.contains_key("ref") {
+                rename(&mut shape, "ref", "name");
+                if shape.remove("slant").is_some() {
+                    report.record(context, "component slant");
+                }
+                downgrade_transform(&mut shape);
+                components.push(Plist::Dictionary(shape));
+            } else {
+                if shape.remove("attr").is_some() {
+                    report.record(context, "path attributes");
+                }
+                if let Some(Plist::Array(nodes)) = shape.get_mut("nodes") {
+                    for node in nodes {
+                        if let Some(downgraded) = downgrade_node(node) {
+                            *node = downgraded;
+                        }
+                    }
+                }
+                paths.push(Plist::Dictionary(shape));
+            }
+        }
+    }
+    if !components.is_empty() {
+        layer.insert("components".into(), Plist::Array(components));
+    }
+    if !paths.is_empty() {
+        layer.insert("paths".into(), Plist::Array(paths));
+    }
+
+    for anchor in dicts_mut(layer, "anchors") {
+        if anchor.remove("orientation").is_some() {
+            report.record(context, "anchor orientation");
+        }
+        rename(anchor, "pos", "position");
+        if !anchor.contains_key("position") {
+            anchor.insert("position".into(), Plist::from("{0, 0}".to_string()));
+        }
+        format_point(anchor, "position");
+    }
+}
+
+/// Convert a node like `(100, 200, cs)` to `"100 200 CURVE SMOOTH"`.
+fn downgrade_node(node: &Plist) -> Option<Plist> {
+    let node = node.as_array()?;
+    let x = node.first()?.as_f64()?;
+    let y = node.get(1)?.as_f64()?;
+    let node_type = match node.get(2)?.as_str()? {
+        "l" => "LINE",
+        "ls" => "LINE SMOOTH",
+        "c" => "CURVE",
+        "cs" => "CURVE SMOOTH",
+        "q" => "QCURVE",
+        "qs" => "QCURVE SMOOTH",
+        "o" => "OFFCURVE",
+        _ => return None,
+    };
+    Some(Plist::from(format!(
+        "{} {} {node_type}",
+        format_number(x),
+        format_number(y)
+    )))
+}
+
+/// Replace the `pos`, `scale` and `angle` of Glyphs 3 with a `transform`.
+fn downgrade_transform(dict: &mut Dict) {
+    let pos = dict.remove("pos").as_ref().and_then(numbers);
+    let scale = dict.remove("scale").as_ref().and_then(numbers);
+    let angle = dict.remove("angle").as_ref().and_then(Plist::as_f64);
+    if pos.is_none() && scale.is_none() && angle.is_none() {
+        return;
+    }
+    let (tx, ty) = match pos.as_deref() {
+        Some(&[tx, ty]) => (tx, ty),
+        _ => (0.0, 0.0),
+    };
+    let (sx, sy) = match scale.as_deref() {
+        Some(&[sx, sy]) => (sx, sy),
+        _ => (1.0, 1.0),
+    };
+    let (sin, cos) = angle.unwrap_or(0.0).to_radians().sin_cos();
+    let transform: Vec<String> = [sx * cos, sx * sin, -sy * sin, sy * cos, tx, ty]
+        .into_iter()
+        .map(format_number)
+        .collect();
+    let transform = format!("{{{}}}", transform.join(", "));
+    dict.insert("transform".into(), Plist::from(transform));
+}
+
+/// Convert a point like `(10, 20)` to `"{10, 20}"`.
+fn format_point(dict: &mut Dict, key: &str) {
+    let point = dict.get(key).and_then(numbers);
+    if let Some(&[x, y]) = point.as_deref() {
+        let point = format!("{{{}, {}}}", format_number(x), format_number(y));
+        dict.insert(key.into(), Plist::from(point));
+    }
+}
+
+/// The numbers of an array.
+fn numbers(plist: &Plist) -> Option<Vec<f64>> {
+    plist.as_array()?.iter().map(Plist::as_f64).collect()
+}
+
+/// A number for a string value, rounded to hide floating point noise from
+/// computing transforms.
+fn format_number(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+    // Adding zero turns -0 into 0.
+    format!("{}", rounded + 0.0)
+}
+
+/// Add a custom parameter to a font or master.
+fn push_custom_parameter(dict: &mut Dict, name: &str, value: Plist) {
+    let parameter = Dict::from([
+        ("name".to_string(), Plist::from(name.to_string())),
+        ("value".to_string(), value),
+    ]);
+    let parameters = dict
+        .entry("customParameters".into())
+        .or_insert_with(|| Plist::Array(Vec::new()));
+    if let Plist::Array(parameters) = parameters {
+        parameters.push(Plist::Dictionary(parameter));
+    }
+}
+
+fn rename(dict: &mut Dict, old_key: &str, new_key: &str) {
+    if let Some(value) = dict.remove(old_key) {
+        dict.insert(new_key.into(), value);
+    }
+}
+
+/// Remove a custom parameter from a font or master, returning its value.
+fn take_custom_parameter(dict: &mut Dict, name: &str) -> Option<Plist> {
+    let Some(Plist::Array(parameters)) = dict.get_mut("customParameters") else {
+        return None;
+    };
+    let i = parameters
+        .iter()
+        .position(|p| p.get("name").and_then(Plist::as_str) == Some(name))?;
+    let Plist::Dictionary(mut parameter) = parameters.remove(i) else {
+        return None;
+    };
+    if parameters.is_empty() {
+        dict.remove("customParameters");
+    }
+    parameter.remove("value")
+}
+
+fn dicts<'a>(dict: &'a Dict, key: &str) -> impl Iterator<Item = &'a Dict> {
+    dict.get(key)
+        .and_then(Plist::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Plist::as_dict)
+}
+
+fn dicts_mut<'a>(dict: &'a mut Dict, key: &str) -> impl Iterator<Item = &'a mut Dict> {
+    let items = match dict.get_mut(key) {
+        Some(Plist::Array(items)) => Some(items),
+        _ => None,
+    };
+    items.into_iter().flatten().filter_map(|item| match item {
+        Plist::Dictionary(item) => Some(item),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Point;
+
+    use crate::{Font, FormatVersion, MetricType, NodeType, Plist, Shape, ToPlist};
+
+    const GLYPHS2: &str = r#"{
+.appVersion = "1352";
+date = "2020-01-01 00:00:00 +0000";
+designer = "A Designer";
+disablesAutomaticAlignment = 1;
+familyName = Test;
+customParameters = (
+{
+name = Axes;
+value = (
+{
+Name = Weight;
+Tag = wght;
+},
+{
+Name = Optical;
+Tag = opsz;
+}
+);
+}
+);
+fontMaster = (
+{
+alignmentZones = (
+"{800, 16}",
+"{0, -16}",
+"{-200, -16}"
+);
+ascender = 800;
+capHeight = 700;
+descender = -200;
+id = m01;
+italicAngle = 10;
+verticalStems = (
+80
+);
+weight = Light;
+weightValue = 50;
+widthValue = 12;
+xHeight = 500;
+},
+{
+id = m02;
+weight = Bold;
+weightValue = 150;
+widthValue = 24;
+}
+);
+glyphs = (
+{
+glyphname = A;
+leftKerningGroup = A;
+rightMetricsKey = "=H";
+unicode = 0041;
+layers = (
+{
+anchors = (
+{
+name = top;
+position = "{250, 700}";
+}
+);
+layerId = m01;
+paths = (
+{
+closed = 1;
+nodes = (
+"0 0 LINE",
+"100 0 OFFCURVE",
+"200 100 OFFCURVE",
+"200 200 CURVE SMOOTH",
+"100 300 LINE"
+);
+}
+);
+width = 500;
+},
+{
+associatedMasterId = m01;
+layerId = "brace";
+name = "{75, 18}";
+width = 500;
+},
+{
+associatedMasterId = m01;
+layerId = "bracket";
+name = "Alternate ]120]";
+width = 500;
+}
+);
+},
+{
+glyphname = Adieresis;
+unicode = "00C4,1E00";
+layers = (
+{
+components = (
+{
+name = A;
+},
+{
+name = dieresiscomb;
+transform = "{-1, 0, 0, 1, 300, 150}";
+}
+);
+layerId = m01;
+width = 500;
+}
+);
+},
+{
+glyphname = one;
+unicode = 1234;
+layers = ();
+}
+);
+instances = (
+{
+interpolationWeight = 120;
+name = SemiBold;
+weightClass = SemiBold;
+widthClass = Condensed;
+}
+);
+kerning = {
+m01 = {
+"@MMK_L_A" = {
+"@MMK_R_A" = -20;
+};
+};
+};
+unitsPerEm = 1000;
+versionMajor = 1;
+versionMinor = 0;
+}"#;
+
+    fn upgraded() -> Font {
+        let mut plist = Plist::parse(GLYPHS2).unwrap();
+        let Plist::Dictionary(dict) = &mut plist else {
+            panic!("expected a dictionary");
+        };
+        super::upgrade(dict);
+        match Font::try_from(plist) {
+            Ok(font) => font,
+            Err(why) => panic!("{why}\n{why:?}"),
+        }
+    }
+
+    #[test]
+    fn upgrade_font() {
+        let font = upgraded();
+        assert_eq!(font.format_version, Some(3));
+        assert!(font.settings.unwrap().disables_automatic_alignment);
+        assert!(font.custom_parameters.is_none());
+        assert!(font.other_stuff.contains_key("properties"));
+        assert!(!font.other_stuff.contains_key("designer"));
+
+        let axes = font.axes.unwrap();
+        let tags: Vec<&str> = axes.iter().map(|a| a.tag.as_str()).collect();
+        assert_eq!(tags, ["wght", "opsz"]);
+
+        let metric_types: Vec<_> = font
+            .metrics
+            .iter()
+            .filter_map(|m| m.r#type.clone())
+            .collect();
+        assert_eq!(
+            metric_types,
+            [
+                MetricType::Ascender,
+                MetricType::CapHeight,
+                MetricType::XHeight,
+                MetricType::Baseline,
+                MetricType::Descender,
+                MetricType::ItalicAngle
+            ]
+        );
+        let (light, bold) = (&font.font_master[0], &font.font_master[1]);
+        assert_eq!((light.name.as_str(), bold.name.as_str()), ("Light", "Bold"));
+        let light_metrics: Vec<(f64, f64)> = light
+            .metric_values
+            .iter()
+            .map(|m| (m.pos, m.over))
+            .collect();
+        assert_eq!(
+            light_metrics,
+            [
+                (800.0, 16.0),
+                (700.0, 0.0),
+                (500.0, 0.0),
+                (0.0, -16.0),
+                (-200.0, -16.0),
+                (10.0, 0.0)
+            ]
+        );
+        assert_eq!(bold.metric_values[0].pos, 800.0);
+        assert_eq!(light.axes_values, Some(vec![50.0, 12.0]));
+        assert_eq!(bold.axes_values, Some(vec![150.0, 24.0]));
+        assert_eq!(light.stem_values, Some(vec![80.0]));
+        assert_eq!(bold.stem_values, Some(vec![0.0]));
+        assert!(!light.other_stuff.contains_key("weightValue"));
+
+        let instance = &font.instances.as_ref().unwrap()[0];
+        assert_eq!(instance.axes_values, Some(vec![120.0, 100.0]));
+        assert_eq!((instance.weight_class, instance.width_class), (600, 3));
+
+        let kerning = &font.kerning_ltr.as_ref().unwrap()["m01"];
+        assert_eq!(kerning["@MMK_L_A"]["@MMK_R_A"], -20.0);
+    }
+
+    #[test]
+    fn upgrade_glyphs() {
+        let font = upgraded();
+        let a = font.get_glyph("A").unwrap();
+        assert_eq!(a.kern_left.as_deref(), Some("A"));
+        assert_eq!(a.metric_right.as_deref(), Some("=H"));
+        assert_eq!(font.glyph_for_codepoint('A').unwrap().glyphname, "A");
+        assert_eq!(
+            font.glyph_for_codepoint('\u{1E00}').unwrap().glyphname,
+            "Adieresis"
+        );
+        assert_eq!(
+            font.glyph_for_codepoint('\u{1234}').unwrap().glyphname,
+            "one"
+        );
+
+        let layer = &a.layers[0];
+        assert_eq!(
+            layer.anchors.as_ref().unwrap()[0].pos,
+            Point::new(250.0, 700.0)
+        );
+        let Shape::Path(path) = &layer.shapes[0] else {
+            panic!("expected a path");
+        };
+        assert!(path.closed);
+        let types: Vec<NodeType> = path.nodes.iter().map(|n| n.node_type).collect();
+        assert_eq!(
+            types,
+            [
+                NodeType::Line,
+                NodeType::OffCurve,
+                NodeType::OffCurve,
+                NodeType::CurveSmooth,
+                NodeType::Line
+            ]
+        );
+        assert_eq!(path.nodes[3].pt, Point::new(200.0, 200.0));
+
+        let brace = a.layers[1].attr.as_ref().unwrap();
+        assert_eq!(brace.coordinates, Some(vec![75.0, 18.0]));
+        let bracket = a.layers[2].attr.as_ref().unwrap();
+        let rules = bracket.axis_rules.as_ref().unwrap();
+        assert_eq!((rules[0].min, rules[0].max), (None, Some(120.0)));
+
+        let components: Vec<_> = font.get_glyph("Adieresis").unwrap().layers[0]
+            .components()
+            .collect();
+        assert_eq!(components[0].reference, "A");
+        assert_eq!(components[0].pos, None);
+        assert_eq!(components[1].pos, Some(Point::new(300.0, 150.0)));
+        let scale = components[1].scale.as_ref().unwrap();
+        assert_eq!((scale.horizontal, scale.vertical), (-1.0, 1.0));
+        assert_eq!(components[1].rotation, None);
+    }
+
+    fn downgraded(font: Font) -> (Plist, super::ConversionReport) {
+        let mut plist = font.to_plist().into_hashmap();
+        let report = super::downgrade(&mut plist);
+        (Plist::Dictionary(plist), report)
+    }
+
+    #[test]
+    fn downgrade_roundtrip() {
+        let contents = std::fs::read_to_string("testdata/NewFont.glyphs").unwrap();
+        let original = Plist::parse(&contents).unwrap();
+        let (plist, report) = downgraded(Font::load("testdata/NewFont.glyphs").unwrap());
+        assert!(report.is_lossless());
+        assert_eq!(plist, original);
+
+        let font = upgraded();
+        let (plist, report) = downgraded(font.clone());
+        assert!(report.is_lossless(), "{report:?}");
+        let Plist::Dictionary(mut dict) = plist else {
+            panic!("expected a dictionary");
+        };
+        super::upgrade(&mut dict);
+        assert_eq!(Font::try_from(Plist::Dictionary(dict)).unwrap(), font);
+
+        // Everything Glyphs 2 can represent survives, and it reads back.
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let (plist, report) = downgraded(font.clone());
+        assert!(report
+            .dropped
+            .contains(&r#"font: metric "x-height" for "case == 3""#.into()));
+        let mut dict = plist.into_hashmap();
+        super::upgrade(&mut dict);
+        let reread = Font::try_from(Plist::Dictionary(dict)).unwrap();
+        assert_eq!(reread.glyphs.len(), font.glyphs.len());
+    }
+
+    #[test]
+    fn downgrade_reports_losses() {
+        let mut font = upgraded();
+        font.font_master[0].number_values = Some(vec![1.0]);
+        font.instances.as_mut().unwrap()[0].r#type = Some(crate::font::InstanceType::Variable);
+        let a = font.get_glyph_mut("A").unwrap();
+        a.tags.push("tag".into());
+        let Shape::Path(path) = &mut a.layers[0].shapes[0] else {
+            panic!("expected a path");
+        };
+        let attr = crate::plist_dict! { "lineCapStart" => 1 };
+        path.attr = Some(attr.try_into().unwrap());
+
+        let (plist, report) = downgraded(font);
+        assert_eq!(
+            report.dropped,
+            [
+                r#"master "Light": number values"#,
+                r#"instance "SemiBold": variable font setting"#,
+                r#"glyph "A": tags"#,
+                r#"glyph "A", layer "m01": path attributes"#,
+            ]
+        );
+        assert_eq!(
+            plist.get("instances").and_then(Plist::as_array),
+            Some(&[][..])
+        );
+    }
+
+    #[test]
+    fn convert_to() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let glyph_count = font.glyphs.len();
+        // Rotations only survive the conversion to a transform matrix
+        // approximately.
+        let layers = font.glyphs.iter_mut().flat_map(|g| &mut g.layers);
+        for image in layers.filter_map(|l| l.background_image.as_mut()) {
+            image.angle = 0.0;
+        }
+
+        let report = font.convert_to(FormatVersion::Glyphs2).unwrap();
+        assert!(!report.is_lossless());
+        assert_eq!(font.save_format, FormatVersion::Glyphs2);
+        // The model itself stays in the Glyphs 3 format.
+        assert_eq!(font.format_version, Some(3));
+        assert!(!font.to_plist_string().contains(".formatVersion"));
+        assert_eq!(font.glyphs.len(), glyph_count);
+        let a = font.get_glyph("A").unwrap();
+        assert!(a.tags.is_empty());
+        assert!(a
+            .layers
+            .iter()
+            .flat_map(|l| l.paths())
+            .all(|p| p.attr.is_none()));
+
+        // Once converted, nothing more is lost.
+        let converted = font.clone();
+        assert!(font
+            .convert_to(FormatVersion::Glyphs2)
+            .unwrap()
+            .is_lossless());
+        assert_eq!(font, converted);
+
+        let report = font.convert_to(FormatVersion::Glyphs3).unwrap();
+        assert!(report.is_lossless());
+        assert_eq!(font.save_format, FormatVersion::Glyphs3);
+        assert!(font.to_plist_string().contains(".formatVersion = 3;"));
+    }
+
+    #[test]
+    fn upgrade_transform() {
+        let mut component = super::Dict::from([(
+            "transform".to_string(),
+            Plist::from("{0, 1, -1, 0, 10, 0}".to_string()),
+        )]);
+        super::upgrade_transform(&mut component);
+        assert_eq!(component["angle"], Plist::Integer(90));
+        assert_eq!(component.get("scale"), None);
+        assert_eq!(
+            component["pos"],
+            Plist::Array(vec![Plist::Integer(10), Plist::Integer(0)])
+        );
+    }
+}
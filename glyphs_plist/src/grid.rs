@@ -0,0 +1,150 @@
+//! Rounding coordinates to the grid set up in the font settings.
+
+use kurbo::Point;
+
+use crate::{Font, Layer, Settings, Shape};
+
+impl Settings {
+    /// The distance between grid points in font units: the grid length
+    /// divided by the grid subdivision, both 1 when not set. `None` when
+    /// the grid is switched off with a grid length of 0.
+    pub fn grid_step(&self) -> Option<f64> {
+        let (length, sub_division) = self.grid()?;
+        Some(f64::from(length) / f64::from(sub_division))
+    }
+
+    fn grid(&self) -> Option<(u16, u16)> {
+        let length = self.grid_length.unwrap_or(1);
+        let sub_division = self.grid_sub_division.unwrap_or(1).max(1);
+        (length > 0).then_some((length, sub_division))
+    }
+}
+
+impl Font {
+    /// Round node positions, anchors, component offsets and advance widths
+    /// in all layers to the grid from the font settings, returning the names
+    /// of the glyphs that changed.
+    ///
+    /// Nothing is rounded when the grid is switched off. Backgrounds are
+    /// left alone.
+    pub fn round_to_grid(&mut self) -> Vec<String> {
+        let settings = self.settings.clone().unwrap_or_default();
+        let Some(grid) = settings.grid() else {
+            return Vec::new();
+        };
+        let mut changed = Vec::new();
+        for glyph in &mut self.glyphs {
+            let mut glyph_changed = false;
+            for layer in &mut glyph.layers {
+                glyph_changed |= layer.round_to_grid(grid);
+            }
+            if glyph_changed {
+                changed.push(glyph.glyphname.to_string());
+            }
+        }
+        changed
+    }
+}
+
+impl Layer {
+    /// Round the layer to multiples of `length / sub_division`, returning
+    /// whether anything moved.
+    fn round_to_grid(&mut self, (length, sub_division): (u16, u16)) -> bool {
+        let (length, sub_division) = (f64::from(length), f64::from(sub_division));
+        let mut changed = false;
+        let mut round = |value: &mut f64| {
+            // Dividing last keeps fractional steps like 0.1 exact.
+            let rounded = (*value * sub_division / length).round() * length / sub_division;
+            changed |= rounded != *value;
+            *value = rounded;
+        };
+        let mut round_point = |pt: &mut Point| {
+            round(&mut pt.x);
+            round(&mut pt.y);
+        };
+
+        for shape in &mut self.shapes {
+            match shape {
+                Shape::Path(path) => {
+                    for node in &mut path.nodes {
+                        round_point(&mut node.pt);
+                    }
+                }
+                Shape::Component(component) => {
+                    if let Some(pos) = &mut component.pos {
+                        round_point(pos);
+                    }
+                }
+            }
+        }
+        for anchor in self.anchors.iter_mut().flatten() {
+            round_point(&mut anchor.pos);
+        }
+        round(&mut self.width);
+        for value in [&mut self.vert_width, &mut self.vert_origin]
+            .into_iter()
+            .flatten()
+        {
+            round(value);
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Point;
+
+    use crate::{Font, Settings, Shape};
+
+    #[test]
+    fn grid_step() {
+        let mut settings = Settings::new();
+        assert_eq!(settings.grid_step(), Some(1.0));
+        settings.grid_length = Some(10);
+        settings.grid_sub_division = Some(100);
+        assert_eq!(settings.grid_step(), Some(0.1));
+        settings.grid_length = Some(0);
+        assert_eq!(settings.grid_step(), None);
+    }
+
+    #[test]
+    fn round_to_grid() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let settings = font.settings.as_ref().unwrap();
+        assert_eq!(
+            (settings.grid_length, settings.grid_sub_division),
+            (Some(10), Some(10))
+        );
+        assert_eq!(font.round_to_grid(), Vec::<String>::new());
+
+        let a = &mut font.get_glyph_mut("A").unwrap().layers[0];
+        a.width += 0.4;
+        a.set_anchor("top", (100.26, 700.0));
+        let Shape::Path(path) = &mut a.shapes[0] else {
+            panic!("expected a path");
+        };
+        path.nodes[0].pt.x += 0.75;
+        font.settings.as_mut().unwrap().grid_sub_division = Some(100);
+        let original = font.clone();
+
+        assert_eq!(font.round_to_grid(), ["A"]);
+        let (a, original_a) = (
+            &font.get_glyph("A").unwrap().layers[0],
+            &original.get_glyph("A").unwrap().layers[0],
+        );
+        assert_eq!(a.width, (original_a.width * 10.0).round() / 10.0);
+        assert_eq!(a.anchor("top").unwrap().pos, Point::new(100.3, 700.0));
+        let (Shape::Path(path), Shape::Path(original_path)) = (&a.shapes[0], &original_a.shapes[0])
+        else {
+            panic!("expected a path");
+        };
+        let x = original_path.nodes[0].pt.x;
+        assert_ne!(x, (x * 10.0).round() / 10.0);
+        assert_eq!(path.nodes[0].pt.x, (x * 10.0).round() / 10.0);
+
+        font.settings.as_mut().unwrap().grid_length = Some(0);
+        font.get_glyph_mut("A").unwrap().layers[0].width += 0.01;
+        assert!(font.round_to_grid().is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! Creating and editing guides, on layers and masters.
+
+use kurbo::{Affine, Point, Vec2};
+
+use crate::font::GuideLine;
+use crate::to_plist::ToPlist;
+use crate::{FontMaster, Layer, Path, Plist};
+
+impl GuideLine {
+    /// An unlocked guide at a position, at an angle in degrees
+    /// counter-clockwise from the x axis.
+    pub fn new(pos: impl Into<Point>, angle: f64) -> Self {
+        Self {
+            name: None,
+            angle,
+            pos: pos.into(),
+            locked: false,
+            lock_angle: 0.0,
+            show_measurement: false,
+            orientation: None,
+            filter: None,
+        }
+    }
+
+    /// A guide through two points, placed at the first, like Glyphs.app adds
+    /// for two selected nodes.
+    pub fn through_points(p0: impl Into<Point>, p1: impl Into<Point>) -> Self {
+        let p0 = p0.into();
+        let angle = (p1.into() - p0).atan2().to_degrees();
+        Self::new(p0, angle)
+    }
+
+    /// A guide through a node of a path, along the direction of the path
+    /// there, like Glyphs.app adds for a single selected node: from the
+    /// point before the node to the point after it, so along the handles of
+    /// smooth nodes. `None` if the path has no such node or is a lone point.
+    pub fn along_node(path: &Path, index: usize) -> Option<Self> {
+        let node = path.nodes.get(index)?;
+        let last = path.nodes.len() - 1;
+        let before = match index {
+            0 if path.closed => path.nodes[last].pt,
+            0 => node.pt,
+            _ => path.nodes[index - 1].pt,
+        };
+        let after = match index {
+            _ if index < last => path.nodes[index + 1].pt,
+            _ if path.closed => path.nodes[0].pt,
+            _ => node.pt,
+        };
+        let direction = after - before;
+        (direction != Vec2::ZERO).then(|| Self::new(node.pt, direction.atan2().to_degrees()))
+    }
+
+    /// The unit vector along the guide.
+    pub fn direction(&self) -> Vec2 {
+        Vec2::from_angle(self.angle.to_radians())
+    }
+
+    /// Transform the position and angle of the guide.
+    pub fn apply_affine(&mut self, transform: Affine) {
+        let direction = transform * (self.pos + self.direction()) - transform * self.pos;
+        self.pos = transform * self.pos;
+        self.angle = direction.atan2().to_degrees();
+    }
+
+    /// Mirror the guide across the vertical line at `x`.
+    pub fn mirror_horizontally(&mut self, x: f64) {
+        self.apply_affine(Affine::new([-1.0, 0.0, 0.0, 1.0, 2.0 * x, 0.0]));
+    }
+
+    /// Mirror the guide across the horizontal line at `y`.
+    pub fn mirror_vertically(&mut self, y: f64) {
+        self.apply_affine(Affine::new([1.0, 0.0, 0.0, -1.0, 0.0, 2.0 * y]));
+    }
+}
+
+impl Layer {
+    /// The guides of the layer.
+    pub fn guides(&self) -> impl Iterator<Item = &GuideLine> {
+        self.guides.iter().flatten()
+    }
+
+    /// Add a guide to the layer.
+    pub fn add_guide(&mut self, guide: GuideLine) {
+        self.guides.get_or_insert_with(Vec::new).push(guide);
+    }
+
+    /// Lock or unlock all guides of the layer.
+    pub fn set_guides_locked(&mut self, locked: bool) {
+        for guide in self.guides.iter_mut().flatten() {
+            guide.locked = locked;
+        }
+    }
+}
+
+impl FontMaster {
+    /// The global guides of the master, which apply to all its layers.
+    /// Guides that can't be read are skipped.
+    pub fn guides(&self) -> impl Iterator<Item = GuideLine> + '_ {
+        self.other_stuff
+            .get("guides")
+            .and_then(Plist::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|guide| GuideLine::try_from(guide.clone()).ok())
+    }
+
+    /// Replace the global guides of the master.
+    pub fn set_guides(&mut self, guides: Vec<GuideLine>) {
+        if guides.is_empty() {
+            self.other_stuff.remove("guides");
+        } else {
+            let guides = guides.iter().map(ToPlist::to_plist).collect();
+            self.other_stuff
+                .insert("guides".into(), Plist::Array(guides));
+        }
+    }
+
+    /// Add a global guide to the master.
+    pub fn add_guide(&mut self, guide: GuideLine) {
+        let mut guides: Vec<GuideLine> = self.guides().collect();
+        guides.push(guide);
+        self.set_guides(guides);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Font, NodeType};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn creating_guides() {
+        let guide = GuideLine::through_points((10.0, 10.0), (20.0, 20.0));
+        assert_eq!(guide.pos, Point::new(10.0, 10.0));
+        assert_close(guide.angle, 45.0);
+
+        let mut path = Path::new(false);
+        for (x, y) in [(0.0, 0.0), (0.0, 100.0), (100.0, 100.0)] {
+            path.add((x, y), NodeType::Line);
+        }
+        assert_close(GuideLine::along_node(&path, 0).unwrap().angle, 90.0);
+        assert_close(GuideLine::along_node(&path, 1).unwrap().angle, 45.0);
+        assert_close(GuideLine::along_node(&path, 2).unwrap().angle, 0.0);
+        path.closed = true;
+        assert_close(GuideLine::along_node(&path, 0).unwrap().angle, 180.0);
+        assert_eq!(GuideLine::along_node(&path, 3), None);
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut guide = GuideLine::new((100.0, 50.0), 30.0);
+        guide.mirror_horizontally(250.0);
+        assert!(guide.pos.distance(Point::new(400.0, 50.0)) < 1e-9);
+        assert_close(guide.angle, 150.0);
+        guide.mirror_vertically(0.0);
+        assert!(guide.pos.distance(Point::new(400.0, -50.0)) < 1e-9);
+        assert_close(guide.angle, -150.0);
+    }
+
+    #[test]
+    fn layer_and_master_guides() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let master = &mut font.font_master[0];
+        let guides: Vec<GuideLine> = master.guides().collect();
+        assert_eq!(guides[0].name.as_deref(), Some("guide name"));
+        assert!(guides[0].locked);
+
+        master.add_guide(GuideLine::new((0.0, 500.0), 0.0));
+        let mut all: Vec<GuideLine> = master.guides().collect();
+        assert_eq!(all.len(), guides.len() + 1);
+        all.truncate(guides.len());
+        master.set_guides(all);
+        assert_eq!(master.guides().collect::<Vec<_>>(), guides);
+
+        let mut layer = Layer::new("m01");
+        layer.add_guide(GuideLine::new((0.0, 0.0), 90.0));
+        layer.add_guide(GuideLine::new((0.0, 200.0), 0.0));
+        layer.set_guides_locked(true);
+        assert_eq!(layer.guides().filter(|g| g.locked).count(), 2);
+    }
+}
@@ -0,0 +1,306 @@
+//! Carrying the hints of layers through UFO conversion.
+//!
+//! Hints aren't part of the model yet; layers keep them as read, in
+//! `other_stuff`. On export, PostScript stem hints are written to the
+//! `public.postscript.hints` glyph lib key, and all hints as Glyphs stores
+//! them to a Glyphs-specific key, so they come back unchanged on import.
+//! Glyphs' TrueType hints are instructions for its own autohinter rather
+//! than TrueType assembly, so there is nothing to put in
+//! `public.truetype.instructions`; instructions read from a UFO are kept in
+//! the layer's userData and written back on export.
+
+use std::collections::HashMap;
+
+use crate::scale::scale_number;
+use crate::{Layer, Plist};
+
+/// The glyph lib key for the hints as stored in Glyphs files.
+const GLYPHS_HINTS: &str = "com.schriftgestaltung.hints";
+const PUBLIC_POSTSCRIPT_HINTS: &str = "public.postscript.hints";
+const PUBLIC_TRUETYPE_INSTRUCTIONS: &str = "public.truetype.instructions";
+
+/// Write the hints of a layer to the lib of its UFO glyph.
+pub(crate) fn hints_to_ufo(layer: &Layer, lib: &mut norad::Plist) {
+    if let Some(instructions) = layer.user_data.get(PUBLIC_TRUETYPE_INSTRUCTIONS) {
+        lib.insert(PUBLIC_TRUETYPE_INSTRUCTIONS.into(), to_value(instructions));
+    }
+    let Some(Plist::Array(hints)) = layer.other_stuff.get("hints") else {
+        return;
+    };
+    lib.insert(GLYPHS_HINTS.into(), to_value(&Plist::Array(hints.clone())));
+
+    let stems: Vec<plist::Value> = hints
+        .iter()
+        .filter_map(|hint| postscript_stem(layer, hint))
+        .map(|(horizontal, pos, width)| {
+            let kind = if horizontal { "hstem" } else { "vstem" };
+            format!("{kind} {pos} {width}").into()
+        })
+        .collect();
+    if !stems.is_empty() {
+        let hint_set = plist::Dictionary::from_iter([
+            ("pointTag", plist::Value::from("")),
+            ("stems", stems.into()),
+        ]);
+        let hints = plist::Dictionary::from_iter([
+            ("formatVersion", plist::Value::from("1")),
+            ("id", "".into()),
+            ("hintSetList", vec![plist::Value::from(hint_set)].into()),
+            ("flexList", Vec::<plist::Value>::new().into()),
+        ]);
+        lib.insert(PUBLIC_POSTSCRIPT_HINTS.into(), hints.into());
+    }
+}
+
+/// Read the hints of a layer from the lib of its UFO glyph.
+///
+/// Without Glyphs' own hints, PostScript stems become stem hints at fixed
+/// positions, as the nodes they were attached to aren't known.
+pub(crate) fn hints_from_ufo(lib: &norad::Plist, layer: &mut Layer) {
+    if let Some(instructions) = lib.get(PUBLIC_TRUETYPE_INSTRUCTIONS) {
+        layer.user_data.insert(
+            PUBLIC_TRUETYPE_INSTRUCTIONS.into(),
+            from_value(instructions),
+        );
+    }
+    if let Some(hints) = lib.get(GLYPHS_HINTS) {
+        layer.other_stuff.insert("hints".into(), from_value(hints));
+        return;
+    }
+
+    let stems = lib
+        .get(PUBLIC_POSTSCRIPT_HINTS)
+        .and_then(plist::Value::as_dictionary)
+        .and_then(|hints| hints.get("hintSetList")?.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|set| set.as_dictionary()?.get("stems")?.as_array())
+        .flatten()
+        .filter_map(plist::Value::as_string);
+    let mut hints = Vec::new();
+    for stem in stems {
+        let [kind, pos, width] = stem.split_whitespace().collect::<Vec<_>>()[..] else {
+            continue;
+        };
+        let (Ok(pos), Ok(width)) = (pos.parse::<f64>(), width.parse::<f64>()) else {
+            continue;
+        };
+        let mut hint = vec![
+            ("type".to_string(), Plist::String("Stem".into())),
+            (
+                "place".to_string(),
+                Plist::Array(vec![pos.into(), width.into()]),
+            ),
+        ];
+        match kind {
+            "hstem" => hint.push(("horizontal".to_string(), Plist::Integer(1))),
+            "vstem" => (),
+            _ => continue,
+        }
+        hints.push(Plist::Dictionary(hint.into_iter().collect()));
+    }
+    if !hints.is_empty() {
+        layer
+            .other_stuff
+            .insert("hints".into(), Plist::Array(hints));
+    }
+}
+
+/// Move the hints kept in `other_stuff` horizontally. Hints attached to
+/// nodes move with them; vertical stems at fixed places are moved here.
+pub(crate) fn shift_hints(other_stuff: &mut HashMap<String, Plist>, delta: f64) {
+    let Some(Plist::Array(hints)) = other_stuff.get_mut("hints") else {
+        return;
+    };
+    for hint in hints {
+        if hint.get("horizontal").and_then(Plist::as_i64) == Some(1) {
+            continue;
+        }
+        let Plist::Dictionary(hint) = hint else {
+            continue;
+        };
+        if let Some(Plist::Array(place)) = hint.get_mut("place") {
+            if let Some(pos) = place.first_mut() {
+                if let Some(value) = pos.as_f64() {
+                    *pos = Plist::Float(value + delta);
+                }
+            }
+        }
+    }
+}
+
+/// Scale the fixed places of the hints kept in `other_stuff` as part of
+/// scaling the whole font. The widths of ghost hints are fixed markers
+/// rather than distances and stay as they are.
+pub(crate) fn scale_hints(other_stuff: &mut HashMap<String, Plist>, factor: f64) {
+    let Some(Plist::Array(hints)) = other_stuff.get_mut("hints") else {
+        return;
+    };
+    for hint in hints {
+        let Plist::Dictionary(hint) = hint else {
+            continue;
+        };
+        let ghost = matches!(
+            hint.get("type").and_then(Plist::as_str),
+            Some("TopGhost" | "BottomGhost")
+        );
+        if let Some(Plist::Array(place)) = hint.get_mut("place") {
+            let scaled = if ghost { 1 } else { 2 };
+            for value in place.iter_mut().take(scaled) {
+                scale_number(value, factor);
+            }
+        }
+    }
+}
+
+/// Whether a hint is a horizontal PostScript stem, and its position and
+/// width, from its fixed place or the nodes it is attached to.
+fn postscript_stem(layer: &Layer, hint: &Plist) -> Option<(bool, f64, f64)> {
+    if hint.get("type").and_then(Plist::as_str).unwrap_or("Stem") != "Stem" {
+        return None;
+    }
+    let horizontal = hint.get("horizontal").and_then(Plist::as_i64) == Some(1);
+    let coordinate = |point: kurbo::Point| if horizontal { point.y } else { point.x };
+    let (pos, width) = match hint.get("place") {
+        Some(place) => {
+            let place = place.as_array()?;
+            (place.first()?.as_f64()?, place.get(1)?.as_f64()?)
+        }
+        None => {
+            let origin = coordinate(node_position(layer, hint.get("origin")?)?);
+            let target = coordinate(node_position(layer, hint.get("target")?)?);
+            (origin, target - origin)
+        }
+    };
+    // Stems are written from their lower edge.
+    let (pos, width) = if width < 0.0 {
+        (pos + width, -width)
+    } else {
+        (pos, width)
+    };
+    Some((horizontal, pos, width))
+}
+
+/// The position of the node a hint refers to by path and node index.
+fn node_position(layer: &Layer, reference: &Plist) -> Option<kurbo::Point> {
+    let reference = reference.as_array()?;
+    let path = usize::try_from(reference.first()?.as_i64()?).ok()?;
+    let node = usize::try_from(reference.get(1)?.as_i64()?).ok()?;
+    Some(layer.paths().nth(path)?.nodes.get(node)?.pt)
+}
+
+fn to_value(plist: &Plist) -> plist::Value {
+    match plist {
+        Plist::Dictionary(dict) => dict
+            .iter()
+            .map(|(key, value)| (key.clone(), to_value(value)))
+            .collect::<plist::Dictionary>()
+            .into(),
+        Plist::Array(items) => items.iter().map(to_value).collect::<Vec<_>>().into(),
+        Plist::String(s) => s.clone().into(),
+        Plist::Integer(i) => (*i).into(),
+        Plist::Float(f) => (*f).into(),
+    }
+}
+
+fn from_value(value: &plist::Value) -> Plist {
+    match value {
+        plist::Value::Dictionary(dict) => Plist::Dictionary(
+            dict.iter()
+                .map(|(key, value)| (key.clone(), from_value(value)))
+                .collect(),
+        ),
+        plist::Value::Array(items) => Plist::Array(items.iter().map(from_value).collect()),
+        plist::Value::Boolean(b) => Plist::Integer(*b as i64),
+        plist::Value::Integer(i) => match i.as_signed() {
+            Some(i) => Plist::Integer(i),
+            None => Plist::Float(i.as_unsigned().unwrap_or_default() as f64),
+        },
+        plist::Value::Real(f) => Plist::Float(*f),
+        plist::Value::String(s) => Plist::String(s.clone()),
+        other => Plist::String(format!("{other:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Font;
+
+    #[test]
+    fn postscript_stems() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let layer = font
+            .glyphs
+            .iter()
+            .flat_map(|g| &g.layers)
+            .find(|l| {
+                l.other_stuff
+                    .get("hints")
+                    .and_then(|h| h.as_array()?.first()?.get("place"))
+                    .is_none()
+                    && l.other_stuff.contains_key("hints")
+            })
+            .unwrap();
+        let mut lib = norad::Plist::new();
+        hints_to_ufo(layer, &mut lib);
+
+        let stems = lib[PUBLIC_POSTSCRIPT_HINTS].as_dictionary().unwrap()["hintSetList"]
+            .as_array()
+            .unwrap()[0]
+            .as_dictionary()
+            .unwrap()["stems"]
+            .as_array()
+            .unwrap()
+            .clone();
+        let path = layer.paths().next().unwrap();
+        let (y0, y1) = (path.nodes[0].pt.y, path.nodes[7].pt.y);
+        let expected = format!("hstem {} {}", y0.min(y1), (y1 - y0).abs());
+        assert_eq!(stems[0].as_string(), Some(expected.as_str()));
+
+        let mut imported = Layer::new("m01");
+        hints_from_ufo(&lib, &mut imported);
+        assert_eq!(imported.other_stuff["hints"], layer.other_stuff["hints"]);
+    }
+
+    #[test]
+    fn from_public_keys() {
+        let mut lib = norad::Plist::new();
+        let hint_set = plist::Dictionary::from_iter([(
+            "stems",
+            plist::Value::from(vec![plist::Value::from("vstem 40 60")]),
+        )]);
+        let hints =
+            plist::Dictionary::from_iter([("hintSetList", vec![plist::Value::from(hint_set)])]);
+        lib.insert(PUBLIC_POSTSCRIPT_HINTS.into(), hints.into());
+        lib.insert(PUBLIC_TRUETYPE_INSTRUCTIONS.into(), "assembly".into());
+
+        let mut layer = Layer::new("m01");
+        hints_from_ufo(&lib, &mut layer);
+        assert_eq!(
+            layer.other_stuff["hints"],
+            Plist::Array(vec![Plist::Dictionary(
+                [
+                    ("type".to_string(), Plist::String("Stem".into())),
+                    (
+                        "place".to_string(),
+                        Plist::Array(vec![40.0.into(), 60.0.into()])
+                    ),
+                ]
+                .into()
+            )])
+        );
+
+        let mut exported = norad::Plist::new();
+        hints_to_ufo(&layer, &mut exported);
+        assert_eq!(
+            exported[PUBLIC_TRUETYPE_INSTRUCTIONS],
+            plist::Value::from("assembly")
+        );
+        let stems = &exported[PUBLIC_POSTSCRIPT_HINTS].as_dictionary().unwrap()["hintSetList"];
+        assert_eq!(
+            stems.as_array().unwrap()[0].as_dictionary().unwrap()["stems"],
+            plist::Value::from(vec![plist::Value::from("vstem 40 60")])
+        );
+    }
+}
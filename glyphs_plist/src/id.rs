@@ -0,0 +1,198 @@
+//! The IDs of masters and layers, shared between the many layers that refer
+//! to the same master.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::to_plist::ToPlist;
+use crate::{Font, Plist};
+
+/// The ID of a master or a layer.
+///
+/// Cloning an ID shares it, and loading a font makes the master layers of
+/// all glyphs share the IDs of their masters, so comparing them with each
+/// other usually doesn't need to look at the text.
+#[derive(Clone, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Id(Arc<str>);
+
+impl Id {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Id {
+    fn eq(&self, other: &Id) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Hash for Id {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl Deref for Id {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Id {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Id {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", &*self.0)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(id: &str) -> Id {
+        Id(id.into())
+    }
+}
+
+impl From<String> for Id {
+    fn from(id: String) -> Id {
+        Id(id.into())
+    }
+}
+
+impl From<&String> for Id {
+    fn from(id: &String) -> Id {
+        Id(id.as_str().into())
+    }
+}
+
+impl From<&Id> for Id {
+    fn from(id: &Id) -> Id {
+        id.clone()
+    }
+}
+
+impl From<Id> for String {
+    fn from(id: Id) -> String {
+        id.0.to_string()
+    }
+}
+
+impl PartialEq<str> for Id {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Id {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Id {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+impl PartialEq<Id> for str {
+    fn eq(&self, other: &Id) -> bool {
+        self == &*other.0
+    }
+}
+
+impl PartialEq<Id> for &str {
+    fn eq(&self, other: &Id) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<Id> for String {
+    fn eq(&self, other: &Id) -> bool {
+        self.as_str() == &*other.0
+    }
+}
+
+impl From<Plist> for Id {
+    fn from(plist: Plist) -> Id {
+        String::from(plist).into()
+    }
+}
+
+impl ToPlist for Id {
+    fn to_plist(&self) -> Plist {
+        self.0.to_string().into()
+    }
+}
+
+impl Font {
+    /// Make the master layers of the glyphs share the IDs of their masters.
+    pub(crate) fn share_ids(&mut self) {
+        let ids: Vec<Id> = self.font_master.iter().map(|m| m.id.clone()).collect();
+        let share = |id: &mut Id| {
+            if let Some(shared) = ids.iter().find(|shared| *shared == id) {
+                *id = shared.clone();
+            }
+        };
+        for layer in self.glyphs.iter_mut().flat_map(|g| &mut g.layers) {
+            share(&mut layer.layer_id);
+            if let Some(id) = &mut layer.associated_master_id {
+                share(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_ids() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let master_id = &font.font_master[0].id;
+        for glyph in &font.glyphs {
+            let layer = glyph.master_layer(master_id).unwrap();
+            assert!(Arc::ptr_eq(&layer.layer_id.0, &master_id.0));
+        }
+
+        // Layers associated with a master share its ID too.
+        let mut font = font.clone();
+        let master_id = font.font_master[0].id.to_string();
+        font.glyphs[0]
+            .layers
+            .push(crate::Layer::new_associated("brace", master_id.as_str()));
+        let font: Font = font.to_plist().to_string().parse().unwrap();
+        let layer = font.glyphs[0].layers.iter().find(|l| l.layer_id == "brace");
+        let associated = layer.unwrap().associated_master_id.as_ref().unwrap();
+        assert!(Arc::ptr_eq(&font.font_master[0].id.0, &associated.0));
+        assert_eq!(Id::from("m01"), "m01");
+        assert_eq!(Id::from("m01"), Id::from(String::from("m01")));
+    }
+}
@@ -0,0 +1,1146 @@
+//! Interpolating glyphs between their master and intermediate layers, and
+//! adding masters interpolated from the others.
+//!
+//! Locations are normalized per axis to -1..1 around the origin master, and
+//! the layers are combined with the same variation model fontTools uses, so
+//! the results match what a compiled variable font would draw.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+use kurbo::Point;
+use thiserror::Error;
+
+use crate::font::Scale;
+use crate::{
+    Font, FontMaster, Glyph, Id, Layer, MasterMetric, NodeType, ParameterValue, Plist, Shape,
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum InterpolationError {
+    #[error("the font has no masters")]
+    NoMasters,
+    #[error("expected a location with {expected} axis values, got {found}")]
+    WrongLocation { expected: usize, found: usize },
+    #[error("glyph {0:?} has no layer for the origin master")]
+    MissingOriginLayer(String),
+    #[error("layer {layer:?} of glyph {glyph:?} is incompatible: {reason}")]
+    Incompatible {
+        glyph: String,
+        layer: String,
+        reason: String,
+    },
+}
+
+impl Glyph {
+    /// Interpolate the glyph at a location in design coordinates, one per
+    /// axis of the font.
+    ///
+    /// The master layers and the brace layers within the masters' range take
+    /// part; a glyph may lack layers for masters other than the origin
+    /// master, which is the one named by the "Variable Font Origin" custom
+    /// parameter or else the first. Node positions, anchors, component
+    /// placement and the advance width are interpolated; everything else is
+    /// taken from the origin master's layer. Locations outside the masters'
+    /// range are clamped to it.
+    pub fn interpolate(&self, font: &Font, location: &[f64]) -> Result<Layer, InterpolationError> {
+        self.interpolate_at(font, location, false)
+    }
+
+    /// Interpolate the glyph like [`Glyph::interpolate`], extrapolating
+    /// beyond the masters' range if `extrapolate` is set rather than
+    /// clamping to it.
+    fn interpolate_at(
+        &self,
+        font: &Font,
+        location: &[f64],
+        extrapolate: bool,
+    ) -> Result<Layer, InterpolationError> {
+        if font.font_master.is_empty() {
+            return Err(InterpolationError::NoMasters);
+        }
+        let axis_count = font.axes.as_ref().map_or(0, Vec::len);
+        if location.len() != axis_count {
+            return Err(InterpolationError::WrongLocation {
+                expected: axis_count,
+                found: location.len(),
+            });
+        }
+        let space = DesignSpace::new(font, axis_count);
+        let origin = &font.font_master[space.origin];
+        let base = self
+            .master_layer(&origin.id)
+            .ok_or_else(|| InterpolationError::MissingOriginLayer(self.glyphname.to_string()))?;
+
+        let mut sources: Vec<(Vec<f64>, &Layer)> = vec![(vec![0.0; axis_count], base)];
+        for master in &font.font_master {
+            let Some(layer) = self.master_layer(&master.id) else {
+                continue;
+            };
+            let location = space.normalize(&master_location(master, axis_count));
+            if !sources.iter().any(|(l, _)| *l == location) {
+                sources.push((location, layer));
+            }
+        }
+        for layer in &self.layers {
+            let Some(coordinates) = layer.coordinates() else {
+                continue;
+            };
+            if layer.is_master_layer() || !space.contains(coordinates) {
+                continue;
+            }
+            let location = space.normalize(coordinates);
+            if !sources.iter().any(|(l, _)| *l == location) {
+                sources.push((location, layer));
+            }
+        }
+
+        let values = sources
+            .iter()
+            .map(|(_, layer)| {
+                layer_values(base, layer).map_err(|reason| InterpolationError::Incompatible {
+                    glyph: self.glyphname.to_string(),
+                    layer: layer.layer_id.to_string(),
+                    reason,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let locations = sources.into_iter().map(|(l, _)| l).collect();
+        let model = VariationModel::new(locations);
+        let interpolated = match extrapolate {
+            true => model.extrapolate(&values, &space.normalize_unclamped(location)),
+            false => model.interpolate(&values, &space.normalize(location)),
+        };
+        Ok(layer_from_values(base, &interpolated))
+    }
+}
+
+impl Font {
+    /// Interpolate values given per master, in master order, at a location
+    /// in design coordinates. Locations outside the masters' range are
+    /// clamped to it.
+    pub(crate) fn interpolate_master_values(
+        &self,
+        values: &[Vec<f64>],
+        location: &[f64],
+    ) -> Result<Vec<f64>, InterpolationError> {
+        self.master_values_at(values, location, false)
+    }
+
+    /// Interpolate values given per master like
+    /// [`Font::interpolate_master_values`], extrapolating beyond the
+    /// masters' range if `extrapolate` is set.
+    fn master_values_at(
+        &self,
+        values: &[Vec<f64>],
+        location: &[f64],
+        extrapolate: bool,
+    ) -> Result<Vec<f64>, InterpolationError> {
+        if self.font_master.is_empty() {
+            return Err(InterpolationError::NoMasters);
+        }
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let space = DesignSpace::new(self, axis_count);
+        let others = (0..self.font_master.len()).filter(|&i| i != space.origin);
+        let mut locations: Vec<Vec<f64>> = Vec::new();
+        let mut sources = Vec::new();
+        for i in std::iter::once(space.origin).chain(others) {
+            let location = space.normalize(&master_location(&self.font_master[i], axis_count));
+            if !locations.contains(&location) {
+                locations.push(location);
+                sources.push(values[i].clone());
+            }
+        }
+        let model = VariationModel::new(locations);
+        Ok(match extrapolate {
+            true => model.extrapolate(&sources, &space.normalize_unclamped(location)),
+            false => model.interpolate(&sources, &space.normalize(location)),
+        })
+    }
+}
+
+impl Layer {
+    /// The coordinates of an intermediate layer normalized to -1..1 on each
+    /// axis, the way a variable font built from the font would see them.
+    ///
+    /// The origin master is at 0 and the extremes of each axis at -1 and 1.
+    /// An axis spans the masters, widened to the design values of its
+    /// "Axis Mappings" custom parameter if there is one. Returns `None` for
+    /// layers without coordinates or with one per axis too few or many.
+    pub fn normalized_coordinates(&self, font: &Font) -> Option<Vec<f64>> {
+        let axis_count = font.axes.as_ref().map_or(0, Vec::len);
+        let coordinates = self.coordinates()?;
+        if coordinates.len() != axis_count || font.font_master.is_empty() {
+            return None;
+        }
+        let space = DesignSpace::new(font, axis_count).with_axis_mappings(font);
+        Some(space.normalize(coordinates))
+    }
+}
+
+impl Font {
+    /// The layers drawn at a location in design coordinates, in glyph order:
+    /// the master layers of the masters there, and the intermediate layers
+    /// with exactly those coordinates.
+    pub fn layers_at_location(&self, location: &[f64]) -> Vec<(&Glyph, &Layer)> {
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let masters: Vec<&str> = self
+            .font_master
+            .iter()
+            .filter(|m| master_location(m, axis_count) == location)
+            .map(|m| m.id.as_str())
+            .collect();
+        self.glyphs
+            .iter()
+            .flat_map(|glyph| glyph.layers.iter().map(move |layer| (glyph, layer)))
+            .filter(|(_, layer)| match layer.is_master_layer() {
+                true => masters.contains(&layer.layer_id.as_str()),
+                false => layer.coordinates() == Some(location),
+            })
+            .collect()
+    }
+}
+
+impl Font {
+    /// Add a master at a location in design coordinates, one per axis, and
+    /// return its ID. Its glyph layers, metrics, numbers, stems and kerning
+    /// are interpolated from the existing masters as by
+    /// [`Glyph::interpolate`], or extrapolated outside their range.
+    ///
+    /// Nothing else, like custom parameters or user data, is copied from the
+    /// other masters, except that the master gets an "Axis Location" custom
+    /// parameter at its user coordinates if any other master has one. The ID
+    /// is the first of `m01`, `m02` and so on that is free.
+    /// Kerning pairs a master lacks count as zero, or for left-to-right
+    /// kerning as the group kerning that applies. If any glyph can't be
+    /// interpolated, nothing changes.
+    pub fn add_master_at(
+        &mut self,
+        location: &[f64],
+        name: &str,
+    ) -> Result<Id, InterpolationError> {
+        if self.font_master.is_empty() {
+            return Err(InterpolationError::NoMasters);
+        }
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        if location.len() != axis_count {
+            return Err(InterpolationError::WrongLocation {
+                expected: axis_count,
+                found: location.len(),
+            });
+        }
+        let id: Id = (1..)
+            .map(|i| format!("m{i:02}"))
+            .find(|id| !self.font_master.iter().any(|m| m.id == id.as_str()))
+            .unwrap()
+            .into();
+
+        let layers = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let mut layer = glyph.interpolate_at(self, location, true)?;
+                layer.layer_id = id.clone();
+                layer.associated_master_id = None;
+                Ok(layer)
+            })
+            .collect::<Result<Vec<_>, InterpolationError>>()?;
+
+        let metric_count = self.metrics.len();
+        let number_count = self.numbers.as_ref().map_or(0, Vec::len);
+        let stem_count = self.stems.as_ref().map_or(0, Vec::len);
+        let padded = |values: &Option<Vec<f64>>, count: usize| {
+            let mut values = values.clone().unwrap_or_default();
+            values.resize(count, 0.0);
+            values
+        };
+        let values: Vec<Vec<f64>> = self
+            .font_master
+            .iter()
+            .map(|master| {
+                let mut values: Vec<f64> = (0..metric_count)
+                    .flat_map(|i| {
+                        let metric = master.metric_values.get(i).cloned().unwrap_or_default();
+                        [metric.pos, metric.over]
+                    })
+                    .collect();
+                values.extend(padded(&master.number_values, number_count));
+                values.extend(padded(&master.stem_values, stem_count));
+                values
+            })
+            .collect();
+        let mut values = self.master_values_at(&values, location, true)?.into_iter();
+        let mut next = || values.next().expect("a value for every entry");
+        let origin = &self.font_master[self.origin_master_index()];
+        let metric_values = (0..metric_count)
+            .map(|_| MasterMetric {
+                pos: next(),
+                over: next(),
+            })
+            .collect();
+        let number_values: Vec<f64> = (0..number_count).map(|_| next()).collect();
+        let stem_values: Vec<f64> = (0..stem_count).map(|_| next()).collect();
+        let mut master = FontMaster {
+            metric_values,
+            number_values: (origin.number_values.is_some() || number_count > 0)
+                .then_some(number_values),
+            stem_values: (origin.stem_values.is_some() || stem_count > 0).then_some(stem_values),
+            axes_values: (origin.axes_values.is_some() || axis_count > 0)
+                .then(|| location.to_vec()),
+            ..FontMaster::new(id.clone(), name)
+        };
+        let has_axis_location = self
+            .font_master
+            .iter()
+            .any(|m| m.custom_parameter("Axis Location").is_some());
+        if let Some((_, _, axes)) = self.design_space().filter(|_| has_axis_location) {
+            let locations = axes
+                .iter()
+                .zip(location)
+                .map(|(axis, &design)| {
+                    Plist::Dictionary(HashMap::from([
+                        ("Axis".to_string(), Plist::String(axis.name.clone())),
+                        ("Location".to_string(), Plist::Float(axis.to_user(design))),
+                    ]))
+                })
+                .collect();
+            let parameter = HashMap::from([
+                ("name".to_string(), Plist::String("Axis Location".into())),
+                ("value".to_string(), Plist::Array(locations)),
+            ]);
+            master.other_stuff.insert(
+                "customParameters".into(),
+                Plist::Array(vec![Plist::Dictionary(parameter)]),
+            );
+        }
+
+        let kerning_ltr = self
+            .kerning_ltr
+            .as_ref()
+            .map(|kerning| {
+                self.kerning_at(kerning, location, |master, first, second| {
+                    self.kern_value(master, first, second)
+                })
+            })
+            .transpose()?;
+        let kerning_rtl = self
+            .kerning_rtl
+            .as_ref()
+            .map(|kerning| {
+                self.kerning_at(kerning, location, |master, first, second| {
+                    kerning.get(master)?.get(first)?.get(second).copied()
+                })
+            })
+            .transpose()?;
+        let kerning_vertical = self
+            .kerning_vertical
+            .as_ref()
+            .map(|kerning| {
+                self.kerning_at(kerning, location, |master, first, second| {
+                    kerning.get(master)?.get(first)?.get(second).copied()
+                })
+            })
+            .transpose()?;
+        for (kerning, interpolated) in [
+            (&mut self.kerning_ltr, kerning_ltr),
+            (&mut self.kerning_rtl, kerning_rtl),
+            (&mut self.kerning_vertical, kerning_vertical),
+        ] {
+            if let (Some(kerning), Some(interpolated)) = (kerning, interpolated) {
+                if !interpolated.is_empty() {
+                    kerning.insert(id.to_string(), interpolated);
+                }
+            }
+        }
+
+        for (glyph, layer) in self.glyphs.iter_mut().zip(layers) {
+            glyph.layers.push(layer);
+        }
+        self.font_master.push(master);
+        Ok(id)
+    }
+
+    /// Interpolate or extrapolate the pairs of any master's kerning, looked
+    /// up in each master with `value`.
+    fn kerning_at(
+        &self,
+        kerning: &HashMap<String, norad::Kerning>,
+        location: &[f64],
+        value: impl Fn(&str, &str, &str) -> Option<f64>,
+    ) -> Result<norad::Kerning, InterpolationError> {
+        let pairs: BTreeSet<(&norad::Name, &norad::Name)> = kerning
+            .values()
+            .flatten()
+            .flat_map(|(first, seconds)| seconds.keys().map(move |second| (first, second)))
+            .collect();
+        let values: Vec<Vec<f64>> = self
+            .font_master
+            .iter()
+            .map(|master| {
+                pairs
+                    .iter()
+                    .map(|(first, second)| value(&master.id, first, second).unwrap_or(0.0))
+                    .collect()
+            })
+            .collect();
+        let mut interpolated = norad::Kerning::new();
+        for ((first, second), value) in pairs
+            .into_iter()
+            .zip(self.master_values_at(&values, location, true)?)
+        {
+            interpolated
+                .entry(first.clone())
+                .or_default()
+                .insert(second.clone(), value);
+        }
+        Ok(interpolated)
+    }
+}
+
+fn master_location(master: &FontMaster, axis_count: usize) -> Vec<f64> {
+    let mut location = master.axes_values.clone().unwrap_or_default();
+    location.resize(axis_count, 0.0);
+    location
+}
+
+/// The extent of the masters on each axis, for normalizing locations.
+struct DesignSpace {
+    /// The index of the origin master.
+    origin: usize,
+    /// The minimum, default and maximum of each axis.
+    ranges: Vec<(f64, f64, f64)>,
+}
+
+impl DesignSpace {
+    fn new(font: &Font, axis_count: usize) -> Self {
+        let origin = font.origin_master_index();
+        let locations = font
+            .font_master
+            .iter()
+            .map(|m| master_location(m, axis_count))
+            .collect::<Vec<_>>();
+        let ranges = (0..axis_count)
+            .map(|axis| {
+                let values = locations.iter().map(|l| l[axis]);
+                let min = values.clone().fold(f64::INFINITY, f64::min);
+                let max = values.fold(f64::NEG_INFINITY, f64::max);
+                (min, locations[origin][axis], max)
+            })
+            .collect();
+        Self { origin, ranges }
+    }
+
+    /// Widen the range of each axis to the design values its "Axis Mappings"
+    /// custom parameter maps to.
+    fn with_axis_mappings(mut self, font: &Font) -> Self {
+        let Some(mappings) = font.custom_parameter("Axis Mappings") else {
+            return self;
+        };
+        for (axis, range) in font.axes.iter().flatten().zip(&mut self.ranges) {
+            let values = mappings
+                .get(&axis.tag)
+                .and_then(Plist::as_dict)
+                .into_iter()
+                .flat_map(|mapping| mapping.values())
+                .filter_map(f64::from_parameter);
+            for value in values {
+                range.0 = range.0.min(value);
+                range.2 = range.2.max(value);
+            }
+        }
+        self
+    }
+
+    fn contains(&self, location: &[f64]) -> bool {
+        location.len() == self.ranges.len()
+            && location
+                .iter()
+                .zip(&self.ranges)
+                .all(|(v, (min, _, max))| min <= v && v <= max)
+    }
+
+    fn normalize(&self, location: &[f64]) -> Vec<f64> {
+        let clamped: Vec<f64> = location
+            .iter()
+            .zip(&self.ranges)
+            .map(|(&v, &(min, _, max))| v.clamp(min, max))
+            .collect();
+        self.normalize_unclamped(&clamped)
+    }
+
+    /// Normalize a location that may lie outside the masters' range, to
+    /// beyond -1 or 1. An axis the origin master is at the end of extends
+    /// past that end at the scale of its other side.
+    fn normalize_unclamped(&self, location: &[f64]) -> Vec<f64> {
+        location
+            .iter()
+            .zip(&self.ranges)
+            .map(|(&v, &(min, default, max))| {
+                let below = default - min;
+                let above = max - default;
+                if v < default && below > 0.0 {
+                    (v - default) / below
+                } else if v != default && above > 0.0 {
+                    (v - default) / above
+                } else if v != default && below > 0.0 {
+                    (v - default) / below
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+/// A region of influence on one axis: lower bound, peak and upper bound.
+type Support = (f64, f64, f64);
+
+/// The fontTools variation model: every source gets a region of influence,
+/// and stores its difference from what the sources before it produce there.
+struct VariationModel {
+    /// Indices into the sources, in the order deltas are computed.
+    order: Vec<usize>,
+    /// One region per ordered source, `None` for axes it doesn't vary on.
+    supports: Vec<Vec<Option<Support>>>,
+    /// For each ordered source, the weights of the deltas before it.
+    delta_weights: Vec<Vec<(usize, f64)>>,
+    /// The extent of the sources on each axis, including the origin.
+    axis_ranges: Vec<(f64, f64)>,
+}
+
+impl VariationModel {
+    /// Build the model for normalized source locations, the first of which
+    /// must be the origin.
+    fn new(locations: Vec<Vec<f64>>) -> Self {
+        let mut order = (0..locations.len()).collect::<Vec<_>>();
+        let axis_points = axis_points(&locations);
+        order.sort_by(|&a, &b| {
+            location_sort_key(&locations[a], &axis_points)
+                .partial_cmp(&location_sort_key(&locations[b], &axis_points))
+                .unwrap_or(Ordering::Equal)
+        });
+        let locations = order
+            .iter()
+            .map(|&i| locations[i].clone())
+            .collect::<Vec<_>>();
+
+        let supports = master_supports(&locations);
+        let delta_weights = locations
+            .iter()
+            .enumerate()
+            .map(|(i, location)| {
+                supports[..i]
+                    .iter()
+                    .enumerate()
+                    .map(|(j, support)| (j, support_scalar(location, support, None)))
+                    .filter(|&(_, scalar)| scalar != 0.0)
+                    .collect()
+            })
+            .collect();
+        Self {
+            order,
+            supports,
+            delta_weights,
+            axis_ranges: axis_ranges(&locations),
+        }
+    }
+
+    /// Interpolate the value lists of the sources, in source order, at a
+    /// normalized location.
+    fn interpolate(&self, values: &[Vec<f64>], location: &[f64]) -> Vec<f64> {
+        self.evaluate(values, location, None)
+    }
+
+    /// Interpolate like [`VariationModel::interpolate`], continuing the
+    /// outermost regions past the sources like fontTools does when
+    /// extrapolating.
+    fn extrapolate(&self, values: &[Vec<f64>], location: &[f64]) -> Vec<f64> {
+        self.evaluate(values, location, Some(&self.axis_ranges))
+    }
+
+    fn evaluate(
+        &self,
+        values: &[Vec<f64>],
+        location: &[f64],
+        axis_ranges: Option<&[(f64, f64)]>,
+    ) -> Vec<f64> {
+        let mut deltas: Vec<Vec<f64>> = Vec::with_capacity(values.len());
+        for (&source, weights) in self.order.iter().zip(&self.delta_weights) {
+            let mut delta = values[source].clone();
+            for &(j, weight) in weights {
+                for (d, previous) in delta.iter_mut().zip(&deltas[j]) {
+                    *d -= previous * weight;
+                }
+            }
+            deltas.push(delta);
+        }
+
+        let mut result = vec![0.0; values[0].len()];
+        for (delta, support) in deltas.iter().zip(&self.supports) {
+            let scalar = support_scalar(location, support, axis_ranges);
+            if scalar == 0.0 {
+                continue;
+            }
+            for (r, d) in result.iter_mut().zip(delta) {
+                *r += d * scalar;
+            }
+        }
+        result
+    }
+}
+
+/// The values each axis takes at sources that vary on that axis alone.
+fn axis_points(locations: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let axis_count = locations.first().map_or(0, Vec::len);
+    let mut points = vec![vec![0.0]; axis_count];
+    for location in locations {
+        let mut non_zero = location.iter().enumerate().filter(|(_, v)| **v != 0.0);
+        if let (Some((axis, &v)), None) = (non_zero.next(), non_zero.next()) {
+            points[axis].push(v);
+        }
+    }
+    points
+}
+
+/// Sort sources by the number of axes they vary on, preferring those lying
+/// on the axes, then by axis order and position, like fontTools.
+fn location_sort_key(location: &[f64], axis_points: &[Vec<f64>]) -> (usize, isize, Vec<f64>) {
+    let axes = (0..location.len())
+        .filter(|&axis| location[axis] != 0.0)
+        .collect::<Vec<_>>();
+    let on_point = axes
+        .iter()
+        .filter(|&&axis| axis_points[axis].contains(&location[axis]))
+        .count();
+    let mut rest = axes.iter().map(|&axis| axis as f64).collect::<Vec<_>>();
+    rest.extend(axes.iter().map(|&axis| location[axis].signum()));
+    rest.extend(axes.iter().map(|&axis| location[axis].abs()));
+    (axes.len(), -(on_point as isize), rest)
+}
+
+/// The lowest and highest value of each axis among the locations and the
+/// origin.
+fn axis_ranges(locations: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    let axis_count = locations.first().map_or(0, Vec::len);
+    (0..axis_count)
+        .map(|axis| {
+            let values = locations.iter().map(|l| l[axis]);
+            (
+                values.clone().fold(0.0, f64::min),
+                values.fold(0.0, f64::max),
+            )
+        })
+        .collect()
+}
+
+fn master_supports(locations: &[Vec<f64>]) -> Vec<Vec<Option<Support>>> {
+    let (min, max): (Vec<f64>, Vec<f64>) = axis_ranges(locations).into_iter().unzip();
+
+    let mut regions: Vec<Vec<Option<Support>>> = Vec::with_capacity(locations.len());
+    for location in locations {
+        let mut region = location
+            .iter()
+            .enumerate()
+            .map(|(axis, &v)| {
+                if v > 0.0 {
+                    Some((0.0, v, max[axis]))
+                } else if v < 0.0 {
+                    Some((min[axis], v, 0.0))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for previous in &regions {
+            // Only sources varying on the same axes split the region.
+            if previous
+                .iter()
+                .map(Option::is_some)
+                .ne(region.iter().map(Option::is_some))
+            {
+                continue;
+            }
+            let relevant =
+                region
+                    .iter()
+                    .zip(previous)
+                    .all(|(support, prev)| match (support, prev) {
+                        (Some((lower, peak, upper)), Some((_, prev_peak, _))) => {
+                            prev_peak == peak || (lower < prev_peak && prev_peak < upper)
+                        }
+                        _ => true,
+                    });
+            if !relevant {
+                continue;
+            }
+
+            // Split in the direction with the largest range ratio.
+            let mut best_ratio = -1.0;
+            let mut best_axes = Vec::new();
+            for (axis, prev) in previous.iter().enumerate() {
+                let (Some((_, value, _)), Some((lower, peak, upper))) = (prev, region[axis]) else {
+                    continue;
+                };
+                let value = *value;
+                let (split, ratio) = if value < peak {
+                    ((value, peak, upper), (value - peak) / (lower - peak))
+                } else if peak < value {
+                    ((lower, peak, value), (value - peak) / (upper - peak))
+                } else {
+                    continue;
+                };
+                if ratio > best_ratio {
+                    best_ratio = ratio;
+                    best_axes.clear();
+                }
+                if ratio == best_ratio {
+                    best_axes.push((axis, split));
+                }
+            }
+            for (axis, split) in best_axes {
+                region[axis] = Some(split);
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+/// How much a region contributes at a location, from 0 to 1, or beyond
+/// when extrapolating.
+///
+/// With the ranges of the axes, regions reaching the end of an axis keep
+/// growing or shrinking past it instead of stopping, so that locations
+/// outside the sources are extrapolated.
+fn support_scalar(
+    location: &[f64],
+    support: &[Option<Support>],
+    axis_ranges: Option<&[(f64, f64)]>,
+) -> f64 {
+    let mut scalar = 1.0;
+    for (axis, (&v, support)) in location.iter().zip(support).enumerate() {
+        let Some((lower, peak, upper)) = *support else {
+            continue;
+        };
+        if peak == 0.0 || lower > peak || peak > upper || (lower < 0.0 && upper > 0.0) {
+            continue;
+        }
+        if v == peak {
+            continue;
+        }
+        if let Some(&(axis_min, axis_max)) = axis_ranges.and_then(|ranges| ranges.get(axis)) {
+            if v < axis_min && lower <= axis_min {
+                if peak <= axis_min && peak < upper {
+                    scalar *= (v - upper) / (peak - upper);
+                    continue;
+                } else if axis_min < peak {
+                    scalar *= (v - lower) / (peak - lower);
+                    continue;
+                }
+            } else if axis_max < v && axis_max <= upper {
+                if axis_max <= peak && lower < peak {
+                    scalar *= (v - lower) / (peak - lower);
+                    continue;
+                } else if peak < axis_max {
+                    scalar *= (v - upper) / (peak - upper);
+                    continue;
+                }
+            }
+        }
+        if v <= lower || upper <= v {
+            return 0.0;
+        }
+        scalar *= if v < peak {
+            (v - lower) / (peak - lower)
+        } else {
+            (v - upper) / (peak - upper)
+        };
+    }
+    scalar
+}
+
+/// Flatten the interpolatable values of a layer, checking it matches the
+/// structure of the base layer.
+fn layer_values(base: &Layer, layer: &Layer) -> Result<Vec<f64>, String> {
+    if base.shapes.len() != layer.shapes.len() {
+        return Err(format!(
+            "{} shapes instead of {}",
+            layer.shapes.len(),
+            base.shapes.len()
+        ));
+    }
+    let mut values = vec![layer.width];
+    for (i, (base_shape, shape)) in base.shapes.iter().zip(&layer.shapes).enumerate() {
+        match (base_shape, shape) {
+            (Shape::Path(base_path), Shape::Path(path)) => {
+                let is_off_curve = |n: &crate::Node| n.node_type == NodeType::OffCurve;
+                if base_path.closed != path.closed
+                    || base_path.nodes.len() != path.nodes.len()
+                    || base_path
+                        .nodes
+                        .iter()
+                        .map(is_off_curve)
+                        .ne(path.nodes.iter().map(is_off_curve))
+                {
+                    return Err(format!("path {i} has different nodes"));
+                }
+                values.extend(path.nodes.iter().flat_map(|n| [n.pt.x, n.pt.y]));
+            }
+            (Shape::Component(base_component), Shape::Component(component))
+                if base_component.reference == component.reference =>
+            {
+                let pos = component.pos.unwrap_or_default();
+                let scale = component.scale.as_ref().map_or((1.0, 1.0), scale_pair);
+                let slant = component.slant.as_ref().map_or((0.0, 0.0), scale_pair);
+                values.extend([
+                    pos.x,
+                    pos.y,
+                    scale.0,
+                    scale.1,
+                    component.rotation.unwrap_or(0.0),
+                    slant.0,
+                    slant.1,
+                ]);
+            }
+            _ => return Err(format!("shape {i} is a different kind or component")),
+        }
+    }
+    for anchor in base.anchors.iter().flatten() {
+        let Some(other) = layer
+            .anchors
+            .iter()
+            .flatten()
+            .find(|a| a.name == anchor.name)
+        else {
+            return Err(format!("anchor {:?} is missing", anchor.name));
+        };
+        values.extend([other.pos.x, other.pos.y]);
+    }
+    Ok(values)
+}
+
+fn scale_pair(scale: &Scale) -> (f64, f64) {
+    (scale.horizontal, scale.vertical)
+}
+
+/// Build a layer shaped like the base layer from values in the order
+/// [`layer_values`] produces them.
+fn layer_from_values(base: &Layer, values: &[f64]) -> Layer {
+    let mut values = values.iter().copied();
+    let mut next = || values.next().expect("values match the base layer");
+    let mut layer = Layer {
+        background: None,
+        background_image: None,
+        ..base.clone()
+    };
+    layer.width = next();
+    for shape in &mut layer.shapes {
+        match shape {
+            Shape::Path(path) => {
+                for node in &mut path.nodes {
+                    node.pt = Point::new(next(), next());
+                }
+            }
+            Shape::Component(component) => {
+                let pos = Point::new(next(), next());
+                let scale = (next(), next());
+                let rotation = next();
+                let slant = (next(), next());
+                component.pos = (component.pos.is_some() || pos != Point::ZERO).then_some(pos);
+                component.scale =
+                    (component.scale.is_some() || scale != (1.0, 1.0)).then_some(Scale {
+                        horizontal: scale.0,
+                        vertical: scale.1,
+                    });
+                component.rotation =
+                    (component.rotation.is_some() || rotation != 0.0).then_some(rotation);
+                component.slant =
+                    (component.slant.is_some() || slant != (0.0, 0.0)).then_some(Scale {
+                        horizontal: slant.0,
+                        vertical: slant.1,
+                    });
+            }
+        }
+    }
+    for anchor in layer.anchors.iter_mut().flatten() {
+        anchor.pos = Point::new(next(), next());
+    }
+    layer
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{InterpolationError, VariationModel};
+    use crate::{Anchor, Font, Layer, NodeType, Path, Plist, Shape};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn variation_model() {
+        let model = VariationModel::new(vec![vec![0.0], vec![1.0], vec![0.5]]);
+        let values = [vec![0.0], vec![100.0], vec![80.0]];
+        assert_close(model.interpolate(&values, &[0.25])[0], 40.0);
+        assert_close(model.interpolate(&values, &[0.5])[0], 80.0);
+        assert_close(model.interpolate(&values, &[0.75])[0], 90.0);
+
+        // Two axes with a corner source.
+        let model = VariationModel::new(vec![
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+        ]);
+        let values = [vec![0.0], vec![10.0], vec![20.0], vec![50.0]];
+        assert_close(model.interpolate(&values, &[1.0, 1.0])[0], 50.0);
+        assert_close(model.interpolate(&values, &[0.5, 0.5])[0], 20.0);
+
+        let model = VariationModel::new(vec![vec![0.0], vec![1.0]]);
+        let values = [vec![0.0], vec![100.0]];
+        assert_close(model.extrapolate(&values, &[0.5])[0], 50.0);
+        assert_close(model.extrapolate(&values, &[1.5])[0], 150.0);
+        assert_close(model.extrapolate(&values, &[-0.5])[0], -50.0);
+    }
+
+    /// A glyph with a square of the given size and an anchor on top of it.
+    fn square_layer(layer_id: &str, master_id: Option<&str>, size: f64) -> Layer {
+        let mut path = Path::new(true);
+        for (x, y) in [(size, 0.0), (size, size), (0.0, size), (0.0, 0.0)] {
+            path.add((x, y), NodeType::Line);
+        }
+        Layer {
+            shapes: vec![Shape::Path(Box::new(path))],
+            anchors: Some(vec![Anchor {
+                name: "top".into(),
+                orientation: None,
+                pos: (size / 2.0, size).into(),
+                user_data: Default::default(),
+            }]),
+            width: size,
+            associated_master_id: master_id.map(Into::into),
+            ..Layer::new(layer_id)
+        }
+    }
+
+    #[test]
+    fn interpolate_glyph() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        // Weight from 100 to 900, other axes unused.
+        font.font_master.truncate(2);
+        let bold_id = font.font_master[1].id.clone();
+        let glyph = font.get_glyph_mut("Smily").unwrap();
+        let mut brace = square_layer("brace", Some("m01"), 180.0);
+        brace.attr = glyph
+            .layers
+            .iter()
+            .find(|l| l.coordinates() == Some(&[450.0, 0.0, 0.0][..]))
+            .unwrap()
+            .attr
+            .clone();
+        glyph.layers = vec![
+            square_layer("m01", None, 100.0),
+            square_layer(&bold_id, None, 200.0),
+            brace,
+        ];
+        let glyph = font.get_glyph("Smily").unwrap();
+
+        let size_at = |weight: f64| {
+            let layer = glyph.interpolate(&font, &[weight, 0.0, 0.0]).unwrap();
+            let Shape::Path(path) = &layer.shapes[0] else {
+                panic!("expected a path");
+            };
+            let top = &layer.anchors.as_ref().unwrap()[0];
+            assert_close(top.pos.x, path.nodes[0].pt.x / 2.0);
+            assert_close(layer.width, path.nodes[0].pt.x);
+            path.nodes[0].pt.x
+        };
+        assert_close(size_at(100.0), 100.0);
+        assert_close(size_at(900.0), 200.0);
+        // On the brace layer, and halfway between it and the masters.
+        assert_close(size_at(450.0), 180.0);
+        assert_close(size_at(275.0), 140.0);
+        assert_close(size_at(675.0), 190.0);
+        // Clamped to the masters' range.
+        assert_close(size_at(1000.0), 200.0);
+    }
+
+    #[test]
+    fn normalized_coordinates() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let glyph = font.get_glyph("Smily").unwrap();
+        let brace = glyph
+            .layers
+            .iter()
+            .find(|l| l.coordinates() == Some(&[450.0, 0.0, 0.0][..]))
+            .unwrap()
+            .clone();
+        let normalized = brace.normalized_coordinates(&font).unwrap();
+        assert_close(normalized[0], 350.0 / 800.0);
+        assert_eq!(&normalized[1..], [0.0, 0.0]);
+        assert_eq!(glyph.layers[0].normalized_coordinates(&font), None);
+
+        let mapping = Plist::Dictionary(
+            [
+                ("100".to_string(), Plist::Integer(0)),
+                ("900".to_string(), Plist::Integer(900)),
+            ]
+            .into(),
+        );
+        let tag = font.axes.as_ref().unwrap()[0].tag.clone();
+        font.custom_parameters
+            .get_or_insert_with(Vec::new)
+            .push(crate::CustomParameter {
+                name: "Axis Mappings".into(),
+                value: Plist::Dictionary([(tag, mapping)].into()),
+                disabled: false,
+            });
+        let normalized = brace.normalized_coordinates(&font).unwrap();
+        assert_close(normalized[0], 350.0 / 800.0);
+        let mut light = brace.clone();
+        light.attr.as_mut().unwrap().coordinates = Some(vec![50.0, 0.0, 0.0]);
+        assert_close(light.normalized_coordinates(&font).unwrap()[0], -0.5);
+    }
+
+    #[test]
+    fn layers_at_location() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let layers = font.layers_at_location(&[450.0, 0.0, 0.0]);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].0.glyphname, "Smily");
+
+        let layers = font.layers_at_location(&[100.0, 0.0, 0.0]);
+        assert!(!layers.is_empty());
+        assert!(layers
+            .iter()
+            .all(|(_, l)| l.layer_id == "m01" || l.associated_master_id.is_some()));
+        assert_eq!(
+            layers.iter().filter(|(_, l)| l.is_master_layer()).count(),
+            font.glyphs
+                .iter()
+                .filter(|g| g.master_layer("m01").is_some())
+                .count()
+        );
+    }
+
+    #[test]
+    fn interpolation_errors() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let glyph = font.get_glyph("Smily").unwrap();
+        assert_eq!(
+            glyph.interpolate(&font, &[100.0]),
+            Err(InterpolationError::WrongLocation {
+                expected: 3,
+                found: 1
+            })
+        );
+        // The third master's layer is empty.
+        assert!(matches!(
+            glyph.interpolate(&font, &[500.0, 0.0, 0.0]),
+            Err(InterpolationError::Incompatible { layer, .. })
+                if layer == "74B5DD59-F76E-4ABE-885C-483A9EE6B546"
+        ));
+
+        let mut font = Font::default();
+        font.font_master.clear();
+        assert_eq!(
+            font.glyphs[0].interpolate(&font, &[]),
+            Err(InterpolationError::NoMasters)
+        );
+        assert_eq!(
+            font.interpolate_master_values(&[], &[]),
+            Err(InterpolationError::NoMasters)
+        );
+    }
+
+    #[test]
+    fn add_master_at() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        // Weight from 100 to 900, other axes unused.
+        font.font_master.truncate(2);
+        let bold_id = font.font_master[1].id.clone();
+        for glyph in &mut font.glyphs {
+            glyph.layers = vec![
+                square_layer("m01", None, 100.0),
+                square_layer(&bold_id, None, 200.0),
+            ];
+        }
+        font.font_master[0].metric_values[0].pos = 700.0;
+        font.font_master[1].metric_values[0].pos = 800.0;
+        font.kerning_ltr = Some(HashMap::from([
+            ("m01".to_string(), norad::Kerning::new()),
+            (bold_id.to_string(), norad::Kerning::new()),
+        ]));
+        font.set_kerning("m01", "A", "V", -40.0).unwrap();
+        font.set_kerning(&bold_id, "A", "V", -80.0).unwrap();
+        font.font_master[0]
+            .user_data
+            .insert("key".into(), Plist::Integer(1));
+        let original = font.clone();
+
+        let id = font.add_master_at(&[500.0, 0.0, 0.0], "Medium").unwrap();
+        let master = font.font_master.last().unwrap();
+        assert_eq!(master.id, id);
+        assert_eq!(master.name, "Medium");
+        assert_eq!(master.axes_values, Some(vec![500.0, 0.0, 0.0]));
+        assert_close(master.metric_values[0].pos, 750.0);
+        assert_eq!(font.kern_value(&id, "A", "V"), Some(-60.0));
+        let layer = font.glyphs[0].master_layer(&id).unwrap();
+        assert_close(layer.width, 150.0);
+        assert!(master.user_data.is_empty());
+        assert!(master.custom_parameter("Axis Location").is_none());
+
+        // Beyond the masters, the values keep going.
+        let id = font.add_master_at(&[1300.0, 0.0, 0.0], "Black").unwrap();
+        assert_close(font.glyphs[0].master_layer(&id).unwrap().width, 250.0);
+        assert_close(font.font_master.last().unwrap().metric_values[0].pos, 850.0);
+        assert_eq!(font.kern_value(&id, "A", "V"), Some(-100.0));
+
+        let mut broken = original.clone();
+        broken.glyphs[0].layers[1].shapes.clear();
+        let unchanged = broken.clone();
+        assert!(matches!(
+            broken.add_master_at(&[500.0, 0.0, 0.0], "Medium"),
+            Err(InterpolationError::Incompatible { .. })
+        ));
+        assert_eq!(broken, unchanged);
+
+        // User coordinates follow the other masters' axis locations.
+        let mut located = original.clone();
+        for (master, user) in located.font_master.iter_mut().zip([200.0, 600.0]) {
+            let location = HashMap::from([
+                ("Axis".to_string(), Plist::String("Weight".into())),
+                ("Location".to_string(), Plist::Float(user)),
+            ]);
+            let parameter = HashMap::from([
+                ("name".to_string(), Plist::String("Axis Location".into())),
+                (
+                    "value".to_string(),
+                    Plist::Array(vec![Plist::Dictionary(location)]),
+                ),
+            ]);
+            master.other_stuff.insert(
+                "customParameters".into(),
+                Plist::Array(vec![Plist::Dictionary(parameter)]),
+            );
+        }
+        let id = located.add_master_at(&[500.0, 0.0, 0.0], "Medium").unwrap();
+        let master = located.font_master.iter().find(|m| m.id == id).unwrap();
+        let Some(locations) = master.custom_parameter_as::<&[Plist]>("Axis Location") else {
+            panic!("expected an axis location");
+        };
+        assert_eq!(locations[0].get("Location"), Some(&Plist::Float(400.0)));
+
+        let mut font = original;
+        assert_eq!(
+            font.add_master_at(&[500.0], "Medium"),
+            Err(InterpolationError::WrongLocation {
+                expected: 3,
+                found: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! The font prepared for compiling with fontc, in the types of its `fontir`
+//! intermediate representation: axes in user coordinates with their mapping
+//! to design coordinates, the glyph order, and the glyph outlines and kerning
+//! at each normalized location, so a frontend only has to move the values
+//! across.
+
+use std::collections::{BTreeMap, HashMap};
+
+use fontdrasil::coords::{
+    CoordConverter, DesignCoord, NormalizedCoord, NormalizedLocation, UserCoord,
+};
+use fontdrasil::types::{Axis, GlyphName};
+use fontir::ir::{Component, GlyphInstance, GlyphOrder};
+use kurbo::Point;
+use thiserror::Error;
+
+use crate::{AxisIr, Font, Glyph, Id, Layer, SourceIr};
+
+/// The font-wide data and the glyphs of a font, ready for compiling.
+#[derive(Clone, Debug)]
+pub struct FontIr {
+    pub units_per_em: u16,
+    /// The axes in user coordinates, converting to design coordinates
+    /// through the "Axis Mappings" or "Axis Location" custom parameters.
+    pub axes: Vec<Axis>,
+    /// The masters, which are the full sources of the font, and where they
+    /// are. The default master is at the origin.
+    pub sources: Vec<(Id, NormalizedLocation)>,
+    pub glyph_order: GlyphOrder,
+    /// The glyphs, in export order.
+    pub glyphs: Vec<GlyphIr>,
+    /// The members of each kerning group, by the prefixed group name used in
+    /// the kerning pairs, like `@MMK_L_A`.
+    pub kerning_groups: BTreeMap<String, Vec<GlyphName>>,
+    /// The kerning of each master that has any, in master order.
+    pub kerning: Vec<KerningIr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GlyphIr {
+    pub name: GlyphName,
+    pub codepoints: Vec<char>,
+    /// Whether the glyph ends up in the compiled font. Glyphs that don't are
+    /// kept for the composites that use them.
+    pub export: bool,
+    /// The glyph at each master it has a layer for and at the locations of
+    /// its brace layers.
+    pub sources: HashMap<NormalizedLocation, GlyphInstance>,
+    /// The anchors at each location in `sources`.
+    pub anchors: HashMap<NormalizedLocation, Vec<(String, Point)>>,
+}
+
+/// The kerning pairs at a location, with each side a glyph name or a
+/// prefixed group name.
+#[derive(Clone, Debug)]
+pub struct KerningIr {
+    pub location: NormalizedLocation,
+    pub pairs: Vec<(String, String, f64)>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IrError {
+    #[error("the font has no masters")]
+    NoMasters,
+    #[error("glyph {0:?} has no layer for the default master")]
+    MissingDefaultLayer(String),
+    #[error("axis {0:?} has an invalid tag")]
+    BadAxisTag(String),
+}
+
+impl Font {
+    /// Prepare the font for compiling.
+    ///
+    /// The default master is the one named by the "Variable Font Origin"
+    /// custom parameter, or else the first. Every glyph needs a layer for it;
+    /// other masters may lack layers, as the glyph is then sparse there.
+    pub fn to_ir(&self) -> Result<FontIr, IrError> {
+        let (sources, default, axes) = self.design_space().ok_or(IrError::NoMasters)?;
+        let default_id = sources[default].id.clone();
+        let axes: Vec<Axis> = axes.iter().map(axis_ir).collect::<Result<_, _>>()?;
+        let normalize = |location: &[f64]| -> NormalizedLocation {
+            axes.iter()
+                .zip(location)
+                .map(|(axis, &value)| {
+                    let coord: NormalizedCoord =
+                        DesignCoord::new(value).to_normalized(&axis.converter);
+                    (axis.tag, coord)
+                })
+                .collect()
+        };
+
+        let glyph_names: Vec<&str> = self.glyph_order();
+        let mut glyph_order = GlyphOrder::new();
+        for name in &glyph_names {
+            glyph_order.insert(GlyphName::new(name));
+        }
+        let glyphs = glyph_names
+            .iter()
+            .filter_map(|name| self.get_glyph(name))
+            .map(|glyph| glyph_ir(glyph, &sources, &default_id, &normalize))
+            .collect::<Result<_, _>>()?;
+
+        let kerning = sources
+            .iter()
+            .filter_map(|source| {
+                let mut pairs: Vec<(String, String, f64)> = self
+                    .kerning_pairs(&source.id)
+                    .map(|(first, second, value)| (first.to_string(), second.to_string(), value))
+                    .collect();
+                pairs.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+                (!pairs.is_empty()).then(|| KerningIr {
+                    location: normalize(&source.location),
+                    pairs,
+                })
+            })
+            .collect();
+        let kerning_groups = self
+            .kern_groups()
+            .into_iter()
+            .map(|(group, members)| (group, members.iter().map(GlyphName::new).collect()))
+            .collect();
+
+        Ok(FontIr {
+            units_per_em: self.units_per_em,
+            sources: sources
+                .iter()
+                .map(|source| (source.id.clone(), normalize(&source.location)))
+                .collect(),
+            axes,
+            glyph_order,
+            glyphs,
+            kerning_groups,
+            kerning,
+        })
+    }
+}
+
+/// The axis in user coordinates, mapped to design coordinates as the axis'
+/// mapping says, or else linearly.
+fn axis_ir(axis: &AxisIr) -> Result<Axis, IrError> {
+    let user = |value: f64| UserCoord::new(value);
+    let converter = match axis.mapping.is_empty() {
+        true => CoordConverter::unmapped(
+            user(axis.user_min),
+            user(axis.user_default),
+            user(axis.user_max),
+        ),
+        false => {
+            // The converter needs the ends and the default among the pairs.
+            let mut mapping = axis.mapping.clone();
+            for (user, design) in [
+                (axis.user_min, axis.min),
+                (axis.user_default, axis.default),
+                (axis.user_max, axis.max),
+            ] {
+                if !mapping.iter().any(|&(u, _)| u == user) {
+                    mapping.push((user, design));
+                }
+            }
+            mapping.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let default_index = mapping
+                .iter()
+                .position(|&(u, _)| u == axis.user_default)
+                .unwrap();
+            let mapping = mapping
+                .into_iter()
+                .map(|(u, d)| (user(u), DesignCoord::new(d)))
+                .collect();
+            CoordConverter::new(mapping, default_index)
+        }
+    };
+    Ok(Axis {
+        name: axis.name.clone(),
+        tag: axis
+            .tag
+            .parse()
+            .map_err(|_| IrError::BadAxisTag(axis.name.clone()))?,
+        min: user(axis.user_min),
+        default: user(axis.user_default),
+        max: user(axis.user_max),
+        hidden: axis.hidden,
+        converter,
+    })
+}
+
+fn glyph_ir(
+    glyph: &Glyph,
+    sources: &[SourceIr],
+    default_id: &str,
+    normalize: &impl Fn(&[f64]) -> NormalizedLocation,
+) -> Result<GlyphIr, IrError> {
+    if glyph.master_layer(default_id).is_none() {
+        return Err(IrError::MissingDefaultLayer(glyph.glyphname.to_string()));
+    }
+    let master_layers = sources.iter().filter_map(|source| {
+        let layer = glyph.master_layer(&source.id)?;
+        Some((layer, source.location.as_slice()))
+    });
+    let brace_layers = glyph
+        .layers
+        .iter()
+        .filter_map(|layer| Some((layer, layer.coordinates()?)));
+
+    let (mut instances, mut anchors) = (HashMap::new(), HashMap::new());
+    for (layer, location) in master_layers.chain(brace_layers) {
+        let location = normalize(location);
+        anchors.insert(location.clone(), layer_anchors(layer));
+        instances.insert(location, instance_ir(layer));
+    }
+    Ok(GlyphIr {
+        name: GlyphName::new(glyph.glyphname.as_str()),
+        codepoints: glyph.unicode.iter().flat_map(|u| u.iter()).collect(),
+        export: glyph.export,
+        sources: instances,
+        anchors,
+    })
+}
+
+fn instance_ir(layer: &Layer) -> GlyphInstance {
+    GlyphInstance {
+        width: layer.width,
+        contours: layer.paths().map(|path| path.to_bezpath()).collect(),
+        components: layer
+            .components()
+            .map(|c| Component {
+                base: GlyphName::new(c.reference.as_str()),
+                transform: c.transform(),
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn layer_anchors(layer: &Layer) -> Vec<(String, Point)> {
+    layer
+        .anchors
+        .iter()
+        .flatten()
+        .map(|a| (a.name.clone(), a.pos))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_font() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ir = font.to_ir().unwrap();
+
+        assert_eq!(ir.sources.len(), font.font_master.len());
+        assert!(ir.sources[0]
+            .1
+            .iter()
+            .all(|(_, coord)| coord.to_f64() == 0.0));
+        for axis in &ir.axes {
+            assert!(axis.min <= axis.default && axis.default <= axis.max);
+        }
+        assert_eq!(ir.glyph_order.len(), ir.glyphs.len());
+
+        let a = ir.glyphs.iter().find(|g| g.name.as_str() == "A").unwrap();
+        assert_eq!(a.codepoints, ['A', 'a']);
+        let default = &a.sources[&ir.sources[0].1];
+        let layer = font.get_glyph("A").unwrap().master_layer("m01").unwrap();
+        assert_eq!(default.width, layer.width);
+        assert_eq!(default.contours.len(), layer.paths().count());
+    }
+
+    #[test]
+    fn kerning() {
+        let mut font = Font::default();
+        font.glyphs[0].kern_right = Some(norad::Name::new("space").unwrap());
+        font.set_kerning("m01", "@MMK_L_space", "space", -10.0)
+            .unwrap();
+        let ir = font.to_ir().unwrap();
+        assert_eq!(ir.kerning_groups["@MMK_L_space"], [GlyphName::new("space")]);
+        assert_eq!(
+            ir.kerning[0].pairs,
+            [("@MMK_L_space".to_string(), "space".to_string(), -10.0)]
+        );
+
+        font.font_master.clear();
+        assert_eq!(font.to_ir().unwrap_err(), IrError::NoMasters);
+    }
+}
@@ -0,0 +1,277 @@
+//! Writing the left-to-right kerning of a master or instance as a `kern`
+//! feature in AFDKO feature syntax, or flattened to glyph pairs.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+use crate::kerning::is_group;
+use crate::Font;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum KernFeaError {
+    #[error("no master or instance named {0:?}")]
+    UnknownSource(String),
+}
+
+impl Font {
+    /// The kerning of a master, given by id or name, or of an instance, given
+    /// by name, as class definitions and a `kern` feature.
+    ///
+    /// The kerning of an instance is interpolated from the masters, with the
+    /// effective value of each pair in masters that don't store it. Values
+    /// are rounded to whole units. Groups become classes named like the
+    /// group sides of the pairs, with only the exported glyphs in them, and
+    /// pairs with a side that doesn't exist or isn't exported are left out.
+    ///
+    /// Exceptions are written before the pairs they override: glyph pairs
+    /// first, then glyph-group and group-glyph pairs as `enum pos`, then
+    /// group pairs, so that the most specific pair applies like in Glyphs.
+    /// Returns an empty string if there is no kerning.
+    pub fn kerning_to_fea(&self, master_or_instance: &str) -> Result<String, KernFeaError> {
+        let pairs = self
+            .master_kerning(master_or_instance)
+            .or_else(|| self.instance_kerning(master_or_instance))
+            .ok_or_else(|| KernFeaError::UnknownSource(master_or_instance.to_string()))?;
+
+        let groups: BTreeMap<String, Vec<String>> = self
+            .kern_groups()
+            .into_iter()
+            .map(|(name, members)| {
+                let members = members
+                    .into_iter()
+                    .filter(|member| self.get_glyph(member).is_some_and(|g| g.export))
+                    .collect::<Vec<_>>();
+                (name, members)
+            })
+            .filter(|(_, members)| !members.is_empty())
+            .collect();
+        let exists = |side: &str| match is_group(side) {
+            true => groups.contains_key(side),
+            false => self.get_glyph(side).is_some_and(|g| g.export),
+        };
+
+        // Glyph pairs, then the two kinds of exceptions, then group pairs.
+        let mut buckets: [Vec<String>; 4] = Default::default();
+        let mut used_groups = BTreeSet::new();
+        for ((first, second), value) in &pairs {
+            if !exists(first) || !exists(second) {
+                continue;
+            }
+            let value = value.round() as i64;
+            let (bucket, rule) = match (is_group(first), is_group(second)) {
+                (false, false) => (0, format!("pos {first} {second} {value};")),
+                (false, true) => (1, format!("enum pos {first} {second} {value};")),
+                (true, false) => (2, format!("enum pos {first} {second} {value};")),
+                (true, true) => (3, format!("pos {first} {second} {value};")),
+            };
+            buckets[bucket].push(rule);
+            used_groups.extend([first, second].into_iter().filter(|s| is_group(s)));
+        }
+        if buckets.iter().all(Vec::is_empty) {
+            return Ok(String::new());
+        }
+
+        let mut fea = String::new();
+        for name in &used_groups {
+            fea.push_str(&format!(
+                "{name} = [{}];\n",
+                groups[name.as_str()].join(" ")
+            ));
+        }
+        if !used_groups.is_empty() {
+            fea.push('\n');
+        }
+        fea.push_str("feature kern {\n");
+        for rule in buckets.iter().flatten() {
+            fea.push_str(&format!("    {rule}\n"));
+        }
+        fea.push_str("} kern;\n");
+        Ok(fea)
+    }
+
+    /// The stored kerning of a master, by id or name.
+    fn master_kerning(&self, id_or_name: &str) -> Option<BTreeMap<(String, String), f64>> {
+        let master = self
+            .font_master
+            .iter()
+            .find(|m| m.id.as_str() == id_or_name)
+            .or_else(|| self.font_master.iter().find(|m| m.name == id_or_name))?;
+        Some(
+            self.kerning_pairs(&master.id)
+                .map(|(first, second, value)| ((first.to_string(), second.to_string()), value))
+                .collect(),
+        )
+    }
+
+    /// The kerning of an instance, by name, flattened to pairs of glyphs for
+    /// proofing and static builds.
+    ///
+    /// The pairs stored in any master are interpolated like for
+    /// [`Font::kerning_to_fea`], with the effective value of each pair in
+    /// masters that don't store it, so that exceptions hold across masters.
+    /// Groups are then expanded to their members, and each glyph pair gets
+    /// the value of its most specific pair, as in [`Font::kern_value`].
+    /// Values aren't rounded, and glyphs that don't exist or aren't exported
+    /// are kept. `None` if there is no instance of that name.
+    pub fn instance_flat_kerning(&self, name: &str) -> Option<BTreeMap<(String, String), f64>> {
+        let pairs = self.instance_kerning(name)?;
+        let groups = self.kern_groups();
+        let members = |side: &String| match is_group(side) {
+            true => groups.get(side).cloned().unwrap_or_default(),
+            false => vec![side.clone()],
+        };
+        let mut flat = BTreeMap::new();
+        for (first, second) in pairs.keys() {
+            for first in members(first) {
+                for second in members(second) {
+                    let key = (first.clone(), second);
+                    if flat.contains_key(&key) {
+                        continue;
+                    }
+                    let value = self
+                        .lookup_chain(&key.0, &key.1)
+                        .into_iter()
+                        .find_map(|pair| pairs.get(&pair).copied());
+                    if let Some(value) = value {
+                        flat.insert(key, value);
+                    }
+                }
+            }
+        }
+        Some(flat)
+    }
+
+    /// The kerning of an instance, interpolated from the effective kerning of
+    /// every pair stored in any master.
+    fn instance_kerning(&self, name: &str) -> Option<BTreeMap<(String, String), f64>> {
+        let instance = self.instances.iter().flatten().find(|i| i.name == name)?;
+        let sides: BTreeSet<(&str, &str)> = self
+            .font_master
+            .iter()
+            .flat_map(|master| self.kerning_pairs(&master.id))
+            .map(|(first, second, _)| (first, second))
+            .collect();
+        let values: Vec<Vec<f64>> = self
+            .font_master
+            .iter()
+            .map(|master| {
+                sides
+                    .iter()
+                    .map(|(first, second)| {
+                        self.kern_value(&master.id, first, second).unwrap_or(0.0)
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut location = instance.axes_values.clone().unwrap_or_default();
+        location.resize(self.axes.as_ref().map_or(0, Vec::len), 0.0);
+        let interpolated = self.interpolate_master_values(&values, &location).ok()?;
+        Some(
+            sides
+                .into_iter()
+                .zip(interpolated)
+                .map(|((first, second), value)| ((first.to_string(), second.to_string()), value))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FontMaster, Glyph, Instance};
+
+    fn font() -> Font {
+        let mut font = Font::default();
+        for name in ["A", "Aacute", "V", "W"] {
+            let mut glyph = Glyph::new(norad::Name::new(name).unwrap(), None);
+            glyph.layers.push(crate::Layer::new("m01"));
+            let group = if name.starts_with('A') { "A" } else { "V" };
+            glyph.kern_right = Some(norad::Name::new(group).unwrap());
+            glyph.kern_left = Some(norad::Name::new(group).unwrap());
+            font.glyphs.push(glyph);
+        }
+        font.set_kerning("m01", "@MMK_L_A", "@MMK_R_V", -40.0)
+            .unwrap();
+        font.set_kerning("m01", "Aacute", "@MMK_R_V", -20.0)
+            .unwrap();
+        font.set_kerning("m01", "A", "W", -60.4).unwrap();
+        font.set_kerning("m01", "A", "missing", -10.0).unwrap();
+        font
+    }
+
+    #[test]
+    fn master() {
+        let font = font();
+        assert_eq!(
+            font.kerning_to_fea("Regular").unwrap(),
+            "@MMK_L_A = [A Aacute];\n\
+             @MMK_R_V = [V W];\n\
+             \n\
+             feature kern {\n    \
+                 pos A W -60;\n    \
+                 enum pos Aacute @MMK_R_V -20;\n    \
+                 pos @MMK_L_A @MMK_R_V -40;\n\
+             } kern;\n"
+        );
+        assert_eq!(font.kerning_to_fea("m01"), font.kerning_to_fea("Regular"));
+        assert_eq!(
+            font.kerning_to_fea("Bold"),
+            Err(KernFeaError::UnknownSource("Bold".into()))
+        );
+    }
+
+    /// The test font with a bold master and a semibold instance between.
+    fn instance_font() -> Font {
+        let mut font = font();
+        font.axes = Some(vec![crate::Axis {
+            name: "Weight".into(),
+            tag: "wght".into(),
+            hidden: false,
+        }]);
+        font.font_master[0].axes_values = Some(vec![400.0]);
+        let mut bold = FontMaster::new("m02", "Bold");
+        bold.axes_values = Some(vec![700.0]);
+        font.font_master.push(bold);
+        for glyph in font.glyphs.iter_mut() {
+            glyph.layers.push(crate::Layer::new("m02"));
+        }
+        // Aacute has no exception in the bold master, so the group pair
+        // applies there.
+        font.set_kerning("m02", "@MMK_L_A", "@MMK_R_V", -80.0)
+            .unwrap();
+        let mut instance = Instance::new("Semibold");
+        instance.axes_values = Some(vec![550.0]);
+        font.instances = Some(vec![instance]);
+        font
+    }
+
+    #[test]
+    fn instance() {
+        let font = instance_font();
+        let fea = font.kerning_to_fea("Semibold").unwrap();
+        assert!(fea.contains("pos A W -70;"), "{fea}");
+        assert!(fea.contains("enum pos Aacute @MMK_R_V -50;"), "{fea}");
+        assert!(fea.contains("pos @MMK_L_A @MMK_R_V -60;"), "{fea}");
+    }
+
+    #[test]
+    fn instance_flat_kerning() {
+        let font = instance_font();
+        let flat = font.instance_flat_kerning("Semibold").unwrap();
+        let pair = |first: &str, second: &str| flat[&(first.to_string(), second.to_string())];
+        assert_eq!(pair("A", "V"), -60.0);
+        assert_eq!(pair("A", "W"), -70.2);
+        assert_eq!(pair("Aacute", "V"), -50.0);
+        assert_eq!(pair("Aacute", "W"), -50.0);
+        assert_eq!(pair("A", "missing"), -5.0);
+        assert_eq!(flat.len(), 5);
+        assert_eq!(font.instance_flat_kerning("Bold"), None);
+
+        // Without masters there is nothing to interpolate from.
+        let mut font = instance_font();
+        font.font_master.clear();
+        assert_eq!(font.instance_flat_kerning("Semibold"), None);
+    }
+}
@@ -0,0 +1,459 @@
+//! Querying and editing kerning.
+//!
+//! Kerning pairs are stored per master, between glyph names or kerning groups.
+//! Groups are written with the prefixes Glyphs uses: `@MMK_L_` for the group
+//! of the first glyph of a pair (its right-hand side), `@MMK_R_` for the group
+//! of the second glyph (its left-hand side).
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::Font;
+
+pub(crate) const FIRST_GROUP_PREFIX: &str = "@MMK_L_";
+pub(crate) const SECOND_GROUP_PREFIX: &str = "@MMK_R_";
+
+/// The side of a glyph a kerning group applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KernSide {
+    /// The group in [`Glyph::kern_left`](crate::Glyph::kern_left), written
+    /// with the `@MMK_R_` prefix.
+    Left,
+    /// The group in [`Glyph::kern_right`](crate::Glyph::kern_right), written
+    /// with the `@MMK_L_` prefix.
+    Right,
+}
+
+impl KernSide {
+    fn prefix(self) -> &'static str {
+        match self {
+            KernSide::Left => SECOND_GROUP_PREFIX,
+            KernSide::Right => FIRST_GROUP_PREFIX,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum KernGroupError {
+    #[error("no {0:?} kerning group named {1:?}")]
+    UnknownGroup(KernSide, String),
+    #[error("there already is a {0:?} kerning group named {1:?}")]
+    DuplicateGroup(KernSide, String),
+    #[error("bad group name: {0}")]
+    Naming(#[from] norad::error::NamingError),
+}
+
+impl Font {
+    /// The effective left-to-right kerning between two glyphs in a master.
+    ///
+    /// Like Glyphs, the most specific pair wins: the glyph pair, then the
+    /// exceptions of the first glyph against the second glyph's group and of
+    /// the first glyph's group against the second glyph, then the group pair.
+    /// Returns `None` if no pair applies.
+    pub fn kern_value(&self, master_id: &str, first: &str, second: &str) -> Option<f64> {
+        let kerning = self.kerning_ltr.as_ref()?.get(master_id)?;
+        self.lookup_chain(first, second)
+            .iter()
+            .find_map(|(first, second)| lookup(kerning, first, second))
+    }
+
+    /// Iterate over the left-to-right kerning pairs of a master, as stored.
+    pub fn kerning_pairs<'a>(
+        &'a self,
+        master_id: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a str, f64)> + 'a {
+        let kerning = self.kerning_ltr.as_ref().and_then(|k| k.get(master_id));
+        kerning.into_iter().flatten().flat_map(|(first, seconds)| {
+            seconds
+                .iter()
+                .map(move |(second, value)| (first.as_str(), second.as_str(), *value))
+        })
+    }
+
+    /// Set a left-to-right kerning pair of a master. Either side may be a
+    /// glyph name or a prefixed group name.
+    pub fn set_kerning(
+        &mut self,
+        master_id: &str,
+        first: &str,
+        second: &str,
+        value: f64,
+    ) -> Result<(), norad::error::NamingError> {
+        let (first, second) = (norad::Name::new(first)?, norad::Name::new(second)?);
+        self.kerning_ltr
+            .get_or_insert_with(Default::default)
+            .entry(master_id.to_string())
+            .or_default()
+            .entry(first)
+            .or_default()
+            .insert(second, value);
+        Ok(())
+    }
+
+    /// Remove a left-to-right kerning pair of a master, returning its value.
+    pub fn remove_kerning(&mut self, master_id: &str, first: &str, second: &str) -> Option<f64> {
+        let kerning = self.kerning_ltr.as_mut()?.get_mut(master_id)?;
+        remove_pair(kerning, first, second)
+    }
+
+    /// Remove the left-to-right kerning pairs that make no difference: zero
+    /// pairs with nothing to override and exceptions with the same value as
+    /// the pair they are an exception to. Returns the number of pairs
+    /// removed.
+    pub fn compress_kerning(&mut self) -> usize {
+        let Some(mut all_kerning) = self.kerning_ltr.take() else {
+            return 0;
+        };
+        let mut removed = 0;
+        for kerning in all_kerning.values_mut() {
+            // Go from the least to the most specific pairs, so that every pair
+            // is compared to what remains below it.
+            let mut pairs: Vec<(String, String)> = kerning
+                .iter()
+                .flat_map(|(first, seconds)| {
+                    seconds
+                        .keys()
+                        .map(move |second| (first.to_string(), second.to_string()))
+                })
+                .collect();
+            pairs.sort_by_key(|(first, second)| {
+                usize::from(!is_group(first)) + usize::from(!is_group(second))
+            });
+            for (first, second) in pairs {
+                let value = lookup(kerning, &first, &second).unwrap_or_default();
+                // Every glyph pair that resolves to this pair must resolve to
+                // the same value without it, not just the pair itself.
+                let firsts = self.side_members(&first);
+                let seconds = self.side_members(&second);
+                let affected = std::iter::once((first.clone(), second.clone())).chain(
+                    firsts.iter().flat_map(|first| {
+                        seconds
+                            .iter()
+                            .map(move |second| (first.clone(), second.clone()))
+                    }),
+                );
+                let unchanged = affected.into_iter().all(|(glyph1, glyph2)| {
+                    let chain = self.lookup_chain(&glyph1, &glyph2);
+                    let mut hits = chain
+                        .iter()
+                        .filter_map(|(f, s)| Some(((f, s), lookup(kerning, f, s)?)));
+                    match hits.next() {
+                        Some(((f, s), _)) if *f == first && *s == second => {
+                            hits.next().map(|(_, v)| v).unwrap_or_default() == value
+                        }
+                        _ => true,
+                    }
+                });
+                if unchanged {
+                    remove_pair(kerning, &first, &second);
+                    removed += 1;
+                }
+            }
+        }
+        self.kerning_ltr = Some(all_kerning);
+        removed
+    }
+
+    /// Rename a kerning group on one side, in the glyphs that belong to it
+    /// and in the left-to-right and right-to-left kerning of every master.
+    ///
+    /// Fails without changing anything if no glyph or kerning pair uses the
+    /// group, or if the new name is already in use on that side.
+    pub fn rename_kern_group(
+        &mut self,
+        side: KernSide,
+        old: &str,
+        new: &str,
+    ) -> Result<(), KernGroupError> {
+        let prefix = side.prefix();
+        let (old_key, new_key) = (format!("{prefix}{old}"), format!("{prefix}{new}"));
+        let new_name = norad::Name::new(new)?;
+        let new_key = norad::Name::new(&new_key)?;
+        let is_member = |glyph: &crate::Glyph, group: &str| {
+            let glyph_group = match side {
+                KernSide::Left => &glyph.kern_left,
+                KernSide::Right => &glyph.kern_right,
+            };
+            glyph_group.as_deref() == Some(group)
+        };
+        let all_kerning = || {
+            [&self.kerning_ltr, &self.kerning_rtl]
+                .into_iter()
+                .flatten()
+                .flat_map(|kerning| kerning.values())
+        };
+        let in_use = |group: &str, key: &str| {
+            self.glyphs.iter().any(|glyph| is_member(glyph, group))
+                || all_kerning().any(|kerning| {
+                    kerning.contains_key(key)
+                        || kerning.values().any(|seconds| seconds.contains_key(key))
+                })
+        };
+        if !in_use(old, &old_key) {
+            return Err(KernGroupError::UnknownGroup(side, old.to_string()));
+        }
+        if old != new && in_use(new, &new_key) {
+            return Err(KernGroupError::DuplicateGroup(side, new.to_string()));
+        }
+
+        for glyph in &mut self.glyphs {
+            let group = match side {
+                KernSide::Left => &mut glyph.kern_left,
+                KernSide::Right => &mut glyph.kern_right,
+            };
+            if group.as_deref() == Some(old) {
+                *group = Some(new_name.clone());
+            }
+        }
+        let all_kerning = [&mut self.kerning_ltr, &mut self.kerning_rtl]
+            .into_iter()
+            .flatten()
+            .flat_map(|kerning| kerning.values_mut());
+        for kerning in all_kerning {
+            if let Some(seconds) = kerning.remove(old_key.as_str()) {
+                kerning.insert(new_key.clone(), seconds);
+            }
+            for seconds in kerning.values_mut() {
+                if let Some(value) = seconds.remove(old_key.as_str()) {
+                    seconds.insert(new_key.clone(), value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The pairs to look up for the kerning between two sides, most specific
+    /// first: the sides themselves, then the groups of glyph sides.
+    pub(crate) fn lookup_chain(&self, first: &str, second: &str) -> Vec<(String, String)> {
+        let first_group = (!is_group(first))
+            .then(|| self.get_glyph(first)?.kern_right.as_ref())
+            .flatten()
+            .map(|group| format!("{FIRST_GROUP_PREFIX}{group}"));
+        let second_group = (!is_group(second))
+            .then(|| self.get_glyph(second)?.kern_left.as_ref())
+            .flatten()
+            .map(|group| format!("{SECOND_GROUP_PREFIX}{group}"));
+
+        let firsts = [Some(first.to_string()), first_group];
+        let seconds = [Some(second.to_string()), second_group];
+        [(0, 0), (0, 1), (1, 0), (1, 1)]
+            .into_iter()
+            .filter_map(|(f, s)| Some((firsts[f].clone()?, seconds[s].clone()?)))
+            .collect()
+    }
+
+    /// The members of the kerning groups of the font, by prefixed group name,
+    /// in glyph order.
+    pub(crate) fn kern_groups(&self) -> BTreeMap<String, Vec<String>> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for glyph in &self.glyphs {
+            if let Some(group) = &glyph.kern_right {
+                groups
+                    .entry(format!("{FIRST_GROUP_PREFIX}{group}"))
+                    .or_default()
+                    .push(glyph.glyphname.to_string());
+            }
+            if let Some(group) = &glyph.kern_left {
+                groups
+                    .entry(format!("{SECOND_GROUP_PREFIX}{group}"))
+                    .or_default()
+                    .push(glyph.glyphname.to_string());
+            }
+        }
+        groups
+    }
+
+    /// The glyphs a side of a kerning pair stands for: the members of a
+    /// group, or the side itself.
+    fn side_members(&self, side: &str) -> Vec<String> {
+        let members = |group: &str, side: KernSide| {
+            self.glyphs
+                .iter()
+                .filter(|glyph| {
+                    let glyph_group = match side {
+                        KernSide::Left => &glyph.kern_left,
+                        KernSide::Right => &glyph.kern_right,
+                    };
+                    glyph_group.as_deref() == Some(group)
+                })
+                .map(|glyph| glyph.glyphname.to_string())
+                .collect()
+        };
+        if let Some(group) = side.strip_prefix(FIRST_GROUP_PREFIX) {
+            members(group, KernSide::Right)
+        } else if let Some(group) = side.strip_prefix(SECOND_GROUP_PREFIX) {
+            members(group, KernSide::Left)
+        } else {
+            vec![side.to_string()]
+        }
+    }
+}
+
+pub(crate) fn is_group(side: &str) -> bool {
+    side.starts_with(FIRST_GROUP_PREFIX) || side.starts_with(SECOND_GROUP_PREFIX)
+}
+
+fn lookup(kerning: &norad::Kerning, first: &str, second: &str) -> Option<f64> {
+    kerning.get(first)?.get(second).copied()
+}
+
+fn remove_pair(kerning: &mut norad::Kerning, first: &str, second: &str) -> Option<f64> {
+    let seconds = kerning.get_mut(first)?;
+    let value = seconds.remove(second);
+    if seconds.is_empty() {
+        kerning.remove(first);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KernGroupError, KernSide};
+    use crate::Font;
+
+    #[test]
+    fn kern_value_group_fallback() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(30.0));
+        assert_eq!(font.kern_value("m01", "B", "A"), None);
+        assert_eq!(font.kern_value("unknown", "A", "B"), None);
+
+        // A's right-hand group is "A", B joins the left-hand group "B".
+        font.get_glyph_mut("B").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        font.get_glyph_mut("C").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        let kerning = font.kerning_ltr.as_mut().unwrap().get_mut("m01").unwrap();
+        let name = |n: &str| norad::Name::new(n).unwrap();
+        kerning
+            .entry(name("@MMK_L_A"))
+            .or_default()
+            .insert(name("@MMK_R_B"), -20.0);
+        kerning
+            .entry(name("@MMK_L_A"))
+            .or_default()
+            .insert(name("C"), -10.0);
+        kerning
+            .entry(name("A"))
+            .or_default()
+            .insert(name("@MMK_R_B"), -5.0);
+
+        // The glyph pair beats the exceptions and the group pair.
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(30.0));
+        // The first glyph's exception beats the second's.
+        assert_eq!(font.kern_value("m01", "A", "C"), Some(-5.0));
+        font.remove_kerning("m01", "A", "@MMK_R_B");
+        assert_eq!(font.kern_value("m01", "A", "C"), Some(-10.0));
+        font.remove_kerning("m01", "@MMK_L_A", "C");
+        assert_eq!(font.kern_value("m01", "A", "C"), Some(-20.0));
+    }
+
+    #[test]
+    fn edit_kerning() {
+        let mut font = Font::new();
+        font.set_kerning("m01", "A", "V", -50.0).unwrap();
+        font.set_kerning("m01", "@MMK_L_A", "@MMK_R_V", -40.0)
+            .unwrap();
+        font.set_kerning("m01", "A", "V", -60.0).unwrap();
+        let mut pairs = font.kerning_pairs("m01").collect::<Vec<_>>();
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(pairs, [("@MMK_L_A", "@MMK_R_V", -40.0), ("A", "V", -60.0)]);
+        assert_eq!(font.kerning_pairs("other").count(), 0);
+
+        assert_eq!(font.remove_kerning("m01", "A", "V"), Some(-60.0));
+        assert_eq!(font.remove_kerning("m01", "A", "V"), None);
+        assert!(!font.kerning_ltr.as_ref().unwrap()["m01"].contains_key("A"));
+    }
+
+    #[test]
+    fn compress_kerning() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.get_glyph_mut("B").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        font.get_glyph_mut("C").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        for (first, second, value) in [
+            ("@MMK_L_A", "@MMK_R_B", -20.0),
+            // Overrides the group pair, so must stay.
+            ("A", "@MMK_R_B", 0.0),
+            // Same as the exception it falls back to.
+            ("A", "C", 0.0),
+            // Nothing to override.
+            ("B", "C", 0.0),
+        ] {
+            font.set_kerning("m01", first, second, value).unwrap();
+        }
+
+        assert_eq!(font.compress_kerning(), 2);
+        let mut pairs = font.kerning_pairs("m01").collect::<Vec<_>>();
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            pairs,
+            [
+                ("@MMK_L_A", "@MMK_R_B", -20.0),
+                ("A", "@MMK_R_B", 0.0),
+                ("A", "B", 30.0),
+            ]
+        );
+        assert_eq!(font.kern_value("m01", "A", "C"), Some(0.0));
+    }
+
+    #[test]
+    fn compress_kerning_keeps_fallbacks() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.get_glyph_mut("B").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        font.get_glyph_mut("C").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        for (first, second, value) in [
+            ("@MMK_L_A", "@MMK_R_B", -20.0),
+            // Same as the group pair, but A-C falls back through it rather
+            // than to the exception below.
+            ("A", "@MMK_R_B", -20.0),
+            ("@MMK_L_A", "C", -10.0),
+        ] {
+            font.set_kerning("m01", first, second, value).unwrap();
+        }
+
+        assert_eq!(font.kern_value("m01", "A", "C"), Some(-20.0));
+        assert_eq!(font.compress_kerning(), 0);
+        assert_eq!(font.kern_value("m01", "A", "C"), Some(-20.0));
+    }
+
+    #[test]
+    fn rename_kern_group() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.get_glyph_mut("B").unwrap().kern_left = Some(norad::Name::new("B").unwrap());
+        font.set_kerning("m01", "@MMK_L_A", "@MMK_R_B", -20.0)
+            .unwrap();
+        font.set_kerning("m01", "@MMK_L_B", "@MMK_R_A", -10.0)
+            .unwrap();
+        let value = font.kern_value("m01", "A", "C");
+
+        font.rename_kern_group(KernSide::Right, "A", "A2").unwrap();
+        assert_eq!(
+            font.get_glyph("A").unwrap().kern_right.as_deref(),
+            Some("A2")
+        );
+        // The left-hand group of the same name is a different group.
+        assert_eq!(font.get_glyph("A").unwrap().kern_left.as_deref(), Some("A"));
+        let mut pairs = font.kerning_pairs("m01").collect::<Vec<_>>();
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(pairs.contains(&("@MMK_L_A2", "@MMK_R_B", -20.0)));
+        assert!(pairs.contains(&("@MMK_L_B", "@MMK_R_A", -10.0)));
+        assert_eq!(font.kern_value("m01", "A", "C"), value);
+
+        font.rename_kern_group(KernSide::Left, "B", "B2").unwrap();
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(30.0));
+        font.remove_kerning("m01", "A", "B");
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(-20.0));
+
+        assert!(matches!(
+            font.rename_kern_group(KernSide::Left, "B", "C"),
+            Err(KernGroupError::UnknownGroup(KernSide::Left, _))
+        ));
+        assert!(matches!(
+            font.rename_kern_group(KernSide::Left, "B2", "A"),
+            Err(KernGroupError::DuplicateGroup(KernSide::Left, _))
+        ));
+        assert_eq!(
+            font.get_glyph("B").unwrap().kern_left.as_deref(),
+            Some("B2")
+        );
+    }
+}
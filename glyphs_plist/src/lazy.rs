@@ -0,0 +1,165 @@
+//! Loading a font without converting its glyphs until they are used, for
+//! tools that only look at a few glyphs of a large font.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::font::FontLoadError;
+use crate::{Font, Glyph, GlyphsFromPlistError, Plist};
+
+/// A font whose glyphs are kept as plists and converted on first access.
+///
+/// Everything but the glyphs is converted when loading, and problems with a
+/// glyph only come up when it is accessed.
+#[derive(Debug)]
+pub struct LazyFont {
+    /// The font-level data. Its glyph list is empty; the glyphs are accessed
+    /// through the `LazyFont`.
+    pub font: Font,
+    glyphs: Vec<LazyGlyph>,
+    index: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct LazyGlyph {
+    name: String,
+    plist: Plist,
+    glyph: OnceLock<Glyph>,
+}
+
+impl LazyGlyph {
+    fn get(&self) -> Result<&Glyph, GlyphsFromPlistError> {
+        if let Some(glyph) = self.glyph.get() {
+            return Ok(glyph);
+        }
+        let glyph = self.plist.clone().try_into()?;
+        Ok(self.glyph.get_or_init(|| glyph))
+    }
+
+    fn into_glyph(self) -> Result<Glyph, GlyphsFromPlistError> {
+        match self.glyph.into_inner() {
+            Some(glyph) => Ok(glyph),
+            None => self.plist.try_into(),
+        }
+    }
+}
+
+impl LazyFont {
+    /// Load a font from a `.glyphs` file or a `.glyphspackage` directory,
+    /// like [`Font::load`], leaving the glyphs unconverted.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<LazyFont, FontLoadError> {
+        let path = path.as_ref();
+        Self::load_file(path).map_err(|e| e.in_file(path))
+    }
+
+    fn load_file(path: &std::path::Path) -> Result<LazyFont, FontLoadError> {
+        let file = Font::load_plist(path)?;
+        let mut dict = file.plist.into_hashmap();
+        let glyph_plists = dict.remove("glyphs").map(Plist::into_vec);
+
+        let mut glyphs = Vec::new();
+        let mut index = HashMap::new();
+        for plist in glyph_plists.into_iter().flatten() {
+            let name = plist
+                .get("glyphname")
+                .cloned()
+                .ok_or(GlyphsFromPlistError::MissingField("glyphname"))?;
+            let name = norad::Name::try_from(name)
+                .map_err(GlyphsFromPlistError::from)?
+                .to_string();
+            index.entry(name.clone()).or_insert(glyphs.len());
+            glyphs.push(LazyGlyph {
+                name,
+                plist,
+                glyph: OnceLock::new(),
+            });
+        }
+
+        dict.insert("glyphs".into(), Plist::Array(Vec::new()));
+        let mut font: Font = Plist::from(dict).try_into()?;
+        font.upgraded_from = file.upgraded_from;
+        font.encoding = file.encoding;
+        Ok(LazyFont {
+            font,
+            glyphs,
+            index,
+        })
+    }
+
+    /// The names of the glyphs, in the font's order.
+    pub fn glyph_names(&self) -> impl Iterator<Item = &str> {
+        self.glyphs.iter().map(|glyph| glyph.name.as_str())
+    }
+
+    /// Look up a glyph by name, converting it if it hasn't been yet.
+    pub fn get_glyph(&self, glyphname: &str) -> Result<Option<&Glyph>, GlyphsFromPlistError> {
+        match self.index.get(glyphname) {
+            Some(&i) => self.glyphs[i].get().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up a glyph by name for changing it, converting it if it hasn't
+    /// been yet.
+    pub fn get_glyph_mut(
+        &mut self,
+        glyphname: &str,
+    ) -> Result<Option<&mut Glyph>, GlyphsFromPlistError> {
+        let Some(&i) = self.index.get(glyphname) else {
+            return Ok(None);
+        };
+        let lazy = &mut self.glyphs[i];
+        lazy.get()?;
+        Ok(lazy.glyph.get_mut())
+    }
+
+    /// Convert the remaining glyphs, giving the whole font.
+    pub fn into_font(self) -> Result<Font, GlyphsFromPlistError> {
+        let mut font = self.font;
+        font.glyphs = self
+            .glyphs
+            .into_iter()
+            .map(LazyGlyph::into_glyph)
+            .collect::<Result<_, _>>()?;
+        font.share_ids();
+        Ok(font)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FormatVersion;
+
+    #[test]
+    fn lazy_font() {
+        let path = "testdata/GlyphsFileFormatv3.glyphs";
+        let font = Font::load(path).unwrap();
+        let mut lazy = LazyFont::load(path).unwrap();
+
+        assert!(lazy.font.glyphs.is_empty());
+        assert_eq!(lazy.font.font_master, font.font_master);
+        assert!(lazy.glyphs.iter().all(|g| g.glyph.get().is_none()));
+        assert!(lazy
+            .glyph_names()
+            .eq(font.glyphs.iter().map(|g| g.glyphname.as_str())));
+
+        assert_eq!(lazy.get_glyph("A").unwrap(), font.get_glyph("A"));
+        assert_eq!(lazy.get_glyph("nonexistent").unwrap(), None);
+        let converted = lazy.glyphs.iter().filter(|g| g.glyph.get().is_some());
+        assert_eq!(converted.count(), 1);
+
+        lazy.get_glyph_mut("B").unwrap().unwrap().layers[0].width = 1.0;
+        let mut expected = font.clone();
+        expected.get_glyph_mut("B").unwrap().layers[0].width = 1.0;
+        assert_eq!(lazy.into_font().unwrap(), expected);
+    }
+
+    #[test]
+    fn lazy_font_glyphs2() {
+        let path = "testdata/NewFont.glyphs";
+        let lazy = LazyFont::load(path).unwrap();
+        assert_eq!(lazy.font.upgraded_from, Some(FormatVersion::Glyphs2));
+        assert_eq!(lazy.into_font().unwrap(), Font::load(path).unwrap());
+    }
+}
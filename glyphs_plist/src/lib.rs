@@ -1,16 +1,109 @@
 //! Lightweight library for reading and writing Glyphs font files.
 
+mod alignment;
+mod axes;
+#[cfg(feature = "babelfont")]
+mod babelfont;
+mod codecs;
+mod curves;
+mod custom_parameters;
+mod density;
+mod designspace;
+mod diff;
+mod fea_import;
+mod features;
+mod filters;
 mod font;
 mod from_plist;
+mod glyph_data;
+mod glyph_list;
+mod glyph_order;
+mod glyphs2;
+mod grid;
+mod guides;
+mod hints;
+mod id;
+mod interpolation;
+#[cfg(feature = "fontir")]
+mod ir;
+mod kern_fea;
+mod kerning;
+mod lazy;
+mod load;
+mod lossless;
+mod masks;
+mod masters;
+mod measure;
+mod merge;
+mod naming;
+mod nodes;
 mod norad_interop;
+mod outline;
+mod overlap;
+mod package;
+mod pen;
 mod plist;
+mod predicate;
+mod query;
+mod rename;
+mod roundtrip;
+mod scale;
+mod stems;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
+mod summary;
+mod tags;
 mod to_plist;
+mod validate;
+mod variable_font;
+mod vertical_metrics;
 
+pub use alignment::Alignment;
+pub use axes::AxisError;
+pub use codecs::{CodecError, Codecs};
+pub use custom_parameters::ParameterValue;
+pub use designspace::{
+    AlternateRule, AxisCondition, FeatureCondition, SparseSource, SparseSourceError,
+    SubstitutionRule,
+};
+pub use diff::{FontDiff, GlyphDiff, KerningChange, LayerDiff, MetricChange, ShapeChange};
+pub use fea_import::{FeaImportError, FeaImportReport};
+pub use filters::{LineCap, StrokePosition, StrokeStyle};
 pub use font::{
-    Anchor, Axis, BackgroundLayer, Component, Font, FontLoadError, FontMaster, FontNumbers,
-    FontStems, Glyph, GlyphsFromPlistError, Instance, Layer, LayerAttr, MasterMetric, Metric,
-    MetricType, Node, NodeType, Path, Settings, Shape,
+    Anchor, Axis, BackgroundImage, BackgroundLayer, Case, Color, ColorLayerGlyph, Component,
+    CustomParameter, Direction, Encoding, Feature, FeatureClass, FeaturePrefix, Font,
+    FontLoadError, FontMaster, FontNumbers, FontSaveError, FontStems, FormatVersion, Glyph,
+    GlyphsFromPlistError, GuideLine, Instance, InstanceType, Layer, LayerAttr, MasterMetric,
+    Metric, MetricType, Node, NodeType, Path, SaveOptions, Settings, Shape,
 };
 pub use from_plist::FromPlist;
+pub use glyph_data::{GlyphData, GlyphDataError, GlyphInfo};
+pub use glyph_list::GlyphList;
+pub use glyphs2::ConversionReport;
+pub use id::Id;
+pub use interpolation::InterpolationError;
+#[cfg(feature = "fontir")]
+pub use ir::{FontIr, GlyphIr, IrError, KerningIr};
+pub use kern_fea::KernFeaError;
+pub use kerning::{KernGroupError, KernSide};
+pub use lazy::LazyFont;
+pub use load::{GlyphError, LoadMode, LoadOptions};
+pub use lossless::SourceFont;
+pub use masks::MaskOp;
+pub use masters::MasterError;
+pub use merge::{ConflictPolicy, MergeError, MergeOptions, MergeReport};
+pub use naming::InstanceNames;
+pub use norad_interop::{BraceLayerNaming, ConversionOptions, UfoConversionError};
+pub use overlap::OutlineOp;
+pub use pen::{Pen, PointPen, ShapePen};
 pub use plist::Plist;
+pub use predicate::{Comparison, Operator, Predicate, PredicateError, Value};
+pub use query::GlyphQuery;
+pub use rename::{DanglingReferences, RemoveGlyphError, RenameError, RenameReport};
+pub use roundtrip::{verify_roundtrip, RoundtripDifference, RoundtripReport};
+pub use stems::StemMismatch;
+pub use tags::GlyphSelection;
 pub use to_plist::ToPlist;
+pub use validate::{Diagnostic, Severity};
+pub use variable_font::{AxisIr, SourceIr, VariableFontConfig, VariableFontExport};
+pub use vertical_metrics::VerticalMetrics;
@@ -0,0 +1,608 @@
+//! Loading fonts more strictly or more leniently than [`Font::load`].
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::font::{Encoding, FontFile, FontLoadError};
+use crate::validate::Diagnostics;
+use crate::{Diagnostic, Font, Glyph, GlyphsFromPlistError, Layer, Plist, Severity, Shape};
+
+/// A glyph that couldn't be converted, with where in the font the problem
+/// is, like `glyphs["A"].layers["m01"]`.
+#[derive(Debug, Error)]
+#[error("{path}: {error}")]
+pub struct GlyphError {
+    pub path: String,
+    pub error: GlyphsFromPlistError,
+}
+
+/// How [`Font::load_with`] deals with problems in a font.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Fail only on data that can't be converted to the model, like
+    /// [`Font::load`], and warn about keys the format doesn't have, values
+    /// out of the range Glyphs.app allows and numbers where text is expected.
+    #[default]
+    Normal,
+    /// Also fail on keys the format doesn't have, values out of the range
+    /// Glyphs.app allows, numbers where text is expected and the errors
+    /// [`Font::validate`] finds. Glyphs
+    /// that can't be converted fail the load with the errors of all of them.
+    Strict,
+    /// Skip glyphs that can't be converted, and report them together with
+    /// unknown keys, values out of range, numbers where text is expected and
+    /// what [`Font::validate`] finds.
+    Lenient,
+}
+
+/// Options controlling how [`Font::load_with`] loads a font.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadOptions {
+    pub mode: LoadMode,
+    /// Parse `.glyphs` files straight from a memory mapping instead of
+    /// reading them into memory first, for very large sources. Only has an
+    /// effect with the `mmap` feature.
+    pub memory_map: bool,
+    /// The encoding of `.glyphs` files, instead of detecting it.
+    pub encoding: Option<Encoding>,
+}
+
+/// The keys of the format that the model keeps in `other_stuff`, by where
+/// they appear: those the Glyphs 3 file format specification lists there,
+/// less the ones the model reads into fields.
+const FONT_KEYS: &[&str] = &["DisplayStrings", "note", "properties", "userData"];
+const SETTINGS_KEYS: &[&str] = &[
+    "disablesLastChange",
+    "fontType",
+    "keepAlternatesTogether",
+    "keyboardIncrement",
+    "keyboardIncrementBig",
+    "keyboardIncrementHuge",
+    "previewRemoveOverlap",
+];
+const FEATURE_KEYS: &[&str] = &["labels"];
+const MASTER_KEYS: &[&str] = &["customParameters", "guides", "iconName", "properties"];
+const INSTANCE_KEYS: &[&str] = &[
+    "customParameters",
+    "instanceInterpolations",
+    "manualInterpolation",
+    "properties",
+];
+const GLYPH_KEYS: &[&str] = &[
+    "lastChange",
+    "metricVertWidth",
+    "partsSettings",
+    "sortName",
+    "sortNameKeep",
+];
+const LAYER_KEYS: &[&str] = &["annotations", "hints", "partSelection", "visible"];
+const LAYER_ATTR_KEYS: &[&str] = &["color", "colorPalette", "sbixSize", "svg"];
+const BACKGROUND_KEYS: &[&str] = &["annotations", "guides", "hints"];
+const COMPONENT_KEYS: &[&str] = &[
+    "anchorTo",
+    "attr",
+    "locked",
+    "orientation",
+    "piece",
+    "userData",
+];
+
+/// The keys whose values are text, which files sometimes have unquoted
+/// numbers for, like a glyph named `1`. The model reads them as text.
+const TEXT_KEYS: &[&str] = &[
+    "associatedMasterId",
+    "category",
+    "familyName",
+    "glyphname",
+    "kernBottom",
+    "kernLeft",
+    "kernRight",
+    "kernTop",
+    "layerId",
+    "name",
+    "note",
+    "production",
+    "ref",
+    "script",
+    "subCategory",
+    "tag",
+];
+
+/// The keys holding free-form plists, which aren't checked for numbers where
+/// text is expected.
+const FREE_FORM_KEYS: &[&str] = &["properties", "userData", "value"];
+
+impl Font {
+    /// Load a font like [`Font::load`], dealing with problems as `options`
+    /// say. Returns the font with the problems found: in strict mode the
+    /// warnings, as errors fail the load, and otherwise all of them.
+    ///
+    /// With the `log` feature, the problems are also logged, with the
+    /// `glyphs_plist::load` target.
+    pub fn load_with(
+        path: impl AsRef<std::path::Path>,
+        options: &LoadOptions,
+    ) -> Result<(Font, Vec<Diagnostic>), FontLoadError> {
+        let path = path.as_ref();
+        Self::load_file_with(path, options).map_err(|e| e.in_file(path))
+    }
+
+    fn load_file_with(
+        path: &std::path::Path,
+        options: &LoadOptions,
+    ) -> Result<(Font, Vec<Diagnostic>), FontLoadError> {
+        let FontFile {
+            plist,
+            upgraded_from,
+            encoding,
+        } = Self::load_plist_with(path, options)?;
+        let severity = match options.mode {
+            LoadMode::Strict => Severity::Error,
+            LoadMode::Normal | LoadMode::Lenient => Severity::Warning,
+        };
+        let mut diagnostics = Diagnostics::default();
+        check_text_numbers(&plist, "", severity, &mut diagnostics);
+        let mut font = match options.mode {
+            LoadMode::Normal => Self::from_font_plist(plist)?,
+            LoadMode::Strict => match Self::convert_collecting_errors(plist)? {
+                (font, errors) if errors.is_empty() => font,
+                (_, errors) => return Err(FontLoadError::Glyphs(errors)),
+            },
+            LoadMode::Lenient => {
+                let (font, errors) = Self::convert_collecting_errors(plist)?;
+                for GlyphError { path, error } in errors {
+                    diagnostics.error(path, format!("skipped glyph: {error}"));
+                }
+                font
+            }
+        };
+        font.upgraded_from = upgraded_from;
+        font.encoding = encoding;
+
+        font.check_keys(severity, &mut diagnostics);
+        font.check_ranges(severity, &mut diagnostics);
+        if options.mode != LoadMode::Normal {
+            diagnostics.0.extend(font.validate());
+        }
+
+        let mut diagnostics = diagnostics.0;
+        #[cfg(feature = "log")]
+        for diagnostic in &diagnostics {
+            let level = match diagnostic.severity {
+                Severity::Warning => log::Level::Warn,
+                Severity::Error => log::Level::Error,
+            };
+            let (path, message) = (&diagnostic.path, &diagnostic.message);
+            log::log!(target: "glyphs_plist::load", level, "{path}: {message}");
+        }
+        if options.mode == LoadMode::Strict {
+            let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+                .into_iter()
+                .partition(|d| d.severity == Severity::Error);
+            if !errors.is_empty() {
+                return Err(FontLoadError::Invalid(errors));
+            }
+            diagnostics = warnings;
+        }
+        Ok((font, diagnostics))
+    }
+
+    /// Convert the plist of a font, leaving out the glyphs that can't be
+    /// converted and collecting their errors.
+    fn convert_collecting_errors(plist: Plist) -> Result<(Font, Vec<GlyphError>), FontLoadError> {
+        let mut dict = plist.into_hashmap();
+        let glyphs = dict
+            .insert("glyphs".into(), Plist::Array(Vec::new()))
+            .map(Plist::into_vec)
+            .unwrap_or_default();
+        let mut font: Font = Plist::from(dict).try_into()?;
+        let mut errors = Vec::new();
+        for (i, plist) in glyphs.into_iter().enumerate() {
+            match convert_glyph(i, plist) {
+                Ok(glyph) => font.glyphs.push(glyph),
+                Err(glyph_errors) => errors.extend(glyph_errors),
+            }
+        }
+        font.share_ids();
+        Ok((font, errors))
+    }
+
+    /// Report the keys the format doesn't have.
+    fn check_keys(&self, severity: Severity, diagnostics: &mut Diagnostics) {
+        let mut check = |path: &str, other_stuff: &HashMap<String, Plist>, known: &[&str]| {
+            let mut keys: Vec<&String> = other_stuff
+                .keys()
+                .filter(|key| !known.contains(&key.as_str()))
+                .collect();
+            keys.sort();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{path}.{key}"),
+                };
+                diagnostics.push(severity, path, "unknown key".into());
+            }
+        };
+
+        check("", &self.other_stuff, FONT_KEYS);
+        if let Some(settings) = &self.settings {
+            check("settings", &settings.other_stuff, SETTINGS_KEYS);
+        }
+        for (i, class) in self.classes.iter().flatten().enumerate() {
+            check(&format!("classes[{i}]"), &class.other_stuff, FEATURE_KEYS);
+        }
+        for (i, prefix) in self.feature_prefixes.iter().flatten().enumerate() {
+            check(
+                &format!("featurePrefixes[{i}]"),
+                &prefix.other_stuff,
+                FEATURE_KEYS,
+            );
+        }
+        for (i, feature) in self.features.iter().flatten().enumerate() {
+            check(
+                &format!("features[{i}]"),
+                &feature.other_stuff,
+                FEATURE_KEYS,
+            );
+        }
+        for (i, master) in self.font_master.iter().enumerate() {
+            check(
+                &format!("fontMaster[{i}]"),
+                &master.other_stuff,
+                MASTER_KEYS,
+            );
+        }
+        for (i, instance) in self.instances.iter().flatten().enumerate() {
+            check(
+                &format!("instances[{i}]"),
+                &instance.other_stuff,
+                INSTANCE_KEYS,
+            );
+        }
+        for glyph in &self.glyphs {
+            let glyph_path = format!("glyphs[{:?}]", glyph.glyphname.as_str());
+            check(&glyph_path, &glyph.other_stuff, GLYPH_KEYS);
+            for layer in &glyph.layers {
+                let path = format!("{glyph_path}.layers[{:?}]", layer.layer_id);
+                check(&path, &layer.other_stuff, LAYER_KEYS);
+                if let Some(attr) = &layer.attr {
+                    check(&format!("{path}.attr"), &attr.other_stuff, LAYER_ATTR_KEYS);
+                }
+                if let Some(image) = &layer.background_image {
+                    check(&format!("{path}.backgroundImage"), &image.other_stuff, &[]);
+                }
+                let background = layer.background.as_ref();
+                if let Some(background) = background {
+                    check(
+                        &format!("{path}.background"),
+                        &background.other_stuff,
+                        BACKGROUND_KEYS,
+                    );
+                }
+                let shapes = [("shapes", &layer.shapes)]
+                    .into_iter()
+                    .chain(background.map(|b| ("background.shapes", &b.shapes)));
+                for (key, shapes) in shapes {
+                    for (i, shape) in shapes.iter().enumerate() {
+                        if let Shape::Component(component) = shape {
+                            check(
+                                &format!("{path}.{key}[{i}]"),
+                                &component.other_stuff,
+                                COMPONENT_KEYS,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Report values out of the range Glyphs.app allows.
+    fn check_ranges(&self, severity: Severity, diagnostics: &mut Diagnostics) {
+        let mut check = |path: String, value: i64, min: i64, max: i64| {
+            if !(min..=max).contains(&value) {
+                let message = format!("{value} is out of the range {min} to {max}");
+                diagnostics.push(severity, path, message);
+            }
+        };
+        check("unitsPerEm".into(), self.units_per_em.into(), 16, 16384);
+        for (i, instance) in self.instances.iter().flatten().enumerate() {
+            check(
+                format!("instances[{i}].weightClass"),
+                instance.weight_class,
+                1,
+                1000,
+            );
+            check(
+                format!("instances[{i}].widthClass"),
+                instance.width_class,
+                1,
+                9,
+            );
+        }
+    }
+}
+
+/// Report numbers under [`TEXT_KEYS`], naming glyphs and layers in the path
+/// by their name and ID.
+fn check_text_numbers(
+    plist: &Plist,
+    path: &str,
+    severity: Severity,
+    diagnostics: &mut Diagnostics,
+) {
+    match plist {
+        Plist::Dictionary(dict) => {
+            let mut keys: Vec<&String> = dict.keys().collect();
+            keys.sort();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{path}.{key}"),
+                };
+                match &dict[key] {
+                    Plist::Integer(_) | Plist::Float(_) if TEXT_KEYS.contains(&key.as_str()) => {
+                        let message =
+                            format!("number where text is expected, read as {}", dict[key]);
+                        diagnostics.push(severity, path, message);
+                    }
+                    _ if FREE_FORM_KEYS.contains(&key.as_str()) => (),
+                    value => check_text_numbers(value, &path, severity, diagnostics),
+                }
+            }
+        }
+        Plist::Array(array) => {
+            for (i, value) in array.iter().enumerate() {
+                let id = ["glyphname", "layerId"]
+                    .into_iter()
+                    .find_map(|key| value.get(key));
+                let path = match id {
+                    Some(Plist::String(id)) => format!("{path}[{id:?}]"),
+                    Some(id) => format!("{path}[\"{id}\"]"),
+                    None => format!("{path}[{i}]"),
+                };
+                check_text_numbers(value, &path, severity, diagnostics);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Convert the plist of a glyph, failing with the errors of the layers that
+/// can't be converted, or else of the glyph. The layers are converted one by
+/// one so that each error names its layer.
+fn convert_glyph(index: usize, plist: Plist) -> Result<Glyph, Vec<GlyphError>> {
+    let Plist::Dictionary(mut dict) = plist else {
+        let path = format!("glyphs[{index}]");
+        let error = Glyph::try_from(plist).unwrap_err();
+        return Err(vec![GlyphError { path, error }]);
+    };
+    let path = match dict.get("glyphname").cloned().map(norad::Name::try_from) {
+        Some(Ok(name)) => format!("glyphs[{:?}]", name.as_str()),
+        _ => format!("glyphs[{index}]"),
+    };
+    let layer_plists = match dict.get_mut("layers") {
+        Some(Plist::Array(layers)) => std::mem::take(layers),
+        _ => Vec::new(),
+    };
+
+    let mut layers = Vec::with_capacity(layer_plists.len());
+    let mut errors = Vec::new();
+    for (i, layer) in layer_plists.into_iter().enumerate() {
+        let layer_id = layer
+            .get("layerId")
+            .and_then(Plist::as_str)
+            .map(str::to_string);
+        match Layer::try_from(layer) {
+            Ok(layer) => layers.push(layer),
+            Err(error) => {
+                let path = match layer_id {
+                    Some(layer_id) => format!("{path}.layers[{layer_id:?}]"),
+                    None => format!("{path}.layers[{i}]"),
+                };
+                errors.push(GlyphError { path, error });
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut glyph = Glyph::try_from(Plist::Dictionary(dict))
+        .map_err(|error| vec![GlyphError { path, error }])?;
+    glyph.layers = layers;
+    Ok(glyph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(mode: LoadMode) -> Result<(Font, Vec<Diagnostic>), FontLoadError> {
+        Font::load_with(
+            "testdata/GlyphsFileFormatv3.glyphs",
+            &LoadOptions {
+                mode,
+                ..Default::default()
+            },
+        )
+        .map_err(FontLoadError::into_inner)
+    }
+
+    #[test]
+    fn load_with() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        for mode in [LoadMode::Normal, LoadMode::Strict, LoadMode::Lenient] {
+            let (loaded, diagnostics) = load(mode).unwrap();
+            assert_eq!(loaded, font);
+            assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+        }
+        assert!(load(LoadMode::Normal).unwrap().1.is_empty());
+
+        let options = LoadOptions {
+            memory_map: true,
+            ..Default::default()
+        };
+        let (mapped, _) = Font::load_with("testdata/GlyphsFileFormatv3.glyphs", &options).unwrap();
+        assert_eq!(mapped, font);
+    }
+
+    #[test]
+    fn load_warnings() {
+        let path = std::env::temp_dir().join("glyphs_plist_load_warnings.glyphs");
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.other_stuff.insert("unknown".into(), Plist::Integer(1));
+        let source = font
+            .to_plist_string()
+            .replace("glyphname = B;", "glyphname = 1;")
+            .replace("familyName = \"", "familyName = 2024;\nx = \"");
+        std::fs::write(&path, source).unwrap();
+
+        let (font, diagnostics) = Font::load_with(&path, &LoadOptions::default()).unwrap();
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "warning: familyName: number where text is expected, read as 2024",
+                r#"warning: glyphs["1"].glyphname: number where text is expected, read as 1"#,
+                "warning: unknown: unknown key",
+                "warning: x: unknown key",
+            ]
+        );
+        assert_eq!(font.family_name, "2024");
+        assert!(font.contains_glyph("1"));
+
+        let Err(FontLoadError::Invalid(errors)) = Font::load_with(
+            &path,
+            &LoadOptions {
+                mode: LoadMode::Strict,
+                ..Default::default()
+            },
+        )
+        .map_err(FontLoadError::into_inner) else {
+            panic!("expected strict loading to fail");
+        };
+        assert_eq!(errors.len(), 4);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn strict_and_lenient() {
+        let path = std::env::temp_dir().join("glyphs_plist_load_with.glyphs");
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.units_per_em = 10;
+        font.glyphs[0]
+            .other_stuff
+            .insert("unknown".into(), Plist::Integer(1));
+        font.font_master[0].metric_values.pop();
+        let mut plist = crate::ToPlist::to_plist(&font).into_hashmap();
+        if let Some(Plist::Array(glyphs)) = plist.get_mut("glyphs") {
+            glyphs[1] = Plist::Dictionary(HashMap::new());
+            let Plist::Dictionary(b) = &mut glyphs[3] else {
+                panic!("expected a glyph");
+            };
+            let Some(Plist::Array(layers)) = b.get_mut("layers") else {
+                panic!("expected layers");
+            };
+            let Plist::Dictionary(layer) = &mut layers[0] else {
+                panic!("expected a layer");
+            };
+            layer.remove("width");
+        }
+        std::fs::write(&path, Plist::from(plist).to_string()).unwrap();
+        let load = |mode| {
+            Font::load_with(
+                &path,
+                &LoadOptions {
+                    mode,
+                    ..Default::default()
+                },
+            )
+            .map_err(FontLoadError::into_inner)
+        };
+
+        assert!(matches!(
+            load(LoadMode::Normal),
+            Err(FontLoadError::ParseGlyphs(_))
+        ));
+        let Err(FontLoadError::Glyphs(errors)) = load(LoadMode::Strict) else {
+            panic!("expected strict loading to fail");
+        };
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "glyphs[1]: missing field glyphname",
+                r#"glyphs["B"].layers["m01"]: missing field width"#,
+            ]
+        );
+        let (font, diagnostics) = load(LoadMode::Lenient).unwrap();
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages[..5],
+            [
+                "error: glyphs[1]: skipped glyph: missing field glyphname",
+                r#"error: glyphs["B"].layers["m01"]: skipped glyph: missing field width"#,
+                r#"warning: glyphs["A"].unknown: unknown key"#,
+                "warning: unitsPerEm: 10 is out of the range 16 to 16384",
+                "error: fontMaster[0].metricValues: 7 metric values, but the font has 8",
+            ]
+        );
+        assert!(font.get_glyph("A").is_some());
+
+        // Without the broken glyph, strict mode fails on the rest.
+        let mut font = font;
+        font.glyphs[0].other_stuff.clear();
+        std::fs::write(&path, crate::ToPlist::to_plist(&font).to_string()).unwrap();
+        let Err(FontLoadError::Invalid(errors)) = load(LoadMode::Strict) else {
+            panic!("expected strict loading to fail");
+        };
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "error: unitsPerEm: 10 is out of the range 16 to 16384",
+                "error: fontMaster[0].metricValues: 7 metric values, but the font has 8",
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn strict_accepts_format_keys() {
+        let path = std::env::temp_dir().join("glyphs_plist_format_keys.glyphs");
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let a_dieresis = font.get_glyph_mut("Ä").unwrap();
+        a_dieresis
+            .other_stuff
+            .insert("metricVertWidth".into(), Plist::String("=A".into()));
+        let Shape::Component(component) = &mut a_dieresis.layers[0].shapes[1] else {
+            panic!("expected a component");
+        };
+        component
+            .other_stuff
+            .insert("anchorTo".into(), Plist::String("top".into()));
+        component
+            .other_stuff
+            .insert("orientation".into(), Plist::Integer(1));
+        font.settings
+            .get_or_insert_with(Default::default)
+            .other_stuff
+            .insert("disablesLastChange".into(), Plist::Integer(1));
+        std::fs::write(&path, crate::ToPlist::to_plist(&font).to_string()).unwrap();
+
+        let options = LoadOptions {
+            mode: LoadMode::Strict,
+            ..Default::default()
+        };
+        let (loaded, diagnostics) = Font::load_with(&path, &options).unwrap();
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| !d.message.contains("unknown key")),
+            "{diagnostics:?}"
+        );
+        assert_eq!(loaded.get_glyph("Ä"), font.get_glyph("Ä"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+//! Editing fonts without reformatting the parts that weren't changed, so that
+//! diffs of the saved file only show the actual edits.
+
+use std::fs;
+
+use crate::font::{decode, write_atomic, FontLoadError, FontSaveError};
+use crate::plist::{escape_string, Span, SpanChildren};
+use crate::{Font, Plist};
+
+/// A font loaded together with its source text, for saving it with the
+/// unchanged parts written exactly as they were read.
+///
+/// Edits are made to [`SourceFont::font`]. When saving, every dictionary
+/// entry and array element that the model writes the same as when the font
+/// was loaded is copied from the source, keeping its formatting and key
+/// order, and only the changed parts are written anew. Glyphs and layers are
+/// matched by name and ID, other array elements by position.
+#[derive(Clone, Debug)]
+pub struct SourceFont {
+    pub font: Font,
+    source: String,
+    span: Span,
+    /// The plist of the font as loaded, as the model writes it, for telling
+    /// which parts changed.
+    original: Option<Plist>,
+}
+
+impl SourceFont {
+    /// Load a font from a `.glyphs` file, keeping its source text.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<SourceFont, FontLoadError> {
+        let path = path.as_ref();
+        let load = || -> Result<SourceFont, FontLoadError> {
+            let bytes = fs::read(path)?;
+            let (text, encoding) = decode(&bytes, None)?;
+            let mut font: SourceFont = text.parse()?;
+            font.font.encoding = encoding;
+            Ok(font)
+        };
+        load().map_err(|e| e.in_file(path))
+    }
+
+    /// The font as [`Font::save`] would write it, with the unchanged parts
+    /// copied from the source.
+    ///
+    /// Fonts upgraded from Glyphs 2 are written anew in the Glyphs 3 format.
+    pub fn to_plist_string(&self) -> String {
+        let new = self.font.to_file_plist();
+        let Some(original) = &self.original else {
+            return new.to_string();
+        };
+        let mut out = String::new();
+        out.push_str(&self.source[..self.span.range.start]);
+        emit(&mut out, &self.source, &new, Some((original, &self.span)));
+        out.push_str(&self.source[self.span.range.end..]);
+        out
+    }
+
+    /// Save the font like [`Font::save`], with the unchanged parts copied
+    /// from the source.
+    pub fn save(&self, path: &std::path::Path) -> Result<(), FontSaveError> {
+        write_atomic(path, &self.to_plist_string(), false)?;
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for SourceFont {
+    type Err = FontLoadError;
+
+    /// Load a font from the contents of a `.glyphs` file, keeping them.
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let (mut plist, span) = Plist::parse_with_spans(source)?;
+        let upgraded_from = Font::upgrade_plist(&mut plist);
+        let mut font = Font::from_font_plist(plist)?;
+        font.upgraded_from = upgraded_from;
+        let original = upgraded_from.is_none().then(|| font.to_file_plist());
+        Ok(SourceFont {
+            font,
+            source: source.to_string(),
+            span,
+            original,
+        })
+    }
+}
+
+/// Write `new`, copying the parts that are the same in `old` from the
+/// source. `old` is what the model wrote for the value `new` replaces, and
+/// the span of that value in the source.
+fn emit(out: &mut String, source: &str, new: &Plist, old: Option<(&Plist, &Span)>) {
+    let Some((old, span)) = old else {
+        out.push_str(&new.to_string());
+        return;
+    };
+    if new == old {
+        out.push_str(&source[span.range.clone()]);
+        return;
+    }
+    match (new, old, &span.children) {
+        (Plist::Dictionary(new), Plist::Dictionary(old), SpanChildren::Dictionary(entries)) => {
+            // Entries that aren't in the source go before the first entry
+            // that sorts after them.
+            let mut added: Vec<&String> = new
+                .keys()
+                .filter(|k| !entries.iter().any(|(key, ..)| key == *k))
+                .collect();
+            added.sort();
+            let mut added = added.into_iter().peekable();
+
+            out.push_str("{\n");
+            for (key, entry, value_span) in entries {
+                while let Some(added_key) = added.next_if(|k| *k < key) {
+                    emit_entry(out, source, added_key, &new[added_key], None);
+                }
+                match (new.get(key), old.get(key)) {
+                    // Entries the model leaves out, as they have the default
+                    // value, are kept as they are.
+                    (None, None) => {
+                        out.push_str(&source[entry.clone()]);
+                        out.push('\n');
+                    }
+                    (None, Some(_)) => (),
+                    (Some(value), old) if Some(value) == old => {
+                        out.push_str(&source[entry.clone()]);
+                        out.push('\n');
+                    }
+                    (Some(value), old) => {
+                        let old = old.map(|old| (old, value_span));
+                        emit_entry(out, source, key, value, old);
+                    }
+                }
+            }
+            for added_key in added {
+                emit_entry(out, source, added_key, &new[added_key], None);
+            }
+            out.push('}');
+        }
+        (Plist::Array(new), Plist::Array(old), SpanChildren::Array(spans))
+            if !new.is_empty() && old.len() == spans.len() =>
+        {
+            out.push_str("(\n");
+            for (i, value) in new.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(",\n");
+                }
+                let j = match value.identity() {
+                    Some(id) => old.iter().position(|old| old.identity() == Some(id)),
+                    None => (i < old.len()).then_some(i),
+                };
+                emit(out, source, value, j.map(|j| (&old[j], &spans[j])));
+            }
+            out.push_str("\n)");
+        }
+        _ => out.push_str(&new.to_string()),
+    }
+}
+
+fn emit_entry(
+    out: &mut String,
+    source: &str,
+    key: &str,
+    value: &Plist,
+    old: Option<(&Plist, &Span)>,
+) {
+    escape_string(out, key);
+    out.push_str(" = ");
+    emit(out, source, value, old);
+    out.push_str(";\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged() {
+        let source = fs::read_to_string("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let font: SourceFont = source.parse().unwrap();
+        assert_eq!(
+            font.font,
+            Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap()
+        );
+        assert_eq!(font.to_plist_string(), source);
+    }
+
+    #[test]
+    fn edits() {
+        // Glyphs.app writes glyph keys in its own order, not sorted, and the
+        // source may be formatted differently.
+        let source = r#"{
+.appVersion = "3259";
+.formatVersion = 3;
+date = "2024-01-01 00:00:00 +0000";
+familyName = Test;
+fontMaster = (
+{
+id = m01;
+metricValues = ();
+name = Regular;
+}
+);
+glyphs = (
+{
+glyphname = A;
+layers = (
+{
+layerId = m01;
+width = 600;
+},
+{
+  layerId = other;
+  associatedMasterId = m01;
+  width = 600;
+}
+);
+unicode = 65;
+},
+{
+glyphname = B;
+layers = ({layerId = m01; width = 500;});
+unicode = 66;
+}
+);
+metrics = ();
+unitsPerEm = 1000;
+versionMajor = 1;
+versionMinor = 0;
+}
+"#;
+        let mut font: SourceFont = source.parse().unwrap();
+        let a = font.font.get_glyph_mut("A").unwrap();
+        a.layers[0].width = 650.0;
+        a.note = Some("wider".into());
+        font.font.glyphs.swap(0, 1);
+        font.font.family_name = "Renamed".into();
+
+        let expected = r#"{
+.appVersion = "3259";
+.formatVersion = 3;
+date = "2024-01-01 00:00:00 +0000";
+familyName = Renamed;
+fontMaster = (
+{
+id = m01;
+metricValues = ();
+name = Regular;
+}
+);
+glyphs = (
+{
+glyphname = B;
+layers = ({layerId = m01; width = 500;});
+unicode = 66;
+},
+{
+glyphname = A;
+layers = (
+{
+layerId = m01;
+width = 650;
+},
+{
+  layerId = other;
+  associatedMasterId = m01;
+  width = 600;
+}
+);
+note = wider;
+unicode = 65;
+}
+);
+metrics = ();
+unitsPerEm = 1000;
+versionMajor = 1;
+versionMinor = 0;
+}
+"#;
+        let saved = font.to_plist_string();
+        assert_eq!(saved, expected);
+        assert_eq!(saved.parse::<Font>().unwrap(), font.font);
+    }
+}
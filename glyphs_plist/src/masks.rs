@@ -0,0 +1,276 @@
+//! Evaluating the mask and fill attributes of paths, with the boolean
+//! operation supplied by the caller.
+//!
+//! A mask path cuts its area out of the paths before it in the same layer,
+//! including the layers of component glyphs. Components aren't cut by the
+//! masks of the layer they are placed in.
+
+use kurbo::{Affine, BezPath, PathEl};
+
+use crate::{Font, Layer, Path, Shape};
+
+/// A boolean path operation from a geometry library, used to cut masks out
+/// of outlines without this crate depending on one.
+pub trait MaskOp {
+    /// The area of `path` outside of `mask`, both filled under the nonzero
+    /// winding rule.
+    fn difference(&self, path: &BezPath, mask: &BezPath) -> BezPath;
+}
+
+impl<F: Fn(&BezPath, &BezPath) -> BezPath> MaskOp for F {
+    fn difference(&self, path: &BezPath, mask: &BezPath) -> BezPath {
+        self(path, mask)
+    }
+}
+
+impl Path {
+    /// Whether the path is a mask, cutting its area out of the shapes below
+    /// it rather than being drawn.
+    pub fn is_mask(&self) -> bool {
+        self.attr
+            .as_ref()
+            .and_then(|attr| attr.mask)
+            .is_some_and(|mask| mask != 0)
+    }
+
+    /// The area the path covers: the outline of its stroke if it has one,
+    /// together with its inside if it is closed and filled as well, or else
+    /// the path itself.
+    pub(crate) fn covered_bezpath(&self) -> BezPath {
+        let Some(attr) = &self.attr else {
+            return self.to_bezpath();
+        };
+        let Some(style) = attr.stroke_style() else {
+            return self.to_bezpath();
+        };
+        let mut bez = BezPath::new();
+        for outline in self.stroke(&style) {
+            bez.extend(outline.to_bezpath());
+        }
+        if self.closed && attr.fill.is_some_and(|fill| fill != 0) {
+            bez.extend(self.to_bezpath());
+        }
+        bez
+    }
+}
+
+impl Layer {
+    /// The outline of the layer as Glyphs.app shows it: components resolved
+    /// as in [`Layer::flattened_bezpath`], strokes expanded as in
+    /// [`Layer::with_strokes_expanded`], and mask paths cut out of the
+    /// paths before them with `op` instead of being drawn, in this layer and
+    /// the layers of its components.
+    ///
+    /// Open paths without a stroke aren't filled, so masks leave them alone.
+    /// Without masks, `op` isn't used.
+    pub fn masked_bezpath(&self, font: &Font, op: &impl MaskOp) -> BezPath {
+        let mut bez = BezPath::new();
+        self.flatten_into(font, Affine::IDENTITY, 0, Some(op), &mut bez);
+        bez
+    }
+
+    /// Replace the paths of a layer with mask paths by the outline of the
+    /// paths with the masks cut out, oriented like
+    /// [`Layer::correct_path_direction`] does for PostScript outlines.
+    /// Components are kept as they are. Layers without masks are left alone.
+    pub fn apply_masks(&mut self, op: &impl MaskOp) {
+        if !self.paths().any(Path::is_mask) {
+            return;
+        }
+        let mut paths = Some(masked_paths(self.paths(), op));
+        let shapes = std::mem::take(&mut self.shapes);
+        for shape in shapes {
+            match shape {
+                Shape::Path(_) => {
+                    // The outline takes the place of the first path.
+                    let paths = paths.take().into_iter().flatten();
+                    self.shapes
+                        .extend(paths.map(|path| Shape::Path(Box::new(path))));
+                }
+                Shape::Component(_) => self.shapes.push(shape),
+            }
+        }
+        self.correct_path_direction(true);
+    }
+}
+
+/// The area of the paths, with each mask cut out of the closed paths before
+/// it and left out itself. Closed paths come first, then open ones.
+pub(crate) fn masked_bezpath<'a>(
+    paths: impl IntoIterator<Item = &'a Path>,
+    op: &(impl MaskOp + ?Sized),
+) -> BezPath {
+    let mut outline = BezPath::new();
+    let mut open = BezPath::new();
+    for path in paths {
+        if path.is_mask() {
+            if !outline.elements().is_empty() {
+                outline = op.difference(&outline, &path.covered_bezpath());
+            }
+            continue;
+        }
+        let covered = path.covered_bezpath();
+        if covered.elements().last() == Some(&PathEl::ClosePath) {
+            outline.extend(covered);
+        } else {
+            open.extend(covered);
+        }
+    }
+    outline.extend(open);
+    outline
+}
+
+/// The paths of [`masked_bezpath`], as paths.
+pub(crate) fn masked_paths<'a>(
+    paths: impl IntoIterator<Item = &'a Path>,
+    op: &(impl MaskOp + ?Sized),
+) -> Vec<Path> {
+    Path::from_bezpath(&masked_bezpath(paths, op))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use kurbo::Shape as _;
+
+    use super::*;
+    use crate::font::PathAttrs;
+    use crate::{Component, Glyph, NodeType};
+
+    fn square(x: f64, y: f64, size: f64) -> Path {
+        let mut path = Path::new(true);
+        for (dx, dy) in [(size, 0.0), (size, size), (0.0, size), (0.0, 0.0)] {
+            path.add((x + dx, y + dy), NodeType::Line);
+        }
+        path
+    }
+
+    /// Stands in for a real difference by adding the reversed mask, which
+    /// is right for masks inside the outline.
+    fn cut_out(path: &BezPath, mask: &BezPath) -> BezPath {
+        let mut result = path.clone();
+        result.extend(mask.reverse_subpaths());
+        result
+    }
+
+    #[test]
+    fn masks() {
+        let font = Font::new();
+        let mut mask = square(25.0, 25.0, 50.0);
+        mask.attr = Some(PathAttrs {
+            mask: Some(1),
+            ..Default::default()
+        });
+        let mut layer = Layer::new("m01");
+        for path in [square(0.0, 0.0, 100.0), mask, square(200.0, 0.0, 100.0)] {
+            layer.shapes.push(Shape::Path(Box::new(path)));
+        }
+
+        let calls = Cell::new(0);
+        let op = |path: &BezPath, mask: &BezPath| {
+            calls.set(calls.get() + 1);
+            // Only the shapes before the mask are cut.
+            assert_eq!(path.bounding_box().x1, 100.0);
+            cut_out(path, mask)
+        };
+        let outline = layer.masked_bezpath(&font, &op);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(outline.area(), 100.0 * 100.0 - 50.0 * 50.0 + 100.0 * 100.0);
+
+        // Without a boolean operation, the mask is left out.
+        assert_eq!(layer.flattened_bezpath(&font).area(), 2.0 * 100.0 * 100.0);
+
+        layer.apply_masks(&cut_out);
+        assert_eq!(layer.shapes.len(), 3);
+        assert!(!layer.paths().any(Path::is_mask));
+
+        // Without masks, nothing changes.
+        let mut plain = Layer::new("m01");
+        plain
+            .shapes
+            .push(Shape::Path(Box::new(square(0.0, 0.0, 10.0))));
+        let unchanged = plain.clone();
+        plain.apply_masks(&|_: &BezPath, _: &BezPath| -> BezPath { unreachable!() });
+        assert_eq!(plain, unchanged);
+    }
+
+    #[test]
+    fn masks_in_components() {
+        let mut font = Font::new();
+        let mut mask = square(25.0, 25.0, 50.0);
+        mask.attr = Some(PathAttrs {
+            mask: Some(1),
+            ..Default::default()
+        });
+        let mut base = Glyph::new(norad::Name::new("base").unwrap(), None);
+        let mut base_layer = Layer::new("m01");
+        for path in [square(0.0, 0.0, 100.0), mask] {
+            base_layer.shapes.push(Shape::Path(Box::new(path)));
+        }
+        base.layers.push(base_layer);
+        font.glyphs.push(base);
+
+        let mut layer = Layer::new("m01");
+        layer.shapes.push(Shape::Component(Component {
+            reference: "base".into(),
+            rotation: None,
+            pos: Some((300.0, 0.0).into()),
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        }));
+        let mut mask = square(0.0, 0.0, 500.0);
+        mask.attr = Some(PathAttrs {
+            mask: Some(1),
+            ..Default::default()
+        });
+        layer
+            .shapes
+            .push(Shape::Path(Box::new(square(0.0, 0.0, 10.0))));
+        layer.shapes.push(Shape::Path(Box::new(mask)));
+
+        let outline = layer.masked_bezpath(&font, &cut_out);
+        // The component's own mask is cut out of it, the layer's mask isn't.
+        assert_eq!(
+            outline.area(),
+            10.0 * 10.0 - 500.0 * 500.0 + 100.0 * 100.0 - 50.0 * 50.0
+        );
+        assert_eq!(
+            layer.flattened_bezpath(&font).area(),
+            10.0 * 10.0 + 100.0 * 100.0
+        );
+
+        layer.apply_masks(&cut_out);
+        assert!(matches!(layer.shapes[0], Shape::Component(_)));
+        assert!(!layer.paths().any(Path::is_mask));
+    }
+
+    #[test]
+    fn filled_strokes() {
+        let mut path = square(0.0, 0.0, 100.0);
+        path.attr = Some(PathAttrs {
+            stroke_width: Some(10.0),
+            ..Default::default()
+        });
+        assert_eq!(
+            path.covered_bezpath()
+                .elements()
+                .iter()
+                .filter(|el| matches!(el, PathEl::MoveTo(_)))
+                .count(),
+            2
+        );
+        path.attr.as_mut().unwrap().fill = Some(1);
+        assert_eq!(
+            path.covered_bezpath()
+                .elements()
+                .iter()
+                .filter(|el| matches!(el, PathEl::MoveTo(_)))
+                .count(),
+            3
+        );
+    }
+}
@@ -0,0 +1,581 @@
+//! Adding and removing masters while keeping the glyph layers, kerning and
+//! the per-master value arrays consistent with the rest of the font, and
+//! editing the metrics, numbers and stems those arrays are coupled to.
+
+use thiserror::Error;
+
+use crate::{Font, FontMaster, FontNumbers, FontStems, Layer, MasterMetric, Metric, MetricType};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MasterError {
+    #[error("a master with the ID {0:?} already exists")]
+    DuplicateId(String),
+    #[error("no master with the ID {0:?}")]
+    UnknownMaster(String),
+    #[error("the last master can't be removed")]
+    LastMaster,
+    #[error("the font already has a {kind} named {name:?}")]
+    DuplicateName { kind: &'static str, name: String },
+    #[error("the font has no {kind} named {name:?}")]
+    UnknownName { kind: &'static str, name: String },
+    #[error("master has {found} {field}, but the font has {expected}")]
+    LengthMismatch {
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Font {
+    /// Add a master to the font, along with a master layer for every glyph
+    /// and its kerning.
+    ///
+    /// The layers and kerning are copied from the `template` master if given;
+    /// otherwise the glyphs get empty layers and the master no kerning.
+    ///
+    /// The master's metric, number and stem values are index-coupled to the
+    /// font's metrics, numbers and stems: missing trailing values are filled
+    /// with zeros, but there may not be more values than the font has
+    /// entries. Its axis values must match the font's axes exactly.
+    pub fn add_master(
+        &mut self,
+        mut master: FontMaster,
+        template: Option<&str>,
+    ) -> Result<(), MasterError> {
+        if self.font_master.iter().any(|m| m.id == master.id) {
+            return Err(MasterError::DuplicateId(master.id.into()));
+        }
+        if let Some(template) = template {
+            if !self.font_master.iter().any(|m| m.id == template) {
+                return Err(MasterError::UnknownMaster(template.to_string()));
+            }
+        }
+
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let axis_value_count = master.axes_values.as_ref().map_or(0, Vec::len);
+        if axis_value_count != axis_count {
+            return Err(MasterError::LengthMismatch {
+                field: "axis values",
+                expected: axis_count,
+                found: axis_value_count,
+            });
+        }
+        pad_values(
+            &mut master.metric_values,
+            self.metrics.len(),
+            "metric values",
+        )?;
+        let number_count = self.numbers.as_ref().map_or(0, Vec::len);
+        if let Some(values) = &mut master.number_values {
+            pad_values(values, number_count, "number values")?;
+        } else if number_count > 0 {
+            master.number_values = Some(vec![0.0; number_count]);
+        }
+        let stem_count = self.stems.as_ref().map_or(0, Vec::len);
+        if let Some(values) = &mut master.stem_values {
+            pad_values(values, stem_count, "stem values")?;
+        } else if stem_count > 0 {
+            master.stem_values = Some(vec![0.0; stem_count]);
+        }
+
+        for glyph in &mut self.glyphs {
+            let layer = match template.and_then(|id| glyph.master_layer(id)) {
+                Some(layer) => Layer {
+                    layer_id: master.id.clone(),
+                    ..layer.clone()
+                },
+                None => Layer::new(master.id.clone()),
+            };
+            glyph.layers.push(layer);
+        }
+        if let Some(template) = template {
+            for kerning in [
+                &mut self.kerning_ltr,
+                &mut self.kerning_rtl,
+                &mut self.kerning_vertical,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if let Some(template_kerning) = kerning.get(template).cloned() {
+                    kerning.insert(master.id.to_string(), template_kerning);
+                }
+            }
+        }
+        self.font_master.push(master);
+        Ok(())
+    }
+
+    /// Remove a master from the font, along with its kerning and every glyph
+    /// layer that belongs to it, including its brace, bracket and color
+    /// layers.
+    pub fn remove_master(&mut self, master_id: &str) -> Result<FontMaster, MasterError> {
+        let index = self
+            .font_master
+            .iter()
+            .position(|m| m.id == master_id)
+            .ok_or_else(|| MasterError::UnknownMaster(master_id.to_string()))?;
+        if self.font_master.len() == 1 {
+            return Err(MasterError::LastMaster);
+        }
+
+        for glyph in &mut self.glyphs {
+            glyph.layers.retain(|layer| {
+                layer.layer_id != master_id
+                    && layer.associated_master_id.as_deref() != Some(master_id)
+            });
+        }
+        for kerning in [
+            &mut self.kerning_ltr,
+            &mut self.kerning_rtl,
+            &mut self.kerning_vertical,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            kerning.remove(master_id);
+        }
+        Ok(self.font_master.remove(index))
+    }
+
+    /// Set the position and overshoot of a master's metric of the given
+    /// type, the one that applies to all glyphs.
+    ///
+    /// If the font has no such metric yet, it is added after the others.
+    /// The metric values of every master are padded with zeros to the
+    /// font's metrics, so that they stay index-coupled; a master with more
+    /// values than the font has metrics is an error, and nothing changes.
+    pub fn set_metric(
+        &mut self,
+        master_id: &str,
+        metric_type: MetricType,
+        pos: f64,
+        over: f64,
+    ) -> Result<(), MasterError> {
+        let master_index = self
+            .font_master
+            .iter()
+            .position(|m| m.id == master_id)
+            .ok_or_else(|| MasterError::UnknownMaster(master_id.to_string()))?;
+        if let Some(master) = self
+            .font_master
+            .iter()
+            .find(|m| m.metric_values.len() > self.metrics.len())
+        {
+            return Err(MasterError::LengthMismatch {
+                field: "metric values",
+                expected: self.metrics.len(),
+                found: master.metric_values.len(),
+            });
+        }
+
+        let index = match self
+            .metrics
+            .iter()
+            .position(|m| m.filter.is_none() && m.r#type.as_ref() == Some(&metric_type))
+        {
+            Some(index) => index,
+            None => {
+                self.metrics.push(Metric {
+                    filter: None,
+                    name: None,
+                    r#type: Some(metric_type),
+                });
+                self.metrics.len() - 1
+            }
+        };
+        for master in &mut self.font_master {
+            pad_values(
+                &mut master.metric_values,
+                self.metrics.len(),
+                "metric values",
+            )?;
+        }
+        self.font_master[master_index].metric_values[index] = MasterMetric { pos, over };
+        Ok(())
+    }
+
+    /// Add a number to the font, with a value of zero for every master.
+    pub fn add_number(&mut self, name: impl Into<String>) -> Result<(), MasterError> {
+        let name = name.into();
+        if self.numbers.iter().flatten().any(|n| n.name == name) {
+            return Err(MasterError::DuplicateName {
+                kind: "number",
+                name,
+            });
+        }
+        let count = self.numbers.as_ref().map_or(0, Vec::len);
+        self.append_coupled_value(count, "number values", |m| &mut m.number_values)?;
+        self.numbers
+            .get_or_insert_with(Vec::new)
+            .push(FontNumbers { name });
+        Ok(())
+    }
+
+    /// Add a stem to the font, with a value of zero for every master.
+    pub fn add_stem(
+        &mut self,
+        name: impl Into<String>,
+        horizontal: bool,
+    ) -> Result<(), MasterError> {
+        let name = name.into();
+        if self.stems.iter().flatten().any(|s| s.name == name) {
+            return Err(MasterError::DuplicateName { kind: "stem", name });
+        }
+        let count = self.stems.as_ref().map_or(0, Vec::len);
+        self.append_coupled_value(count, "stem values", |m| &mut m.stem_values)?;
+        self.stems.get_or_insert_with(Vec::new).push(FontStems {
+            name,
+            filter: None,
+            horizontal,
+        });
+        Ok(())
+    }
+
+    /// Remove a number from the font, along with its value in every master.
+    pub fn remove_number(&mut self, name: &str) -> Result<FontNumbers, MasterError> {
+        let index = self.number_index(name)?;
+        self.remove_coupled_value(index, |m| &mut m.number_values);
+        Ok(self.numbers.as_mut().unwrap().remove(index))
+    }
+
+    /// Remove a stem from the font, along with its value in every master.
+    pub fn remove_stem(&mut self, name: &str) -> Result<FontStems, MasterError> {
+        let index = self.stem_index(name)?;
+        self.remove_coupled_value(index, |m| &mut m.stem_values);
+        Ok(self.stems.as_mut().unwrap().remove(index))
+    }
+
+    /// Set a master's value for the number of the given name, padding its
+    /// number values with zeros up to it.
+    pub fn set_number_value(
+        &mut self,
+        master_id: &str,
+        name: &str,
+        value: f64,
+    ) -> Result<(), MasterError> {
+        let index = self.number_index(name)?;
+        let master = self.master_mut(master_id)?;
+        set_coupled_value(&mut master.number_values, index, value);
+        Ok(())
+    }
+
+    /// Set a master's value for the stem of the given name, padding its stem
+    /// values with zeros up to it.
+    pub fn set_stem_value(
+        &mut self,
+        master_id: &str,
+        name: &str,
+        value: f64,
+    ) -> Result<(), MasterError> {
+        let index = self.stem_index(name)?;
+        let master = self.master_mut(master_id)?;
+        set_coupled_value(&mut master.stem_values, index, value);
+        Ok(())
+    }
+
+    fn number_index(&self, name: &str) -> Result<usize, MasterError> {
+        self.numbers
+            .iter()
+            .flatten()
+            .position(|n| n.name == name)
+            .ok_or_else(|| MasterError::UnknownName {
+                kind: "number",
+                name: name.to_string(),
+            })
+    }
+
+    fn stem_index(&self, name: &str) -> Result<usize, MasterError> {
+        self.stems
+            .iter()
+            .flatten()
+            .position(|s| s.name == name)
+            .ok_or_else(|| MasterError::UnknownName {
+                kind: "stem",
+                name: name.to_string(),
+            })
+    }
+
+    fn master_mut(&mut self, master_id: &str) -> Result<&mut FontMaster, MasterError> {
+        self.font_master
+            .iter_mut()
+            .find(|m| m.id == master_id)
+            .ok_or_else(|| MasterError::UnknownMaster(master_id.to_string()))
+    }
+
+    /// Pad the values of every master to `count` and add a zero for a new
+    /// entry after them, unless some master has too many values already.
+    fn append_coupled_value(
+        &mut self,
+        count: usize,
+        field: &'static str,
+        values: fn(&mut FontMaster) -> &mut Option<Vec<f64>>,
+    ) -> Result<(), MasterError> {
+        for master in &mut self.font_master {
+            let found = values(master).as_ref().map_or(0, Vec::len);
+            if found > count {
+                return Err(MasterError::LengthMismatch {
+                    field,
+                    expected: count,
+                    found,
+                });
+            }
+        }
+        for master in &mut self.font_master {
+            values(master)
+                .get_or_insert_with(Vec::new)
+                .resize(count + 1, 0.0);
+        }
+        Ok(())
+    }
+
+    fn remove_coupled_value(
+        &mut self,
+        index: usize,
+        values: fn(&mut FontMaster) -> &mut Option<Vec<f64>>,
+    ) {
+        for master in &mut self.font_master {
+            if let Some(values) = values(master) {
+                if index < values.len() {
+                    values.remove(index);
+                }
+            }
+        }
+    }
+}
+
+impl FontMaster {
+    /// The master's value for the font's number of the given name.
+    pub fn number_value(&self, font: &Font, name: &str) -> Option<f64> {
+        let index = font.number_index(name).ok()?;
+        self.number_values.as_ref()?.get(index).copied()
+    }
+
+    /// The master's value for the font's stem of the given name.
+    pub fn stem_value(&self, font: &Font, name: &str) -> Option<f64> {
+        let index = font.stem_index(name).ok()?;
+        self.stem_values.as_ref()?.get(index).copied()
+    }
+}
+
+fn set_coupled_value(values: &mut Option<Vec<f64>>, index: usize, value: f64) {
+    let values = values.get_or_insert_with(Vec::new);
+    if values.len() <= index {
+        values.resize(index + 1, 0.0);
+    }
+    values[index] = value;
+}
+
+/// Fill in missing trailing values with their default.
+fn pad_values<T: Default>(
+    values: &mut Vec<T>,
+    expected: usize,
+    field: &'static str,
+) -> Result<(), MasterError> {
+    if values.len() > expected {
+        return Err(MasterError::LengthMismatch {
+            field,
+            expected,
+            found: values.len(),
+        });
+    }
+    values.resize_with(expected, T::default);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MasterError;
+    use crate::{Font, FontMaster, MasterMetric, MetricType};
+
+    #[test]
+    fn add_and_remove_master() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let original = font.clone();
+
+        let master = FontMaster {
+            axes_values: Some(vec![400.0, 0.0, 0.0]),
+            ..FontMaster::new("new", "Regular")
+        };
+        font.add_master(master, Some("m01")).unwrap();
+
+        let added = font.font_master.last().unwrap();
+        assert_eq!(added.metric_values.len(), font.metrics.len());
+        assert_eq!(
+            added.number_values.as_ref().unwrap().len(),
+            font.numbers.as_ref().unwrap().len()
+        );
+        assert_eq!(
+            added.stem_values.as_ref().unwrap().len(),
+            font.stems.as_ref().unwrap().len()
+        );
+        for glyph in &font.glyphs {
+            let layer = glyph.master_layer("new").unwrap();
+            match glyph.master_layer("m01") {
+                Some(template) => assert_eq!(layer.shapes, template.shapes),
+                None => assert!(layer.shapes.is_empty()),
+            }
+        }
+        assert_eq!(font.kern_value("new", "A", "B"), Some(30.0));
+
+        font.remove_master("new").unwrap();
+        assert_eq!(font, original);
+
+        // Removing a master takes its other layers with it.
+        font.remove_master("m01").unwrap();
+        assert!(font
+            .glyphs
+            .iter()
+            .flat_map(|g| &g.layers)
+            .all(|l| l.layer_id != "m01" && l.associated_master_id.as_deref() != Some("m01")));
+        assert!(!font.kerning_ltr.as_ref().unwrap().contains_key("m01"));
+    }
+
+    #[test]
+    fn master_errors() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        let master = |id: &str| FontMaster {
+            axes_values: Some(vec![400.0, 0.0, 0.0]),
+            ..FontMaster::new(id, "Regular")
+        };
+        assert_eq!(
+            font.add_master(master("m01"), None),
+            Err(MasterError::DuplicateId("m01".into()))
+        );
+        assert_eq!(
+            font.add_master(master("new"), Some("unknown")),
+            Err(MasterError::UnknownMaster("unknown".into()))
+        );
+        assert_eq!(
+            font.add_master(FontMaster::new("new", "Regular"), None),
+            Err(MasterError::LengthMismatch {
+                field: "axis values",
+                expected: 3,
+                found: 0,
+            })
+        );
+        let too_many_stems = FontMaster {
+            stem_values: Some(vec![0.0; 10]),
+            ..master("new")
+        };
+        assert!(matches!(
+            font.add_master(too_many_stems, None),
+            Err(MasterError::LengthMismatch {
+                field: "stem values",
+                ..
+            })
+        ));
+
+        let mut font = Font::new();
+        font.font_master = vec![FontMaster::new("only", "Regular")];
+        assert_eq!(font.remove_master("only"), Err(MasterError::LastMaster));
+        assert_eq!(
+            font.remove_master("unknown"),
+            Err(MasterError::UnknownMaster("unknown".into()))
+        );
+    }
+
+    #[test]
+    fn set_metric() {
+        let mut font = Font::default();
+        let second = FontMaster {
+            metric_values: Vec::new(),
+            ..FontMaster::new("m02", "Bold")
+        };
+        font.add_master(second, None).unwrap();
+
+        font.set_metric("m01", MetricType::Ascender, 750.0, 12.0)
+            .unwrap();
+        assert_eq!(font.metrics.len(), 3);
+        assert_eq!(
+            font.font_master[0].resolved_metrics(&font).ascender(),
+            Some(750.0)
+        );
+
+        font.set_metric("m02", MetricType::XHeight, 500.0, 10.0)
+            .unwrap();
+        assert_eq!(font.metrics.len(), 4);
+        assert_eq!(font.metrics[3].r#type, Some(MetricType::XHeight));
+        for master in &font.font_master {
+            assert_eq!(master.metric_values.len(), 4);
+        }
+        assert_eq!(
+            font.font_master[1].metric_values[3],
+            MasterMetric {
+                pos: 500.0,
+                over: 10.0
+            }
+        );
+        assert_eq!(
+            font.font_master[0].metric_values[3],
+            MasterMetric::default()
+        );
+
+        assert_eq!(
+            font.set_metric("unknown", MetricType::XHeight, 0.0, 0.0),
+            Err(MasterError::UnknownMaster("unknown".into()))
+        );
+        font.font_master[0]
+            .metric_values
+            .push(MasterMetric::default());
+        let broken = font.clone();
+        assert!(font
+            .set_metric("m01", MetricType::CapHeight, 700.0, 0.0)
+            .is_err());
+        assert_eq!(font, broken);
+    }
+
+    #[test]
+    fn numbers_and_stems() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let stem_count = font.stems.as_ref().unwrap().len();
+
+        font.add_stem("H stem", false).unwrap();
+        font.set_stem_value("m01", "H stem", 88.0).unwrap();
+        let master = &font.font_master[0];
+        assert_eq!(master.stem_value(&font, "H stem"), Some(88.0));
+        assert_eq!(font.font_master[1].stem_value(&font, "H stem"), Some(0.0));
+        for master in &font.font_master {
+            assert_eq!(master.stem_values.as_ref().unwrap().len(), stem_count + 1);
+        }
+        assert_eq!(
+            font.add_stem("H stem", true),
+            Err(MasterError::DuplicateName {
+                kind: "stem",
+                name: "H stem".into()
+            })
+        );
+
+        let first = font.stems.as_ref().unwrap()[0].name.clone();
+        let second_value = font.font_master[0].stem_values.as_ref().unwrap()[1];
+        assert_eq!(font.remove_stem(&first).unwrap().name, first);
+        assert_eq!(
+            font.font_master[0].stem_values.as_ref().unwrap()[0],
+            second_value
+        );
+        assert_eq!(font.font_master[0].stem_value(&font, "H stem"), Some(88.0));
+
+        let mut font = Font::default();
+        font.add_number("spacing").unwrap();
+        assert_eq!(font.font_master[0].number_values, Some(vec![0.0]));
+        font.set_number_value("m01", "spacing", 12.0).unwrap();
+        assert_eq!(
+            font.font_master[0].number_value(&font, "spacing"),
+            Some(12.0)
+        );
+        assert_eq!(
+            font.set_number_value("m02", "spacing", 1.0),
+            Err(MasterError::UnknownMaster("m02".into()))
+        );
+        font.remove_number("spacing").unwrap();
+        assert_eq!(font.font_master[0].number_values, Some(vec![]));
+        assert_eq!(
+            font.remove_number("spacing"),
+            Err(MasterError::UnknownName {
+                kind: "number",
+                name: "spacing".into()
+            })
+        );
+    }
+}
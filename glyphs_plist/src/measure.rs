@@ -0,0 +1,103 @@
+//! Measuring outlines along lines, like the measurement tool of Glyphs.app.
+
+use kurbo::{Line, ParamCurve, Point};
+
+use crate::{Font, Layer};
+
+/// Intersections closer than this, in font units, are one: a line through a
+/// node crosses both segments meeting there.
+const SAME_POINT: f64 = 1e-6;
+
+impl Layer {
+    /// The points where the line from `p0` to `p1` crosses the outline of
+    /// the layer, with components resolved as in
+    /// [`Layer::flattened_bezpath`], ordered from `p0`.
+    ///
+    /// The distances between consecutive points are what the measurement
+    /// tool shows: alternately the thickness of the strokes and the space
+    /// between them.
+    pub fn intersections_with_line(
+        &self,
+        p0: impl Into<Point>,
+        p1: impl Into<Point>,
+        font: &Font,
+    ) -> Vec<Point> {
+        let line = Line::new(p0, p1);
+        let mut hits: Vec<(f64, Point)> = self
+            .flattened_bezpath(font)
+            .segments()
+            .flat_map(|segment| {
+                segment
+                    .intersect_line(line)
+                    .into_iter()
+                    .map(move |hit| (hit.line_t, segment.eval(hit.segment_t)))
+            })
+            .collect();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut points: Vec<Point> = Vec::with_capacity(hits.len());
+        for (_, point) in hits {
+            if !points
+                .last()
+                .is_some_and(|last| last.distance(point) <= SAME_POINT)
+            {
+                points.push(point);
+            }
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Component, Glyph, NodeType, Path, Shape};
+
+    fn square(x: f64, y: f64, size: f64) -> Path {
+        let mut path = Path::new(true);
+        for (dx, dy) in [(size, 0.0), (size, size), (0.0, size), (0.0, 0.0)] {
+            path.add((x + dx, y + dy), NodeType::Line);
+        }
+        path
+    }
+
+    #[test]
+    fn intersections() {
+        let mut font = Font::default();
+        let mut part = Glyph::new(norad::Name::new("part").unwrap(), None);
+        let mut layer = Layer::new("m01");
+        layer
+            .shapes
+            .push(Shape::Path(Box::new(square(0.0, 0.0, 50.0))));
+        part.layers.push(layer);
+        font.glyphs.push(part);
+
+        let mut layer = Layer::new("m01");
+        layer
+            .shapes
+            .push(Shape::Path(Box::new(square(0.0, 0.0, 100.0))));
+        layer.shapes.push(Shape::Component(Component {
+            reference: "part".into(),
+            rotation: None,
+            pos: Some((200.0, 0.0).into()),
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        }));
+
+        let points = layer.intersections_with_line((300.0, 25.0), (-10.0, 25.0), &font);
+        let xs: Vec<f64> = points.iter().map(|p| p.x).collect();
+        assert_eq!(xs, [250.0, 200.0, 100.0, 0.0]);
+        assert!(points.iter().all(|p| p.y == 25.0));
+
+        // Through a corner, the two segments meeting there give one point.
+        let points = layer.intersections_with_line((-10.0, -10.0), (50.0, 50.0), &font);
+        assert_eq!(points, [Point::ZERO]);
+
+        // The line ends before reaching the outline.
+        assert!(layer
+            .intersections_with_line((110.0, 50.0), (150.0, 50.0), &font)
+            .is_empty());
+    }
+}
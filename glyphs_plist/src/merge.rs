@@ -0,0 +1,405 @@
+//! Importing glyphs and their kerning from another font.
+
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::kerning::{FIRST_GROUP_PREFIX, SECOND_GROUP_PREFIX};
+use crate::rename::{metrics_keys_mut, rename_in_metrics_key};
+use crate::{Font, FontMaster, Glyph, Id, Shape};
+
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("no master in the other font matches master {0:?}")]
+    IncompatibleMasters(String),
+    #[error("masters {0:?} and {1:?} both match master {2:?} in the other font")]
+    AmbiguousMasters(String, String, String),
+    #[error("no glyph named {0:?} in the other font")]
+    UnknownGlyph(String),
+    #[error("bad glyph name: {0}")]
+    Naming(#[from] norad::error::NamingError),
+}
+
+/// What to do with an imported glyph that has the name of an existing one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the existing glyph and don't import the other one.
+    #[default]
+    Skip,
+    /// Replace the existing glyph with the imported one.
+    Overwrite,
+    /// Import the glyph under a new name with a numeric suffix, like
+    /// `A.001`, without its Unicode values.
+    Rename,
+}
+
+/// Options controlling how glyphs are imported by [`Font::merge`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergeOptions {
+    /// The names of the glyphs to import, or `None` to import all of them.
+    pub glyphs: Option<Vec<String>>,
+    /// What to do with glyphs that already exist.
+    pub conflict: ConflictPolicy,
+}
+
+/// The glyphs and kerning imported by [`Font::merge`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergeReport {
+    /// Glyphs that were new to the font.
+    pub added: Vec<String>,
+    /// Existing glyphs that were replaced.
+    pub overwritten: Vec<String>,
+    /// Glyphs that weren't imported because they already exist.
+    pub skipped: Vec<String>,
+    /// Glyphs that were imported under a new name, as pairs of the old and
+    /// the new name.
+    pub renamed: Vec<(String, String)>,
+    /// The number of kerning pairs that were imported, over all masters and
+    /// directions.
+    pub kerning_pairs: usize,
+}
+
+impl Font {
+    /// Import glyphs from another font with compatible masters, along with
+    /// their kerning groups and kerning.
+    ///
+    /// Every master of this font needs a counterpart in the other font,
+    /// matched by ID, then by location, then by name. Imported glyphs keep
+    /// only the layers of matched masters.
+    ///
+    /// A kerning pair is imported if one of its sides is an imported glyph
+    /// or one of its groups, and both sides exist in the merged font. Existing
+    /// pairs are only replaced with [`ConflictPolicy::Overwrite`].
+    pub fn merge(
+        &mut self,
+        other: &Font,
+        options: &MergeOptions,
+    ) -> Result<MergeReport, MergeError> {
+        let master_ids = self.matching_masters(other)?;
+        let glyphs = match &options.glyphs {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    other
+                        .get_glyph(name)
+                        .ok_or_else(|| MergeError::UnknownGlyph(name.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => other.glyphs.iter().collect(),
+        };
+
+        let mut report = MergeReport::default();
+        let mut renames = HashMap::new();
+        let mut taken: HashSet<String> = HashSet::new();
+        let mut imported = Vec::new();
+        for glyph in glyphs {
+            let name = glyph.glyphname.to_string();
+            if self.contains_glyph(&name) {
+                match options.conflict {
+                    ConflictPolicy::Skip => {
+                        report.skipped.push(name);
+                        continue;
+                    }
+                    ConflictPolicy::Overwrite => report.overwritten.push(name.clone()),
+                    ConflictPolicy::Rename => {
+                        let new_name = (1..)
+                            .map(|i| format!("{name}.{i:03}"))
+                            .find(|n| {
+                                !self.contains_glyph(n)
+                                    && !other.contains_glyph(n)
+                                    && !taken.contains(n)
+                            })
+                            .unwrap();
+                        taken.insert(new_name.clone());
+                        report.renamed.push((name.clone(), new_name.clone()));
+                        renames.insert(name.clone(), new_name);
+                    }
+                }
+            } else {
+                report.added.push(name.clone());
+            }
+            imported.push((name, glyph));
+        }
+
+        let imported_names: HashSet<&str> = imported.iter().map(|(n, _)| n.as_str()).collect();
+        let imported_groups: HashSet<&str> =
+            imported.iter().flat_map(|(_, g)| kern_groups(g)).collect();
+        let mut new_glyphs = Vec::new();
+        for (name, glyph) in &imported {
+            let mut glyph = Glyph {
+                layers: glyph
+                    .layers
+                    .iter()
+                    .filter_map(|layer| {
+                        let mut layer = layer.clone();
+                        match &layer.associated_master_id {
+                            Some(master_id) => {
+                                layer.associated_master_id =
+                                    Some(master_ids.get(master_id.as_str())?.clone());
+                            }
+                            None => {
+                                layer.layer_id = master_ids.get(layer.layer_id.as_str())?.clone();
+                            }
+                        }
+                        Some(layer)
+                    })
+                    .collect(),
+                ..(*glyph).clone()
+            };
+            if let Some(new_name) = renames.get(name) {
+                glyph.glyphname = norad::Name::new(new_name)?;
+                glyph.unicode = None;
+            }
+            for key in metrics_keys_mut(&mut glyph) {
+                rename_in_metrics_key(key, &renames);
+            }
+            for layer in &mut glyph.layers {
+                let background_shapes = layer.background.iter_mut().flat_map(|b| &mut b.shapes);
+                for shape in layer.shapes.iter_mut().chain(background_shapes) {
+                    if let Shape::Component(component) = shape {
+                        if let Some(new_name) = renames.get(&component.reference) {
+                            component.reference.clone_from(new_name);
+                        }
+                    }
+                }
+            }
+            new_glyphs.push(glyph);
+        }
+        for glyph in new_glyphs {
+            match self
+                .glyphs
+                .iter_mut()
+                .find(|g| g.glyphname == glyph.glyphname)
+            {
+                Some(existing) => *existing = glyph,
+                None => self.glyphs.push(glyph),
+            }
+        }
+
+        let groups: HashSet<&str> = self.glyphs.iter().flat_map(kern_groups).collect();
+        let mut pairs = Vec::new();
+        for (direction, kerning) in [
+            &other.kerning_ltr,
+            &other.kerning_rtl,
+            &other.kerning_vertical,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            for (other_id, master_kerning) in kerning.iter().flatten() {
+                let Some(master_id) = master_ids.get(other_id.as_str()) else {
+                    continue;
+                };
+                for (first, seconds) in master_kerning {
+                    for (second, value) in seconds {
+                        let sides = [first.as_str(), second.as_str()];
+                        let is_associated = sides.iter().any(|side| match group_name(side) {
+                            Some(group) => imported_groups.contains(group),
+                            None => imported_names.contains(side),
+                        });
+                        let exists = sides.iter().all(|side| match group_name(side) {
+                            Some(group) => groups.contains(group),
+                            None => imported_names.contains(side) || self.contains_glyph(side),
+                        });
+                        if is_associated && exists {
+                            let [first, second] = sides.map(|side| {
+                                renames
+                                    .get(side)
+                                    .map(String::as_str)
+                                    .unwrap_or(side)
+                                    .to_string()
+                            });
+                            pairs.push((direction, master_id.clone(), first, second, *value));
+                        }
+                    }
+                }
+            }
+        }
+        for (direction, master_id, first, second, value) in pairs {
+            let kerning = [
+                &mut self.kerning_ltr,
+                &mut self.kerning_rtl,
+                &mut self.kerning_vertical,
+            ]
+            .into_iter()
+            .nth(direction)
+            .unwrap();
+            let seconds = kerning
+                .get_or_insert_with(Default::default)
+                .entry(master_id.to_string())
+                .or_default()
+                .entry(norad::Name::new(&first)?)
+                .or_default();
+            let second = norad::Name::new(&second)?;
+            if options.conflict == ConflictPolicy::Overwrite || !seconds.contains_key(&second) {
+                seconds.insert(second, value);
+                report.kerning_pairs += 1;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Map the master IDs of another font to the IDs of the masters they
+    /// match in this one. Each master of the other font can match only one.
+    fn matching_masters<'a>(&self, other: &'a Font) -> Result<HashMap<&'a str, Id>, MergeError> {
+        let matches: [fn(&FontMaster, &FontMaster) -> bool; 3] = [
+            |a, b| a.id == b.id,
+            |a, b| a.axes_values.is_some() && a.axes_values == b.axes_values,
+            |a, b| a.name == b.name,
+        ];
+        let mut master_ids = HashMap::new();
+        for master in &self.font_master {
+            let other_master = matches
+                .iter()
+                .find_map(|matches| other.font_master.iter().find(|m| matches(master, m)))
+                .ok_or_else(|| MergeError::IncompatibleMasters(master.name.clone()))?;
+            if let Some(id) = master_ids.insert(other_master.id.as_str(), master.id.clone()) {
+                let name = |id: &Id| {
+                    let master = self.font_master.iter().find(|m| &m.id == id);
+                    master.map_or_else(|| id.to_string(), |m| m.name.clone())
+                };
+                return Err(MergeError::AmbiguousMasters(
+                    name(&id),
+                    master.name.clone(),
+                    other_master.name.clone(),
+                ));
+            }
+        }
+        Ok(master_ids)
+    }
+}
+
+/// The kerning groups a glyph belongs to, in any direction.
+fn kern_groups(glyph: &Glyph) -> impl Iterator<Item = &str> {
+    [
+        &glyph.kern_left,
+        &glyph.kern_right,
+        &glyph.kern_top,
+        &glyph.kern_bottom,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|group| group.as_str())
+}
+
+/// The name of the group a kerning side refers to, if it is a group.
+fn group_name(side: &str) -> Option<&str> {
+    side.strip_prefix(FIRST_GROUP_PREFIX)
+        .or_else(|| side.strip_prefix(SECOND_GROUP_PREFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConflictPolicy, MergeError, MergeOptions, MergeReport};
+    use crate::{Font, FontMaster, Shape};
+
+    fn options(glyphs: &[&str], conflict: ConflictPolicy) -> MergeOptions {
+        MergeOptions {
+            glyphs: Some(glyphs.iter().map(|g| g.to_string()).collect()),
+            conflict,
+        }
+    }
+
+    #[test]
+    fn merge_glyphs() {
+        let source = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut font = source.clone();
+        font.remove_glyph_cascading("A").unwrap();
+        font.remove_glyph_cascading("Ä").unwrap();
+
+        let report = font
+            .merge(&source, &options(&["A", "Ä", "B"], ConflictPolicy::Skip))
+            .unwrap();
+        assert_eq!(
+            report,
+            MergeReport {
+                added: vec!["A".into(), "Ä".into()],
+                skipped: vec!["B".into()],
+                kerning_pairs: 4,
+                ..Default::default()
+            }
+        );
+        assert_eq!(font.get_glyph("A"), source.get_glyph("A"));
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(30.0));
+
+        let report = font
+            .merge(&source, &options(&["A", "Ä"], ConflictPolicy::Rename))
+            .unwrap();
+        assert_eq!(
+            report.renamed,
+            [
+                ("A".to_string(), "A.001".to_string()),
+                ("Ä".to_string(), "Ä.001".to_string())
+            ]
+        );
+        let a = font.get_glyph("A.001").unwrap();
+        assert_eq!(a.unicode, None);
+        let Shape::Component(component) = &font.get_glyph("Ä.001").unwrap().layers[0].shapes[0]
+        else {
+            panic!("expected a component");
+        };
+        assert_eq!(component.reference, "A.001");
+        assert_eq!(font.kern_value("m01", "A.001", "B"), Some(30.0));
+
+        font.set_kerning("m01", "A", "B", 0.0).unwrap();
+        let report = font
+            .merge(&source, &options(&["A"], ConflictPolicy::Overwrite))
+            .unwrap();
+        assert_eq!(report.overwritten, ["A"]);
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(30.0));
+    }
+
+    #[test]
+    fn merge_masters() {
+        let source = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        // Masters are matched by location when the IDs differ.
+        let mut font = source.clone();
+        font.remove_glyph_cascading("A").unwrap();
+        for master in &mut font.font_master {
+            let id = crate::Id::from(format!("new-{}", master.id));
+            for glyph in &mut font.glyphs {
+                for layer in &mut glyph.layers {
+                    if layer.layer_id == master.id {
+                        layer.layer_id.clone_from(&id);
+                    }
+                }
+            }
+            master.id = id;
+        }
+        font.merge(&source, &options(&["A"], ConflictPolicy::Skip))
+            .unwrap();
+        let a = font.get_glyph("A").unwrap();
+        assert_eq!(
+            a.master_layer("new-m01").unwrap().shapes,
+            source
+                .get_glyph("A")
+                .unwrap()
+                .master_layer("m01")
+                .unwrap()
+                .shapes
+        );
+        assert_eq!(font.kern_value("new-m01", "A", "B"), Some(30.0));
+
+        let mut font = Font::new();
+        font.font_master = vec![FontMaster::new("other", "Other")];
+        assert!(matches!(
+            font.merge(&source, &MergeOptions::default()),
+            Err(MergeError::IncompatibleMasters(name)) if name == "Other"
+        ));
+        let mut font = source.clone();
+        let mut master = font.font_master[0].clone();
+        (master.id, master.name) = ("copy".into(), "Copy".into());
+        font.font_master.push(master);
+        assert!(matches!(
+            font.merge(&source, &MergeOptions::default()),
+            Err(MergeError::AmbiguousMasters(first, second, _))
+                if first == font.font_master[0].name && second == "Copy"
+        ));
+        let mut font = source.clone();
+        assert!(matches!(
+            font.merge(&source, &options(&["missing"], ConflictPolicy::Skip)),
+            Err(MergeError::UnknownGlyph(name)) if name == "missing"
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+//! The names instances are exported with.
+
+use std::collections::HashMap;
+
+use crate::{Font, Instance, Plist};
+
+/// The names of an exported instance, as resolved by
+/// [`Instance::resolved_names`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstanceNames {
+    pub family_name: String,
+    pub style_name: String,
+    pub full_name: String,
+    pub postscript_name: String,
+    /// The family name for style linking, with the part of the style name
+    /// that isn't covered by bold and italic.
+    pub style_map_family_name: String,
+    /// `regular`, `bold`, `italic` or `bold italic`.
+    pub style_map_style_name: String,
+}
+
+impl Instance {
+    /// Resolve the names the instance is exported with, like Glyphs.app and
+    /// glyphsLib do.
+    ///
+    /// Each name comes from the instance's properties if set there, then from
+    /// its custom parameters, and is otherwise derived: the family name from
+    /// the font's properties, custom parameters or family name, the style
+    /// name from the instance name, the full name and PostScript name from
+    /// those two. Localised properties use the default language, then
+    /// English.
+    ///
+    /// The style map family name adds the linked style to the family name.
+    /// Without a linked style, or when it is `Regular`, it's the style name
+    /// less the last `Bold`, `Italic` or `Regular` covered by the instance's
+    /// bold and italic flags.
+    pub fn resolved_names(&self, font: &Font) -> InstanceNames {
+        let names = |property: &str, parameter: &str| {
+            localised_property(&self.other_stuff, property)
+                .or_else(|| self.custom_parameter_as(parameter))
+        };
+
+        let family_name = names("familyNames", "familyName")
+            .or_else(|| localised_property(&font.other_stuff, "familyNames"))
+            .or_else(|| font.custom_parameter_as("familyName"))
+            .unwrap_or(&font.family_name)
+            .to_string();
+        let style_name = names("styleNames", "styleName")
+            .unwrap_or(&self.name)
+            .to_string();
+        let full_name = names("postscriptFullNames", "postscriptFullName")
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{family_name} {style_name}"));
+        let postscript_name = names("postscriptFontName", "postscriptFontName")
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{family_name}-{style_name}").replace(' ', ""));
+
+        let style_map_style_name = match (self.is_bold, self.is_italic) {
+            (true, true) => "bold italic",
+            (true, false) => "bold",
+            (false, true) => "italic",
+            (false, false) => "regular",
+        };
+        let linked_style = match self.link_style.as_deref() {
+            Some(linked) if !linked.is_empty() && linked != "Regular" => linked.to_string(),
+            _ => unlinked_style(&style_name, self.is_bold, self.is_italic),
+        };
+        let style_map_family_name = names("styleMapFamilyNames", "styleMapFamilyName")
+            .map(str::to_string)
+            .unwrap_or_else(|| match linked_style.as_str() {
+                "" => family_name.clone(),
+                linked => format!("{family_name} {linked}"),
+            });
+        let style_map_style_name = names("styleMapStyleNames", "styleMapStyleName")
+            .unwrap_or(style_map_style_name)
+            .to_string();
+
+        InstanceNames {
+            family_name,
+            style_name,
+            full_name,
+            postscript_name,
+            style_map_family_name,
+            style_map_style_name,
+        }
+    }
+}
+
+/// The style name without the last of the words that the bold and italic
+/// flags stand for.
+fn unlinked_style(style_name: &str, is_bold: bool, is_italic: bool) -> String {
+    let (mut is_regular, mut is_bold, mut is_italic) = (!is_bold && !is_italic, is_bold, is_italic);
+    let mut parts: Vec<&str> = Vec::new();
+    for part in style_name.split_whitespace().rev() {
+        match part {
+            "Regular" if is_regular => is_regular = false,
+            "Bold" if is_bold => is_bold = false,
+            "Italic" if is_italic => is_italic = false,
+            _ => parts.push(part),
+        }
+    }
+    parts.reverse();
+    parts.join(" ")
+}
+
+/// A value from the `properties` of a font or instance, taking the default
+/// language or English for localised properties.
+fn localised_property<'a>(other_stuff: &'a HashMap<String, Plist>, key: &str) -> Option<&'a str> {
+    let property = other_stuff
+        .get("properties")?
+        .as_array()?
+        .iter()
+        .filter_map(Plist::as_dict)
+        .find(|property| property.get("key").and_then(Plist::as_str) == Some(key))?;
+    if let Some(value) = property.get("value") {
+        return value.as_str();
+    }
+    let values: Vec<_> = property
+        .get("values")?
+        .as_array()?
+        .iter()
+        .filter_map(Plist::as_dict)
+        .collect();
+    ["dflt", "ENG"]
+        .iter()
+        .find_map(|language| {
+            values
+                .iter()
+                .find(|v| v.get("language").and_then(Plist::as_str) == Some(language))
+        })
+        .or(values.first())
+        .and_then(|v| v.get("value")?.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolved_names() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let names: Vec<InstanceNames> = font
+            .instances
+            .iter()
+            .flatten()
+            .map(|instance| instance.resolved_names(&font))
+            .collect();
+
+        assert_eq!(
+            names[0],
+            InstanceNames {
+                family_name: font.family_name.clone(),
+                style_name: "Regular".into(),
+                full_name: format!("{} Regular", font.family_name),
+                postscript_name: format!("{}-Regular", font.family_name.replace(' ', "")),
+                style_map_family_name: font.family_name.clone(),
+                style_map_style_name: "regular".into(),
+            }
+        );
+        // From the localised property; italic, so "Regular" isn't linked.
+        assert_eq!(names[1].family_name, "Instance Family Name");
+        assert_eq!(names[1].postscript_name, "InstanceFamilyName-Regular");
+        assert_eq!(
+            names[1].style_map_family_name,
+            "Instance Family Name Regular"
+        );
+        assert_eq!(names[1].style_map_style_name, "italic");
+        // From the custom parameter, linked to the Light instance.
+        assert_eq!(names[2].family_name, "Instance Family Name");
+        assert_eq!(names[2].style_name, "Bold");
+        assert_eq!(names[2].style_map_family_name, "Instance Family Name Light");
+        assert_eq!(names[2].style_map_style_name, "bold");
+    }
+
+    #[test]
+    fn family_name_from_font() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let family_name = |font: &Font, index: usize| {
+            let instance = &font.instances.as_ref().unwrap()[index];
+            instance.resolved_names(font).family_name
+        };
+        font.custom_parameters
+            .get_or_insert_with(Vec::new)
+            .push(crate::CustomParameter {
+                name: "familyName".into(),
+                value: Plist::String("Parameter Family".into()),
+                disabled: false,
+            });
+        assert_eq!(family_name(&font, 0), "Parameter Family");
+
+        let property = Plist::Dictionary(HashMap::from([
+            ("key".to_string(), Plist::String("familyNames".into())),
+            (
+                "values".to_string(),
+                Plist::Array(vec![Plist::Dictionary(HashMap::from([
+                    ("language".to_string(), Plist::String("ENG".into())),
+                    ("value".to_string(), Plist::String("Property Family".into())),
+                ]))]),
+            ),
+        ]));
+        font.other_stuff
+            .insert("properties".into(), Plist::Array(vec![property]));
+        assert_eq!(family_name(&font, 0), "Property Family");
+        // The instance's own names come first.
+        assert_eq!(family_name(&font, 1), "Instance Family Name");
+    }
+
+    #[test]
+    fn unlinked_style() {
+        assert_eq!(super::unlinked_style("Bold Italic", true, true), "");
+        assert_eq!(super::unlinked_style("Light Italic", false, true), "Light");
+        assert_eq!(
+            super::unlinked_style("Regular Italic", false, false),
+            "Italic"
+        );
+        assert_eq!(super::unlinked_style("Bold", false, false), "Bold");
+    }
+}
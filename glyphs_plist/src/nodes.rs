@@ -0,0 +1,661 @@
+//! Editing the nodes of paths while keeping their segments well-formed.
+
+use kurbo::{CubicBez, Line, ParamCurve, ParamCurveArclen, PathSeg, Point, QuadBez};
+
+use crate::curves::{Segment, Segments};
+use crate::outline::{is_smooth, segment_node_type};
+use crate::{Node, NodeType, Path};
+
+/// The accuracy of the arc lengths weighing the segments around a removed
+/// node, in font units.
+const ARCLEN_ACCURACY: f64 = 0.1;
+
+/// Where a node sits among the segments of a path.
+enum Location {
+    /// The first node of an open path.
+    Start,
+    /// The off-curve node at an index in a segment.
+    OffCurve(usize, usize),
+    /// The on-curve node ending a segment.
+    End(usize),
+}
+
+impl Path {
+    /// The segments of the path in drawing order, as drawn by
+    /// [`Path::draw`]: a quadratic curve with several off-curve nodes gives
+    /// a segment for each of them.
+    pub fn segments(&self) -> impl Iterator<Item = PathSeg> {
+        let segments = Segments::new(self);
+        let pieces: Vec<PathSeg> = (0..segments.segments.len())
+            .flat_map(|index| pieces(&segments.segments[index], segments.start_of(index)))
+            .collect();
+        pieces.into_iter()
+    }
+
+    /// Split the path with a new on-curve node at path time `t`, without
+    /// changing its outline, and return the index of the new node.
+    ///
+    /// The integer part of `t` is the index of a segment in
+    /// [`Path::segments`], the fractional part the curve parameter within
+    /// it, like the path time of Glyphs.app. `None` if `t` is outside the
+    /// path or on an existing node.
+    ///
+    /// The new node is smooth within a curve and a corner within a line. The
+    /// implied on-curve points around a split quadratic curve are made
+    /// explicit, and closed paths are stored with their starting node last.
+    pub fn insert_node_at(&mut self, t: f64) -> Option<usize> {
+        if t.is_nan() || t <= 0.0 || t.fract() == 0.0 {
+            return None;
+        }
+        let piece_index = t.trunc() as usize;
+        let t = t.fract();
+
+        let mut segments = Segments::new(self);
+        let (index, piece, seg) = (0..segments.segments.len())
+            .flat_map(|index| {
+                let start = segments.start_of(index);
+                pieces(&segments.segments[index], start)
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(piece, seg)| (index, piece, seg))
+            })
+            .nth(piece_index)?;
+
+        let segment = &segments.segments[index];
+        let end = segment.end.clone();
+        let (before, after) = (seg.subsegment(0.0..t), seg.subsegment(t..1.0));
+        let mut split = Vec::new();
+        let mut new_node = 0;
+        match (before, after) {
+            (PathSeg::Line(before), PathSeg::Line(_)) => {
+                split.push(on_curve(&[], before.p1, NodeType::Line));
+                split.push(Segment {
+                    off_curves: segment.off_curves.clone(),
+                    end,
+                });
+            }
+            (PathSeg::Cubic(before), PathSeg::Cubic(after)) => {
+                split.push(on_curve(
+                    &[before.p1, before.p2],
+                    before.p3,
+                    NodeType::CurveSmooth,
+                ));
+                split.push(on_curve(&[after.p1, after.p2], end.pt, end.node_type));
+            }
+            (PathSeg::Quad(before), PathSeg::Quad(after)) => {
+                let smooth = segment_node_type(end.node_type, true);
+                let off_curves = &segment.off_curves;
+                // The neighbouring parts of a quadratic spline now end at
+                // explicit nodes rather than implied ones.
+                if piece > 0 {
+                    split.push(on_curve(&off_curves[..piece], before.p0, smooth));
+                }
+                new_node = split.len();
+                split.push(on_curve(&[before.p1], before.p2, smooth));
+                if piece + 1 < off_curves.len() {
+                    split.push(on_curve(&[after.p1], after.p2, smooth));
+                    split.push(on_curve(&off_curves[piece + 1..], end.pt, end.node_type));
+                } else {
+                    split.push(on_curve(&[after.p1], end.pt, end.node_type));
+                }
+            }
+            _ => unreachable!("subsegments keep the kind of their segment"),
+        }
+
+        let new_index = segments.start.iter().count()
+            + segments.segments[..index]
+                .iter()
+                .map(|segment| segment.off_curves.len() + 1)
+                .sum::<usize>()
+            + split[..new_node]
+                .iter()
+                .map(|segment| segment.off_curves.len() + 1)
+                .sum::<usize>()
+            + split[new_node].off_curves.len();
+        segments.segments.splice(index..=index, split);
+        self.nodes = segments.into_nodes();
+        Some(new_index)
+    }
+
+    /// Remove the node at `index`, keeping the segments around it
+    /// well-formed, and return whether there was one.
+    ///
+    /// Removing an off-curve node of a cubic curve turns the curve into a
+    /// line; a quadratic curve keeps its other off-curve nodes. Removing an
+    /// on-curve node joins the segments on either side: two lines become a
+    /// line and two quadratic splines a spline, anything else becomes a
+    /// cubic curve keeping the direction of the outer handles, lengthened as
+    /// if the segments had been split from one curve. The off-curve nodes before
+    /// the first or after the last node of an open path go with it.
+    pub fn remove_node(&mut self, index: usize) -> bool {
+        if index >= self.nodes.len() {
+            return false;
+        }
+        let Some(location) = self.locate(index) else {
+            // Off-curve nodes without an on-curve node to end their segment.
+            self.nodes.remove(index);
+            return true;
+        };
+
+        let mut segments = Segments::new(self);
+        match location {
+            Location::Start => {
+                segments.start = (!segments.segments.is_empty()).then(|| {
+                    let first = segments.segments.remove(0);
+                    Node {
+                        pt: first.end.pt,
+                        node_type: NodeType::Line,
+                    }
+                });
+            }
+            Location::OffCurve(index, off_curve) => {
+                let segment = &mut segments.segments[index];
+                let quadratic = matches!(
+                    segment.end.node_type,
+                    NodeType::QCurve | NodeType::QCurveSmooth
+                );
+                if quadratic && segment.off_curves.len() > 1 {
+                    segment.off_curves.remove(off_curve);
+                } else {
+                    segment.off_curves.clear();
+                    segment.end.node_type =
+                        segment_node_type(NodeType::Line, is_smooth(segment.end.node_type));
+                }
+            }
+            Location::End(index) => {
+                let count = segments.segments.len();
+                if !self.closed && index + 1 == count {
+                    segments.segments.pop();
+                } else if self.closed && count == 1 {
+                    segments.segments.clear();
+                } else {
+                    let next = (index + 1) % count;
+                    let start = segments.start_of(index);
+                    let joined = join(start, &segments.segments[index], &segments.segments[next]);
+                    segments.segments[next] = joined;
+                    segments.segments.remove(index);
+                }
+            }
+        }
+        self.nodes = segments.into_nodes();
+        true
+    }
+
+    /// Whether the node types of the path follow each other as Glyphs.app
+    /// expects: lines without off-curve nodes before them, cubic curves with
+    /// one or two (one draws a quadratic curve), quadratic curves with any
+    /// number, and no off-curve nodes at either end of an open path. A
+    /// closed path of off-curve nodes only is a quadratic spline.
+    pub fn is_valid(&self) -> bool {
+        let Some(last_on_curve) = self
+            .nodes
+            .iter()
+            .rposition(|node| node.node_type != NodeType::OffCurve)
+        else {
+            return self.closed || self.nodes.is_empty();
+        };
+        if !self.closed
+            && (self.nodes[0].node_type == NodeType::OffCurve
+                || last_on_curve + 1 != self.nodes.len())
+        {
+            return false;
+        }
+
+        // Count the off-curve nodes before each on-curve node, starting
+        // after the last one so that those of a closed path wrap around.
+        let len = self.nodes.len();
+        let mut off_curves = 0;
+        for i in (last_on_curve + 1..last_on_curve + 1 + len).map(|i| i % len) {
+            let valid = match self.nodes[i].node_type {
+                NodeType::OffCurve => {
+                    off_curves += 1;
+                    continue;
+                }
+                // Nothing comes before the first node of an open path.
+                _ if i == 0 && !self.closed => true,
+                NodeType::Line | NodeType::LineSmooth => off_curves == 0,
+                NodeType::Curve | NodeType::CurveSmooth => (1..=2).contains(&off_curves),
+                NodeType::QCurve | NodeType::QCurveSmooth => true,
+            };
+            if !valid {
+                return false;
+            }
+            off_curves = 0;
+        }
+        true
+    }
+
+    /// Close an open path with a line from its last node back to its first.
+    ///
+    /// Like Glyphs.app, closed paths store their starting node at the end
+    /// of the node list, so the first node moves there. When the path
+    /// already ends where it starts, the last node takes the place of the
+    /// first instead of drawing a line of no length.
+    pub fn close(&mut self) {
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        let (Some(first), Some(last)) = (self.nodes.first(), self.nodes.last()) else {
+            return;
+        };
+        if self.nodes.len() > 1 && first.pt == last.pt {
+            self.nodes.remove(0);
+        } else {
+            let first = &mut self.nodes[0];
+            first.node_type = segment_node_type(NodeType::Line, is_smooth(first.node_type));
+            self.nodes.rotate_left(1);
+        }
+    }
+
+    /// Open a closed path at the on-curve node at `index`, which then both
+    /// starts and ends the path, and return whether it could be opened.
+    ///
+    /// Opening undoes [`Path::close`] for a path that ended where it
+    /// started. Both ends of the open path are corners.
+    pub fn open_at(&mut self, index: usize) -> bool {
+        let is_on_curve = self
+            .nodes
+            .get(index)
+            .is_some_and(|node| node.node_type != NodeType::OffCurve);
+        if !self.closed || !is_on_curve {
+            return false;
+        }
+        // Closed paths end on their starting node.
+        self.nodes.rotate_left(index + 1);
+        let last = self.nodes.last_mut().unwrap();
+        last.node_type = segment_node_type(last.node_type, false);
+        let start = Node {
+            pt: last.pt,
+            node_type: NodeType::Line,
+        };
+        self.nodes.insert(0, start);
+        self.closed = false;
+        true
+    }
+
+    /// Find the node at `index` among the segments of [`Segments::new`].
+    /// `None` for the off-curve nodes the segments leave out, at the end of
+    /// an open path or making up a closed path on their own.
+    fn locate(&self, index: usize) -> Option<Location> {
+        let len = self.nodes.len();
+        let mut position = if self.closed {
+            let last_on_curve = self
+                .nodes
+                .iter()
+                .rposition(|node| node.node_type != NodeType::OffCurve)?;
+            (index + len - last_on_curve - 1) % len
+        } else if index == 0 {
+            return Some(Location::Start);
+        } else {
+            index - 1
+        };
+        for (segment_index, segment) in Segments::new(self).segments.iter().enumerate() {
+            match position.checked_sub(segment.off_curves.len()) {
+                None => return Some(Location::OffCurve(segment_index, position)),
+                Some(0) => return Some(Location::End(segment_index)),
+                Some(rest) => position = rest - 1,
+            }
+        }
+        None
+    }
+}
+
+/// The segments a run of nodes draws, starting from `start`.
+fn pieces(segment: &Segment, start: Point) -> Vec<PathSeg> {
+    let end = segment.end.pt;
+    match (segment.end.node_type, &segment.off_curves[..]) {
+        (NodeType::Line | NodeType::LineSmooth, _) | (_, []) => {
+            vec![PathSeg::Line(Line::new(start, end))]
+        }
+        (NodeType::Curve | NodeType::CurveSmooth, [c]) => {
+            vec![PathSeg::Quad(QuadBez::new(start, *c, end))]
+        }
+        (NodeType::Curve | NodeType::CurveSmooth, [c1, .., c2]) => {
+            vec![PathSeg::Cubic(CubicBez::new(start, *c1, *c2, end))]
+        }
+        (_, off_curves) => {
+            // Consecutive quadratic off-curve nodes imply an on-curve point
+            // halfway between them.
+            let last = off_curves.len() - 1;
+            off_curves
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| {
+                    let p0 = if i == 0 {
+                        start
+                    } else {
+                        off_curves[i - 1].midpoint(c)
+                    };
+                    let p2 = if i == last {
+                        end
+                    } else {
+                        c.midpoint(off_curves[i + 1])
+                    };
+                    PathSeg::Quad(QuadBez::new(p0, c, p2))
+                })
+                .collect()
+        }
+    }
+}
+
+/// The cubic curve drawing a segment.
+fn as_cubic(seg: PathSeg) -> CubicBez {
+    match seg {
+        PathSeg::Line(line) => CubicBez::new(
+            line.p0,
+            line.p0.lerp(line.p1, 1.0 / 3.0),
+            line.p0.lerp(line.p1, 2.0 / 3.0),
+            line.p1,
+        ),
+        PathSeg::Quad(quad) => quad.raise(),
+        PathSeg::Cubic(cubic) => cubic,
+    }
+}
+
+/// A segment from off-curve points and the position and type of its end.
+fn on_curve(off_curves: &[Point], pt: Point, node_type: NodeType) -> Segment {
+    Segment {
+        off_curves: off_curves.to_vec(),
+        end: Node { pt, node_type },
+    }
+}
+
+/// Join two consecutive segments into one, as if the node between them was
+/// removed.
+fn join(start: Point, incoming: &Segment, outgoing: &Segment) -> Segment {
+    let removed = incoming.end.pt;
+    let end = &outgoing.end;
+    let is_quadratic = |segment: &Segment| {
+        matches!(
+            segment.end.node_type,
+            NodeType::QCurve | NodeType::QCurveSmooth
+        )
+    };
+    if incoming.off_curves.is_empty() && outgoing.off_curves.is_empty() {
+        return on_curve(
+            &[],
+            end.pt,
+            segment_node_type(NodeType::Line, is_smooth(end.node_type)),
+        );
+    }
+    if is_quadratic(incoming) && is_quadratic(outgoing) {
+        let off_curves = [&incoming.off_curves[..], &outgoing.off_curves[..]].concat();
+        return on_curve(&off_curves, end.pt, end.node_type);
+    }
+
+    // The outer handles keep their direction and are lengthened as if the
+    // two segments had been split from one curve, at the parameter dividing
+    // their lengths. Lines and quadratic curves have the handles of the
+    // cubic curves drawing them.
+    let first = as_cubic(pieces(incoming, start)[0]);
+    let last = as_cubic(*pieces(outgoing, removed).last().unwrap());
+    let arclen = |segment: &Segment, start: Point| -> f64 {
+        pieces(segment, start)
+            .iter()
+            .map(|seg| seg.arclen(ARCLEN_ACCURACY))
+            .sum()
+    };
+    let (len_in, len_out) = (arclen(incoming, start), arclen(outgoing, removed));
+    // A segment of no length can go without changing the other.
+    if len_in == 0.0 {
+        return on_curve(&outgoing.off_curves, end.pt, end.node_type);
+    } else if len_out == 0.0 {
+        let node_type = segment_node_type(incoming.end.node_type, is_smooth(end.node_type));
+        return on_curve(&incoming.off_curves, end.pt, node_type);
+    }
+    let t = len_in / (len_in + len_out);
+    let (d1, d2) = ((first.p1 - start) / t, (last.p2 - end.pt) / (1.0 - t));
+    on_curve(
+        &[start + d1, end.pt + d2],
+        end.pt,
+        segment_node_type(NodeType::Curve, is_smooth(end.node_type)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{PathEl, Shape as _};
+
+    use super::*;
+
+    fn path(closed: bool, nodes: &[(f64, f64, NodeType)]) -> Path {
+        let mut path = Path::new(closed);
+        for &(x, y, node_type) in nodes {
+            path.add((x, y), node_type);
+        }
+        path
+    }
+
+    fn node_types(path: &Path) -> Vec<NodeType> {
+        path.nodes.iter().map(|node| node.node_type).collect()
+    }
+
+    /// A closed path of a line and a cubic curve, starting at (0, 0).
+    fn bump() -> Path {
+        use NodeType::*;
+        path(
+            true,
+            &[
+                (100.0, 0.0, Line),
+                (100.0, 60.0, OffCurve),
+                (0.0, 60.0, OffCurve),
+                (0.0, 0.0, Curve),
+            ],
+        )
+    }
+
+    #[test]
+    fn segments() {
+        use NodeType::*;
+        let segments: Vec<PathSeg> = bump().segments().collect();
+        assert_eq!(
+            segments,
+            [
+                PathSeg::Line(kurbo::Line::new((0.0, 0.0), (100.0, 0.0))),
+                PathSeg::Cubic(CubicBez::new(
+                    (100.0, 0.0),
+                    (100.0, 60.0),
+                    (0.0, 60.0),
+                    (0.0, 0.0)
+                )),
+            ]
+        );
+
+        let spline = path(
+            false,
+            &[
+                (0.0, 0.0, Line),
+                (0.0, 100.0, OffCurve),
+                (100.0, 100.0, OffCurve),
+                (100.0, 0.0, QCurve),
+            ],
+        );
+        let segments: Vec<PathSeg> = spline.segments().collect();
+        assert_eq!(
+            segments,
+            [
+                PathSeg::Quad(QuadBez::new((0.0, 0.0), (0.0, 100.0), (50.0, 100.0))),
+                PathSeg::Quad(QuadBez::new((50.0, 100.0), (100.0, 100.0), (100.0, 0.0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_node_at() {
+        use NodeType::*;
+        let mut path = bump();
+        let area = path.to_bezpath().area();
+
+        assert_eq!(path.insert_node_at(0.25), Some(0));
+        assert_eq!(path.nodes[0].pt, Point::new(25.0, 0.0));
+        assert_eq!(path.insert_node_at(2.5), Some(4));
+        assert_eq!(
+            node_types(&path),
+            [
+                Line,
+                Line,
+                OffCurve,
+                OffCurve,
+                CurveSmooth,
+                OffCurve,
+                OffCurve,
+                Curve
+            ]
+        );
+        assert_eq!(path.nodes[4].pt, Point::new(50.0, 45.0));
+        assert!((path.to_bezpath().area() - area).abs() < 1e-9);
+        assert!(path.is_valid());
+
+        assert_eq!(path.insert_node_at(1.0), None);
+        assert_eq!(path.insert_node_at(3.5 + 1.0), None);
+
+        let mut spline = super::tests::path(
+            false,
+            &[
+                (0.0, 0.0, Line),
+                (0.0, 100.0, OffCurve),
+                (100.0, 100.0, OffCurve),
+                (100.0, 0.0, QCurve),
+            ],
+        );
+        let before = spline.to_bezpath();
+        assert_eq!(spline.insert_node_at(1.5), Some(4));
+        assert_eq!(
+            node_types(&spline),
+            [
+                Line,
+                OffCurve,
+                QCurveSmooth,
+                OffCurve,
+                QCurveSmooth,
+                OffCurve,
+                QCurve
+            ]
+        );
+        assert_eq!(spline.nodes[2].pt, Point::new(50.0, 100.0));
+        let (split, halves) = (before.segments().nth(1).unwrap(), spline.segments().skip(1));
+        for (half, range) in halves.zip([0.0..0.5, 0.5..1.0]) {
+            for t in [0.0, 0.3, 1.0] {
+                let expected = split.eval(range.start + t * (range.end - range.start));
+                assert!((half.eval(t) - expected).hypot() < 1e-9);
+            }
+        }
+        assert!(spline.is_valid());
+    }
+
+    #[test]
+    fn remove_node() {
+        use NodeType::*;
+        // Removing a node added on a curve gives the curve back.
+        let mut path = bump();
+        let index = path.insert_node_at(1.5).unwrap();
+        assert!(path.remove_node(index));
+        for (node, expected) in path.nodes.iter().zip(&bump().nodes) {
+            assert!((node.pt - expected.pt).hypot() < 1e-6, "{path:?}");
+        }
+        assert_eq!(node_types(&path), node_types(&bump()));
+
+        // Removing a handle of a cubic curve turns it into a line.
+        let mut path = bump();
+        assert!(path.remove_node(1));
+        assert_eq!(node_types(&path), [Line, Line]);
+
+        // Removing the start of a closed path moves it to the previous node.
+        let mut path = bump();
+        assert!(path.remove_node(3));
+        assert_eq!(node_types(&path), [OffCurve, OffCurve, Curve]);
+        assert_eq!(path.nodes[2].pt, Point::new(100.0, 0.0));
+        assert!(path.is_valid());
+
+        // Removing the ends of an open path takes their handles along.
+        let mut open = super::tests::path(
+            false,
+            &[
+                (0.0, 0.0, Line),
+                (0.0, 50.0, OffCurve),
+                (50.0, 100.0, OffCurve),
+                (100.0, 100.0, Curve),
+                (200.0, 100.0, Line),
+            ],
+        );
+        assert!(open.remove_node(0));
+        assert_eq!(node_types(&open), [Line, Line]);
+        assert!(!open.remove_node(2));
+    }
+
+    #[test]
+    fn close_and_open() {
+        use NodeType::*;
+        let mut path = path(
+            false,
+            &[
+                (0.0, 0.0, Line),
+                (0.0, 50.0, OffCurve),
+                (50.0, 100.0, OffCurve),
+                (100.0, 100.0, CurveSmooth),
+                (200.0, 100.0, Line),
+            ],
+        );
+        let outline = path.to_bezpath();
+        path.close();
+        assert!(path.closed);
+        assert_eq!(
+            node_types(&path),
+            [OffCurve, OffCurve, CurveSmooth, Line, Line]
+        );
+        assert_eq!(path.nodes[4].pt, Point::ZERO);
+        let mut elements = outline.elements().to_vec();
+        elements.extend([PathEl::LineTo(Point::ZERO), PathEl::ClosePath]);
+        assert_eq!(path.to_bezpath().elements(), elements);
+
+        // Opening at the starting node keeps the closing line.
+        let closed = path.clone();
+        assert!(path.open_at(4));
+        assert!(!path.closed);
+        assert_eq!(
+            node_types(&path),
+            [Line, OffCurve, OffCurve, CurveSmooth, Line, Line]
+        );
+        assert_eq!(path.nodes[5].pt, Point::ZERO);
+        assert!(path.is_valid());
+
+        // Closing again merges the ends.
+        path.close();
+        assert_eq!(path, closed);
+
+        // Opening elsewhere starts the path at that node.
+        assert!(path.open_at(2));
+        assert_eq!(path.nodes[0].pt, Point::new(100.0, 100.0));
+        assert_eq!(
+            node_types(&path),
+            [Line, Line, Line, OffCurve, OffCurve, Curve]
+        );
+        assert!(!path.open_at(0));
+
+        let mut closed = bump();
+        assert!(!closed.open_at(1));
+        assert!(!closed.open_at(4));
+    }
+
+    #[test]
+    fn is_valid() {
+        use NodeType::*;
+        assert!(bump().is_valid());
+        assert!(Path::new(true).is_valid());
+        assert!(path(true, &[(0.0, 0.0, OffCurve), (10.0, 0.0, OffCurve)]).is_valid());
+        assert!(!path(false, &[(0.0, 0.0, OffCurve), (10.0, 0.0, Line)]).is_valid());
+        assert!(!path(false, &[(0.0, 0.0, Line), (10.0, 0.0, OffCurve)]).is_valid());
+        assert!(!path(true, &[(0.0, 0.0, OffCurve), (10.0, 0.0, Line)]).is_valid());
+        assert!(!path(
+            true,
+            &[
+                (0.0, 0.0, OffCurve),
+                (5.0, 0.0, OffCurve),
+                (10.0, 0.0, OffCurve),
+                (10.0, 10.0, Curve)
+            ]
+        )
+        .is_valid());
+    }
+}
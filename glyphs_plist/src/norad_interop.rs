@@ -1,6 +1,1013 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::f64::consts::PI;
+use std::path::PathBuf;
 
-use crate::{font::Scale, Anchor, Component, Node, NodeType, Path};
+use thiserror::Error;
+
+use crate::hints::{hints_from_ufo, hints_to_ufo};
+use crate::masks::masked_paths;
+use crate::{
+    font::Scale, plist::Plist, Anchor, BackgroundImage, BackgroundLayer, Color, Component, Font,
+    FontMaster, Glyph, Layer, MasterMetric, Metric, MetricType, Node, NodeType, Path, Shape,
+};
+
+const PUBLIC_BACKGROUND: &str = "public.background";
+const PUBLIC_GLYPH_ORDER: &str = "public.glyphOrder";
+const PUBLIC_MARK_COLOR: &str = "public.markColor";
+const PUBLIC_POSTSCRIPT_NAMES: &str = "public.postscriptNames";
+const PUBLIC_SKIP_EXPORT_GLYPHS: &str = "public.skipExportGlyphs";
+const UFO2FT_COLOR_LAYERS: &str = "com.github.googlei18n.ufo2ft.colorLayers";
+const UFO2FT_COLOR_PALETTES: &str = "com.github.googlei18n.ufo2ft.colorPalettes";
+
+/// Master custom parameters that correspond to fontinfo fields.
+const FONTINFO_PARAMETERS: [&str; 12] = [
+    "typoAscender",
+    "typoDescender",
+    "typoLineGap",
+    "winAscent",
+    "winDescent",
+    "hheaAscender",
+    "hheaDescender",
+    "hheaLineGap",
+    "underlinePosition",
+    "underlineThickness",
+    "strikeoutPosition",
+    "strikeoutSize",
+];
+
+/// The label colors of Glyphs, by index, as glyphsLib writes them.
+const MARK_COLORS: [(f64, f64, f64, f64); 12] = [
+    (0.85, 0.26, 0.06, 1.0), // red
+    (0.99, 0.62, 0.11, 1.0), // orange
+    (0.65, 0.48, 0.2, 1.0),  // brown
+    (0.97, 1.0, 0.0, 1.0),   // yellow
+    (0.67, 0.95, 0.38, 1.0), // light green
+    (0.04, 0.57, 0.04, 1.0), // dark green
+    (0.0, 0.67, 0.91, 1.0),  // light blue
+    (0.18, 0.16, 0.78, 1.0), // dark blue
+    (0.5, 0.09, 0.79, 1.0),  // purple
+    (0.98, 0.36, 0.67, 1.0), // magenta
+    (0.75, 0.75, 0.75, 1.0), // light gray
+    (0.25, 0.25, 0.25, 1.0), // charcoal
+];
+
+#[derive(Debug, Error)]
+pub enum UfoConversionError {
+    #[error("no master with ID {0:?}")]
+    UnknownMaster(String),
+    #[error("bad name: {0}")]
+    Naming(#[from] norad::error::NamingError),
+    #[error("can't read image {path:?}: {source}")]
+    ReadImage {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("can't store image: {0}")]
+    StoreImage(#[from] norad::error::StoreEntryError),
+    #[error("units per em {0} don't fit a 16-bit integer")]
+    UnitsPerEm(f64),
+}
+
+/// Options controlling how a [`Font`] is converted to a UFO.
+#[derive(Clone, Debug)]
+pub struct ConversionOptions {
+    /// The number of decimal places point and anchor coordinates and
+    /// component transforms are rounded to, or `None` to keep them as they
+    /// are. `Some(0)` rounds to integers.
+    pub precision: Option<u32>,
+    /// Give composite glyphs without anchors of their own the anchors of
+    /// their components.
+    pub propagate_anchors: bool,
+    /// How the UFO layers holding brace (intermediate) layers are named.
+    pub brace_layer_naming: BraceLayerNaming,
+    /// Replace smart components with outlines interpolated at their part
+    /// settings.
+    pub decompose_smart_components: bool,
+    /// Replace paths that have a stroke with the outlines of their strokes,
+    /// like Glyphs.app does on export.
+    pub expand_strokes: bool,
+    /// The directory relative background image paths are resolved against,
+    /// usually the one containing the Glyphs file. Background images are
+    /// only copied into the UFO when this is set, otherwise they are left
+    /// out with a warning logged if the `log` feature is enabled.
+    pub image_dir: Option<PathBuf>,
+    /// The boolean difference mask paths are cut out of the paths before
+    /// them with, see [`MaskOp`](crate::MaskOp). Without it, mask paths are
+    /// left out of the UFO.
+    pub mask_op: Option<fn(&kurbo::BezPath, &kurbo::BezPath) -> kurbo::BezPath>,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            precision: Some(5),
+            propagate_anchors: false,
+            brace_layer_naming: BraceLayerNaming::Coordinates,
+            decompose_smart_components: false,
+            expand_strokes: true,
+            image_dir: None,
+            mask_op: None,
+        }
+    }
+}
+
+impl ConversionOptions {
+    fn round(&self, v: f64) -> f64 {
+        match self.precision {
+            Some(precision) => f64_precision(v, i32::try_from(precision).unwrap_or(i32::MAX)),
+            None => v,
+        }
+    }
+
+    fn round_contour(&self, mut contour: norad::Contour) -> norad::Contour {
+        for point in &mut contour.points {
+            point.x = self.round(point.x);
+            point.y = self.round(point.y);
+        }
+        contour
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BraceLayerNaming {
+    /// Name the UFO layer after the axis coordinates, e.g. `{400, 100}`.
+    #[default]
+    Coordinates,
+    /// Use the name of the Glyphs layer, falling back to the coordinates for
+    /// unnamed layers.
+    LayerName,
+}
+
+impl Font {
+    /// Convert the master with the given ID into a UFO.
+    ///
+    /// Glyphs without a layer for the master are left out. Backgrounds of the
+    /// master layers are written to a `public.background` layer.
+    ///
+    /// Color palette layers become separate layer glyphs, listed in the
+    /// ufo2ft color layer and palette lib keys for building COLRv0 tables.
+    /// Brace layers of the master are written to additional UFO layers.
+    ///
+    /// Background images are copied into the UFO's image store, see
+    /// [`ConversionOptions::image_dir`].
+    ///
+    /// The master's metrics and the custom parameters for the OS/2, hhea and
+    /// post tables go into fontinfo.
+    pub fn to_ufo(&self, master_id: &str) -> Result<norad::Font, UfoConversionError> {
+        self.to_ufo_with_options(master_id, &ConversionOptions::default())
+    }
+
+    /// Convert the master with the given ID into a UFO, as [`Font::to_ufo`]
+    /// does, with control over the details of the conversion.
+    pub fn to_ufo_with_options(
+        &self,
+        master_id: &str,
+        options: &ConversionOptions,
+    ) -> Result<norad::Font, UfoConversionError> {
+        let master = self
+            .font_master
+            .iter()
+            .find(|m| m.id == master_id)
+            .ok_or_else(|| UfoConversionError::UnknownMaster(master_id.to_string()))?;
+
+        let mut ufo = norad::Font::new();
+        ufo.font_info.family_name = Some(self.family_name.clone());
+        ufo.font_info.style_name = Some(master.name.clone());
+        ufo.font_info.units_per_em =
+            norad::NonNegativeIntegerOrFloat::new(self.units_per_em.into());
+        ufo.font_info.version_major = self.version_major.try_into().ok();
+        ufo.font_info.version_minor = self.version_minor.try_into().ok();
+        ufo.features = self.features_fea();
+        metrics_to_ufo(self, master, &mut ufo.font_info);
+
+        let mut backgrounds = Vec::new();
+        let mut brace_layers = Vec::new();
+        let mut images = ImageNames::default();
+        for glyph in &self.glyphs {
+            if let Some(master_layer) = glyph.master_layer(master_id) {
+                let mut ufo_glyph = glyph_to_ufo(self, glyph, master_layer, options)?;
+                image_to_ufo(
+                    &mut ufo_glyph,
+                    master_layer,
+                    &mut ufo.images,
+                    &mut images,
+                    options,
+                )?;
+                ufo.default_layer_mut().insert_glyph(ufo_glyph);
+                if let Some(background) = &master_layer.background {
+                    backgrounds.push(background_to_ufo(
+                        self,
+                        glyph,
+                        master_layer,
+                        background,
+                        options,
+                    )?);
+                }
+            }
+            for brace_layer in glyph.layers.iter().filter(|l| {
+                l.associated_master_id.as_deref() == Some(master_id) && l.is_intermediate_layer()
+            }) {
+                let mut ufo_glyph = glyph_to_ufo(self, glyph, brace_layer, options)?;
+                image_to_ufo(
+                    &mut ufo_glyph,
+                    brace_layer,
+                    &mut ufo.images,
+                    &mut images,
+                    options,
+                )?;
+                brace_layers.push((
+                    brace_layer_name(brace_layer, options.brace_layer_naming),
+                    ufo_glyph,
+                ));
+            }
+        }
+        let mut color_layers = norad::Plist::new();
+        let mut color_layer_glyphs = Vec::new();
+        for glyph in &self.glyphs {
+            let layer_glyphs = glyph.color_layer_glyphs(master_id);
+            if layer_glyphs.is_empty() {
+                continue;
+            }
+            for (layer, layer_glyph) in glyph.color_palette_layers(master_id).zip(&layer_glyphs) {
+                let ufo_glyph = layer_to_ufo(self, &layer_glyph.glyph_name, layer, options)?;
+                ufo.default_layer_mut().insert_glyph(ufo_glyph);
+                color_layer_glyphs.push(layer_glyph.glyph_name.to_string());
+            }
+            let mapping: Vec<plist::Value> = layer_glyphs
+                .into_iter()
+                .map(|l| {
+                    vec![
+                        plist::Value::from(l.glyph_name),
+                        i64::from(l.palette_index).into(),
+                    ]
+                    .into()
+                })
+                .collect();
+            color_layers.insert(glyph.glyphname.to_string(), mapping.into());
+        }
+        if !color_layers.is_empty() {
+            ufo.lib
+                .insert(UFO2FT_COLOR_LAYERS.into(), color_layers.into());
+            let palettes: Vec<plist::Value> = self
+                .color_palettes()
+                .iter()
+                .map(|palette| {
+                    palette
+                        .iter()
+                        .filter_map(color_to_ufo)
+                        .collect::<Vec<_>>()
+                        .into()
+                })
+                .collect();
+            if !palettes.is_empty() {
+                ufo.lib
+                    .insert(UFO2FT_COLOR_PALETTES.into(), palettes.into());
+            }
+        }
+
+        if !backgrounds.is_empty() {
+            let background_layer = ufo.layers.new_layer(PUBLIC_BACKGROUND)?;
+            for background in backgrounds {
+                background_layer.insert_glyph(background);
+            }
+        }
+        for (name, ufo_glyph) in brace_layers {
+            if ufo.layers.get(&name).is_none() {
+                ufo.layers.new_layer(&name)?;
+            }
+            ufo.layers.get_mut(&name).unwrap().insert_glyph(ufo_glyph);
+        }
+
+        // The glyphs made for color layers follow the font's glyphs.
+        let glyph_order: Vec<plist::Value> = self
+            .glyph_order()
+            .into_iter()
+            .map(str::to_string)
+            .chain(color_layer_glyphs)
+            .map(Into::into)
+            .collect();
+        ufo.lib
+            .insert(PUBLIC_GLYPH_ORDER.into(), glyph_order.into());
+
+        let skip_export_glyphs: Vec<plist::Value> = self
+            .glyphs
+            .iter()
+            .filter(|g| !g.export)
+            .map(|g| g.glyphname.to_string().into())
+            .collect();
+        if !skip_export_glyphs.is_empty() {
+            ufo.lib
+                .insert(PUBLIC_SKIP_EXPORT_GLYPHS.into(), skip_export_glyphs.into());
+        }
+
+        let mut postscript_names = norad::Plist::new();
+        for glyph in &self.glyphs {
+            if let Some(production) = &glyph.production {
+                postscript_names.insert(glyph.glyphname.to_string(), production.clone().into());
+            }
+        }
+        if !postscript_names.is_empty() {
+            ufo.lib
+                .insert(PUBLIC_POSTSCRIPT_NAMES.into(), postscript_names.into());
+        }
+
+        Ok(ufo)
+    }
+
+    /// Build a single-master font from the default layer of a UFO.
+    ///
+    /// Glyphs are ordered by `public.glyphOrder` where present, followed by
+    /// any remaining glyphs in layer order. Glyphs in a `public.background`
+    /// layer become the backgrounds of the corresponding master layers.
+    /// Production names are taken from `public.postscriptNames`, vertical
+    /// metrics from fontinfo. Glyph images become background images referring
+    /// to the image's file name; the image data stays in the UFO. Unnamed
+    /// anchors are left out, and units per em that don't fit a `u16` are an
+    /// error.
+    pub fn from_ufo(ufo: &norad::Font) -> Result<Font, UfoConversionError> {
+        let mut font = Font {
+            glyphs: Default::default(),
+            ..Font::new()
+        };
+        if let Some(family_name) = &ufo.font_info.family_name {
+            font.family_name = family_name.clone();
+        }
+        if let Some(style_name) = &ufo.font_info.style_name {
+            font.font_master[0].name = style_name.clone();
+        }
+        if let Some(units_per_em) = ufo.font_info.units_per_em {
+            let units_per_em = units_per_em.get();
+            font.units_per_em = u16::try_from(units_per_em.round() as i64)
+                .map_err(|_| UfoConversionError::UnitsPerEm(units_per_em))?;
+        }
+        if let Some(version_major) = ufo.font_info.version_major {
+            font.version_major = version_major.into();
+        }
+        if let Some(version_minor) = ufo.font_info.version_minor {
+            font.version_minor = version_minor.into();
+        }
+        metrics_from_ufo(&ufo.font_info, &mut font);
+
+        let master_id = font.font_master[0].id.clone();
+        let layer = ufo.default_layer();
+        let glyph_order = lib_string_array(&ufo.lib, PUBLIC_GLYPH_ORDER);
+        let ordered: HashSet<&str> = glyph_order.iter().copied().collect();
+        let names = glyph_order
+            .iter()
+            .copied()
+            .filter(|name| layer.contains_glyph(name))
+            .chain(
+                layer
+                    .iter()
+                    .map(|g| g.name().as_str())
+                    .filter(|name| !ordered.contains(name)),
+            );
+
+        let skip_export_glyphs: HashSet<&str> =
+            lib_string_array(&ufo.lib, PUBLIC_SKIP_EXPORT_GLYPHS)
+                .into_iter()
+                .collect();
+        let postscript_names = ufo
+            .lib
+            .get(PUBLIC_POSTSCRIPT_NAMES)
+            .and_then(|v| v.as_dictionary());
+        let background_layer = ufo.layers.get(PUBLIC_BACKGROUND);
+        for name in names {
+            let ufo_glyph = layer.get_glyph(name).unwrap();
+            let mut glyph = glyph_from_ufo(ufo_glyph, &master_id);
+            glyph.export = !skip_export_glyphs.contains(name);
+            glyph.production = postscript_names
+                .and_then(|names| names.get(name))
+                .and_then(|v| v.as_string())
+                .map(str::to_string);
+            if let Some(background) = background_layer.and_then(|l| l.get_glyph(name)) {
+                glyph.layers[0].background = Some(background_from_ufo(background));
+            }
+            font.glyphs.push(glyph);
+        }
+
+        Ok(font)
+    }
+}
+
+fn metrics_to_ufo(font: &Font, master: &FontMaster, info: &mut norad::FontInfo) {
+    let metrics = master.resolved_metrics(font);
+    info.ascender = metrics.ascender();
+    info.descender = metrics.descender();
+    info.x_height = metrics.x_height();
+    info.cap_height = metrics.cap_height();
+    info.italic_angle = metrics.italic_angle();
+
+    for name in FONTINFO_PARAMETERS {
+        let Some(value) = master.custom_parameter_as::<f64>(name) else {
+            continue;
+        };
+        let int = value.round() as i32;
+        match name {
+            "typoAscender" => info.open_type_os2_typo_ascender = Some(int),
+            "typoDescender" => info.open_type_os2_typo_descender = Some(int),
+            "typoLineGap" => info.open_type_os2_typo_line_gap = Some(int),
+            "winAscent" => info.open_type_os2_win_ascent = Some(int.max(0) as u32),
+            "winDescent" => info.open_type_os2_win_descent = Some(int.max(0) as u32),
+            "hheaAscender" => info.open_type_hhea_ascender = Some(int),
+            "hheaDescender" => info.open_type_hhea_descender = Some(int),
+            "hheaLineGap" => info.open_type_hhea_line_gap = Some(int),
+            "underlinePosition" => info.postscript_underline_position = Some(value),
+            "underlineThickness" => info.postscript_underline_thickness = Some(value),
+            "strikeoutPosition" => info.open_type_os2_strikeout_position = Some(int),
+            "strikeoutSize" => info.open_type_os2_strikeout_size = Some(int),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn metrics_from_ufo(info: &norad::FontInfo, font: &mut Font) {
+    let metrics = [
+        (MetricType::Ascender, info.ascender),
+        (MetricType::CapHeight, info.cap_height),
+        (MetricType::XHeight, info.x_height),
+        (MetricType::Descender, info.descender),
+        (MetricType::ItalicAngle, info.italic_angle),
+    ];
+    for (metric_type, value) in metrics {
+        let Some(pos) = value else {
+            continue;
+        };
+        let index = match font
+            .metrics
+            .iter()
+            .position(|m| m.r#type.as_ref() == Some(&metric_type) && m.filter.is_none())
+        {
+            Some(index) => index,
+            None => {
+                font.metrics.push(Metric {
+                    filter: None,
+                    name: None,
+                    r#type: Some(metric_type),
+                });
+                font.metrics.len() - 1
+            }
+        };
+        let metric_values = &mut font.font_master[0].metric_values;
+        if metric_values.len() <= index {
+            metric_values.resize(
+                index + 1,
+                MasterMetric {
+                    pos: 0.0,
+                    over: 0.0,
+                },
+            );
+        }
+        metric_values[index].pos = pos;
+    }
+
+    let parameters: Vec<Plist> = FONTINFO_PARAMETERS
+        .into_iter()
+        .filter_map(|name| {
+            let value: Plist = match name {
+                "typoAscender" => info.open_type_os2_typo_ascender?.into(),
+                "typoDescender" => info.open_type_os2_typo_descender?.into(),
+                "typoLineGap" => info.open_type_os2_typo_line_gap?.into(),
+                "winAscent" => i64::from(info.open_type_os2_win_ascent?).into(),
+                "winDescent" => i64::from(info.open_type_os2_win_descent?).into(),
+                "hheaAscender" => info.open_type_hhea_ascender?.into(),
+                "hheaDescender" => info.open_type_hhea_descender?.into(),
+                "hheaLineGap" => info.open_type_hhea_line_gap?.into(),
+                "underlinePosition" => info.postscript_underline_position?.into(),
+                "underlineThickness" => info.postscript_underline_thickness?.into(),
+                "strikeoutPosition" => info.open_type_os2_strikeout_position?.into(),
+                "strikeoutSize" => info.open_type_os2_strikeout_size?.into(),
+                _ => unreachable!(),
+            };
+            Some(Plist::Dictionary(
+                [
+                    ("name".to_string(), Plist::String(name.to_string())),
+                    ("value".to_string(), value),
+                ]
+                .into(),
+            ))
+        })
+        .collect();
+    if !parameters.is_empty() {
+        font.font_master[0]
+            .other_stuff
+            .insert("customParameters".into(), Plist::Array(parameters));
+    }
+}
+
+fn glyph_to_ufo(
+    font: &Font,
+    glyph: &Glyph,
+    layer: &Layer,
+    options: &ConversionOptions,
+) -> Result<norad::Glyph, UfoConversionError> {
+    let mut ufo_glyph = layer_to_ufo(font, glyph.glyphname.as_str(), layer, options)?;
+    ufo_glyph.codepoints = glyph.unicode.clone().unwrap_or_default();
+    ufo_glyph.note = glyph.note.clone();
+    if let Some(color) = layer.color.as_ref().or(glyph.color.as_ref()) {
+        if let Some(color) = mark_color_to_ufo(color) {
+            ufo_glyph.lib.insert(PUBLIC_MARK_COLOR.into(), color.into());
+        }
+    }
+    Ok(ufo_glyph)
+}
+
+/// Draw a layer into a new UFO glyph with the given name.
+fn layer_to_ufo(
+    font: &Font,
+    name: &str,
+    layer: &Layer,
+    options: &ConversionOptions,
+) -> Result<norad::Glyph, UfoConversionError> {
+    let master_id = layer
+        .associated_master_id
+        .as_deref()
+        .unwrap_or(&layer.layer_id);
+    let anchors = match &layer.anchors {
+        None if options.propagate_anchors => Some(Cow::Owned(propagated_anchors(
+            font,
+            &layer.shapes,
+            master_id,
+            0,
+        ))),
+        anchors => anchors.as_deref().map(Cow::Borrowed),
+    };
+    let mut ufo_glyph = norad::Glyph::new(name);
+    ufo_glyph.width = layer.width;
+    draw_to_ufo(
+        &mut ufo_glyph,
+        font,
+        master_id,
+        &layer.shapes,
+        anchors.as_deref(),
+        options,
+    )?;
+    hints_to_ufo(layer, &mut ufo_glyph.lib);
+    Ok(ufo_glyph)
+}
+
+fn background_to_ufo(
+    font: &Font,
+    glyph: &Glyph,
+    layer: &Layer,
+    background: &BackgroundLayer,
+    options: &ConversionOptions,
+) -> Result<norad::Glyph, UfoConversionError> {
+    let mut ufo_glyph = norad::Glyph::new(glyph.glyphname.as_str());
+    ufo_glyph.width = layer.width;
+    draw_to_ufo(
+        &mut ufo_glyph,
+        font,
+        &layer.layer_id,
+        &background.shapes,
+        background.anchors.as_deref(),
+        options,
+    )?;
+    Ok(ufo_glyph)
+}
+
+fn draw_to_ufo(
+    ufo_glyph: &mut norad::Glyph,
+    font: &Font,
+    master_id: &str,
+    shapes: &[Shape],
+    anchors: Option<&[Anchor]>,
+    options: &ConversionOptions,
+) -> Result<(), UfoConversionError> {
+    let paths = || {
+        shapes.iter().filter_map(|shape| match shape {
+            Shape::Path(path) => Some(path.as_ref()),
+            Shape::Component(_) => None,
+        })
+    };
+    let masked = match options.mask_op {
+        Some(op) if paths().any(Path::is_mask) => {
+            let paths = masked_paths(paths(), &op);
+            ufo_glyph
+                .contours
+                .extend(paths.iter().map(|path| options.round_contour(path.into())));
+            true
+        }
+        _ => false,
+    };
+    for shape in shapes {
+        match shape {
+            Shape::Path(path) if masked || path.is_mask() => (),
+            Shape::Path(path) => match options
+                .expand_strokes
+                .then(|| shape.expanded_stroke())
+                .flatten()
+            {
+                Some(outlines) => ufo_glyph.contours.extend(
+                    outlines
+                        .iter()
+                        .map(|outline| options.round_contour(outline.into())),
+                ),
+                None => ufo_glyph
+                    .contours
+                    .push(options.round_contour(path.as_ref().into())),
+            },
+            Shape::Component(component) => {
+                let decomposed = options
+                    .decompose_smart_components
+                    .then(|| decompose_smart_component(font, component, master_id))
+                    .flatten();
+                match decomposed {
+                    Some(paths) => ufo_glyph
+                        .contours
+                        .extend(paths.iter().map(|path| options.round_contour(path.into()))),
+                    None => ufo_glyph
+                        .components
+                        .push(component_to_ufo(component, options)?),
+                }
+            }
+        }
+    }
+    ufo_glyph.anchors = anchors
+        .into_iter()
+        .flatten()
+        .map(|anchor| {
+            let mut anchor: norad::Anchor = anchor.try_into()?;
+            anchor.x = options.round(anchor.x);
+            anchor.y = options.round(anchor.y);
+            Ok::<_, norad::error::NamingError>(anchor)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+/// The file names in the image store of the images copied so far, by the
+/// path they were read from.
+type ImageNames = HashMap<PathBuf, PathBuf>;
+
+/// Copy the background image of a layer into the image store and place it
+/// on the glyph.
+///
+/// Images from different paths with the same file name are stored under
+/// names made unique with a number, like `image-2.png`.
+fn image_to_ufo(
+    ufo_glyph: &mut norad::Glyph,
+    layer: &Layer,
+    images: &mut norad::ImageStore,
+    names: &mut ImageNames,
+    options: &ConversionOptions,
+) -> Result<(), UfoConversionError> {
+    let Some(image) = &layer.background_image else {
+        return Ok(());
+    };
+    let Some(image_dir) = &options.image_dir else {
+        #[cfg(feature = "log")]
+        log::warn!(
+            "glyph {}: background image {:?} left out, as no image directory was given",
+            ufo_glyph.name(),
+            image.image_path
+        );
+        return Ok(());
+    };
+    let path = image_dir.join(&image.image_path);
+    // Layers commonly share an image, only read it once.
+    let file_name = match names.get(&path) {
+        Some(file_name) => file_name.clone(),
+        None => {
+            let Some(file_name) = unique_image_name(&path, images) else {
+                return Err(UfoConversionError::ReadImage {
+                    source: std::io::ErrorKind::InvalidInput.into(),
+                    path,
+                });
+            };
+            let data = std::fs::read(&path).map_err(|source| UfoConversionError::ReadImage {
+                path: path.clone(),
+                source,
+            })?;
+            images.insert(file_name.clone(), data)?;
+            names.insert(path, file_name.clone());
+            file_name
+        }
+    };
+
+    let [x_scale, xy_scale, yx_scale, y_scale, x_offset, y_offset] =
+        image.transform().as_coeffs().map(|v| options.round(v));
+    ufo_glyph.image = Some(norad::Image {
+        file_name,
+        color: None,
+        transform: norad::AffineTransform {
+            x_scale,
+            xy_scale,
+            yx_scale,
+            y_scale,
+            x_offset,
+            y_offset,
+        },
+    });
+    Ok(())
+}
+
+/// The file name of the image at `path`, numbered if an image of that name is
+/// stored already. `None` if the path has no file name.
+fn unique_image_name(path: &std::path::Path, images: &norad::ImageStore) -> Option<PathBuf> {
+    let file_name = PathBuf::from(path.file_name()?);
+    if !images.contains_key(&file_name) {
+        return Some(file_name);
+    }
+    let stem = path.file_stem()?.to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| PathBuf::from(format!("{stem}-{n}{extension}")))
+        .find(|name| !images.contains_key(name))
+}
+
+/// Decompose a glyph image's transform into a background image, inverting
+/// [`BackgroundImage::transform`]. Shear is lost.
+fn image_from_ufo(image: &norad::Image) -> BackgroundImage {
+    let transform = &image.transform;
+    let s_x = transform.x_scale.hypot(transform.xy_scale);
+    let det = transform.x_scale * transform.y_scale - transform.xy_scale * transform.yx_scale;
+    let s_y = if s_x == 0.0 { 0.0 } else { det / s_x };
+    let angle = transform.xy_scale.atan2(transform.x_scale).to_degrees();
+    BackgroundImage {
+        image_path: image.file_name.to_string_lossy().into_owned(),
+        angle,
+        crop: None,
+        pos: kurbo::Point::new(image.transform.x_offset, image.transform.y_offset),
+        scale: (s_x != 1.0 || s_y != 1.0).then_some(Scale {
+            horizontal: s_x,
+            vertical: s_y,
+        }),
+        locked: false,
+        other_stuff: Default::default(),
+    }
+}
+
+fn brace_layer_name(layer: &Layer, naming: BraceLayerNaming) -> String {
+    match (&layer.name, naming) {
+        (Some(name), BraceLayerNaming::LayerName) => name.clone(),
+        _ => {
+            let coordinates = layer
+                .coordinates()
+                .unwrap_or_default()
+                .iter()
+                .map(f64::to_string)
+                .collect::<Vec<_>>();
+            format!("{{{}}}", coordinates.join(", "))
+        }
+    }
+}
+
+/// Collect the anchors a composite glyph inherits from its components.
+///
+/// The first component contributes all of its anchors. Later components
+/// are treated as marks: a mark attached through `_name` replaces the
+/// `name` anchor with its own, so marks can stack.
+fn propagated_anchors(font: &Font, shapes: &[Shape], master_id: &str, depth: usize) -> Vec<Anchor> {
+    // Guard against components referencing each other in a cycle.
+    const MAX_DEPTH: usize = 32;
+
+    let mut anchors: Vec<Anchor> = Vec::new();
+    let components = shapes.iter().filter_map(|shape| match shape {
+        Shape::Component(component) => Some(component),
+        Shape::Path(_) => None,
+    });
+    for (i, component) in components.enumerate() {
+        let Some(layer) = font
+            .get_glyph(&component.reference)
+            .and_then(|g| g.master_layer(master_id))
+        else {
+            continue;
+        };
+        let component_anchors = match &layer.anchors {
+            Some(anchors) => anchors.clone(),
+            None if depth < MAX_DEPTH => {
+                propagated_anchors(font, &layer.shapes, master_id, depth + 1)
+            }
+            None => continue,
+        };
+        let attachments: HashSet<String> = component_anchors
+            .iter()
+            .filter_map(|a| a.name.strip_prefix('_'))
+            .map(str::to_string)
+            .collect();
+        let transform = component.transform();
+        for mut anchor in component_anchors {
+            anchor.pos = transform * anchor.pos;
+            let existing = anchors.iter().position(|a| a.name == anchor.name);
+            if i == 0 {
+                if existing.is_none() {
+                    anchors.push(anchor);
+                }
+            } else if attachments.contains(&anchor.name) {
+                match existing {
+                    Some(index) => anchors[index] = anchor,
+                    None => anchors.push(anchor),
+                }
+            }
+        }
+    }
+    anchors
+}
+
+/// Decompose a smart component into paths, interpolating the part layers
+/// of the referenced glyph at the component's `piece` values.
+///
+/// Each part axis is interpolated between the master layer and the part
+/// layer that differs from it only on that axis, and the deltas are added
+/// up. Returns `None` for ordinary components and for smart glyphs whose
+/// part layers are missing or incompatible.
+fn decompose_smart_component(
+    font: &Font,
+    component: &Component,
+    master_id: &str,
+) -> Option<Vec<Path>> {
+    let glyph = font.get_glyph(&component.reference)?;
+    let settings = glyph.other_stuff.get("partsSettings")?.as_array()?;
+    let master_layer = glyph.master_layer(master_id)?;
+    let part_selection = |layer: &Layer, axis: &str| {
+        layer
+            .other_stuff
+            .get("partSelection")
+            .and_then(|s| s.get(axis))
+            .and_then(Plist::as_i64)
+    };
+    let layer_paths = |layer: &Layer| -> Vec<Path> { layer.paths().cloned().collect() };
+
+    let mut paths = layer_paths(master_layer);
+    for setting in settings {
+        let axis = setting.get("name")?.as_str()?;
+        let bottom = setting.get("bottomValue").and_then(Plist::as_f64)?;
+        let top = setting.get("topValue").and_then(Plist::as_f64)?;
+        let master_selection = part_selection(master_layer, axis);
+        let axis_value = |selection| if selection == Some(2) { top } else { bottom };
+        let Some(value) = component
+            .other_stuff
+            .get("piece")
+            .and_then(|p| p.get(axis))
+            .and_then(Plist::as_f64)
+        else {
+            continue;
+        };
+        let part_layer = glyph.layers.iter().find(|l| {
+            l.associated_master_id.as_deref() == Some(master_id)
+                && l.other_stuff.contains_key("partSelection")
+                && part_selection(l, axis) != master_selection
+                && settings
+                    .iter()
+                    .filter_map(|s| s.get("name").and_then(Plist::as_str))
+                    .filter(|other| *other != axis)
+                    .all(|other| part_selection(l, other) == part_selection(master_layer, other))
+        })?;
+        let (from, to) = (
+            axis_value(master_selection),
+            axis_value(part_selection(part_layer, axis)),
+        );
+        if from == to {
+            continue;
+        }
+        let t = (value - from) / (to - from);
+        let master_paths = layer_paths(master_layer);
+        let part_paths = layer_paths(part_layer);
+        if part_paths.len() != paths.len() {
+            return None;
+        }
+        for ((path, master_path), part_path) in paths.iter_mut().zip(&master_paths).zip(&part_paths)
+        {
+            if part_path.nodes.len() != path.nodes.len() {
+                return None;
+            }
+            for ((node, master_node), part_node) in path
+                .nodes
+                .iter_mut()
+                .zip(&master_path.nodes)
+                .zip(&part_path.nodes)
+            {
+                node.pt += (part_node.pt - master_node.pt) * t;
+            }
+        }
+    }
+
+    let transform = component.transform();
+    for path in &mut paths {
+        for node in &mut path.nodes {
+            node.pt = transform * node.pt;
+        }
+    }
+    Some(paths)
+}
+
+fn glyph_from_ufo(ufo_glyph: &norad::Glyph, master_id: &str) -> Glyph {
+    let unicodes = (!ufo_glyph.codepoints.is_empty()).then(|| ufo_glyph.codepoints.clone());
+    let mut layer = Layer::new(master_id);
+    layer.width = ufo_glyph.width;
+    (layer.shapes, layer.anchors) = drawing_from_ufo(ufo_glyph);
+    layer.background_image = ufo_glyph.image.as_ref().map(image_from_ufo);
+    hints_from_ufo(&ufo_glyph.lib, &mut layer);
+    Glyph {
+        layers: vec![layer],
+        note: ufo_glyph.note.clone(),
+        color: ufo_glyph
+            .lib
+            .get(PUBLIC_MARK_COLOR)
+            .and_then(|v| v.as_string())
+            .and_then(mark_color_from_ufo),
+        ..Glyph::new(ufo_glyph.name().clone(), unicodes)
+    }
+}
+
+fn background_from_ufo(ufo_glyph: &norad::Glyph) -> BackgroundLayer {
+    let (shapes, anchors) = drawing_from_ufo(ufo_glyph);
+    BackgroundLayer {
+        anchors,
+        shapes,
+        other_stuff: Default::default(),
+    }
+}
+
+fn drawing_from_ufo(ufo_glyph: &norad::Glyph) -> (Vec<Shape>, Option<Vec<Anchor>>) {
+    let shapes = ufo_glyph
+        .contours
+        .iter()
+        .map(|contour| Shape::Path(Box::new(contour.into())))
+        .chain(
+            ufo_glyph
+                .components
+                .iter()
+                .map(|component| Shape::Component(component.into())),
+        )
+        .collect();
+    // Glyphs anchors need a name, so unnamed ones are left out.
+    let anchors: Vec<Anchor> = ufo_glyph
+        .anchors
+        .iter()
+        .filter(|anchor| anchor.name.is_some())
+        .map(Into::into)
+        .collect();
+    let anchors = (!anchors.is_empty()).then_some(anchors);
+    (shapes, anchors)
+}
+
+/// Convert a palette color to the RGBA array of floats ufo2ft expects.
+fn color_to_ufo(color: &Color) -> Option<plist::Value> {
+    let (r, g, b, a) = match *color {
+        Color::Rgba(r, g, b, a) => (r, g, b, a),
+        Color::GreyAlpha(g, a) => (g, g, g, a),
+        Color::Index(_) | Color::Cmyka(..) => return None,
+    };
+    let rgba: Vec<plist::Value> = [r, g, b, a]
+        .into_iter()
+        .map(|c| (f64::from(c) / 255.0).into())
+        .collect();
+    Some(rgba.into())
+}
+
+/// Convert a label color to the `r,g,b,a` string of `public.markColor`.
+///
+/// CMYK colors have no equivalent and are left out.
+fn mark_color_to_ufo(color: &Color) -> Option<String> {
+    let (r, g, b, a) = match *color {
+        Color::Index(index) => *MARK_COLORS.get(usize::try_from(index).ok()?)?,
+        Color::Rgba(r, g, b, a) => (channel(r), channel(g), channel(b), channel(a)),
+        Color::GreyAlpha(g, a) => (channel(g), channel(g), channel(g), channel(a)),
+        Color::Cmyka(..) => return None,
+    };
+    Some(format!("{r},{g},{b},{a}"))
+}
+
+/// A color channel as a fraction, rounded like UFO colors usually are.
+fn channel(c: u8) -> f64 {
+    (f64::from(c) / 255.0 * 10000.0).round() / 10000.0
+}
+
+/// Parse a `public.markColor` string, picking the label color of Glyphs it
+/// is, if any.
+fn mark_color_from_ufo(color: &str) -> Option<Color> {
+    let channels = color
+        .split(',')
+        .map(|c| c.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [r, g, b, a] = channels[..] else {
+        return None;
+    };
+    let index = MARK_COLORS.iter().position(|&(mr, mg, mb, ma)| {
+        [(r, mr), (g, mg), (b, mb), (a, ma)]
+            .iter()
+            .all(|(c, m)| (c - m).abs() < 0.005)
+    });
+    if let Some(index) = index {
+        return Some(Color::Index(index as i64));
+    }
+    let byte = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Some(Color::Rgba(byte(r), byte(g), byte(b), byte(a)))
+}
+
+/// Read a lib key holding an array of strings, ignoring anything else.
+fn lib_string_array<'a>(lib: &'a norad::Plist, key: &str) -> Vec<&'a str> {
+    lib.get(key)
+        .and_then(|value| value.as_array())
+        .map(|array| array.iter().filter_map(|v| v.as_string()).collect())
+        .unwrap_or_default()
+}
 
 impl From<&norad::Contour> for Path {
     fn from(contour: &norad::Contour) -> Self {
@@ -74,31 +1081,64 @@ impl From<&Node> for norad::ContourPoint {
 
 impl From<&norad::Component> for Component {
     fn from(component: &norad::Component) -> Self {
-        let (rotation, slant, scale, pos) = if component.transform == Default::default() {
-            (None, None, None, None)
-        } else {
-            let (s_x, s_y, r) = transform_struct_to_scale_and_rotation(&component.transform);
-            (
-                Some(r),
-                None,
-                Some(Scale {
-                    horizontal: s_x,
-                    vertical: s_y,
-                }),
-                Some(kurbo::Point::new(
-                    component.transform.x_offset,
-                    component.transform.y_offset,
-                )),
-            )
-        };
-        Self {
+        let t = &component.transform;
+        let mut glyphs_component = Self {
             reference: component.base.to_string(),
-            rotation,
-            pos,
-            scale,
-            slant,
+            rotation: None,
+            pos: None,
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
             other_stuff: Default::default(),
+        };
+        glyphs_component.set_transform(kurbo::Affine::new([
+            t.x_scale, t.xy_scale, t.yx_scale, t.y_scale, t.x_offset, t.y_offset,
+        ]));
+        glyphs_component
+    }
+}
+
+impl Component {
+    /// Set the position, rotation, scale and slant of the component from a
+    /// transformation, the inverse of [`Component::transform`]. They are all
+    /// cleared for the identity transformation.
+    pub fn set_transform(&mut self, transform: kurbo::Affine) {
+        if transform == kurbo::Affine::IDENTITY {
+            (self.rotation, self.slant, self.scale, self.pos) = (None, None, None, None);
+            return;
         }
+        let [x_scale, xy_scale, yx_scale, y_scale, x_offset, y_offset] = transform.as_coeffs();
+        let transform = norad::AffineTransform {
+            x_scale,
+            xy_scale,
+            yx_scale,
+            y_scale,
+            x_offset,
+            y_offset,
+        };
+        let (s_x, s_y, r, slant) = match transform_struct_to_scale_rotation_and_slant(&transform) {
+            Some((s_x, s_y, r, slant)) => (
+                s_x,
+                s_y,
+                r,
+                Some(Scale {
+                    horizontal: slant,
+                    vertical: 0.0,
+                }),
+            ),
+            None => {
+                let (s_x, s_y, r) = transform_struct_to_scale_and_rotation(&transform);
+                (s_x, s_y, r, None)
+            }
+        };
+        self.rotation = Some(r);
+        self.slant = slant;
+        self.scale = Some(Scale {
+            horizontal: s_x,
+            vertical: s_y,
+        });
+        self.pos = Some(kurbo::Point::new(x_offset, y_offset));
     }
 }
 
@@ -142,58 +1182,79 @@ fn transform_struct_to_scale_and_rotation(transform: &norad::AffineTransform) ->
     (s_x, s_y, r)
 }
 
+/// Decompose a sheared transform into scale, rotation (in degrees) and a
+/// horizontal slant (in degrees), inverting the composition order used in
+/// `TryFrom<&Component> for norad::Component`.
+///
+/// Returns `None` for transforms without shear, which are decomposed the
+/// glyphsLib way instead, and for degenerate transforms.
+fn transform_struct_to_scale_rotation_and_slant(
+    transform: &norad::AffineTransform,
+) -> Option<(f64, f64, f64, f64)> {
+    // The columns of a rotation times a scale are orthogonal, so any overlap
+    // between them is shear.
+    let overlap = transform.x_scale * transform.yx_scale + transform.xy_scale * transform.y_scale;
+    let s_x = transform.x_scale.hypot(transform.xy_scale);
+    let s_y_len = transform.yx_scale.hypot(transform.y_scale);
+    if s_x == 0.0 || s_y_len == 0.0 || (overlap / (s_x * s_y_len)).abs() < 1e-9 {
+        return None;
+    }
+
+    let det = transform.x_scale * transform.y_scale - transform.xy_scale * transform.yx_scale;
+    let (sin, cos) = (transform.xy_scale / s_x, transform.x_scale / s_x);
+    let s_y = det / s_x;
+    let skew = (cos * transform.yx_scale + sin * transform.y_scale) / s_x;
+    let r = transform.xy_scale.atan2(transform.x_scale).to_degrees();
+
+    Some((s_x, s_y, r, skew.atan().to_degrees()))
+}
+
 impl TryFrom<&Component> for norad::Component {
     type Error = norad::error::NamingError;
 
     fn try_from(component: &Component) -> Result<Self, Self::Error> {
-        let name = norad::Name::new(&component.reference)?;
-
-        let offset_x = component.pos.map(|p| p.x).unwrap_or(0.0);
-        let offset_y = component.pos.map(|p| p.y).unwrap_or(0.0);
-        let rotation = component.rotation.unwrap_or(0.0).to_radians();
-        let scale_x = component
-            .scale
-            .as_ref()
-            .map(|s| s.horizontal)
-            .unwrap_or(1.0);
-        let scale_y = component.scale.as_ref().map(|s| s.vertical).unwrap_or(1.0);
-        let skew_x = component
-            .slant
-            .as_ref()
-            .map(|p| p.horizontal)
-            .unwrap_or(0.0);
-        let skew_y = component.slant.as_ref().map(|p| p.vertical).unwrap_or(0.0);
-
-        // Warning: Don't use kurbo's .then_* methods because they apply the ops
-        // in the wrong order! This matches the order glyphsLib does it in.
-        let transform = kurbo::Affine::translate(kurbo::Vec2::new(offset_x, offset_y))
-            * kurbo::Affine::rotate(rotation)
-            * kurbo::Affine::scale_non_uniform(scale_x, scale_y)
-            * kurbo::Affine::skew(skew_x, skew_y);
-
-        // Round values for roundtrip testing.
-        let transform = norad::AffineTransform {
-            x_scale: f64_precision(transform.as_coeffs()[0], 5),
-            xy_scale: f64_precision(transform.as_coeffs()[1], 5),
-            yx_scale: f64_precision(transform.as_coeffs()[2], 5),
-            y_scale: f64_precision(transform.as_coeffs()[3], 5),
-            x_offset: f64_precision(transform.as_coeffs()[4], 5),
-            y_offset: f64_precision(transform.as_coeffs()[5], 5),
-        };
-
-        Ok(Self::new(name, transform, None, None))
+        component_to_ufo(component, &ConversionOptions::default())
     }
 }
 
+fn component_to_ufo(
+    component: &Component,
+    options: &ConversionOptions,
+) -> Result<norad::Component, norad::error::NamingError> {
+    let name = norad::Name::new(&component.reference)?;
+    let [x_scale, xy_scale, yx_scale, y_scale, x_offset, y_offset] =
+        component.transform().as_coeffs().map(|v| options.round(v));
+    let transform = norad::AffineTransform {
+        x_scale,
+        xy_scale,
+        yx_scale,
+        y_scale,
+        x_offset,
+        y_offset,
+    };
+
+    Ok(norad::Component::new(name, transform, None, None))
+}
+
+/// Round to the number of decimal places, leaving values alone that have
+/// fewer than that anyway, as a float can't scale them up far enough.
 fn f64_precision(v: f64, precision: i32) -> f64 {
     let r = 10f64.powi(precision);
-    (v * r).round() / r
+    match (v * r).is_finite() {
+        true => (v * r).round() / r,
+        false => v,
+    }
 }
 
+/// Unnamed anchors get an empty name.
 impl From<&norad::Anchor> for Anchor {
     fn from(anchor: &norad::Anchor) -> Self {
         Self {
-            name: anchor.name.as_ref().unwrap().as_str().to_string(),
+            name: anchor
+                .name
+                .as_ref()
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
             orientation: None,
             pos: kurbo::Point::new(anchor.x, anchor.y),
             user_data: Default::default(),
@@ -234,10 +1295,7 @@ mod tests {
         roundtrip_component(transform);
     }
 
-    /// Test that shear gets lost in translation. This is unwanted, but is due
-    /// to the reference Python code in glyphsLib not extracting it.
     #[test]
-    #[should_panic]
     fn roundtrip_component_shear() {
         let transform = norad::AffineTransform {
             x_scale: 0.5,
@@ -250,18 +1308,40 @@ mod tests {
         roundtrip_component(transform);
     }
 
+    #[test]
+    fn component_shear_to_slant() {
+        let transform = norad::AffineTransform {
+            x_scale: 1.0,
+            xy_scale: 0.0,
+            yx_scale: 10f64.to_radians().tan(),
+            y_scale: 1.0,
+            x_offset: 0.0,
+            y_offset: 0.0,
+        };
+        let name = norad::Name::new("A").unwrap();
+        let norad_component = norad::Component::new(name, transform, None, None);
+        let component: crate::Component = (&norad_component).into();
+
+        let slant = component.slant.expect("shear should become slant");
+        assert!(approx_equal(slant.horizontal, 10.0, 0.00001), "{slant:?}");
+        assert_eq!(slant.vertical, 0.0);
+        assert!(approx_equal(component.rotation.unwrap(), 0.0, 0.00001));
+    }
+
     proptest! {
         #[test]
         fn roundtrip_components(
             x_scale in -10000.0..10000.0,
+            xy_scale in -10000.0..10000.0,
+            yx_scale in -10000.0..10000.0,
             y_scale in -10000.0..10000.0,
             x_offset in -10000.0..10000.0,
             y_offset in -10000.0..10000.0,
         ) {
             let transform = norad::AffineTransform {
                 x_scale,
-                xy_scale: 0.0, // Also proptest once shear is extracted.
-                yx_scale: 0.0, // Also proptest once shear is extracted.
+                xy_scale,
+                yx_scale,
                 y_scale,
                 x_offset,
                 y_offset,
@@ -305,6 +1385,551 @@ mod tests {
         );
     }
 
+    #[test]
+    fn skip_export_glyphs_to_ufo() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+
+        let skip_export_glyphs = ufo
+            .lib
+            .get("public.skipExportGlyphs")
+            .and_then(|v| v.as_array())
+            .expect("public.skipExportGlyphs should be set")
+            .iter()
+            .map(|v| v.as_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(skip_export_glyphs, ["_part.test", "_corner.cut"]);
+
+        let ufo = crate::Font::new().to_ufo("m01").unwrap();
+        assert!(ufo.lib.get("public.skipExportGlyphs").is_none());
+    }
+
+    #[test]
+    fn skip_export_glyphs_from_ufo() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+        let font = crate::Font::from_ufo(&ufo).unwrap();
+
+        let non_exporting = font
+            .glyphs
+            .iter()
+            .filter(|g| !g.export)
+            .map(|g| g.glyphname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(non_exporting, ["_part.test", "_corner.cut"]);
+        assert!(font.get_glyph("A").unwrap().export);
+    }
+
+    #[test]
+    fn unusual_ufo_values() {
+        let mut ufo = crate::Font::new().to_ufo("m01").unwrap();
+        let space = ufo.default_layer_mut().get_glyph_mut("space").unwrap();
+        space
+            .anchors
+            .push(norad::Anchor::new(10.0, 20.0, None, None, None, None));
+        let font = crate::Font::from_ufo(&ufo).unwrap();
+        assert_eq!(font.glyphs[0].layers[0].anchors, None);
+
+        ufo.font_info.units_per_em = norad::NonNegativeIntegerOrFloat::new(70000.0);
+        assert!(matches!(
+            crate::Font::from_ufo(&ufo),
+            Err(super::UfoConversionError::UnitsPerEm(_))
+        ));
+    }
+
+    #[test]
+    fn expand_strokes() {
+        let mut font = crate::Font::new();
+        let mut path = crate::Path::new(false);
+        path.add((0.0, 0.0), crate::NodeType::Line);
+        path.add((100.0, 0.0), crate::NodeType::Line);
+        path.attr = Some(crate::font::PathAttrs {
+            stroke_width: Some(20.0),
+            ..Default::default()
+        });
+        font.glyphs[0].layers[0].shapes = vec![crate::Shape::Path(Box::new(path))];
+
+        let ufo = font.to_ufo("m01").unwrap();
+        let contour = &ufo.get_glyph("space").unwrap().contours[0];
+        assert!(contour.is_closed());
+        assert_eq!(contour.points.len(), 4);
+
+        let options = super::ConversionOptions {
+            expand_strokes: false,
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        let contour = &ufo.get_glyph("space").unwrap().contours[0];
+        assert!(!contour.is_closed());
+    }
+
+    #[test]
+    fn mark_colors() {
+        let mut font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let glyph = font.get_glyph_mut("A").unwrap();
+        glyph.color = Some(crate::Color::Index(4));
+        glyph
+            .layers
+            .iter_mut()
+            .find(|l| l.layer_id == "m01")
+            .unwrap()
+            .color = Some(crate::Color::Rgba(255, 0, 51, 255));
+        font.get_glyph_mut("B").unwrap().color = Some(crate::Color::Index(4));
+
+        let ufo = font.to_ufo("m01").unwrap();
+        let mark_color = |name: &str| {
+            ufo.get_glyph(name)
+                .unwrap()
+                .lib
+                .get(super::PUBLIC_MARK_COLOR)
+                .and_then(|v| v.as_string())
+                .map(str::to_string)
+        };
+        assert_eq!(mark_color("A").as_deref(), Some("1,0,0.2,1"));
+        assert_eq!(mark_color("B").as_deref(), Some("0.67,0.95,0.38,1"));
+
+        let font = crate::Font::from_ufo(&ufo).unwrap();
+        assert_eq!(
+            font.get_glyph("A").unwrap().color,
+            Some(crate::Color::Rgba(255, 0, 51, 255))
+        );
+        assert_eq!(
+            font.get_glyph("B").unwrap().color,
+            Some(crate::Color::Index(4))
+        );
+        assert_eq!(
+            super::mark_color_from_ufo("0.5, 0.09, 0.79, 1"),
+            Some(crate::Color::Index(8))
+        );
+        assert_eq!(super::mark_color_from_ufo("1,0"), None);
+    }
+
+    #[test]
+    fn postscript_names() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+
+        let postscript_names = ufo
+            .lib
+            .get("public.postscriptNames")
+            .and_then(|v| v.as_dictionary())
+            .expect("public.postscriptNames should be set");
+        assert_eq!(postscript_names.len(), 1);
+        assert_eq!(
+            postscript_names.get("Smily").and_then(|v| v.as_string()),
+            Some("someSmily")
+        );
+
+        let font = crate::Font::from_ufo(&ufo).unwrap();
+        assert_eq!(
+            font.get_glyph("Smily").unwrap().production.as_deref(),
+            Some("someSmily")
+        );
+        assert_eq!(font.get_glyph("A").unwrap().production, None);
+
+        let ufo = crate::Font::new().to_ufo("m01").unwrap();
+        assert!(ufo.lib.get("public.postscriptNames").is_none());
+    }
+
+    #[test]
+    fn metrics_to_ufo() {
+        let mut font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let parameters = crate::Plist::parse(
+            "({name = typoAscender; value = 900;}, \
+              {name = winDescent; value = 250;}, \
+              {disabled = 1; name = hheaLineGap; value = 10;}, \
+              {name = underlinePosition; value = -75.5;})",
+        )
+        .unwrap();
+        font.font_master[0]
+            .other_stuff
+            .insert("customParameters".into(), parameters);
+        let ufo = font.to_ufo("m01").unwrap();
+
+        let info = &ufo.font_info;
+        assert_eq!(info.ascender, Some(800.0));
+        assert_eq!(info.cap_height, Some(700.0));
+        // Not the filtered x-height metric further down.
+        assert_eq!(info.x_height, Some(500.0));
+        assert_eq!(info.descender, Some(-200.0));
+        assert_eq!(info.italic_angle, Some(0.0));
+        assert_eq!(info.open_type_os2_typo_ascender, Some(900));
+        assert_eq!(info.open_type_os2_win_descent, Some(250));
+        assert_eq!(info.open_type_hhea_line_gap, None);
+        assert_eq!(info.postscript_underline_position, Some(-75.5));
+
+        let font = crate::Font::from_ufo(&ufo).unwrap();
+        let master = &font.font_master[0];
+        let metric = |metric_type| {
+            master
+                .iter_metrics(&font)
+                .find(|(m, _)| m.r#type.as_ref() == Some(&metric_type))
+                .map(|(_, v)| v.pos)
+        };
+        assert_eq!(metric(crate::MetricType::Ascender), Some(800.0));
+        assert_eq!(metric(crate::MetricType::CapHeight), Some(700.0));
+        assert_eq!(metric(crate::MetricType::XHeight), Some(500.0));
+        assert_eq!(metric(crate::MetricType::Descender), Some(-200.0));
+        assert_eq!(metric(crate::MetricType::ItalicAngle), Some(0.0));
+        assert_eq!(
+            master.custom_parameter("typoAscender"),
+            Some(&crate::Plist::Integer(900))
+        );
+        assert_eq!(
+            master.custom_parameter("underlinePosition"),
+            Some(&crate::Plist::Float(-75.5))
+        );
+        assert_eq!(master.custom_parameter("hheaLineGap"), None);
+    }
+
+    #[test]
+    fn color_layers_to_ufo() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+
+        assert!(ufo.get_glyph("C.color0").is_some());
+        assert!(ufo.get_glyph("C.color1").is_some());
+        let glyph_order = super::lib_string_array(&ufo.lib, super::PUBLIC_GLYPH_ORDER);
+        assert_eq!(glyph_order.len(), font.glyphs.len() + 2);
+        assert_eq!(glyph_order[font.glyphs.len()..], ["C.color0", "C.color1"]);
+        let color_layers = ufo
+            .lib
+            .get("com.github.googlei18n.ufo2ft.colorLayers")
+            .and_then(|v| v.as_dictionary())
+            .expect("color layers should be set");
+        assert_eq!(
+            color_layers.get("C"),
+            Some(&plist::Value::Array(vec![
+                plist::Value::Array(vec!["C.color0".into(), 1i64.into()]),
+                plist::Value::Array(vec!["C.color1".into(), 0xFFFFi64.into()]),
+            ]))
+        );
+        assert_eq!(
+            ufo.lib.get("com.github.googlei18n.ufo2ft.colorPalettes"),
+            Some(&plist::Value::Array(vec![plist::Value::Array(vec![
+                plist::Value::Array(vec![1.0.into(), 0.0.into(), 0.0.into(), 1.0.into()]),
+                plist::Value::Array(vec![
+                    1.0.into(),
+                    (128.0 / 255.0).into(),
+                    0.0.into(),
+                    1.0.into()
+                ]),
+            ])]))
+        );
+
+        let ufo = font.to_ufo("C2ECF50A-02EF-4989-A14C-AF8E838D1105").unwrap();
+        assert!(ufo
+            .lib
+            .get("com.github.googlei18n.ufo2ft.colorLayers")
+            .is_none());
+    }
+
+    #[test]
+    fn huge_precision() {
+        let options = super::ConversionOptions {
+            precision: Some(u32::MAX),
+            ..Default::default()
+        };
+        assert_eq!(options.round(1.23456789), 1.23456789);
+        assert_eq!(super::f64_precision(1.25, 400), 1.25);
+        assert_eq!(super::f64_precision(1.25, 1), 1.3);
+    }
+
+    #[test]
+    fn component_precision() {
+        let component = crate::Component {
+            reference: "A".into(),
+            rotation: None,
+            pos: Some(kurbo::Point::new(10.123456789, 0.0)),
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        };
+
+        let options = |precision| super::ConversionOptions {
+            precision,
+            ..Default::default()
+        };
+        let rounded = super::component_to_ufo(&component, &options(Some(2))).unwrap();
+        assert_eq!(rounded.transform.x_offset, 10.12);
+        let default: norad::Component = (&component).try_into().unwrap();
+        assert_eq!(default.transform.x_offset, 10.12346);
+        let exact = super::component_to_ufo(&component, &options(None)).unwrap();
+        assert_eq!(exact.transform.x_offset, 10.123456789);
+    }
+
+    #[test]
+    fn point_and_anchor_precision() {
+        let mut font = crate::Font::new();
+        let mut path = crate::Path::new(true);
+        path.add((0.4, 0.6), crate::NodeType::Line);
+        path.add((100.25, 0.0), crate::NodeType::Line);
+        let layer = &mut font.glyphs[0].layers[0];
+        layer.shapes = vec![crate::Shape::Path(Box::new(path))];
+        layer.anchors = Some(vec![crate::Anchor {
+            name: "top".into(),
+            orientation: None,
+            pos: kurbo::Point::new(50.5, 700.123),
+            user_data: Default::default(),
+        }]);
+        let glyphname = font.glyphs[0].glyphname.clone();
+
+        let coordinates = |precision| {
+            let options = super::ConversionOptions {
+                precision,
+                ..Default::default()
+            };
+            let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+            let glyph = ufo.get_glyph(glyphname.as_str()).unwrap();
+            let mut coordinates = glyph.contours[0]
+                .points
+                .iter()
+                .map(|p| (p.x, p.y))
+                .collect::<Vec<_>>();
+            coordinates.push((glyph.anchors[0].x, glyph.anchors[0].y));
+            coordinates
+        };
+        assert_eq!(
+            coordinates(Some(0)),
+            [(100.0, 0.0), (0.0, 1.0), (51.0, 700.0)]
+        );
+        assert_eq!(
+            coordinates(Some(1)),
+            [(100.3, 0.0), (0.4, 0.6), (50.5, 700.1)]
+        );
+        assert_eq!(
+            coordinates(None),
+            [(100.25, 0.0), (0.4, 0.6), (50.5, 700.123)]
+        );
+    }
+
+    #[test]
+    fn propagate_anchors() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+        assert!(ufo.get_glyph("Ä").unwrap().anchors.is_empty());
+
+        let options = super::ConversionOptions {
+            propagate_anchors: true,
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        let anchors = ufo
+            .get_glyph("Ä")
+            .unwrap()
+            .anchors
+            .iter()
+            .map(|a| (a.name.as_ref().unwrap().as_str(), a.x, a.y))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            anchors,
+            [
+                ("bottom", 230.0, 0.0),
+                ("ogonek", 413.0, 10.0),
+                ("top", 10.0, 863.0),
+                ("top.alt", 10.0, 635.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn decompose_smart_components() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+        assert_eq!(ufo.get_glyph("D").unwrap().components.len(), 1);
+
+        let options = super::ConversionOptions {
+            decompose_smart_components: true,
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        let glyph = ufo.get_glyph("D").unwrap();
+        assert!(glyph.components.is_empty());
+        let xs = glyph.contours[0]
+            .points
+            .iter()
+            .map(|p| p.x)
+            .collect::<Vec<_>>();
+        // Width = 29.74825 of 0..100 moves the node at x = 138 in the master
+        // layer towards x = 988 in the "Wide" part layer.
+        assert!(
+            approx_equal(xs[2], 138.0 + 850.0 * 0.2974825, 0.00001),
+            "{xs:?}"
+        );
+        assert!(approx_equal(xs[3], 38.0, 0.00001), "{xs:?}");
+    }
+
+    #[test]
+    fn brace_layers_to_ufo() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+        let layer = ufo.layers.get("{450, 0, 0}").expect("brace layer");
+        assert!(layer.contains_glyph("Smily"));
+
+        let options = super::ConversionOptions {
+            brace_layer_naming: super::BraceLayerNaming::LayerName,
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        let layer = ufo.layers.get("25. Feb. 23, 15:21").expect("brace layer");
+        assert!(layer.contains_glyph("Smily"));
+    }
+
+    #[test]
+    fn masks_to_ufo() {
+        let mut font = crate::Font::new();
+        let mut glyph = crate::Glyph::new(norad::Name::new("a").unwrap(), None);
+        let mut layer = crate::Layer::new("m01");
+        for (size, mask) in [(100.0, None), (50.0, Some(1))] {
+            let mut path = crate::Path::new(true);
+            for pt in [(size, 0.0), (size, size), (0.0, size), (0.0, 0.0)] {
+                path.add(pt, crate::NodeType::Line);
+            }
+            path.attr = Some(crate::font::PathAttrs {
+                mask,
+                ..Default::default()
+            });
+            layer.shapes.push(crate::Shape::Path(Box::new(path)));
+        }
+        glyph.layers.push(layer);
+        font.glyphs.push(glyph);
+
+        // The mask isn't ink.
+        let ufo = font.to_ufo("m01").unwrap();
+        assert_eq!(ufo.get_glyph("a").unwrap().contours.len(), 1);
+
+        fn cut_out(path: &kurbo::BezPath, mask: &kurbo::BezPath) -> kurbo::BezPath {
+            let mut result = path.clone();
+            result.extend(mask.reverse_subpaths());
+            result
+        }
+        let options = super::ConversionOptions {
+            mask_op: Some(cut_out),
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        let contours = &ufo.get_glyph("a").unwrap().contours;
+        assert_eq!(contours.len(), 2);
+        let xs = contours[1].points.iter().map(|p| p.x);
+        assert_eq!(xs.fold(0.0, f64::max), 50.0);
+    }
+
+    #[test]
+    fn background_images_with_same_name() {
+        let mut font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let dir = std::env::temp_dir().join("glyphs_plist_image_names");
+        for sub in ["a", "b"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+            std::fs::copy("testdata/files/Smily.png", dir.join(sub).join("Smily.png")).unwrap();
+        }
+        let image = font.get_glyph("C").unwrap().layers[0]
+            .background_image
+            .clone()
+            .unwrap();
+        for (glyph, path) in [
+            ("A", "a/Smily.png"),
+            ("B", "b/Smily.png"),
+            ("C", "a/Smily.png"),
+        ] {
+            font.get_glyph_mut(glyph).unwrap().layers[0].background_image =
+                Some(crate::BackgroundImage {
+                    image_path: path.into(),
+                    ..image.clone()
+                });
+        }
+
+        let options = super::ConversionOptions {
+            image_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ufo.images.len(), 2);
+        let file_name = |glyph| {
+            let image = ufo.get_glyph(glyph).unwrap().image.as_ref().unwrap();
+            image.file_name.to_str().unwrap().to_string()
+        };
+        assert_eq!(file_name("A"), "Smily.png");
+        assert_eq!(file_name("B"), "Smily-2.png");
+        assert_eq!(file_name("C"), "Smily.png");
+    }
+
+    #[test]
+    fn background_images() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let ufo = font.to_ufo("m01").unwrap();
+        assert!(ufo.images.is_empty());
+        assert!(ufo.get_glyph("C").unwrap().image.is_none());
+
+        let options = super::ConversionOptions {
+            image_dir: Some("testdata".into()),
+            ..Default::default()
+        };
+        let ufo = font.to_ufo_with_options("m01", &options).unwrap();
+        let file_name = std::path::PathBuf::from("Smily.png");
+        assert!(ufo.images.contains_key(&file_name));
+        let image = ufo.get_glyph("C").unwrap().image.as_ref().unwrap();
+        assert_eq!(image.file_name, file_name);
+        assert_eq!(image.transform.x_offset, 61.0);
+        assert_eq!(image.transform.y_offset, 90.0);
+
+        let font_from_ufo = crate::Font::from_ufo(&ufo).unwrap();
+        let layer = &font_from_ufo.get_glyph("C").unwrap().layers[0];
+        let background_image = layer.background_image.clone().unwrap();
+        assert_eq!(background_image.image_path, "Smily.png");
+        assert_eq!(background_image.pos, kurbo::Point::new(61.0, 90.0));
+        assert!((background_image.angle - 3.0).abs() < 1e-3);
+        let scale = background_image.scale.unwrap();
+        assert!((scale.horizontal - 0.529).abs() < 1e-4);
+        assert!((scale.vertical - 0.8).abs() < 1e-4);
+
+        // The second master's image isn't next to the file.
+        assert!(matches!(
+            font.to_ufo_with_options("C2ECF50A-02EF-4989-A14C-AF8E838D1105", &options),
+            Err(super::UfoConversionError::ReadImage { .. })
+        ));
+    }
+
+    #[test]
+    fn roundtrip_background_layer() {
+        let mut path = crate::Path::new(true);
+        path.add((0.0, 0.0), crate::NodeType::Line);
+        path.add((100.0, 0.0), crate::NodeType::Line);
+        path.add((100.0, 100.0), crate::NodeType::Line);
+        let background = crate::BackgroundLayer {
+            anchors: Some(vec![crate::Anchor {
+                name: "top".into(),
+                orientation: None,
+                pos: kurbo::Point::new(50.0, 100.0),
+                user_data: Default::default(),
+            }]),
+            shapes: vec![crate::Shape::Path(Box::new(path))],
+            other_stuff: Default::default(),
+        };
+        let mut font = crate::Font::new();
+        font.glyphs[0].layers[0].background = Some(background.clone());
+
+        let ufo = font.to_ufo("m01").unwrap();
+        let ufo_background = ufo
+            .layers
+            .get("public.background")
+            .and_then(|l| l.get_glyph("space"))
+            .expect("background should be written to public.background");
+        assert_eq!(ufo_background.contours.len(), 1);
+        assert_eq!(ufo_background.width, 200.0);
+
+        let font = crate::Font::from_ufo(&ufo).unwrap();
+        assert_eq!(font.glyphs[0].layers[0].background, Some(background));
+    }
+
+    #[test]
+    fn no_background_layer_without_backgrounds() {
+        let ufo = crate::Font::new().to_ufo("m01").unwrap();
+        assert!(ufo.layers.get("public.background").is_none());
+    }
+
     fn approx_equal(a: f64, b: f64, tolerance: f64) -> bool {
         (a - b).abs() < tolerance
     }
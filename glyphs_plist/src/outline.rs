@@ -0,0 +1,801 @@
+//! Conversion between Glyphs outlines and kurbo Bézier paths.
+
+use kurbo::{BezPath, ParamCurve, PathEl, Point, Rect, Shape as _};
+
+use crate::hints::shift_hints;
+use crate::masks::{masked_bezpath, MaskOp};
+use crate::pen::{Pen, ShapePen};
+use crate::{Font, Glyph, Layer, NodeType, Path, Shape};
+
+/// How deeply nested components are followed before giving up, so that
+/// components referencing each other in a cycle don't recurse forever.
+pub(crate) const MAX_COMPONENT_DEPTH: usize = 32;
+
+impl Path {
+    /// Convert the path into a kurbo Bézier path, drawn as by
+    /// [`Path::draw`].
+    pub fn to_bezpath(&self) -> BezPath {
+        let mut bez = BezPath::new();
+        self.draw(&mut bez);
+        bez
+    }
+
+    /// Convert a kurbo Bézier path into paths, one per subpath.
+    ///
+    /// Closed subpaths store their starting node at the end of the node list,
+    /// like Glyphs.app does. Smoothness isn't recorded in a Bézier path, so
+    /// all on-curve nodes are corners.
+    pub fn from_bezpath(bez: &BezPath) -> Vec<Path> {
+        let mut pen = ShapePen::new();
+        for el in bez.elements() {
+            match *el {
+                PathEl::MoveTo(pt) => pen.move_to(pt),
+                PathEl::LineTo(pt) => pen.line_to(pt),
+                PathEl::QuadTo(c, pt) => pen.quad_to(c, pt),
+                PathEl::CurveTo(c1, c2, pt) => pen.curve_to(c1, c2, pt),
+                PathEl::ClosePath => pen.close_path(),
+            }
+        }
+        pen.into_paths()
+    }
+}
+
+impl Path {
+    /// Whether the path runs clockwise, in the y-up coordinates of a font.
+    ///
+    /// Open paths are treated as if they were closed with a straight line.
+    pub fn is_clockwise(&self) -> bool {
+        self.signed_area() < 0.0
+    }
+
+    /// Make the path run clockwise or counter-clockwise, reversing it if
+    /// needed.
+    pub fn set_direction(&mut self, clockwise: bool) {
+        if self.is_clockwise() != clockwise {
+            self.reverse_direction();
+        }
+    }
+
+    /// Reverse the direction the path runs in, keeping the outline the same.
+    ///
+    /// Unlike [`Path::reverse`], which only reverses the node list, this moves
+    /// the segment types to the nodes that end the segments in the new
+    /// direction, and keeps the starting node of a closed path at the end of
+    /// the node list.
+    pub fn reverse_direction(&mut self) {
+        let on_curves: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].node_type != NodeType::OffCurve)
+            .collect();
+        let mut node_types: Vec<NodeType> = self.nodes.iter().map(|n| n.node_type).collect();
+        for (j, &i) in on_curves.iter().enumerate() {
+            // Reversed, each on-curve node ends the segment that used to
+            // start at it, i.e. the one ending at the next on-curve node.
+            let next = match on_curves.get(j + 1) {
+                Some(&next) => Some(next),
+                None if self.closed => Some(on_curves[0]),
+                None => None,
+            };
+            let segment = next.map_or(NodeType::Line, |next| self.nodes[next].node_type);
+            node_types[i] = segment_node_type(segment, is_smooth(self.nodes[i].node_type));
+        }
+        for (node, node_type) in self.nodes.iter_mut().zip(node_types) {
+            node.node_type = node_type;
+        }
+
+        self.nodes.reverse();
+        if self.closed && !self.nodes.is_empty() {
+            self.nodes.rotate_left(1);
+        }
+    }
+
+    /// Transform the nodes of the path. A mirroring transformation reverses
+    /// the path too, so that it keeps running in the same direction.
+    pub fn apply_affine(&mut self, transform: kurbo::Affine) {
+        for node in &mut self.nodes {
+            node.pt = transform * node.pt;
+        }
+        if transform.determinant() < 0.0 {
+            self.reverse_direction();
+        }
+    }
+}
+
+impl Layer {
+    /// Orient the closed paths of the layer by how deeply they are nested in
+    /// the other paths of the layer.
+    ///
+    /// With `postscript`, outer paths run counter-clockwise and the paths
+    /// cutting holes into them clockwise, the convention Glyphs.app uses and
+    /// CFF outlines expect. Otherwise the directions are swapped, as
+    /// TrueType outlines expect. Open paths are left alone.
+    pub fn correct_path_direction(&mut self, postscript: bool) {
+        let bezpaths: Vec<Option<BezPath>> = self
+            .shapes
+            .iter()
+            .map(|shape| match shape {
+                Shape::Path(path) if path.closed => Some(path.to_bezpath()),
+                _ => None,
+            })
+            .collect();
+        let depths: Vec<usize> = bezpaths
+            .iter()
+            .enumerate()
+            .map(|(i, bez)| {
+                let Some(pt) = bez
+                    .as_ref()
+                    .and_then(|b| b.segments().next())
+                    .map(|s| s.eval(0.5))
+                else {
+                    return 0;
+                };
+                bezpaths
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, other)| {
+                        j != i && other.as_ref().is_some_and(|other| other.winding(pt) != 0)
+                    })
+                    .count()
+            })
+            .collect();
+
+        for (shape, depth) in self.shapes.iter_mut().zip(depths) {
+            if let Shape::Path(path) = shape {
+                if path.closed {
+                    let outer = depth % 2 == 0;
+                    path.set_direction(outer != postscript);
+                }
+            }
+        }
+    }
+}
+
+impl Path {
+    /// The bounding box of the outline, tight around the curve extrema rather
+    /// than the off-curve points. `None` for paths without nodes.
+    pub fn bounds(&self) -> Option<Rect> {
+        bezpath_bounds(&self.to_bezpath())
+    }
+}
+
+impl Shape {
+    /// Convert a path shape into a kurbo Bézier path.
+    ///
+    /// Components need the font to be resolved, so `None` is returned for
+    /// them.
+    pub fn to_bezpath(&self) -> Option<BezPath> {
+        match self {
+            Shape::Path(path) => Some(path.to_bezpath()),
+            Shape::Component(_) => None,
+        }
+    }
+}
+
+impl Layer {
+    /// The outline of the layer with all components resolved.
+    ///
+    /// Components are drawn from the layer of the referenced glyph for the
+    /// same master, or the brace layer at the same coordinates when this is a
+    /// brace layer and the referenced glyph has one, transformed by the
+    /// component's position, scale, rotation and slant. Components of
+    /// missing glyphs are left out.
+    ///
+    /// Mask paths aren't drawn, here or in components, as they aren't part of
+    /// the outline. Cutting them out of the other paths takes a boolean
+    /// operation, see [`Layer::masked_bezpath`].
+    pub fn flattened_bezpath(&self, font: &Font) -> BezPath {
+        let mut bez = BezPath::new();
+        self.flatten_into(font, kurbo::Affine::IDENTITY, 0, None, &mut bez);
+        bez
+    }
+
+    /// The bounding box of the layer's outline with components resolved as in
+    /// [`Layer::flattened_bezpath`]. `None` for empty layers.
+    pub fn bounds(&self, font: &Font) -> Option<Rect> {
+        bezpath_bounds(&self.flattened_bezpath(font))
+    }
+
+    /// The left sidebearing, from the origin to the left edge of the bounds.
+    /// `None` for empty layers.
+    pub fn lsb(&self, font: &Font) -> Option<f64> {
+        Some(self.bounds(font)?.x0)
+    }
+
+    /// The right sidebearing, from the right edge of the bounds to the
+    /// advance width. `None` for empty layers.
+    pub fn rsb(&self, font: &Font) -> Option<f64> {
+        Some(self.width - self.bounds(font)?.x1)
+    }
+
+    /// Change the left sidebearing by moving the shapes, anchors, guides
+    /// and hints of the layer and its background horizontally, keeping the
+    /// right sidebearing. Empty layers are left alone.
+    pub fn set_lsb(&mut self, font: &Font, lsb: f64) {
+        let Some(current) = self.lsb(font) else {
+            return;
+        };
+        let delta = lsb - current;
+        shift_shapes(&mut self.shapes, delta);
+        for anchor in self.anchors.iter_mut().flatten() {
+            anchor.pos.x += delta;
+        }
+        for guide in self.guides.iter_mut().flatten() {
+            guide.pos.x += delta;
+        }
+        shift_hints(&mut self.other_stuff, delta);
+        if let Some(background) = &mut self.background {
+            shift_shapes(&mut background.shapes, delta);
+            for anchor in background.anchors.iter_mut().flatten() {
+                anchor.pos.x += delta;
+            }
+            shift_hints(&mut background.other_stuff, delta);
+        }
+        self.width += delta;
+    }
+
+    /// Change the right sidebearing by adjusting the advance width. Empty
+    /// layers are left alone.
+    pub fn set_rsb(&mut self, font: &Font, rsb: f64) {
+        if let Some(current) = self.rsb(font) {
+            self.width += rsb - current;
+        }
+    }
+
+    /// Transform the paths, components, anchors and guides of the layer and
+    /// its background, for slanting, mirroring or moving it. The advance
+    /// width is left alone.
+    pub fn apply_affine(&mut self, transform: kurbo::Affine) {
+        transform_shapes(&mut self.shapes, transform);
+        for anchor in self.anchors.iter_mut().flatten() {
+            anchor.pos = transform * anchor.pos;
+        }
+        for guide in self.guides.iter_mut().flatten() {
+            guide.apply_affine(transform);
+        }
+        if let Some(background) = &mut self.background {
+            transform_shapes(&mut background.shapes, transform);
+            for anchor in background.anchors.iter_mut().flatten() {
+                anchor.pos = transform * anchor.pos;
+            }
+        }
+    }
+
+    /// Draw the layer into `bez`, resolving components. With `masks`, the
+    /// mask paths of every layer are cut out of the paths before them and
+    /// strokes are expanded, as in [`Layer::masked_bezpath`]; without, mask
+    /// paths are left out.
+    pub(crate) fn flatten_into(
+        &self,
+        font: &Font,
+        transform: kurbo::Affine,
+        depth: usize,
+        masks: Option<&dyn MaskOp>,
+        bez: &mut BezPath,
+    ) {
+        if let Some(op) = masks.filter(|_| self.paths().any(Path::is_mask)) {
+            bez.extend(transform * masked_bezpath(self.paths(), op));
+            for shape in &self.shapes {
+                if let Shape::Component(_) = shape {
+                    self.flatten_shape_into(shape, font, transform, depth, masks, bez);
+                }
+            }
+            return;
+        }
+        for shape in &self.shapes {
+            self.flatten_shape_into(shape, font, transform, depth, masks, bez);
+        }
+    }
+
+    fn flatten_shape_into(
+        &self,
+        shape: &Shape,
+        font: &Font,
+        transform: kurbo::Affine,
+        depth: usize,
+        masks: Option<&dyn MaskOp>,
+        bez: &mut BezPath,
+    ) {
+        match shape {
+            Shape::Path(path) if path.is_mask() => (),
+            Shape::Path(path) if masks.is_some() => bez.extend(transform * path.covered_bezpath()),
+            Shape::Path(path) => bez.extend(transform * path.to_bezpath()),
+            Shape::Component(component) if depth < MAX_COMPONENT_DEPTH => {
+                let Some(layer) = font
+                    .get_glyph(&component.reference)
+                    .and_then(|glyph| glyph.get_layer(self.matching_layer_id(glyph)))
+                else {
+                    return;
+                };
+                let transform = transform * component.transform();
+                layer.flatten_into(font, transform, depth + 1, masks, bez);
+            }
+            Shape::Component(_) => (),
+        }
+    }
+
+    /// The ID of the layer in another glyph that corresponds to this one.
+    pub(crate) fn matching_layer_id<'a>(&'a self, glyph: &'a Glyph) -> &'a str {
+        let master_id = self
+            .associated_master_id
+            .as_deref()
+            .unwrap_or(&self.layer_id);
+        match self.coordinates() {
+            Some(coordinates) => glyph
+                .layers
+                .iter()
+                .find(|l| {
+                    l.associated_master_id.as_deref() == Some(master_id)
+                        && l.coordinates() == Some(coordinates)
+                })
+                .map_or(master_id, |l| l.layer_id.as_str()),
+            None => master_id,
+        }
+    }
+}
+
+impl Glyph {
+    /// The bounds of the glyph's master layers, keyed by master ID in master
+    /// order. Masters the glyph has no layer for are left out.
+    pub fn bounds_per_master<'a>(&self, font: &'a Font) -> Vec<(&'a str, Option<Rect>)> {
+        font.font_master
+            .iter()
+            .filter_map(|master| {
+                let layer = self.master_layer(&master.id)?;
+                Some((master.id.as_str(), layer.bounds(font)))
+            })
+            .collect()
+    }
+}
+
+/// Move shapes horizontally, without touching the scale or rotation of
+/// components the way [`transform_shapes`] would.
+fn shift_shapes(shapes: &mut [Shape], delta: f64) {
+    for shape in shapes {
+        match shape {
+            Shape::Path(path) => {
+                for node in &mut path.nodes {
+                    node.pt.x += delta;
+                }
+            }
+            Shape::Component(component) => {
+                component.pos.get_or_insert(Point::ZERO).x += delta;
+            }
+        }
+    }
+}
+
+fn transform_shapes(shapes: &mut [Shape], transform: kurbo::Affine) {
+    for shape in shapes {
+        match shape {
+            Shape::Path(path) => path.apply_affine(transform),
+            Shape::Component(component) => {
+                component.set_transform(transform * component.transform())
+            }
+        }
+    }
+}
+
+fn bezpath_bounds(bez: &BezPath) -> Option<Rect> {
+    (!bez.elements().is_empty()).then(|| bez.bounding_box())
+}
+
+pub(crate) fn is_smooth(node_type: NodeType) -> bool {
+    matches!(
+        node_type,
+        NodeType::LineSmooth | NodeType::CurveSmooth | NodeType::QCurveSmooth
+    )
+}
+
+/// The node type ending a segment of the same kind as `segment`.
+pub(crate) fn segment_node_type(segment: NodeType, smooth: bool) -> NodeType {
+    match (segment, smooth) {
+        (NodeType::Line | NodeType::LineSmooth, false) => NodeType::Line,
+        (NodeType::Line | NodeType::LineSmooth, true) => NodeType::LineSmooth,
+        (NodeType::Curve | NodeType::CurveSmooth, false) => NodeType::Curve,
+        (NodeType::Curve | NodeType::CurveSmooth, true) => NodeType::CurveSmooth,
+        (NodeType::QCurve | NodeType::QCurveSmooth, false) => NodeType::QCurve,
+        (NodeType::QCurve | NodeType::QCurveSmooth, true) => NodeType::QCurveSmooth,
+        (NodeType::OffCurve, _) => NodeType::OffCurve,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{BezPath, Point, Shape as _};
+
+    use crate::{NodeType, Path, Shape};
+
+    fn path(closed: bool, nodes: &[(f64, f64, NodeType)]) -> Path {
+        let mut path = Path::new(closed);
+        for &(x, y, node_type) in nodes {
+            path.add((x, y), node_type);
+        }
+        path
+    }
+
+    #[test]
+    fn closed_path_starts_at_last_node() {
+        let path = path(
+            true,
+            &[
+                (100.0, 0.0, NodeType::Line),
+                (100.0, 50.0, NodeType::OffCurve),
+                (50.0, 100.0, NodeType::OffCurve),
+                (0.0, 100.0, NodeType::CurveSmooth),
+                (0.0, 0.0, NodeType::Line),
+            ],
+        );
+
+        let mut expected = BezPath::new();
+        expected.move_to((0.0, 0.0));
+        expected.line_to((100.0, 0.0));
+        expected.curve_to((100.0, 50.0), (50.0, 100.0), (0.0, 100.0));
+        expected.line_to((0.0, 0.0));
+        expected.close_path();
+        assert_eq!(path.to_bezpath(), expected);
+    }
+
+    #[test]
+    fn open_path() {
+        let path = path(
+            false,
+            &[
+                (0.0, 0.0, NodeType::Line),
+                (50.0, 100.0, NodeType::OffCurve),
+                (100.0, 0.0, NodeType::QCurve),
+            ],
+        );
+
+        let mut expected = BezPath::new();
+        expected.move_to((0.0, 0.0));
+        expected.quad_to((50.0, 100.0), (100.0, 0.0));
+        assert_eq!(path.to_bezpath(), expected);
+        assert_eq!(Path::from_bezpath(&expected), vec![path]);
+    }
+
+    #[test]
+    fn implied_on_curves() {
+        let path = path(
+            true,
+            &[
+                (0.0, 100.0, NodeType::OffCurve),
+                (100.0, 100.0, NodeType::OffCurve),
+                (100.0, 0.0, NodeType::OffCurve),
+                (0.0, 0.0, NodeType::OffCurve),
+            ],
+        );
+
+        let mut expected = BezPath::new();
+        expected.move_to((0.0, 50.0));
+        expected.quad_to((0.0, 100.0), (50.0, 100.0));
+        expected.quad_to((100.0, 100.0), (100.0, 50.0));
+        expected.quad_to((100.0, 0.0), (50.0, 0.0));
+        expected.quad_to((0.0, 0.0), (0.0, 50.0));
+        expected.close_path();
+        assert_eq!(path.to_bezpath(), expected);
+    }
+
+    #[test]
+    fn from_bezpath_implied_closing_line() {
+        let mut bez = BezPath::new();
+        bez.move_to((0.0, 0.0));
+        bez.line_to((100.0, 0.0));
+        bez.line_to((100.0, 100.0));
+        bez.close_path();
+        bez.move_to((200.0, 0.0));
+        bez.line_to((300.0, 0.0));
+
+        let paths = Path::from_bezpath(&bez);
+        assert_eq!(
+            paths,
+            vec![
+                path(
+                    true,
+                    &[
+                        (100.0, 0.0, NodeType::Line),
+                        (100.0, 100.0, NodeType::Line),
+                        (0.0, 0.0, NodeType::Line),
+                    ]
+                ),
+                path(
+                    false,
+                    &[(200.0, 0.0, NodeType::Line), (300.0, 0.0, NodeType::Line)]
+                ),
+            ]
+        );
+        assert_eq!(paths[0].to_bezpath().elements().len(), 5);
+    }
+
+    #[test]
+    fn flattened_bezpath() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let a = font.get_glyph("A").unwrap().get_layer("m01").unwrap();
+        let dieresiscomb = font
+            .get_glyph("dieresiscomb")
+            .unwrap()
+            .get_layer("m01")
+            .unwrap();
+        let a_dieresis = font.get_glyph("Ä").unwrap().get_layer("m01").unwrap();
+
+        let mut expected = a.flattened_bezpath(&font);
+        expected.extend(
+            kurbo::Affine::translate((-97.0, 135.0)) * dieresiscomb.flattened_bezpath(&font),
+        );
+        assert!(!expected.is_empty());
+        assert_eq!(a_dieresis.flattened_bezpath(&font), expected);
+    }
+
+    #[test]
+    fn flattened_bezpath_missing_component() {
+        let font = crate::Font::new();
+        let mut layer = crate::Layer::new("m01");
+        layer.shapes.push(Shape::Component(crate::Component {
+            reference: "missing".into(),
+            rotation: None,
+            pos: None,
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        }));
+        assert!(layer.flattened_bezpath(&font).is_empty());
+    }
+
+    #[test]
+    fn bounds() {
+        // The extremum of the curve lies well inside the off-curve points.
+        let bump = path(
+            true,
+            &[
+                (0.0, 0.0, NodeType::Line),
+                (0.0, 100.0, NodeType::OffCurve),
+                (100.0, 100.0, NodeType::OffCurve),
+                (100.0, 0.0, NodeType::Curve),
+            ],
+        );
+        let bounds = bump.bounds().unwrap();
+        assert_eq!((bounds.x0, bounds.y0, bounds.x1), (0.0, 0.0, 100.0));
+        assert!((bounds.y1 - 75.0).abs() < 1e-9);
+        assert_eq!(path(true, &[]).bounds(), None);
+
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let a = font.get_glyph("A").unwrap();
+        let a_dieresis = font.get_glyph("Ä").unwrap();
+        let a_bounds = a.bounds_per_master(&font);
+        let a_dieresis_bounds = a_dieresis.bounds_per_master(&font);
+        assert_eq!(a_bounds.len(), font.font_master.len());
+        assert_eq!(a_dieresis_bounds[0].0, "m01");
+        let (a_bounds, a_dieresis_bounds) =
+            (a_bounds[0].1.unwrap(), a_dieresis_bounds[0].1.unwrap());
+        assert!(a_dieresis_bounds.contains_rect(a_bounds));
+        assert!(a_dieresis_bounds.y1 > a_bounds.y1);
+        assert_eq!(crate::Layer::new("m01").bounds(&font), None);
+    }
+
+    #[test]
+    fn apply_affine() {
+        let mut square = square(0.0, 0.0, 100.0);
+        let clockwise = square.is_clockwise();
+        square.apply_affine(kurbo::Affine::FLIP_X);
+        assert_eq!(square.is_clockwise(), clockwise);
+        assert_eq!(
+            square.bounds().unwrap(),
+            kurbo::Rect::new(-100.0, 0.0, 0.0, 100.0)
+        );
+
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut layer = font.get_glyph("Ä").unwrap().layers[0].clone();
+        layer.set_anchor("top", (100.0, 700.0));
+        layer.guides = Some(vec![crate::font::GuideLine {
+            name: None,
+            angle: 0.0,
+            pos: Point::new(0.0, 500.0),
+            locked: false,
+            lock_angle: 0.0,
+            show_measurement: false,
+            orientation: None,
+            filter: None,
+        }]);
+        let original = layer.flattened_bezpath(&font);
+
+        let slant = kurbo::Affine::skew(12f64.to_radians().tan(), 0.0);
+        let transform = kurbo::Affine::translate((10.0, 20.0)) * slant * kurbo::Affine::FLIP_X;
+        layer.apply_affine(transform);
+        let expected = transform * original;
+        let transformed = layer.flattened_bezpath(&font);
+        let (bounds, expected_bounds) = (transformed.bounding_box(), expected.bounding_box());
+        assert!((bounds.origin() - expected_bounds.origin()).hypot() < 1e-6);
+        assert!((bounds.size() - expected_bounds.size()).to_vec2().hypot() < 1e-6);
+        let top = layer.anchor("top").unwrap().pos;
+        assert!(top.distance(transform * Point::new(100.0, 700.0)) < 1e-9);
+        let guide = &layer.guides.as_ref().unwrap()[0];
+        assert!((guide.angle.abs() - 180.0).abs() < 1e-9);
+        assert!(guide.pos.distance(transform * Point::new(0.0, 500.0)) < 1e-9);
+    }
+
+    #[test]
+    fn sidebearings() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut layer = font.get_glyph("Ä").unwrap().layers[0].clone();
+        let (lsb, rsb) = (layer.lsb(&font).unwrap(), layer.rsb(&font).unwrap());
+        let width = layer.width;
+        layer.guides = Some(vec![crate::GuideLine::new((100.0, 0.0), 90.0)]);
+        layer.background = Some(crate::BackgroundLayer {
+            anchors: None,
+            shapes: vec![Shape::Path(Box::new(square(0.0, 0.0, 10.0)))],
+            other_stuff: Default::default(),
+        });
+        let stem = |horizontal: i64| {
+            crate::Plist::Dictionary(
+                [
+                    ("horizontal".to_string(), crate::Plist::Integer(horizontal)),
+                    (
+                        "place".to_string(),
+                        crate::Plist::Array(vec![50.0.into(), 10.0.into()]),
+                    ),
+                ]
+                .into(),
+            )
+        };
+        layer
+            .other_stuff
+            .insert("hints".into(), crate::Plist::Array(vec![stem(0), stem(1)]));
+
+        layer.set_lsb(&font, lsb + 20.0);
+        assert!((layer.lsb(&font).unwrap() - (lsb + 20.0)).abs() < 1e-9);
+        assert!((layer.rsb(&font).unwrap() - rsb).abs() < 1e-9);
+        assert_eq!(layer.width, width + 20.0);
+        assert_eq!(layer.guides.as_ref().unwrap()[0].pos.x, 120.0);
+        let background = layer.background.as_ref().unwrap().shapes[0].clone();
+        assert_eq!(background, Shape::Path(Box::new(square(20.0, 0.0, 10.0))));
+        let place = |i: usize| layer.other_stuff["hints"].as_array().unwrap()[i].get("place");
+        assert_eq!(
+            place(0).unwrap().as_array().unwrap()[0].as_f64(),
+            Some(70.0)
+        );
+        assert_eq!(
+            place(1).unwrap().as_array().unwrap()[0].as_f64(),
+            Some(50.0)
+        );
+
+        layer.set_rsb(&font, 10.0);
+        assert!((layer.rsb(&font).unwrap() - 10.0).abs() < 1e-9);
+        assert!((layer.lsb(&font).unwrap() - (lsb + 20.0)).abs() < 1e-9);
+
+        let mut empty = crate::Layer::new("m01");
+        empty.set_lsb(&font, 50.0);
+        assert_eq!(empty.lsb(&font), None);
+        assert_eq!(empty.width, 600.0);
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Path {
+        path(
+            true,
+            &[
+                (x + size, y, NodeType::Line),
+                (x + size, y + size, NodeType::Line),
+                (x, y + size, NodeType::Line),
+                (x, y, NodeType::Line),
+            ],
+        )
+    }
+
+    #[test]
+    fn path_direction() {
+        let mut path = square(0.0, 0.0, 100.0);
+        assert!(!path.is_clockwise());
+
+        path.set_direction(true);
+        assert!(path.is_clockwise());
+        // The starting node stays at the end.
+        assert_eq!(path.nodes.last().unwrap().pt, Point::new(0.0, 0.0));
+        path.set_direction(false);
+        assert_eq!(path, square(0.0, 0.0, 100.0));
+    }
+
+    #[test]
+    fn open_path_direction() {
+        // An open path is closed with a straight line, wherever it is.
+        for offset in [0.0, 1000.0] {
+            let mut open = path(
+                false,
+                &[
+                    (offset, offset, NodeType::Line),
+                    (offset, offset + 100.0, NodeType::Line),
+                    (offset + 100.0, offset, NodeType::Line),
+                ],
+            );
+            assert!(open.is_clockwise());
+            open.reverse_direction();
+            assert!(!open.is_clockwise());
+        }
+    }
+
+    #[test]
+    fn reverse_direction_moves_segment_types() {
+        let mut path = path(
+            true,
+            &[
+                (100.0, 0.0, NodeType::LineSmooth),
+                (100.0, 50.0, NodeType::OffCurve),
+                (50.0, 100.0, NodeType::OffCurve),
+                (0.0, 100.0, NodeType::Curve),
+                (0.0, 0.0, NodeType::Line),
+            ],
+        );
+        let original = path.clone();
+        let outline = path.to_bezpath().reverse_subpaths();
+
+        path.reverse_direction();
+        assert_eq!(
+            path.nodes
+                .iter()
+                .map(|n| (n.pt, n.node_type))
+                .collect::<Vec<_>>(),
+            [
+                (Point::new(0.0, 100.0), NodeType::Line),
+                (Point::new(50.0, 100.0), NodeType::OffCurve),
+                (Point::new(100.0, 50.0), NodeType::OffCurve),
+                (Point::new(100.0, 0.0), NodeType::CurveSmooth),
+                (Point::new(0.0, 0.0), NodeType::Line),
+            ]
+        );
+        assert_eq!(path.to_bezpath(), outline);
+        path.reverse_direction();
+        assert_eq!(path, original);
+    }
+
+    #[test]
+    fn correct_path_direction() {
+        let mut outer = square(0.0, 0.0, 300.0);
+        outer.set_direction(true);
+        let counter = square(100.0, 100.0, 100.0);
+        let mut layer = crate::Layer::new("m01");
+        layer.shapes = vec![
+            Shape::Path(Box::new(outer)),
+            Shape::Path(Box::new(counter)),
+            Shape::Path(Box::new(square(400.0, 0.0, 100.0))),
+        ];
+
+        let directions = |layer: &crate::Layer| {
+            layer
+                .shapes
+                .iter()
+                .map(|shape| match shape {
+                    Shape::Path(path) => path.is_clockwise(),
+                    Shape::Component(_) => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+        layer.correct_path_direction(true);
+        assert_eq!(directions(&layer), [false, true, false]);
+        layer.correct_path_direction(false);
+        assert_eq!(directions(&layer), [true, false, true]);
+    }
+
+    #[test]
+    fn roundtrip_test_file_paths() {
+        let font = crate::Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let paths = font
+            .glyphs
+            .iter()
+            .flat_map(|g| &g.layers)
+            .flat_map(|l| &l.shapes)
+            .filter_map(|shape| match shape {
+                Shape::Path(path) => Some(path.as_ref()),
+                Shape::Component(_) => None,
+            });
+
+        for path in paths {
+            let roundtripped = Path::from_bezpath(&path.to_bezpath());
+            assert_eq!(roundtripped.len(), 1);
+            let points = |path: &Path| -> Vec<(Point, bool)> {
+                path.nodes
+                    .iter()
+                    .map(|n| (n.pt, n.node_type == NodeType::OffCurve))
+                    .collect()
+            };
+            assert_eq!(points(&roundtripped[0]), points(path));
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Overlap removal, with the boolean operation supplied by the caller.
+
+use kurbo::{BezPath, PathEl};
+
+use crate::{Font, Layer, Path, Shape};
+
+/// A boolean path operation from a geometry library, used to remove overlaps
+/// without this crate depending on one.
+pub trait OutlineOp {
+    /// Union the closed subpaths of `path` under the nonzero winding rule,
+    /// returning the outline without overlaps.
+    fn union(&self, path: &BezPath) -> BezPath;
+}
+
+impl<F: Fn(&BezPath) -> BezPath> OutlineOp for F {
+    fn union(&self, path: &BezPath) -> BezPath {
+        self(path)
+    }
+}
+
+impl Layer {
+    /// Remove overlaps from the layer's outline.
+    ///
+    /// Components are decomposed as in [`Layer::flattened_bezpath`], the
+    /// closed paths unioned with `op` and the result stored back as paths,
+    /// oriented like [`Layer::correct_path_direction`] does for PostScript
+    /// outlines. Open paths are kept as they are. Smoothness isn't preserved,
+    /// as with [`Path::from_bezpath`].
+    pub fn remove_overlap(&mut self, font: &Font, op: &impl OutlineOp) {
+        self.shapes = overlap_removed_shapes(self, font, op);
+        self.correct_path_direction(true);
+    }
+}
+
+impl Font {
+    /// Remove overlaps from every layer of every glyph, see
+    /// [`Layer::remove_overlap`].
+    ///
+    /// All layers are decomposed from the original outlines, so the result
+    /// doesn't depend on the order glyphs are processed in.
+    pub fn remove_overlaps(&mut self, op: &impl OutlineOp) {
+        let shapes: Vec<Vec<Vec<Shape>>> = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                glyph
+                    .layers
+                    .iter()
+                    .map(|layer| overlap_removed_shapes(layer, self, op))
+                    .collect()
+            })
+            .collect();
+        for (glyph, shapes) in self.glyphs.iter_mut().zip(shapes) {
+            for (layer, shapes) in glyph.layers.iter_mut().zip(shapes) {
+                layer.shapes = shapes;
+                layer.correct_path_direction(true);
+            }
+        }
+    }
+}
+
+fn overlap_removed_shapes(layer: &Layer, font: &Font, op: &impl OutlineOp) -> Vec<Shape> {
+    let mut closed = BezPath::new();
+    let mut open = BezPath::new();
+    for subpath in subpaths(&layer.flattened_bezpath(font)) {
+        if subpath.last() == Some(&PathEl::ClosePath) {
+            closed.extend(subpath);
+        } else {
+            open.extend(subpath);
+        }
+    }
+
+    let union = if closed.elements().is_empty() {
+        closed
+    } else {
+        op.union(&closed)
+    };
+    Path::from_bezpath(&union)
+        .into_iter()
+        .chain(Path::from_bezpath(&open))
+        .map(|path| Shape::Path(Box::new(path)))
+        .collect()
+}
+
+/// Split a Bézier path at each `MoveTo`.
+fn subpaths(bez: &BezPath) -> Vec<Vec<PathEl>> {
+    let mut subpaths: Vec<Vec<PathEl>> = Vec::new();
+    for &el in bez.elements() {
+        match (el, subpaths.last_mut()) {
+            (PathEl::MoveTo(_), _) | (_, None) => subpaths.push(vec![el]),
+            (_, Some(subpath)) => subpath.push(el),
+        }
+    }
+    subpaths
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{BezPath, Shape as _};
+
+    use crate::{Font, NodeType, Path, Shape};
+
+    /// Stands in for a real union by returning the bounding box.
+    fn bounding_box(path: &BezPath) -> BezPath {
+        path.bounding_box().to_path(0.1)
+    }
+
+    #[test]
+    fn remove_overlap() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut layer = font.get_glyph("Ä").unwrap().layers[0].clone();
+        assert!(layer.components().count() > 0);
+        let bounds = layer.bounds(&font).unwrap();
+
+        layer.remove_overlap(&font, &bounding_box);
+        assert!(layer.components().next().is_none());
+        assert_eq!(layer.shapes.len(), 1);
+        let Shape::Path(path) = &layer.shapes[0] else {
+            panic!("expected a path");
+        };
+        assert!(path.closed);
+        assert!(!path.is_clockwise());
+        assert_eq!(path.bounds(), Some(bounds));
+    }
+
+    #[test]
+    fn remove_overlaps_keeps_open_paths() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut open = Path::new(false);
+        open.add((0.0, 0.0), NodeType::Line);
+        open.add((10.0, 10.0), NodeType::Line);
+        let a = font.get_glyph_mut("A").unwrap();
+        a.layers[0].push_path(open.clone());
+
+        font.remove_overlaps(&bounding_box);
+        let a = &font.get_glyph("A").unwrap().layers[0];
+        assert_eq!(a.paths().last(), Some(&open));
+        let a_dieresis = &font.get_glyph("Ä").unwrap().layers[0];
+        assert!(a_dieresis.components().next().is_none());
+        let closed: Vec<bool> = a_dieresis.paths().map(|p| p.closed).collect();
+        assert_eq!(closed, [true, false]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Reading and writing `.glyphspackage` directories, which store the font
+//! info in `fontinfo.plist`, the glyph order in `order.plist` and each glyph
+//! in its own file in `glyphs/`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::font::{write_atomic, FontLoadError, FontSaveError};
+use crate::{Font, Plist, ToPlist};
+
+const FONT_INFO: &str = "fontinfo.plist";
+const ORDER: &str = "order.plist";
+const GLYPHS_DIR: &str = "glyphs";
+const GLYPH_EXTENSION: &str = "glyph";
+
+impl Font {
+    /// Read the plist of a `.glyphspackage` directory, with the glyphs put
+    /// back into the font like in a `.glyphs` file.
+    ///
+    /// Errors reading or parsing one of the files name that file.
+    pub(crate) fn read_package(path: &Path) -> Result<Plist, FontLoadError> {
+        let mut font_info = read_plist(&path.join(FONT_INFO))?.into_hashmap();
+        let order: Vec<String> = match read_plist(&path.join(ORDER)) {
+            Ok(order) => order
+                .into_vec()
+                .into_iter()
+                .filter_map(|name| name.as_str().map(str::to_string))
+                .collect(),
+            Err(e) if is_not_found(&e) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let glyphs_dir = path.join(GLYPHS_DIR);
+        let in_glyphs_dir = |e: io::Error| FontLoadError::from(e).in_file(&glyphs_dir);
+        let mut glyphs: Vec<(String, Plist)> = Vec::new();
+        for entry in fs::read_dir(&glyphs_dir).map_err(in_glyphs_dir)? {
+            let path = entry.map_err(in_glyphs_dir)?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(GLYPH_EXTENSION) {
+                continue;
+            }
+            let glyph = read_plist(&path)?;
+            let name = glyph
+                .get("glyphname")
+                .and_then(Plist::as_str)
+                .unwrap_or_default()
+                .to_string();
+            glyphs.push((name, glyph));
+        }
+        // Glyphs in the order file come first, the others after them by name.
+        let position: HashMap<&str, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
+        glyphs.sort_by(|(a, _), (b, _)| {
+            let key = |name: &String| {
+                (
+                    position.get(name.as_str()).unwrap_or(&usize::MAX),
+                    name.clone(),
+                )
+            };
+            key(a).cmp(&key(b))
+        });
+        font_info.insert(
+            "glyphs".into(),
+            glyphs
+                .into_iter()
+                .map(|(_, glyph)| glyph)
+                .collect::<Vec<_>>()
+                .into(),
+        );
+
+        Ok(font_info.into())
+    }
+
+    /// Save the font as a `.glyphspackage` directory, returning the names of
+    /// the glyphs whose files were written.
+    ///
+    /// Saving over an existing package only writes the files whose content
+    /// changed and removes the files of glyphs no longer in the font, so the
+    /// files of untouched glyphs keep their modification times.
+    pub fn save_package(&self, path: &Path) -> Result<Vec<String>, FontSaveError> {
+        // Check the file names first, so a clash leaves the package alone.
+        let mut file_names: HashMap<String, &str> = HashMap::new();
+        for glyph in &self.glyphs {
+            let file_name = glyph_file_name(&glyph.glyphname);
+            if let Some(other) = file_names.insert(file_name, &glyph.glyphname) {
+                return Err(FontSaveError::GlyphFileClash(
+                    other.to_string(),
+                    glyph.glyphname.to_string(),
+                ));
+            }
+        }
+
+        let mut font_info = self.to_plist().into_hashmap();
+        let glyphs = font_info
+            .remove("glyphs")
+            .map(Plist::into_vec)
+            .unwrap_or_default();
+        let order: Vec<Plist> = self
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.glyphname.to_string().into())
+            .collect();
+
+        let glyphs_dir = path.join(GLYPHS_DIR);
+        fs::create_dir_all(&glyphs_dir)?;
+        write_if_changed(&path.join(FONT_INFO), &Plist::from(font_info).to_string())?;
+        write_if_changed(&path.join(ORDER), &Plist::from(order).to_string())?;
+
+        let mut written = Vec::new();
+        for (glyph, plist) in self.glyphs.iter().zip(glyphs) {
+            let file_name = glyph_file_name(&glyph.glyphname);
+            if write_if_changed(&glyphs_dir.join(file_name), &plist.to_string())? {
+                written.push(glyph.glyphname.to_string());
+            }
+        }
+        for entry in fs::read_dir(&glyphs_dir)? {
+            let path = entry?.path();
+            let is_glyph = path.extension().and_then(|e| e.to_str()) == Some(GLYPH_EXTENSION);
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if is_glyph && !file_names.contains_key(file_name.as_ref()) {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Read and parse a plist file of a package.
+fn read_plist(path: &Path) -> Result<Plist, FontLoadError> {
+    let read = || -> Result<Plist, FontLoadError> { Ok(Plist::parse(&fs::read_to_string(path)?)?) };
+    read().map_err(|e| e.in_file(path))
+}
+
+fn is_not_found(error: &FontLoadError) -> bool {
+    match error {
+        FontLoadError::File { source, .. } => is_not_found(source),
+        FontLoadError::Io(e) => e.kind() == io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+/// Write the file unless it already has the content. Returns whether it was
+/// written.
+fn write_if_changed(path: &Path, content: &str) -> io::Result<bool> {
+    match fs::read(path) {
+        Ok(existing) if existing == content.as_bytes() => return Ok(false),
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    write_atomic(path, content, false)?;
+    Ok(true)
+}
+
+/// The file name Glyphs.app gives a glyph: uppercase letters are followed by
+/// an underscore so names differing in case don't clash on case-insensitive
+/// file systems, and characters not allowed in file names are replaced.
+fn glyph_file_name(glyphname: &str) -> String {
+    let mut file_name = String::new();
+    for (i, c) in glyphname.chars().enumerate() {
+        match c {
+            '.' if i == 0 => file_name.push('_'),
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => file_name.push('_'),
+            c if c.is_uppercase() => {
+                file_name.push(c);
+                file_name.push('_');
+            }
+            c => file_name.push(c),
+        }
+    }
+    format!("{file_name}.{GLYPH_EXTENSION}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyph_file_name() {
+        assert_eq!(super::glyph_file_name("A"), "A_.glyph");
+        assert_eq!(super::glyph_file_name("a.sc"), "a.sc.glyph");
+        assert_eq!(super::glyph_file_name(".notdef"), "_notdef.glyph");
+        assert_eq!(super::glyph_file_name("Ä"), "Ä_.glyph");
+    }
+
+    #[test]
+    fn save_package() {
+        let path = std::env::temp_dir().join("glyphs_plist_save_package.glyphspackage");
+        let _ = fs::remove_dir_all(&path);
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        let written = font.save_package(&path).unwrap();
+        assert_eq!(written.len(), font.glyphs.len());
+        assert_eq!(Font::load(&path).unwrap(), font);
+
+        // Only changed glyphs are written again.
+        assert!(font.save_package(&path).unwrap().is_empty());
+        font.get_glyph_mut("A").unwrap().layers[0].width += 10.0;
+        let removed = font.glyphs.pop().unwrap();
+        assert_eq!(font.save_package(&path).unwrap(), ["A"]);
+        let removed_file = path
+            .join(GLYPHS_DIR)
+            .join(super::glyph_file_name(&removed.glyphname));
+        assert!(!removed_file.exists());
+        assert_eq!(Font::load(&path).unwrap(), font);
+
+        let saved = font.clone();
+        font.family_name = "Clash".into();
+        font.glyphs[0].glyphname = norad::Name::new("a:b").unwrap();
+        font.glyphs[1].glyphname = norad::Name::new("a/b").unwrap();
+        assert!(matches!(
+            font.save_package(&path),
+            Err(FontSaveError::GlyphFileClash(first, second)) if first == "a:b" && second == "a/b"
+        ));
+        assert_eq!(Font::load(&path).unwrap(), saved);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn load_errors_name_the_file() {
+        use std::error::Error;
+
+        let path = std::env::temp_dir().join("glyphs_plist_load_errors.glyphspackage");
+        let _ = fs::remove_dir_all(&path);
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.save_package(&path).unwrap();
+        let glyph_file = path.join(GLYPHS_DIR).join(super::glyph_file_name("A"));
+        fs::write(&glyph_file, "{glyphname = A;").unwrap();
+
+        let error = Font::load(&path).unwrap_err();
+        assert_eq!(error.path(), Some(glyph_file.as_path()));
+        assert_eq!(
+            error.to_string(),
+            format!("failed to load {}", glyph_file.display())
+        );
+        let mut chain = Vec::new();
+        let mut next: Option<&dyn Error> = Some(&error);
+        while let Some(error) = next {
+            chain.push(error.to_string());
+            next = error.source();
+        }
+        // Each error only adds to the one it wraps.
+        assert!(chain.len() >= 2);
+        for pair in chain.windows(2) {
+            assert!(!pair[0].contains(&pair[1]), "{chain:?}");
+        }
+        let inner = error.into_inner();
+        assert!(matches!(inner, FontLoadError::ParsePlist(_)));
+        assert_eq!(chain[1], inner.to_string());
+
+        let missing = path.join("missing.glyphs");
+        assert_eq!(
+            Font::load(&missing).unwrap_err().path(),
+            Some(missing.as_path())
+        );
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+//! Pens for drawing outlines segment by segment or point by point, for
+//! building paths and for handing outlines to other libraries.
+
+use kurbo::{Affine, BezPath, Point};
+
+use crate::curves::split_super_bezier;
+use crate::{Component, Layer, NodeType, Path, Shape};
+
+/// A pen drawn with segments, like a kurbo [`BezPath`] or a fontTools
+/// segment pen.
+pub trait Pen {
+    fn move_to(&mut self, pt: Point);
+
+    fn line_to(&mut self, pt: Point);
+
+    fn quad_to(&mut self, c: Point, pt: Point);
+
+    fn curve_to(&mut self, c1: Point, c2: Point, pt: Point);
+
+    /// Close the current subpath, with an implied line back to its start.
+    fn close_path(&mut self);
+
+    /// End the current subpath without closing it.
+    fn end_path(&mut self) {}
+
+    /// Draw a component. Pens that only draw outlines leave it out.
+    fn add_component(&mut self, _glyph_name: &str, _transform: Affine) {}
+}
+
+/// A pen drawn with the nodes of paths, like a fontTools point pen.
+///
+/// The nodes come in the order of [`Path::nodes`], with the starting node of
+/// a closed path at the end, and keep their smoothness.
+pub trait PointPen {
+    fn begin_path(&mut self, closed: bool);
+
+    fn add_point(&mut self, pt: Point, node_type: NodeType);
+
+    fn end_path(&mut self);
+
+    /// Draw a component. Pens that only draw outlines leave it out.
+    fn add_component(&mut self, _glyph_name: &str, _transform: Affine) {}
+}
+
+impl Pen for BezPath {
+    fn move_to(&mut self, pt: Point) {
+        BezPath::move_to(self, pt);
+    }
+
+    fn line_to(&mut self, pt: Point) {
+        BezPath::line_to(self, pt);
+    }
+
+    fn quad_to(&mut self, c: Point, pt: Point) {
+        BezPath::quad_to(self, c, pt);
+    }
+
+    fn curve_to(&mut self, c1: Point, c2: Point, pt: Point) {
+        BezPath::curve_to(self, c1, c2, pt);
+    }
+
+    fn close_path(&mut self) {
+        BezPath::close_path(self);
+    }
+}
+
+/// A pen building the shapes of a layer, drawn with either kind of pen.
+///
+/// Closed paths drawn with segments store their starting node at the end of
+/// the node list, like Glyphs.app does. Smoothness isn't part of a segment,
+/// so their on-curve nodes are corners. Segments drawn before the first
+/// `move_to` are left out.
+#[derive(Debug, Default)]
+pub struct ShapePen {
+    shapes: Vec<Shape>,
+    current: Option<Path>,
+    start: Point,
+}
+
+impl ShapePen {
+    pub fn new() -> ShapePen {
+        ShapePen::default()
+    }
+
+    /// The shapes drawn, ending the current path if there is one.
+    pub fn into_shapes(mut self) -> Vec<Shape> {
+        self.finish();
+        self.shapes
+    }
+
+    /// The paths drawn, ending the current path if there is one and leaving
+    /// out components.
+    pub fn into_paths(self) -> Vec<Path> {
+        self.into_shapes()
+            .into_iter()
+            .filter_map(|shape| match shape {
+                Shape::Path(path) => Some(*path),
+                Shape::Component(_) => None,
+            })
+            .collect()
+    }
+
+    fn finish(&mut self) {
+        if let Some(path) = self.current.take() {
+            self.shapes.push(Shape::Path(Box::new(path)));
+        }
+    }
+
+    fn add(&mut self, pt: Point, node_type: NodeType) {
+        if let Some(path) = &mut self.current {
+            path.add(pt, node_type);
+        }
+    }
+
+    fn component(&mut self, glyph_name: &str, transform: Affine) {
+        self.finish();
+        let mut component = Component {
+            reference: glyph_name.to_string(),
+            rotation: None,
+            pos: None,
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        };
+        component.set_transform(transform);
+        self.shapes.push(Shape::Component(component));
+    }
+}
+
+impl Pen for ShapePen {
+    fn move_to(&mut self, pt: Point) {
+        self.finish();
+        let mut path = Path::new(false);
+        path.add(pt, NodeType::Line);
+        self.current = Some(path);
+        self.start = pt;
+    }
+
+    fn line_to(&mut self, pt: Point) {
+        self.add(pt, NodeType::Line);
+    }
+
+    fn quad_to(&mut self, c: Point, pt: Point) {
+        self.add(c, NodeType::OffCurve);
+        self.add(pt, NodeType::QCurve);
+    }
+
+    fn curve_to(&mut self, c1: Point, c2: Point, pt: Point) {
+        self.add(c1, NodeType::OffCurve);
+        self.add(c2, NodeType::OffCurve);
+        self.add(pt, NodeType::Curve);
+    }
+
+    fn close_path(&mut self) {
+        let Some(path) = &mut self.current else {
+            return;
+        };
+        path.closed = true;
+        path.nodes.remove(0);
+        // Add the implied closing line, if there is one.
+        if path.nodes.last().map(|n| n.pt) != Some(self.start) {
+            path.add(self.start, NodeType::Line);
+        }
+        self.finish();
+    }
+
+    fn end_path(&mut self) {
+        self.finish();
+    }
+
+    fn add_component(&mut self, glyph_name: &str, transform: Affine) {
+        self.component(glyph_name, transform);
+    }
+}
+
+impl PointPen for ShapePen {
+    fn begin_path(&mut self, closed: bool) {
+        self.finish();
+        self.current = Some(Path::new(closed));
+    }
+
+    fn add_point(&mut self, pt: Point, node_type: NodeType) {
+        self.add(pt, node_type);
+    }
+
+    fn end_path(&mut self) {
+        self.finish();
+    }
+
+    fn add_component(&mut self, glyph_name: &str, transform: Affine) {
+        self.component(glyph_name, transform);
+    }
+}
+
+impl Path {
+    /// Draw the path into a pen.
+    ///
+    /// In Glyphs.app, the starting node of a closed path is stored at the end
+    /// of the node list, so drawing starts at the last on-curve node.
+    /// Quadratic curves with several off-curve nodes are split at the implied
+    /// on-curve points, TrueType style. A closed path made of off-curve nodes
+    /// only starts at the implied point between its last and first node.
+    pub fn draw(&self, pen: &mut impl Pen) {
+        let len = self.nodes.len();
+        if len == 0 {
+            return;
+        }
+
+        let (start, order): (Point, Vec<usize>) = if !self.closed {
+            (self.nodes[0].pt, (1..len).collect())
+        } else {
+            match self
+                .nodes
+                .iter()
+                .rposition(|n| n.node_type != NodeType::OffCurve)
+            {
+                Some(i) => (
+                    self.nodes[i].pt,
+                    (i + 1..i + 1 + len).map(|j| j % len).collect(),
+                ),
+                None => (
+                    self.nodes[len - 1].pt.midpoint(self.nodes[0].pt),
+                    (0..len).collect(),
+                ),
+            }
+        };
+
+        pen.move_to(start);
+        let mut current = start;
+        let mut off_curves = Vec::new();
+        for node in order.into_iter().map(|i| &self.nodes[i]) {
+            match node.node_type {
+                NodeType::OffCurve => off_curves.push(node.pt),
+                NodeType::Line | NodeType::LineSmooth => pen.line_to(node.pt),
+                NodeType::Curve | NodeType::CurveSmooth => match off_curves[..] {
+                    [] => pen.line_to(node.pt),
+                    [c] => pen.quad_to(c, node.pt),
+                    [c1, c2] => pen.curve_to(c1, c2, node.pt),
+                    _ => {
+                        for cubic in split_super_bezier(current, &off_curves, node.pt) {
+                            pen.curve_to(cubic.p1, cubic.p2, cubic.p3);
+                        }
+                    }
+                },
+                NodeType::QCurve | NodeType::QCurveSmooth => {
+                    draw_quad_spline(pen, &off_curves, node.pt)
+                }
+            }
+            if node.node_type != NodeType::OffCurve {
+                off_curves.clear();
+                current = node.pt;
+            }
+        }
+        if self.closed {
+            if !off_curves.is_empty() {
+                draw_quad_spline(pen, &off_curves, start);
+            }
+            pen.close_path();
+        } else {
+            pen.end_path();
+        }
+    }
+
+    /// Draw the nodes of the path into a point pen, as they are.
+    pub fn draw_points(&self, pen: &mut impl PointPen) {
+        pen.begin_path(self.closed);
+        for node in &self.nodes {
+            pen.add_point(node.pt, node.node_type);
+        }
+        pen.end_path();
+    }
+}
+
+impl Layer {
+    /// Draw the shapes of the layer into a pen, with components drawn by
+    /// reference.
+    pub fn draw(&self, pen: &mut impl Pen) {
+        for shape in &self.shapes {
+            match shape {
+                Shape::Path(path) => path.draw(pen),
+                Shape::Component(component) => {
+                    pen.add_component(&component.reference, component.transform())
+                }
+            }
+        }
+    }
+
+    /// Draw the shapes of the layer into a point pen, with components drawn
+    /// by reference.
+    pub fn draw_points(&self, pen: &mut impl PointPen) {
+        for shape in &self.shapes {
+            match shape {
+                Shape::Path(path) => path.draw_points(pen),
+                Shape::Component(component) => {
+                    pen.add_component(&component.reference, component.transform())
+                }
+            }
+        }
+    }
+}
+
+/// Draw a run of quadratic off-curve points ending at `end`, splitting it
+/// at the implied on-curve points between consecutive off-curves.
+fn draw_quad_spline(pen: &mut impl Pen, off_curves: &[Point], end: Point) {
+    let Some((last, rest)) = off_curves.split_last() else {
+        pen.line_to(end);
+        return;
+    };
+    for (i, &c) in rest.iter().enumerate() {
+        pen.quad_to(c, c.midpoint(off_curves[i + 1]));
+    }
+    pen.quad_to(*last, end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(closed: bool, nodes: &[(f64, f64, NodeType)]) -> Path {
+        let mut path = Path::new(closed);
+        for &(x, y, node_type) in nodes {
+            path.add((x, y), node_type);
+        }
+        path
+    }
+
+    #[test]
+    fn replay_into_shape_pen() {
+        let path = path(
+            true,
+            &[
+                (100.0, 0.0, NodeType::Line),
+                (100.0, 50.0, NodeType::OffCurve),
+                (50.0, 100.0, NodeType::OffCurve),
+                (0.0, 100.0, NodeType::CurveSmooth),
+                (0.0, 0.0, NodeType::Line),
+            ],
+        );
+
+        let mut pen = ShapePen::new();
+        path.draw_points(&mut pen);
+        assert_eq!(pen.into_paths(), vec![path.clone()]);
+
+        // Segments don't know about smoothness.
+        let mut pen = ShapePen::new();
+        path.draw(&mut pen);
+        let mut corners = path.clone();
+        corners.nodes[3].node_type = NodeType::Curve;
+        assert_eq!(pen.into_paths(), vec![corners]);
+    }
+
+    #[test]
+    fn super_bezier() {
+        let path = path(
+            false,
+            &[
+                (0.0, 0.0, NodeType::Line),
+                (0.0, 100.0, NodeType::OffCurve),
+                (100.0, 200.0, NodeType::OffCurve),
+                (200.0, 100.0, NodeType::OffCurve),
+                (200.0, 0.0, NodeType::Curve),
+            ],
+        );
+        let bezpath = path.to_bezpath();
+        let curves: Vec<_> = bezpath
+            .elements()
+            .iter()
+            .filter_map(|el| match el {
+                kurbo::PathEl::CurveTo(c1, c2, end) => Some((*c1, *c2, *end)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            curves,
+            [
+                (
+                    Point::new(0.0, 100.0),
+                    Point::new(50.0, 150.0),
+                    Point::new(100.0, 150.0)
+                ),
+                (
+                    Point::new(150.0, 150.0),
+                    Point::new(200.0, 100.0),
+                    Point::new(200.0, 0.0)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn open_paths_and_components() {
+        let mut pen = ShapePen::new();
+        Pen::move_to(&mut pen, Point::new(0.0, 0.0));
+        Pen::line_to(&mut pen, Point::new(10.0, 0.0));
+        Pen::add_component(&mut pen, "a", Affine::translate((20.0, 0.0)));
+        Pen::move_to(&mut pen, Point::new(0.0, 10.0));
+        Pen::quad_to(&mut pen, Point::new(5.0, 20.0), Point::new(10.0, 10.0));
+        Pen::end_path(&mut pen);
+        let shapes = pen.into_shapes();
+
+        assert_eq!(shapes.len(), 3);
+        let Shape::Component(component) = &shapes[1] else {
+            panic!("expected a component");
+        };
+        assert_eq!(component.reference, "a");
+        assert_eq!(component.transform(), Affine::translate((20.0, 0.0)));
+
+        let mut layer = Layer::new("m01");
+        layer.shapes = shapes.clone();
+        let mut pen = ShapePen::new();
+        layer.draw(&mut pen);
+        assert_eq!(pen.into_shapes(), shapes);
+    }
+}
@@ -1,9 +1,19 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
+
 use thiserror::Error;
 
 /// An enum representing a property list.
+///
+/// With the `serde` feature, it is serialized as the bare value, so that it
+/// maps onto formats like JSON directly.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
 pub enum Plist {
     Dictionary(HashMap<String, Plist>),
     Array(Vec<Plist>),
@@ -32,6 +42,23 @@ pub enum Error {
     SomethingWentWrong,
 }
 
+/// Where a parsed plist value is in the source text.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Span {
+    pub(crate) range: Range<usize>,
+    pub(crate) children: SpanChildren,
+}
+
+/// The spans of the parts of a dictionary or array.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SpanChildren {
+    None,
+    /// The key, the span of the whole entry from the key to the `;`, and the
+    /// span of the value of each entry, in source order.
+    Dictionary(Vec<(String, Range<usize>, Span)>),
+    Array(Vec<Span>),
+}
+
 enum Token<'a> {
     Eof,
     OpenBrace,
@@ -91,7 +118,13 @@ fn skip_ws(s: &str, mut ix: usize) -> usize {
     ix
 }
 
-fn escape_string(buf: &mut String, s: &str) {
+/// A float as it is written to a file, which is also the text of names and
+/// strings that were read as floats.
+pub(crate) fn format_float(f: f64) -> String {
+    format!("{f}")
+}
+
+pub(crate) fn escape_string(buf: &mut String, s: &str) {
     if !s.is_empty() && s.as_bytes().iter().all(|&b| is_alnum_strict(b)) {
         // Strings can drop quotation marks if they're alphanumeric, but not if
         // they look like numbers.
@@ -132,6 +165,9 @@ impl std::fmt::Display for Plist {
     }
 }
 
+/// The keys that identify the dictionaries of an array.
+const IDENTITY_KEYS: &[&str] = &["glyphname", "layerId"];
+
 impl Plist {
     pub fn parse(s: &str) -> Result<Plist, Error> {
         let (plist, _ix) = Plist::parse_rec(s, 0)?;
@@ -147,6 +183,15 @@ impl Plist {
         }
     }
 
+    /// The key and value identifying a dictionary in an array, for matching
+    /// up the elements of arrays that were reordered or had elements added or
+    /// removed: glyphs by name and layers by ID.
+    pub(crate) fn identity(&self) -> Option<(&str, &Plist)> {
+        IDENTITY_KEYS
+            .iter()
+            .find_map(|&key| Some((key, self.get(key)?)))
+    }
+
     #[allow(unused)]
     pub fn get(&self, key: &str) -> Option<&Plist> {
         match self {
@@ -253,6 +298,66 @@ impl Plist {
         }
     }
 
+    /// Parse a plist, also returning where each part of it is in `s`.
+    pub(crate) fn parse_with_spans(s: &str) -> Result<(Plist, Span), Error> {
+        let (plist, span, _ix) = Plist::parse_rec_spanned(s, 0)?;
+        Ok((plist, span))
+    }
+
+    /// Like `parse_rec`, recording spans.
+    fn parse_rec_spanned(s: &str, ix: usize) -> Result<(Plist, Span, usize), Error> {
+        let start = skip_ws(s, ix);
+        let (tok, mut ix) = Token::lex(s, start)?;
+        let span = |end: usize, children| Span {
+            range: start..end,
+            children,
+        };
+        match tok {
+            Token::Atom(atom) => Ok((Plist::parse_atom(atom), span(ix, SpanChildren::None), ix)),
+            Token::String(string) => Ok((
+                Plist::String(string.into()),
+                span(ix, SpanChildren::None),
+                ix,
+            )),
+            Token::OpenBrace => {
+                let mut dict = HashMap::new();
+                let mut entries = Vec::new();
+                loop {
+                    if let Some(ix) = Token::expect(s, ix, b'}') {
+                        let span = span(ix, SpanChildren::Dictionary(entries));
+                        return Ok((Plist::Dictionary(dict), span, ix));
+                    }
+                    let entry_start = skip_ws(s, ix);
+                    let (key, next) = Token::lex(s, entry_start)?;
+                    let key_str = Token::try_into_string(key)?;
+                    let next = Token::expect(s, next, b'=').ok_or(Error::ExpectedEquals)?;
+                    let (val, val_span, next) = Self::parse_rec_spanned(s, next)?;
+                    ix = Token::expect(s, next, b';').ok_or(Error::ExpectedSemicolon)?;
+                    entries.push((key_str.clone(), entry_start..ix, val_span));
+                    dict.insert(key_str, val);
+                }
+            }
+            Token::OpenParen => {
+                let mut list = Vec::new();
+                let mut spans = Vec::new();
+                if let Some(ix) = Token::expect(s, ix, b')') {
+                    return Ok((Plist::Array(list), span(ix, SpanChildren::Array(spans)), ix));
+                }
+                loop {
+                    let (val, val_span, next) = Self::parse_rec_spanned(s, ix)?;
+                    list.push(val);
+                    spans.push(val_span);
+                    if let Some(ix) = Token::expect(s, next, b')') {
+                        let span = span(ix, SpanChildren::Array(spans));
+                        return Ok((Plist::Array(list), span, ix));
+                    }
+                    ix = Token::expect(s, next, b',').ok_or(Error::ExpectedComma)?;
+                }
+            }
+            _ => Err(Error::SomethingWentWrong),
+        }
+    }
+
     fn parse_atom(s: &str) -> Plist {
         if numeric_ok(s) {
             if let Ok(num) = s.parse() {
@@ -296,7 +401,7 @@ impl Plist {
                 s.push_str(&format!("{}", i));
             }
             Plist::Float(f) => {
-                s.push_str(&format!("{}", f));
+                s.push_str(&format_float(*f));
             }
         }
     }
@@ -634,6 +739,14 @@ mod tests {
         }
     }
 
+    proptest! {
+        #[test]
+        fn format_float_roundtrip(num in proptest::num::f64::NORMAL) {
+            let text = format_float(num);
+            assert_eq!(Plist::parse(&text).unwrap().as_f64(), Some(num));
+        }
+    }
+
     proptest! {
         #[test]
         fn escape_strings_int(num in proptest::num::i64::ANY) {
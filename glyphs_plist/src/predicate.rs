@@ -0,0 +1,631 @@
+//! Glyph filter predicates, as used by metric and guide filters and smart
+//! filters in Glyphs.app.
+//!
+//! Glyphs.app stores these as `NSPredicate` format strings such as
+//! `category == "Letter" && case == 2`. This module parses the subset of
+//! that syntax that makes sense for glyph properties.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::{Case, Font, Glyph, Plist};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PredicateError {
+    #[error("unexpected end of predicate")]
+    UnexpectedEnd,
+    #[error("unexpected {token:?} at position {position}")]
+    UnexpectedToken { token: String, position: usize },
+    #[error("unterminated string starting at position {0}")]
+    UnterminatedString(usize),
+    #[error("unsupported operator {0:?}")]
+    UnsupportedOperator(String),
+}
+
+/// A parsed filter predicate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    /// `TRUEPREDICATE`, matching every glyph.
+    True,
+    /// `FALSEPREDICATE`, matching no glyph.
+    False,
+    Not(Box<Predicate>),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Comparison(Comparison),
+}
+
+/// A comparison of a glyph property with a constant, like
+/// `subCategory == "Lowercase"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// The glyph property, by its name in Glyphs.app, like `category` or
+    /// `userData.someKey`.
+    pub key: String,
+    pub operator: Operator,
+    pub value: Value,
+    /// Whether the `[c]` modifier asks for case-insensitive string
+    /// comparison.
+    pub case_insensitive: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BeginsWith,
+    EndsWith,
+    Contains,
+    /// Matching with `*` and `?` wildcards.
+    Like,
+    In,
+}
+
+/// A constant in a predicate, or the value of a glyph property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+}
+
+impl Predicate {
+    /// Parse a predicate format string.
+    pub fn parse(s: &str) -> Result<Predicate, PredicateError> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            index: 0,
+        };
+        let predicate = parser.parse_or()?;
+        match parser.tokens.get(parser.index) {
+            Some((token, position)) => Err(PredicateError::UnexpectedToken {
+                token: token.to_string(),
+                position: *position,
+            }),
+            None => Ok(predicate),
+        }
+    }
+
+    /// Whether the glyph matches the predicate.
+    ///
+    /// The glyph properties available are `name`, `unicode` (the first code
+    /// point as four or more hex digits), `unicodes`, `category`,
+    /// `subCategory`, `case` (numbered like in Glyphs.app, from 0 for no case
+    /// to 4 for other), `script`, `productionName`, `tags`, `export`,
+    /// `locked`, `note`, `leftKerningGroup`, `rightKerningGroup`,
+    /// `leftMetricsKey`, `rightMetricsKey`, `widthMetricsKey` and
+    /// `userData.<key>`. Any other property is nil.
+    pub fn matches(&self, glyph: &Glyph) -> bool {
+        match self {
+            Predicate::True => true,
+            Predicate::False => false,
+            Predicate::Not(predicate) => !predicate.matches(glyph),
+            Predicate::And(predicates) => predicates.iter().all(|p| p.matches(glyph)),
+            Predicate::Or(predicates) => predicates.iter().any(|p| p.matches(glyph)),
+            Predicate::Comparison(comparison) => {
+                comparison.matches(&glyph_value(glyph, &comparison.key))
+            }
+        }
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = PredicateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Predicate::parse(s)
+    }
+}
+
+impl Font {
+    /// The glyphs matching the predicate, in font order.
+    pub fn glyphs_matching<'a>(
+        &'a self,
+        predicate: &'a Predicate,
+    ) -> impl Iterator<Item = &'a Glyph> + 'a {
+        self.glyphs.iter().filter(|glyph| predicate.matches(glyph))
+    }
+}
+
+impl Comparison {
+    fn matches(&self, lhs: &Value) -> bool {
+        let rhs = &self.value;
+        match self.operator {
+            Operator::Equal => self.equal(lhs, rhs),
+            Operator::NotEqual => !self.equal(lhs, rhs),
+            Operator::Less => self.compare(lhs, rhs).is_some_and(|o| o.is_lt()),
+            Operator::LessOrEqual => self.compare(lhs, rhs).is_some_and(|o| o.is_le()),
+            Operator::Greater => self.compare(lhs, rhs).is_some_and(|o| o.is_gt()),
+            Operator::GreaterOrEqual => self.compare(lhs, rhs).is_some_and(|o| o.is_ge()),
+            Operator::BeginsWith => self.strings(lhs, rhs, |l, r| l.starts_with(r)),
+            Operator::EndsWith => self.strings(lhs, rhs, |l, r| l.ends_with(r)),
+            Operator::Contains => match lhs {
+                Value::List(items) => items.iter().any(|item| self.equal(item, rhs)),
+                _ => self.strings(lhs, rhs, |l, r| l.contains(r)),
+            },
+            Operator::Like => self.strings(lhs, rhs, |l, r| {
+                like(
+                    &l.chars().collect::<Vec<_>>(),
+                    &r.chars().collect::<Vec<_>>(),
+                )
+            }),
+            Operator::In => match rhs {
+                Value::List(items) => items.iter().any(|item| self.equal(lhs, item)),
+                _ => self.strings(lhs, rhs, |l, r| r.contains(l)),
+            },
+        }
+    }
+
+    fn equal(&self, lhs: &Value, rhs: &Value) -> bool {
+        match (lhs, rhs) {
+            (Value::String(_), Value::String(_)) => self.strings(lhs, rhs, |l, r| l == r),
+            (Value::Bool(b), Value::Number(n)) | (Value::Number(n), Value::Bool(b)) => {
+                f64::from(u8::from(*b)) == *n
+            }
+            _ => lhs == rhs,
+        }
+    }
+
+    fn compare(&self, lhs: &Value, rhs: &Value) -> Option<std::cmp::Ordering> {
+        match (lhs, rhs) {
+            (Value::Number(l), Value::Number(r)) => l.partial_cmp(r),
+            (Value::String(l), Value::String(r)) if self.case_insensitive => {
+                Some(l.to_lowercase().cmp(&r.to_lowercase()))
+            }
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            _ => None,
+        }
+    }
+
+    /// Apply a string test, honouring the case-insensitivity modifier. False
+    /// unless both sides are strings.
+    fn strings(&self, lhs: &Value, rhs: &Value, test: impl Fn(&str, &str) -> bool) -> bool {
+        match (lhs, rhs) {
+            (Value::String(l), Value::String(r)) if self.case_insensitive => {
+                test(&l.to_lowercase(), &r.to_lowercase())
+            }
+            (Value::String(l), Value::String(r)) => test(l, r),
+            _ => false,
+        }
+    }
+}
+
+/// Match `text` against a pattern with `*` and `?` wildcards.
+fn like(text: &[char], pattern: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| like(&text[i..], rest)),
+        Some(('?', rest)) => !text.is_empty() && like(&text[1..], rest),
+        Some((c, rest)) => text.first() == Some(c) && like(&text[1..], rest),
+    }
+}
+
+fn glyph_value(glyph: &Glyph, key: &str) -> Value {
+    let string = |s: Option<&str>| s.map_or(Value::Nil, |s| Value::String(s.to_string()));
+    let codepoints = || glyph.unicode.iter().flat_map(|u| u.iter());
+    match key {
+        "name" => Value::String(glyph.glyphname.to_string()),
+        "unicode" => string(codepoints().next().map(hex).as_deref()),
+        "unicodes" => Value::List(codepoints().map(|c| Value::String(hex(c))).collect()),
+        "category" => string(glyph.category.as_deref()),
+        "subCategory" => string(glyph.sub_category.as_deref()),
+        "case" => match &glyph.case {
+            None => Value::Nil,
+            Some(case) => Value::Number(match case {
+                Case::None => 0.0,
+                Case::Upper => 1.0,
+                Case::Lower => 2.0,
+                Case::SmallCaps => 3.0,
+                Case::Other => 4.0,
+            }),
+        },
+        "script" => string(glyph.script.as_deref()),
+        "productionName" => string(glyph.production.as_deref()),
+        "tags" => Value::List(
+            glyph
+                .tags
+                .iter()
+                .map(|t| Value::String(t.clone()))
+                .collect(),
+        ),
+        "export" => Value::Bool(glyph.export),
+        "locked" => Value::Bool(glyph.locked),
+        "note" => string(glyph.note.as_deref()),
+        "leftKerningGroup" => string(glyph.kern_left.as_deref()),
+        "rightKerningGroup" => string(glyph.kern_right.as_deref()),
+        "leftMetricsKey" => string(glyph.metric_left.as_deref()),
+        "rightMetricsKey" => string(glyph.metric_right.as_deref()),
+        "widthMetricsKey" => string(glyph.metric_width.as_deref()),
+        _ => match key.strip_prefix("userData.") {
+            Some(key) => glyph.user_data.get(key).map_or(Value::Nil, plist_value),
+            None => Value::Nil,
+        },
+    }
+}
+
+fn hex(c: char) -> String {
+    format!("{:04X}", c as u32)
+}
+
+fn plist_value(plist: &Plist) -> Value {
+    match plist {
+        Plist::String(s) => Value::String(s.clone()),
+        Plist::Integer(i) => Value::Number(*i as f64),
+        Plist::Float(f) => Value::Number(*f),
+        Plist::Array(items) => Value::List(items.iter().map(plist_value).collect()),
+        Plist::Dictionary(_) => Value::Nil,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    String(String),
+    Number(f64),
+    Operator(Operator),
+    Modifier(String),
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Identifier(s) => write!(f, "{s}"),
+            Token::String(s) => write!(f, "{s:?}"),
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Operator(op) => write!(f, "{op:?}"),
+            Token::Modifier(m) => write!(f, "[{m}]"),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenBrace => write!(f, "{{"),
+            Token::CloseBrace => write!(f, "}}"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>, PredicateError> {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(&(position, c)) = chars.get(i) {
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::OpenParen, 1),
+            (')', _) => (Token::CloseParen, 1),
+            ('{', _) => (Token::OpenBrace, 1),
+            ('}', _) => (Token::CloseBrace, 1),
+            (',', _) => (Token::Comma, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Operator(Operator::Equal), 2),
+            ('!', Some('=')) | ('<', Some('>')) => (Token::Operator(Operator::NotEqual), 2),
+            ('<', Some('=')) | ('=', Some('<')) => (Token::Operator(Operator::LessOrEqual), 2),
+            ('>', Some('=')) | ('=', Some('>')) => (Token::Operator(Operator::GreaterOrEqual), 2),
+            ('=', _) => (Token::Operator(Operator::Equal), 1),
+            ('<', _) => (Token::Operator(Operator::Less), 1),
+            ('>', _) => (Token::Operator(Operator::Greater), 1),
+            ('!', _) => (Token::Not, 1),
+            ('[', _) => {
+                let end = (i + 1..chars.len())
+                    .find(|&j| chars[j].1 == ']')
+                    .ok_or(PredicateError::UnexpectedEnd)?;
+                let modifier = chars[i + 1..end].iter().map(|&(_, c)| c).collect();
+                (Token::Modifier(modifier), end + 1 - i)
+            }
+            ('"' | '\'', _) => {
+                let mut string = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j).map(|&(_, c)| c) {
+                        None => return Err(PredicateError::UnterminatedString(position)),
+                        Some('\\') => {
+                            string.extend(chars.get(j + 1).map(|&(_, c)| c));
+                            j += 2;
+                        }
+                        Some(quote) if quote == c => break,
+                        Some(other) => {
+                            string.push(other);
+                            j += 1;
+                        }
+                    }
+                }
+                (Token::String(string), j + 1 - i)
+            }
+            (c, _) if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|&&(_, c)| c.is_ascii_digit() || c == '.')
+                    .count()
+                    + 1;
+                let text: String = chars[i..i + len].iter().map(|&(_, c)| c).collect();
+                let number = text.parse().map_err(|_| PredicateError::UnexpectedToken {
+                    token: text,
+                    position,
+                })?;
+                (Token::Number(number), len)
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|&&(_, c)| c.is_alphanumeric() || c == '_' || c == '.')
+                    .count();
+                let word: String = chars[i..i + len].iter().map(|&(_, c)| c).collect();
+                (keyword(word)?, len)
+            }
+            (c, _) => {
+                return Err(PredicateError::UnexpectedToken {
+                    token: c.to_string(),
+                    position,
+                })
+            }
+        };
+        tokens.push((token, position));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+fn keyword(word: String) -> Result<Token, PredicateError> {
+    Ok(match word.to_uppercase().as_str() {
+        "AND" => Token::And,
+        "OR" => Token::Or,
+        "NOT" => Token::Not,
+        "BEGINSWITH" => Token::Operator(Operator::BeginsWith),
+        "ENDSWITH" => Token::Operator(Operator::EndsWith),
+        "CONTAINS" => Token::Operator(Operator::Contains),
+        "LIKE" => Token::Operator(Operator::Like),
+        "IN" => Token::Operator(Operator::In),
+        "MATCHES" | "ANY" | "ALL" | "NONE" | "SOME" | "BETWEEN" => {
+            return Err(PredicateError::UnsupportedOperator(word))
+        }
+        _ => Token::Identifier(word),
+    })
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<(Token, usize), PredicateError> {
+        let token = self
+            .tokens
+            .get(self.index)
+            .cloned()
+            .ok_or(PredicateError::UnexpectedEnd)?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        self.index += usize::from(found);
+        found
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, PredicateError> {
+        let mut predicates = vec![self.parse_and()?];
+        while self.eat(&Token::Or) {
+            predicates.push(self.parse_and()?);
+        }
+        Ok(match predicates.len() {
+            1 => predicates.remove(0),
+            _ => Predicate::Or(predicates),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate, PredicateError> {
+        let mut predicates = vec![self.parse_unary()?];
+        while self.eat(&Token::And) {
+            predicates.push(self.parse_unary()?);
+        }
+        Ok(match predicates.len() {
+            1 => predicates.remove(0),
+            _ => Predicate::And(predicates),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Predicate, PredicateError> {
+        let (token, position) = self.next()?;
+        match token {
+            Token::Not => Ok(Predicate::Not(Box::new(self.parse_unary()?))),
+            Token::OpenParen => {
+                let predicate = self.parse_or()?;
+                self.expect(Token::CloseParen)?;
+                Ok(predicate)
+            }
+            Token::Identifier(word) if word.eq_ignore_ascii_case("TRUEPREDICATE") => {
+                Ok(Predicate::True)
+            }
+            Token::Identifier(word) if word.eq_ignore_ascii_case("FALSEPREDICATE") => {
+                Ok(Predicate::False)
+            }
+            Token::Identifier(key) => {
+                let operator = match self.next()? {
+                    (Token::Operator(operator), _) => operator,
+                    (token, position) => return Err(unexpected(token, position)),
+                };
+                let mut case_insensitive = false;
+                if let Some(Token::Modifier(modifier)) = self.peek() {
+                    case_insensitive = modifier.contains('c');
+                    self.index += 1;
+                }
+                let value = self.parse_value()?;
+                Ok(Predicate::Comparison(Comparison {
+                    key,
+                    operator,
+                    value,
+                    case_insensitive,
+                }))
+            }
+            token => Err(unexpected(token, position)),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, PredicateError> {
+        let (token, position) = self.next()?;
+        match token {
+            Token::String(s) => Ok(Value::String(s)),
+            Token::Number(n) => Ok(Value::Number(n)),
+            Token::Identifier(word) => match word.to_uppercase().as_str() {
+                "YES" | "TRUE" => Ok(Value::Bool(true)),
+                "NO" | "FALSE" => Ok(Value::Bool(false)),
+                "NIL" | "NULL" => Ok(Value::Nil),
+                _ => Err(unexpected(Token::Identifier(word), position)),
+            },
+            Token::OpenBrace => {
+                let mut items = Vec::new();
+                if self.eat(&Token::CloseBrace) {
+                    return Ok(Value::List(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    if self.eat(&Token::CloseBrace) {
+                        return Ok(Value::List(items));
+                    }
+                    self.expect(Token::Comma)?;
+                }
+            }
+            token => Err(unexpected(token, position)),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), PredicateError> {
+        match self.next()? {
+            (token, _) if token == expected => Ok(()),
+            (token, position) => Err(unexpected(token, position)),
+        }
+    }
+}
+
+fn unexpected(token: Token, position: usize) -> PredicateError {
+    PredicateError::UnexpectedToken {
+        token: token.to_string(),
+        position,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(key: &str, operator: Operator, value: Value) -> Predicate {
+        Predicate::Comparison(Comparison {
+            key: key.into(),
+            operator,
+            value,
+            case_insensitive: false,
+        })
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Predicate::parse("case == 3").unwrap(),
+            comparison("case", Operator::Equal, Value::Number(3.0))
+        );
+        assert_eq!(
+            Predicate::parse(
+                r#"category == "Letter" && (case = 1 OR NOT script IN {'latin', "greek"})"#
+            )
+            .unwrap(),
+            Predicate::And(vec![
+                comparison("category", Operator::Equal, Value::String("Letter".into())),
+                Predicate::Or(vec![
+                    comparison("case", Operator::Equal, Value::Number(1.0)),
+                    Predicate::Not(Box::new(comparison(
+                        "script",
+                        Operator::In,
+                        Value::List(vec![
+                            Value::String("latin".into()),
+                            Value::String("greek".into())
+                        ])
+                    ))),
+                ]),
+            ])
+        );
+        assert_eq!(
+            "name beginswith[cd] 'A'".parse::<Predicate>().unwrap(),
+            Predicate::Comparison(Comparison {
+                key: "name".into(),
+                operator: Operator::BeginsWith,
+                value: Value::String("A".into()),
+                case_insensitive: true,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Predicate::parse("category =="),
+            Err(PredicateError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Predicate::parse("category == \"Letter"),
+            Err(PredicateError::UnterminatedString(12))
+        );
+        assert_eq!(
+            Predicate::parse("case == 1 case"),
+            Err(PredicateError::UnexpectedToken {
+                token: "case".into(),
+                position: 10
+            })
+        );
+        assert_eq!(
+            Predicate::parse("name MATCHES 'a.*'"),
+            Err(PredicateError::UnsupportedOperator("MATCHES".into()))
+        );
+    }
+
+    #[test]
+    fn glyphs_matching() {
+        let font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let names = |predicate: &str| -> Vec<String> {
+            let predicate = Predicate::parse(predicate).unwrap();
+            font.glyphs_matching(&predicate)
+                .map(|g| g.glyphname.to_string())
+                .collect()
+        };
+
+        assert_eq!(names(r#"category == "Icon""#), ["Smily"]);
+        assert_eq!(names("productionName ENDSWITH[c] 'smily'"), ["Smily"]);
+        assert_eq!(names("unicode == '0041'"), ["A"]);
+        assert_eq!(names("unicodes CONTAINS '0061'"), ["A"]);
+        assert_eq!(
+            names("name LIKE 'A*' AND name != 'A'"),
+            names("name BEGINSWITH 'A' && !(name == 'A')")
+        );
+        assert!(names("name LIKE 'A?*'").contains(&"A.ss01".to_string()));
+        assert_eq!(names("FALSEPREDICATE"), Vec::<String>::new());
+        assert_eq!(names("export == YES").len(), names("export == 1").len());
+        assert_eq!(names("TRUEPREDICATE").len(), font.glyphs.len());
+    }
+}
@@ -0,0 +1,159 @@
+//! Chained filtering of the glyphs in a font.
+
+use crate::{Case, Font, Glyph, Predicate};
+
+/// An iterator over the glyphs of a font that match all the conditions added
+/// to it, in font order. Created with [`Font::query`].
+#[derive(Clone, Debug)]
+pub struct GlyphQuery<'a> {
+    glyphs: crate::glyph_list::Iter<'a>,
+    conditions: Conditions<'a>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Conditions<'a> {
+    category: Option<&'a str>,
+    sub_category: Option<&'a str>,
+    script: Option<&'a str>,
+    case: Option<Case>,
+    exported: Option<bool>,
+    with_unicode: bool,
+    predicate: Option<&'a Predicate>,
+}
+
+impl Font {
+    /// Start a query over the glyphs of the font, matching every glyph until
+    /// conditions are added.
+    ///
+    /// ```no_run
+    /// # let font = glyphs_plist::Font::new();
+    /// let greek_letters = font
+    ///     .query()
+    ///     .category("Letter")
+    ///     .script("greek")
+    ///     .exported(true)
+    ///     .with_unicode()
+    ///     .count();
+    /// ```
+    pub fn query(&self) -> GlyphQuery<'_> {
+        GlyphQuery {
+            glyphs: self.glyphs.iter(),
+            conditions: Conditions::default(),
+        }
+    }
+}
+
+impl<'a> GlyphQuery<'a> {
+    /// Only glyphs of the given category, like `Letter`.
+    pub fn category(mut self, category: &'a str) -> Self {
+        self.conditions.category = Some(category);
+        self
+    }
+
+    /// Only glyphs of the given subcategory, like `Lowercase`.
+    pub fn sub_category(mut self, sub_category: &'a str) -> Self {
+        self.conditions.sub_category = Some(sub_category);
+        self
+    }
+
+    /// Only glyphs of the given script, like `greek`.
+    pub fn script(mut self, script: &'a str) -> Self {
+        self.conditions.script = Some(script);
+        self
+    }
+
+    /// Only glyphs of the given case.
+    pub fn case(mut self, case: Case) -> Self {
+        self.conditions.case = Some(case);
+        self
+    }
+
+    /// Only glyphs that are, or aren't, exported.
+    pub fn exported(mut self, exported: bool) -> Self {
+        self.conditions.exported = Some(exported);
+        self
+    }
+
+    /// Only glyphs with at least one Unicode value.
+    pub fn with_unicode(mut self) -> Self {
+        self.conditions.with_unicode = true;
+        self
+    }
+
+    /// Only glyphs matching a filter predicate.
+    pub fn matching(mut self, predicate: &'a Predicate) -> Self {
+        self.conditions.predicate = Some(predicate);
+        self
+    }
+}
+
+impl<'a> Iterator for GlyphQuery<'a> {
+    type Item = &'a Glyph;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let conditions = &self.conditions;
+        self.glyphs.find(|glyph| conditions.matches(glyph))
+    }
+}
+
+impl Conditions<'_> {
+    fn matches(&self, glyph: &Glyph) -> bool {
+        let field_matches = |field: &Option<String>, wanted: Option<&str>| {
+            wanted.is_none() || field.as_deref() == wanted
+        };
+        if !field_matches(&glyph.category, self.category)
+            || !field_matches(&glyph.sub_category, self.sub_category)
+            || !field_matches(&glyph.script, self.script)
+        {
+            return false;
+        }
+        if self.case.is_some() && glyph.case != self.case {
+            return false;
+        }
+        if self.exported.is_some() && self.exported != Some(glyph.export) {
+            return false;
+        }
+        let has_unicode = glyph.unicode.as_ref().is_some_and(|u| !u.is_empty());
+        if self.with_unicode && !has_unicode {
+            return false;
+        }
+        match self.predicate {
+            Some(predicate) => predicate.matches(glyph),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Case, Font, Predicate};
+
+    #[test]
+    fn query() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let names = |query: crate::GlyphQuery| -> Vec<String> {
+            query.map(|g| g.glyphname.to_string()).collect()
+        };
+        assert_eq!(names(font.query()).len(), font.glyphs.len());
+        assert_eq!(names(font.query().category("Icon")), ["Smily"]);
+        assert!(names(font.query().category("Icon").with_unicode()).is_empty());
+
+        for name in ["A", "B"] {
+            let glyph = font.get_glyph_mut(name).unwrap();
+            glyph.category = Some("Letter".into());
+            glyph.script = Some("latin".into());
+            glyph.case = Some(Case::Upper);
+        }
+        let b = font.get_glyph_mut("B").unwrap();
+        b.export = false;
+        b.unicode = None;
+        let query = font.query().category("Letter").script("latin");
+        assert_eq!(names(query.clone()), ["A", "B"]);
+        assert_eq!(names(query.clone().exported(true)), ["A"]);
+        assert_eq!(names(query.clone().exported(false)), ["B"]);
+        assert_eq!(names(query.clone().case(Case::Lower)), Vec::<String>::new());
+        assert_eq!(names(query.clone().with_unicode()), ["A"]);
+        let predicate = Predicate::parse("name == 'B'").unwrap();
+        assert_eq!(names(query.matching(&predicate)), ["B"]);
+    }
+}
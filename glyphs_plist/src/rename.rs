@@ -0,0 +1,608 @@
+//! Renaming and removing glyphs together with everything that refers to
+//! them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::{Font, Glyph, Shape};
+
+#[derive(Debug, Error)]
+pub enum RenameError {
+    #[error("no glyph named {0:?}")]
+    UnknownGlyph(String),
+    #[error("more than one glyph would be named {0:?}")]
+    DuplicateName(String),
+    #[error("bad glyph name: {0}")]
+    Naming(#[from] norad::error::NamingError),
+}
+
+#[derive(Debug, Error)]
+pub enum RemoveGlyphError {
+    #[error("no glyph named {0:?}")]
+    UnknownGlyph(String),
+    #[error("glyph {glyph:?} is still referenced: {references:?}")]
+    Referenced {
+        glyph: String,
+        references: DanglingReferences,
+    },
+}
+
+/// The references to a glyph from the rest of the font, which would be left
+/// dangling if it were removed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DanglingReferences {
+    /// The glyphs using the glyph as a component, in any layer or background.
+    pub components: Vec<String>,
+    /// The glyphs with metrics keys referring to the glyph, on the glyph or
+    /// its layers.
+    pub metrics_keys: Vec<String>,
+    /// The number of kerning pairs with the glyph on either side, over all
+    /// masters and directions.
+    pub kerning_pairs: usize,
+}
+
+impl DanglingReferences {
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty() && self.metrics_keys.is_empty() && self.kerning_pairs == 0
+    }
+}
+
+/// The references that were updated by a rename, besides the glyph names.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenameReport {
+    /// The glyphs with components that were updated, in any layer or
+    /// background.
+    pub components: Vec<String>,
+    /// The glyphs with metrics keys that were updated, on the glyph or its
+    /// layers.
+    pub metrics_keys: Vec<String>,
+    /// The number of kerning pairs with a renamed side, over all masters and
+    /// directions.
+    pub kerning_pairs: usize,
+    /// The names of the classes, feature prefixes and the tags of the
+    /// features whose code was updated.
+    pub feature_code: Vec<String>,
+}
+
+impl Font {
+    /// The production name of every glyph that has one, keyed by the glyph
+    /// name.
+    pub fn production_names(&self) -> HashMap<String, String> {
+        self.glyphs
+            .iter()
+            .filter_map(|glyph| {
+                let production = glyph.production.as_ref()?;
+                Some((glyph.glyphname.to_string(), production.clone()))
+            })
+            .collect()
+    }
+
+    /// Rename the glyphs that have a production name to it.
+    ///
+    /// Returns the renaming that was applied, inverted, so that passing it to
+    /// [`Font::rename_glyphs`] restores the nice names.
+    pub fn rename_to_production_names(&mut self) -> Result<HashMap<String, String>, RenameError> {
+        let renames = self.production_names();
+        self.rename_glyphs(&renames)?;
+        Ok(renames
+            .into_iter()
+            .map(|(nice, production)| (production, nice))
+            .collect())
+    }
+
+    /// Rename glyphs, keyed by their current name, and update the component
+    /// references, kerning, metrics keys and feature code referring to them.
+    ///
+    /// Kerning groups keep their names, as group membership is stored on the
+    /// glyphs themselves. Nothing is changed if two glyphs would end up with
+    /// the same name.
+    pub fn rename_glyphs(&mut self, renames: &HashMap<String, String>) -> Result<(), RenameError> {
+        self.rename_glyphs_with_report(renames)?;
+        Ok(())
+    }
+
+    /// Rename a single glyph as [`Font::rename_glyphs`] does, and report the
+    /// references to it that were updated.
+    pub fn rename_glyph(&mut self, old: &str, new: &str) -> Result<RenameReport, RenameError> {
+        if !self.contains_glyph(old) {
+            return Err(RenameError::UnknownGlyph(old.to_string()));
+        }
+        self.rename_glyphs_with_report(&HashMap::from([(old.to_string(), new.to_string())]))
+    }
+
+    fn rename_glyphs_with_report(
+        &mut self,
+        renames: &HashMap<String, String>,
+    ) -> Result<RenameReport, RenameError> {
+        let mut names = HashSet::new();
+        for glyph in &self.glyphs {
+            let name = renames
+                .get(glyph.glyphname.as_str())
+                .map(String::as_str)
+                .unwrap_or(glyph.glyphname.as_str());
+            if !names.insert(name) {
+                return Err(RenameError::DuplicateName(name.to_string()));
+            }
+        }
+        let new_names = renames
+            .iter()
+            .map(|(old, new)| Ok((old.as_str(), norad::Name::new(new)?)))
+            .collect::<Result<HashMap<_, _>, norad::error::NamingError>>()?;
+
+        let mut report = RenameReport::default();
+        for glyph in &mut self.glyphs {
+            if let Some(name) = new_names.get(glyph.glyphname.as_str()) {
+                glyph.glyphname = name.clone();
+            }
+            let mut metrics_keys_renamed = false;
+            let mut components_renamed = false;
+            for key in metrics_keys_mut(glyph) {
+                metrics_keys_renamed |= rename_in_metrics_key(key, renames);
+            }
+            for layer in &mut glyph.layers {
+                let background_shapes = layer.background.iter_mut().flat_map(|b| &mut b.shapes);
+                for shape in layer.shapes.iter_mut().chain(background_shapes) {
+                    if let Shape::Component(component) = shape {
+                        if let Some(name) = renames.get(&component.reference) {
+                            component.reference.clone_from(name);
+                            components_renamed = true;
+                        }
+                    }
+                }
+            }
+            if metrics_keys_renamed {
+                report.metrics_keys.push(glyph.glyphname.to_string());
+            }
+            if components_renamed {
+                report.components.push(glyph.glyphname.to_string());
+            }
+        }
+
+        for kerning in [
+            &mut self.kerning_ltr,
+            &mut self.kerning_rtl,
+            &mut self.kerning_vertical,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            for master_kerning in kerning.values_mut() {
+                *master_kerning = std::mem::take(master_kerning)
+                    .into_iter()
+                    .map(|(first, seconds)| {
+                        let first_renamed = new_names.contains_key(first.as_str());
+                        let seconds: BTreeMap<_, _> = seconds
+                            .into_iter()
+                            .map(|(second, value)| {
+                                if first_renamed || new_names.contains_key(second.as_str()) {
+                                    report.kerning_pairs += 1;
+                                }
+                                (rename_kerning_side(second, &new_names), value)
+                            })
+                            .collect();
+                        (rename_kerning_side(first, &new_names), seconds)
+                    })
+                    .collect();
+            }
+        }
+
+        let classes = self
+            .classes
+            .iter_mut()
+            .flatten()
+            .map(|c| (c.name.as_str(), &mut c.code));
+        let prefixes = self
+            .feature_prefixes
+            .iter_mut()
+            .flatten()
+            .map(|p| (p.name.as_str(), &mut p.code));
+        let features = self
+            .features
+            .iter_mut()
+            .flatten()
+            .map(|f| (f.tag.as_str(), &mut f.code));
+        for (name, code) in classes.chain(prefixes).chain(features) {
+            let renamed = rename_in_feature_code(code, renames);
+            if renamed != *code {
+                *code = renamed;
+                report.feature_code.push(name.to_string());
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl Font {
+    /// The references other glyphs and the kerning make to a glyph.
+    ///
+    /// Feature code isn't checked.
+    pub fn dangling_references(&self, glyphname: &str) -> DanglingReferences {
+        let mut references = DanglingReferences::default();
+        for glyph in self
+            .glyphs
+            .iter()
+            .filter(|g| g.glyphname.as_str() != glyphname)
+        {
+            let name = glyph.glyphname.to_string();
+            if metrics_keys(glyph)
+                .flatten()
+                .any(|key| metrics_key_reference(key, |n| n == glyphname).is_some())
+            {
+                references.metrics_keys.push(name.clone());
+            }
+            if glyph.layers.iter().any(|layer| {
+                let background_shapes = layer.background.iter().flat_map(|b| &b.shapes);
+                layer
+                    .shapes
+                    .iter()
+                    .chain(background_shapes)
+                    .any(|s| matches!(s, Shape::Component(c) if c.reference == glyphname))
+            }) {
+                references.components.push(name);
+            }
+        }
+        for master_kerning in self.all_kerning().flat_map(|k| k.values()) {
+            for (first, seconds) in master_kerning {
+                references.kerning_pairs += if first.as_str() == glyphname {
+                    seconds.len()
+                } else {
+                    usize::from(seconds.contains_key(glyphname))
+                };
+            }
+        }
+        references
+    }
+
+    /// Remove a glyph that nothing else refers to.
+    ///
+    /// Fails with the [`DanglingReferences`] if any components, metrics keys
+    /// or kerning pairs still refer to the glyph, leaving the font unchanged.
+    pub fn remove_glyph(&mut self, glyphname: &str) -> Result<Glyph, RemoveGlyphError> {
+        let references = self.dangling_references(glyphname);
+        if !references.is_empty() {
+            return Err(RemoveGlyphError::Referenced {
+                glyph: glyphname.to_string(),
+                references,
+            });
+        }
+        self.remove_glyph_cascading(glyphname)
+            .map(|(glyph, _)| glyph)
+    }
+
+    /// Remove a glyph along with the references to it: components using it
+    /// are removed, metrics keys referring to it are cleared and its kerning
+    /// pairs are removed. Returns the glyph and what was removed with it.
+    ///
+    /// Feature code referring to the glyph is left alone.
+    pub fn remove_glyph_cascading(
+        &mut self,
+        glyphname: &str,
+    ) -> Result<(Glyph, DanglingReferences), RemoveGlyphError> {
+        let index = self
+            .glyphs
+            .iter()
+            .position(|g| g.glyphname.as_str() == glyphname)
+            .ok_or_else(|| RemoveGlyphError::UnknownGlyph(glyphname.to_string()))?;
+        let references = self.dangling_references(glyphname);
+        let glyph = self.glyphs.remove(index);
+
+        for glyph in &mut self.glyphs {
+            for key in metrics_keys_mut(glyph) {
+                if key
+                    .as_deref()
+                    .and_then(|key| metrics_key_reference(key, |n| n == glyphname))
+                    .is_some()
+                {
+                    *key = None;
+                }
+            }
+            for layer in &mut glyph.layers {
+                let is_kept =
+                    |s: &Shape| !matches!(s, Shape::Component(c) if c.reference == glyphname);
+                layer.shapes.retain(is_kept);
+                if let Some(background) = &mut layer.background {
+                    background.shapes.retain(is_kept);
+                }
+            }
+        }
+        for kerning in [
+            &mut self.kerning_ltr,
+            &mut self.kerning_rtl,
+            &mut self.kerning_vertical,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            for master_kerning in kerning.values_mut() {
+                master_kerning.remove(glyphname);
+                master_kerning.retain(|_, seconds| {
+                    seconds.remove(glyphname);
+                    !seconds.is_empty()
+                });
+            }
+        }
+        Ok((glyph, references))
+    }
+
+    fn all_kerning(&self) -> impl Iterator<Item = &HashMap<String, norad::Kerning>> {
+        [&self.kerning_ltr, &self.kerning_rtl, &self.kerning_vertical]
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// The metrics keys of a glyph and its layers.
+fn metrics_keys(glyph: &Glyph) -> impl Iterator<Item = Option<&str>> {
+    let layer_keys = glyph.layers.iter().flat_map(|layer| {
+        [
+            &layer.metric_top,
+            &layer.metric_bottom,
+            &layer.metric_left,
+            &layer.metric_right,
+            &layer.metric_width,
+            &layer.metric_vert_width,
+        ]
+    });
+    [
+        &glyph.metric_top,
+        &glyph.metric_bottom,
+        &glyph.metric_left,
+        &glyph.metric_right,
+        &glyph.metric_width,
+    ]
+    .into_iter()
+    .chain(layer_keys)
+    .map(Option::as_deref)
+}
+
+/// The metrics keys of a glyph and its layers, mutably.
+pub(crate) fn metrics_keys_mut(glyph: &mut Glyph) -> impl Iterator<Item = &mut Option<String>> {
+    let layer_keys = glyph.layers.iter_mut().flat_map(|layer| {
+        [
+            &mut layer.metric_top,
+            &mut layer.metric_bottom,
+            &mut layer.metric_left,
+            &mut layer.metric_right,
+            &mut layer.metric_width,
+            &mut layer.metric_vert_width,
+        ]
+    });
+    [
+        &mut glyph.metric_top,
+        &mut glyph.metric_bottom,
+        &mut glyph.metric_left,
+        &mut glyph.metric_right,
+        &mut glyph.metric_width,
+    ]
+    .into_iter()
+    .chain(layer_keys)
+}
+
+fn rename_kerning_side(side: norad::Name, new_names: &HashMap<&str, norad::Name>) -> norad::Name {
+    new_names.get(side.as_str()).cloned().unwrap_or(side)
+}
+
+/// Rename the glyph a metrics key like `A`, `=A+10` or `=|A` refers to,
+/// returning whether it changed.
+pub(crate) fn rename_in_metrics_key(
+    key: &mut Option<String>,
+    renames: &HashMap<String, String>,
+) -> bool {
+    let Some(key) = key else {
+        return false;
+    };
+    let Some(range) = metrics_key_reference(key, |name| renames.contains_key(name)) else {
+        return false;
+    };
+    let new = &renames[&key[range.clone()]];
+    key.replace_range(range, new);
+    true
+}
+
+/// The range of the glyph name in a metrics key like `A`, `=A+10` or `=|A`,
+/// if it is one of the names accepted by `is_glyph`.
+fn metrics_key_reference(key: &str, is_glyph: impl Fn(&str) -> bool) -> Option<Range<usize>> {
+    let start = key.len() - key.trim_start_matches(['=', '|']).len();
+    let rest = &key[start..];
+    // Glyph names can contain operators like '-', so prefer the longest name
+    // followed by the end of the key or an operator.
+    rest.char_indices()
+        .map(|(i, _)| i)
+        .chain([rest.len()])
+        .rev()
+        .filter(|&end| end == rest.len() || rest[end..].starts_with(['+', '-', '*', '/', ' ']))
+        .find(|&end| is_glyph(&rest[..end]))
+        .map(|end| start..start + end)
+}
+
+/// Rename the glyph names in a piece of feature code, leaving class names
+/// alone.
+fn rename_in_feature_code(code: &str, renames: &HashMap<String, String>) -> String {
+    let is_delimiter = |c: char| c.is_whitespace() || "[](){};,'\"<>=\\@#".contains(c);
+    let mut renamed = String::with_capacity(code.len());
+    let mut rest = code;
+    let mut after_at = false;
+    while !rest.is_empty() {
+        let token_len = rest.find(is_delimiter).unwrap_or(rest.len());
+        if token_len == 0 {
+            let c = rest.chars().next().unwrap();
+            renamed.push(c);
+            after_at = c == '@';
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let (token, tail) = rest.split_at(token_len);
+        match renames.get(token) {
+            Some(new) if !after_at => renamed.push_str(new),
+            _ => renamed.push_str(token),
+        }
+        after_at = false;
+        rest = tail;
+    }
+    renamed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        rename_in_feature_code, rename_in_metrics_key, DanglingReferences, RemoveGlyphError,
+        RenameError, RenameReport,
+    };
+    use crate::{Font, Shape};
+
+    fn renames(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn metrics_keys() {
+        let renames = renames(&[("A", "uni0041"), ("alef-ar", "uni0627")]);
+        let rename = |key: &str| {
+            let mut key = Some(key.to_string());
+            rename_in_metrics_key(&mut key, &renames);
+            key.unwrap()
+        };
+
+        assert_eq!(rename("A"), "uni0041");
+        assert_eq!(rename("=A"), "=uni0041");
+        assert_eq!(rename("=|A"), "=|uni0041");
+        assert_eq!(rename("=A+10"), "=uni0041+10");
+        assert_eq!(rename("=alef-ar"), "=uni0627");
+        assert_eq!(rename("=alef-ar-20"), "=uni0627-20");
+        assert_eq!(rename("=Aacute"), "=Aacute");
+        assert_eq!(rename("=|"), "=|");
+    }
+
+    #[test]
+    fn feature_code() {
+        let renames = renames(&[("A", "uni0041"), ("A.ss01", "uni0041.ss01")]);
+
+        assert_eq!(
+            rename_in_feature_code("sub A by A.ss01;\n@A = [A \\A];", &renames),
+            "sub uni0041 by uni0041.ss01;\n@A = [uni0041 \\uni0041];"
+        );
+    }
+
+    #[test]
+    fn rename_to_production_names() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.get_glyph_mut("A").unwrap().production = Some("uni0041".into());
+        let original = font.clone();
+
+        let undo = font.rename_to_production_names().unwrap();
+        assert!(font.get_glyph("A").is_none());
+        assert!(font.get_glyph("uni0041").is_some());
+        assert_eq!(
+            font.get_glyph("someSmily").unwrap().production.as_deref(),
+            Some("someSmily")
+        );
+
+        let a_dieresis = font.get_glyph("Ä").unwrap();
+        let Shape::Component(component) = &a_dieresis.layers[0].shapes[0] else {
+            panic!("expected a component");
+        };
+        assert_eq!(component.reference, "uni0041");
+        assert_eq!(
+            font.get_glyph("C").unwrap().metric_width.as_deref(),
+            Some("uni0041")
+        );
+        let kerning = &font.kerning_ltr.as_ref().unwrap()["m01"];
+        assert!(kerning.contains_key("uni0041") && !kerning.contains_key("A"));
+        assert!(font
+            .features
+            .iter()
+            .flatten()
+            .any(|f| f.code.contains("sub uni0041 by A.ss01;")));
+        assert!(font.classes.as_ref().unwrap()[1]
+            .code
+            .starts_with("uni0041 B"));
+
+        font.rename_glyphs(&undo).unwrap();
+        assert_eq!(font, original);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let original = font.clone();
+
+        assert!(matches!(
+            font.rename_glyphs(&renames(&[("A", "B")])),
+            Err(RenameError::DuplicateName(name)) if name == "B"
+        ));
+        assert_eq!(font, original);
+        font.rename_glyphs(&renames(&[("A", "B"), ("B", "A")]))
+            .unwrap();
+        assert!(font.get_glyph("A").is_some() && font.get_glyph("B").is_some());
+    }
+
+    #[test]
+    fn rename_glyph_report() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+
+        let report = font.rename_glyph("A", "uni0041").unwrap();
+        assert_eq!(
+            report,
+            RenameReport {
+                components: vec!["Ä".into(), "B".into()],
+                metrics_keys: vec!["C".into(), "alef-ar".into(), "dieresiscomb".into()],
+                kerning_pairs: 4,
+                feature_code: vec!["Uppercase".into(), "AllLetters".into(), "ss01".into()],
+            }
+        );
+        assert!(font.contains_glyph("uni0041"));
+
+        assert!(matches!(
+            font.rename_glyph("A", "B"),
+            Err(RenameError::UnknownGlyph(name)) if name == "A"
+        ));
+    }
+
+    #[test]
+    fn remove_glyph() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let original = font.clone();
+
+        let references = DanglingReferences {
+            components: vec!["Ä".into(), "B".into()],
+            metrics_keys: vec!["C".into(), "alef-ar".into(), "dieresiscomb".into()],
+            kerning_pairs: 4,
+        };
+        assert_eq!(font.dangling_references("A"), references);
+        assert!(matches!(
+            font.remove_glyph("A"),
+            Err(RemoveGlyphError::Referenced { glyph, references: r })
+                if glyph == "A" && r == references
+        ));
+        assert_eq!(font, original);
+        assert!(matches!(
+            font.remove_glyph("nonexistent"),
+            Err(RemoveGlyphError::UnknownGlyph(_))
+        ));
+
+        let (glyph, removed) = font.remove_glyph_cascading("A").unwrap();
+        assert_eq!(glyph.glyphname.as_str(), "A");
+        assert_eq!(removed, references);
+        assert!(!font.contains_glyph("A"));
+        assert!(font.dangling_references("A").is_empty());
+        assert_eq!(font.get_glyph("C").unwrap().metric_width, None);
+
+        let unused = font
+            .glyphs
+            .iter()
+            .find(|g| font.dangling_references(g.glyphname.as_str()).is_empty());
+        let unused = unused.unwrap().glyphname.to_string();
+        assert_eq!(
+            font.remove_glyph(&unused).unwrap().glyphname.as_str(),
+            unused
+        );
+        assert!(!font.contains_glyph(&unused));
+    }
+}
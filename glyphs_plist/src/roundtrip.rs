@@ -0,0 +1,310 @@
+//! Checking that fonts survive being loaded and saved, for making sure the
+//! model supports everything in a file before relying on it.
+
+use std::fmt;
+
+use crate::font::FontLoadError;
+use crate::{Font, FormatVersion, Plist, ToPlist};
+
+/// What changed when a font was loaded and saved, from [`verify_roundtrip`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoundtripReport {
+    /// The format the file was upgraded from when loading, if it was. The
+    /// file is compared in the upgraded form.
+    pub upgraded_from: Option<FormatVersion>,
+    /// The values that were lost, added or changed, with dictionary keys in
+    /// sorted order.
+    pub differences: Vec<RoundtripDifference>,
+}
+
+/// A value that was lost, added or changed by a round trip.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundtripDifference {
+    /// Where the value is, like `glyphs["A"].layers["m01"].width`.
+    pub path: String,
+    /// The value in the file, `None` if the round trip added it.
+    pub old: Option<Plist>,
+    /// The value after the round trip, `None` if it was lost.
+    pub new: Option<Plist>,
+}
+
+impl RoundtripReport {
+    /// Whether the font came through the round trip unchanged.
+    pub fn is_lossless(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+impl fmt::Display for RoundtripDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = &self.path;
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{path}: {old} became {new}"),
+            (Some(old), None) => write!(f, "{path}: {old} was lost"),
+            (None, Some(new)) => write!(f, "{path}: {new} was added"),
+            (None, None) => write!(f, "{path}: unchanged"),
+        }
+    }
+}
+
+/// Load a font from a `.glyphs` file or a `.glyphspackage` directory, save it
+/// and parse it again, reporting the plist values that didn't come through
+/// unchanged.
+///
+/// Numbers compare by value, and numbers read as text compare equal to the
+/// text, so only changes to the data are reported, not to its formatting.
+/// Keys left out because they hold the value the model defaults to, like
+/// `visible = 1;` on a master, aren't reported as lost.
+pub fn verify_roundtrip(
+    path: impl AsRef<std::path::Path>,
+) -> Result<RoundtripReport, FontLoadError> {
+    let path = path.as_ref();
+    let file = Font::load_plist(path).map_err(|e| e.in_file(path))?;
+    let (original, upgraded_from) = (file.plist, file.upgraded_from);
+    let font = Font::from_font_plist(original.clone())
+        .map_err(|e| FontLoadError::from(e).in_file(path))?;
+    let saved = Plist::parse(&font.to_plist().to_string())?;
+
+    let mut differences = Vec::new();
+    compare(&original, &saved, "", &mut Vec::new(), &mut differences);
+    let differences = differences
+        .into_iter()
+        .filter(|(difference, steps)| {
+            difference.new.is_some() || !is_default_valued(&original, &font, steps)
+        })
+        .map(|(difference, _)| difference)
+        .collect();
+    Ok(RoundtripReport {
+        upgraded_from,
+        differences,
+    })
+}
+
+/// A step from a plist value into one of its children.
+#[derive(Clone, Debug)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Whether the dictionary key at `steps` in the original plist only held
+/// the value the model takes when it's missing: leaving it out loads the same
+/// font, while changing it doesn't.
+///
+/// Only the part of the font holding the key is converted again: its glyph,
+/// or the font without its glyphs.
+fn is_default_valued(original: &Plist, font: &Font, steps: &[Step]) -> bool {
+    match steps {
+        [Step::Key(glyphs), Step::Index(i), steps @ ..] if glyphs == "glyphs" => {
+            let glyph = original.get("glyphs").and_then(|g| g.as_array()?.get(*i));
+            match (glyph, font.glyphs.get(*i)) {
+                (Some(plist), Some(glyph)) => reloads_unchanged(plist, glyph, steps),
+                _ => false,
+            }
+        }
+        _ => {
+            let mut plist = original.clone();
+            if let Plist::Dictionary(dict) = &mut plist {
+                dict.insert("glyphs".into(), Plist::Array(Vec::new()));
+            }
+            let mut font = font.clone();
+            font.glyphs.clear();
+            reloads_unchanged(&plist, &font, steps)
+        }
+    }
+}
+
+/// Whether converting `plist` without the dictionary key at `steps` gives
+/// `model`, while converting it with a different value there doesn't.
+fn reloads_unchanged<T>(plist: &Plist, model: &T, steps: &[Step]) -> bool
+where
+    T: TryFrom<Plist> + PartialEq,
+{
+    let Some((Step::Key(key), parent)) = steps.split_last() else {
+        return false;
+    };
+    let load_with = |value: Option<Plist>| {
+        let mut plist = plist.clone();
+        let Some(Plist::Dictionary(dict)) = value_at_mut(&mut plist, parent) else {
+            return None;
+        };
+        match value {
+            Some(value) => dict.insert(key.clone(), value),
+            None => dict.remove(key),
+        };
+        T::try_from(plist).ok()
+    };
+    let changed = match plist.get_path(steps) {
+        Some(Plist::Integer(n)) => Plist::Integer(if *n == 0 { 1 } else { 0 }),
+        Some(Plist::Float(n)) => Plist::Float(n + 1.0),
+        Some(Plist::String(text)) => Plist::String(format!("{text}_")),
+        _ => return false,
+    };
+    load_with(None).as_ref() == Some(model) && load_with(Some(changed)).as_ref() != Some(model)
+}
+
+fn value_at_mut<'a>(plist: &'a mut Plist, steps: &[Step]) -> Option<&'a mut Plist> {
+    steps
+        .iter()
+        .try_fold(plist, |plist, step| match (plist, step) {
+            (Plist::Dictionary(dict), Step::Key(key)) => dict.get_mut(key),
+            (Plist::Array(array), Step::Index(i)) => array.get_mut(*i),
+            _ => None,
+        })
+}
+
+impl Plist {
+    fn get_path(&self, steps: &[Step]) -> Option<&Plist> {
+        steps
+            .iter()
+            .try_fold(self, |plist, step| match (plist, step) {
+                (Plist::Dictionary(dict), Step::Key(key)) => dict.get(key),
+                (Plist::Array(array), Step::Index(i)) => array.get(*i),
+                _ => None,
+            })
+    }
+}
+
+fn compare(
+    old: &Plist,
+    new: &Plist,
+    path: &str,
+    steps: &mut Vec<Step>,
+    differences: &mut Vec<(RoundtripDifference, Vec<Step>)>,
+) {
+    match (old, new) {
+        (Plist::Dictionary(old), Plist::Dictionary(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{path}.{key}"),
+                };
+                steps.push(Step::Key(key.clone()));
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => compare(old, new, &path, steps, differences),
+                    (old, new) => differences.push((
+                        RoundtripDifference {
+                            path,
+                            old: old.cloned(),
+                            new: new.cloned(),
+                        },
+                        steps.clone(),
+                    )),
+                }
+                steps.pop();
+            }
+        }
+        (Plist::Array(old), Plist::Array(new)) => {
+            let mut matched = vec![false; new.len()];
+            for (i, value) in old.iter().enumerate() {
+                let j = match value.identity() {
+                    Some(id) => new.iter().position(|new| new.identity() == Some(id)),
+                    None => (i < new.len()).then_some(i),
+                };
+                let path = element_path(path, value, i);
+                steps.push(Step::Index(i));
+                match j {
+                    Some(j) => {
+                        matched[j] = true;
+                        compare(value, &new[j], &path, steps, differences);
+                    }
+                    None => differences.push((
+                        RoundtripDifference {
+                            path,
+                            old: Some(value.clone()),
+                            new: None,
+                        },
+                        steps.clone(),
+                    )),
+                }
+                steps.pop();
+            }
+            for (j, value) in new.iter().enumerate().filter(|(j, _)| !matched[*j]) {
+                let difference = RoundtripDifference {
+                    path: element_path(path, value, j),
+                    old: None,
+                    new: Some(value.clone()),
+                };
+                differences.push((difference, steps.clone()));
+            }
+        }
+        (old, new) if !same_value(old, new) => differences.push((
+            RoundtripDifference {
+                path: path.to_string(),
+                old: Some(old.clone()),
+                new: Some(new.clone()),
+            },
+            steps.clone(),
+        )),
+        _ => (),
+    }
+}
+
+fn same_value(old: &Plist, new: &Plist) -> bool {
+    match (old, new) {
+        (Plist::Integer(_) | Plist::Float(_), Plist::Integer(_) | Plist::Float(_)) => {
+            old.as_f64() == new.as_f64()
+        }
+        (Plist::Integer(_) | Plist::Float(_), Plist::String(text)) => old.to_string() == *text,
+        _ => old == new,
+    }
+}
+
+fn element_path(path: &str, value: &Plist, i: usize) -> String {
+    match value.identity() {
+        Some((_, Plist::String(id))) => format!("{path}[{id:?}]"),
+        Some((_, id)) => format!("{path}[\"{id}\"]"),
+        None => format!("{path}[{i}]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless() {
+        let path = std::env::temp_dir().join("glyphs_plist_roundtrip.glyphs");
+        Font::default().save(&path).unwrap();
+        let report = verify_roundtrip(&path).unwrap();
+        assert!(report.is_lossless(), "{:?}", report.differences);
+
+        // Keys holding the default value, in a glyph or elsewhere, aren't
+        // lost.
+        let mut plist = Font::default().to_plist().into_hashmap();
+        for (key, default, value) in [("glyphs", "locked", 0), ("fontMaster", "visible", 1)] {
+            let Some(Plist::Array(array)) = plist.get_mut(key) else {
+                panic!("expected {key}");
+            };
+            let Plist::Dictionary(dict) = &mut array[0] else {
+                panic!("expected a dictionary");
+            };
+            dict.insert(default.into(), Plist::Integer(value));
+        }
+        std::fs::write(&path, Plist::from(plist).to_string()).unwrap();
+        let report = verify_roundtrip(&path).unwrap();
+        assert!(report.is_lossless(), "{:?}", report.differences);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn losses() {
+        // The model doesn't know about the user data of nodes yet. Masters
+        // are visible by default, so `visible = 1;` is left out when saving
+        // without being lost.
+        let report = verify_roundtrip("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let paths: Vec<&str> = report.differences.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "glyphs[\"A\"].layers[\"m01\"].shapes[0].nodes[7][3]",
+                "glyphs[\"A\"].layers[\"C2ECF50A-02EF-4989-A14C-AF8E838D1105\"].shapes[0].nodes[7][3]",
+            ]
+        );
+        assert!(report.differences.iter().all(|d| d.new.is_none()));
+        assert_eq!(report.upgraded_from, None);
+    }
+}
@@ -0,0 +1,300 @@
+//! Rescaling a font to a different number of units per em.
+
+use std::collections::HashMap;
+
+use kurbo::Point;
+
+use crate::custom_parameters::dict_parameters_mut;
+use crate::font::Scale;
+use crate::hints::scale_hints;
+use crate::{Font, Layer, MetricType, Plist, Shape};
+
+/// Custom parameters holding distances in font units, at font, master or
+/// instance level.
+const SCALED_PARAMETERS: &[&str] = &[
+    "hheaAscender",
+    "hheaDescender",
+    "hheaLineGap",
+    "strikeoutPosition",
+    "strikeoutSize",
+    "subscriptXOffset",
+    "subscriptXSize",
+    "subscriptYOffset",
+    "subscriptYSize",
+    "superscriptXOffset",
+    "superscriptXSize",
+    "superscriptYOffset",
+    "superscriptYSize",
+    "typoAscender",
+    "typoDescender",
+    "typoLineGap",
+    "underlinePosition",
+    "underlineThickness",
+    "vheaVertAscender",
+    "vheaVertDescender",
+    "vheaVertLineGap",
+    "winAscent",
+    "winDescent",
+];
+
+impl Font {
+    /// Change the units per em, scaling everything measured in font units to
+    /// match: outlines, anchors, guides, hints, component offsets, advance
+    /// widths, master metrics and stems, kerning and the vertical metrics
+    /// custom parameters of the font, masters and instances.
+    ///
+    /// Coordinates aren't rounded. Integer custom parameters are rounded to
+    /// integers. The italic angle and the numbers are left alone, and so are
+    /// the offsets in metrics keys.
+    pub fn scale_upm(&mut self, new_upm: u16) {
+        if new_upm == self.units_per_em || self.units_per_em == 0 {
+            self.units_per_em = new_upm;
+            return;
+        }
+        let factor = f64::from(new_upm) / f64::from(self.units_per_em);
+        self.units_per_em = new_upm;
+
+        for layer in self.glyphs.iter_mut().flat_map(|g| &mut g.layers) {
+            layer.scale(factor);
+        }
+        for master in &mut self.font_master {
+            for (metric, value) in self.metrics.iter().zip(&mut master.metric_values) {
+                if metric.r#type != Some(MetricType::ItalicAngle) {
+                    value.pos *= factor;
+                    value.over *= factor;
+                }
+            }
+            for stem in master.stem_values.iter_mut().flatten() {
+                *stem *= factor;
+            }
+            scale_parameters(&mut master.other_stuff, factor);
+            // Global guides are kept as read.
+            if let Some(Plist::Array(guides)) = master.other_stuff.get_mut("guides") {
+                for guide in guides {
+                    let Plist::Dictionary(guide) = guide else {
+                        continue;
+                    };
+                    if let Some(Plist::Array(pos)) = guide.get_mut("pos") {
+                        pos.iter_mut().for_each(|value| scale_number(value, factor));
+                    }
+                }
+            }
+        }
+        for instance in self.instances.iter_mut().flatten() {
+            scale_parameters(&mut instance.other_stuff, factor);
+        }
+        for parameter in self.custom_parameters.iter_mut().flatten() {
+            if SCALED_PARAMETERS.contains(&parameter.name.as_str()) {
+                scale_number(&mut parameter.value, factor);
+            }
+        }
+        for kerning in [
+            &mut self.kerning_ltr,
+            &mut self.kerning_rtl,
+            &mut self.kerning_vertical,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            for value in kerning
+                .values_mut()
+                .flat_map(|k| k.values_mut())
+                .flat_map(|seconds| seconds.values_mut())
+            {
+                *value *= factor;
+            }
+        }
+    }
+}
+
+impl Layer {
+    /// Scale the layer around the origin as part of scaling the whole font.
+    ///
+    /// Unlike with [`Layer::apply_affine`], components only have their
+    /// offset scaled, as the glyphs they refer to are scaled too.
+    fn scale(&mut self, factor: f64) {
+        let scale_point = |pt: &mut Point| *pt = (pt.to_vec2() * factor).to_point();
+        let scale_shapes = |shapes: &mut [Shape]| {
+            for shape in shapes {
+                match shape {
+                    Shape::Path(path) => {
+                        for node in &mut path.nodes {
+                            scale_point(&mut node.pt);
+                        }
+                    }
+                    Shape::Component(component) => {
+                        if let Some(pos) = &mut component.pos {
+                            scale_point(pos);
+                        }
+                    }
+                }
+            }
+        };
+
+        scale_shapes(&mut self.shapes);
+        for anchor in self.anchors.iter_mut().flatten() {
+            scale_point(&mut anchor.pos);
+        }
+        for guide in self.guides.iter_mut().flatten() {
+            scale_point(&mut guide.pos);
+        }
+        scale_hints(&mut self.other_stuff, factor);
+        if let Some(background) = &mut self.background {
+            scale_shapes(&mut background.shapes);
+            for anchor in background.anchors.iter_mut().flatten() {
+                scale_point(&mut anchor.pos);
+            }
+            scale_hints(&mut background.other_stuff, factor);
+        }
+        if let Some(image) = &mut self.background_image {
+            scale_point(&mut image.pos);
+            let scale = image.scale.get_or_insert(Scale {
+                horizontal: 1.0,
+                vertical: 1.0,
+            });
+            scale.horizontal *= factor;
+            scale.vertical *= factor;
+            if (scale.horizontal, scale.vertical) == (1.0, 1.0) {
+                image.scale = None;
+            }
+        }
+        self.width *= factor;
+        for value in [&mut self.vert_width, &mut self.vert_origin]
+            .into_iter()
+            .flatten()
+        {
+            *value *= factor;
+        }
+    }
+}
+
+/// Scale the custom parameters holding distances among those stored as
+/// dictionaries.
+fn scale_parameters(other_stuff: &mut HashMap<String, Plist>, factor: f64) {
+    for (name, value) in dict_parameters_mut(other_stuff) {
+        if SCALED_PARAMETERS.contains(&name.as_str()) {
+            scale_number(value, factor);
+        }
+    }
+}
+
+pub(crate) fn scale_number(value: &mut Plist, factor: f64) {
+    match value {
+        Plist::Integer(i) => *i = (*i as f64 * factor).round() as i64,
+        Plist::Float(f) => *f *= factor,
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{CustomParameter, Font, MetricType, Plist, Shape};
+
+    #[test]
+    fn scale_upm() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.custom_parameters
+            .get_or_insert_with(Vec::new)
+            .push(CustomParameter {
+                name: "underlinePosition".into(),
+                value: (-75).into(),
+                disabled: false,
+            });
+        let parameter = Plist::Dictionary(HashMap::from([
+            ("name".to_string(), Plist::String("typoAscender".into())),
+            ("value".to_string(), Plist::Integer(800)),
+        ]));
+        font.instances.as_mut().unwrap()[0]
+            .other_stuff
+            .insert("customParameters".into(), Plist::Array(vec![parameter]));
+        let original = font.clone();
+
+        font.scale_upm(2000);
+        assert_eq!(font.units_per_em, 2000);
+        let a = font.get_glyph("A").unwrap().master_layer("m01").unwrap();
+        let original_a = original
+            .get_glyph("A")
+            .unwrap()
+            .master_layer("m01")
+            .unwrap();
+        assert_eq!(a.width, original_a.width * 2.0);
+        let bounds = a.bounds(&font).unwrap();
+        let original_bounds = original_a.bounds(&original).unwrap();
+        assert_eq!(bounds, original_bounds.scale_from_origin(2.0));
+
+        let a_dieresis = font.get_glyph("Ä").unwrap();
+        let Shape::Component(component) = &a_dieresis.layers[0].shapes[1] else {
+            panic!("expected a component");
+        };
+        let Shape::Component(original_component) =
+            &original.get_glyph("Ä").unwrap().layers[0].shapes[1]
+        else {
+            panic!("expected a component");
+        };
+        assert_eq!(component.scale, original_component.scale);
+        assert_eq!(
+            component.pos.unwrap().to_vec2(),
+            original_component.pos.unwrap().to_vec2() * 2.0
+        );
+        assert_eq!(
+            a_dieresis.layers[0].bounds(&font).unwrap(),
+            original.get_glyph("Ä").unwrap().layers[0]
+                .bounds(&original)
+                .unwrap()
+                .scale_from_origin(2.0)
+        );
+
+        assert_eq!(font.kern_value("m01", "A", "B"), Some(60.0));
+        let metrics = font.font_master[0].resolved_metrics(&font);
+        let original_metrics = original.font_master[0].resolved_metrics(&original);
+        assert_eq!(
+            metrics.x_height().unwrap(),
+            original_metrics.x_height().unwrap() * 2.0
+        );
+        assert_eq!(metrics.italic_angle(), original_metrics.italic_angle());
+        assert!(original
+            .metrics
+            .iter()
+            .any(|m| m.r#type == Some(MetricType::ItalicAngle)));
+        assert_eq!(
+            font.custom_parameter("underlinePosition"),
+            Some(&(-150).into())
+        );
+
+        assert_eq!(
+            font.instances.as_ref().unwrap()[0].custom_parameter("typoAscender"),
+            Some(&Plist::Integer(1600))
+        );
+        let guide = font.font_master[0].guides().next().unwrap();
+        assert_eq!(
+            guide.pos.to_vec2(),
+            original.font_master[0]
+                .guides()
+                .next()
+                .unwrap()
+                .pos
+                .to_vec2()
+                * 2.0
+        );
+        let place = |glyph: &str, hint: usize| {
+            let layer = font
+                .get_glyph(glyph)
+                .unwrap()
+                .master_layer("C2ECF50A-02EF-4989-A14C-AF8E838D1105");
+            let hints = layer.unwrap().other_stuff["hints"].as_array().unwrap();
+            let place = hints[hint].get("place").unwrap().as_array().unwrap();
+            place
+                .iter()
+                .map(|v| v.as_f64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(place("A", 1), [-92.0, 116.0]);
+        // Ghost hints keep their marker widths.
+        assert_eq!(place("alef-ar", 0), [1556.0, -20.0]);
+
+        font.scale_upm(1000);
+        assert_eq!(font, original);
+    }
+}
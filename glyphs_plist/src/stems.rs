@@ -0,0 +1,172 @@
+//! Checking the stem values of the masters against the outlines.
+
+use kurbo::Point;
+
+use crate::{Font, FontStems, Id, Layer, Predicate};
+
+/// The glyphs stems are measured in, with the line each is cut along: at a
+/// fraction of the height of the bounds for vertical stems, and of the width
+/// for horizontal ones.
+const KEY_GLYPHS: [(&str, Option<f64>, Option<f64>); 4] = [
+    ("n", Some(0.25), None),
+    ("o", Some(0.5), Some(0.5)),
+    ("H", Some(0.25), Some(0.5)),
+    ("O", Some(0.5), Some(0.5)),
+];
+
+/// A stem value of a master that differs from the stem measured in a glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StemMismatch {
+    pub master_id: Id,
+    /// The name of the stem, as in [`Font::stems`].
+    pub stem: String,
+    pub glyph: String,
+    pub expected: f64,
+    pub measured: f64,
+}
+
+impl Font {
+    /// Measure the stems of the key glyphs `n`, `o`, `H` and `O` in every
+    /// master and report where they differ from the master's stem values by
+    /// more than `tolerance` units.
+    ///
+    /// Vertical stems are measured across the first stroke a horizontal line
+    /// meets, a quarter up the straight glyphs and halfway up the round ones;
+    /// horizontal stems across the first stroke from the bottom on a
+    /// vertical line through the middle of `o`, `H` and `O`. Stems with a
+    /// filter are only measured in the key glyphs it matches. Glyphs that are
+    /// missing or can't be measured are skipped.
+    pub fn check_stems(&self, tolerance: f64) -> Vec<StemMismatch> {
+        let mut mismatches = Vec::new();
+        let Some(stems) = &self.stems else {
+            return mismatches;
+        };
+        for master in &self.font_master {
+            let values = master.stem_values.as_deref().unwrap_or_default();
+            for (stem, &expected) in stems.iter().zip(values) {
+                let filter = stem
+                    .filter
+                    .as_deref()
+                    .and_then(|f| Predicate::parse(f).ok());
+                for (name, fraction) in key_glyphs(stem) {
+                    let Some(glyph) = self.get_glyph(name) else {
+                        continue;
+                    };
+                    if filter.as_ref().is_some_and(|f| !f.matches(glyph)) {
+                        continue;
+                    }
+                    let Some(layer) = glyph.master_layer(&master.id) else {
+                        continue;
+                    };
+                    let Some(measured) = layer.stem_width(self, stem.horizontal, fraction) else {
+                        continue;
+                    };
+                    if (measured - expected).abs() > tolerance {
+                        mismatches.push(StemMismatch {
+                            master_id: master.id.clone(),
+                            stem: stem.name.clone(),
+                            glyph: name.to_string(),
+                            expected,
+                            measured,
+                        });
+                    }
+                }
+            }
+        }
+        mismatches
+    }
+}
+
+impl Layer {
+    /// The thickness of the first stroke met by a line across the bounds of
+    /// the layer: horizontal for vertical stems, at a fraction of the
+    /// height from the bottom and going right, and vertical for horizontal
+    /// stems, at a fraction of the width from the left and going up.
+    fn stem_width(&self, font: &Font, horizontal: bool, fraction: f64) -> Option<f64> {
+        let bounds = self.bounds(font)?.inflate(1.0, 1.0);
+        let (p0, p1) = if horizontal {
+            let x = bounds.x0 + bounds.width() * fraction;
+            (Point::new(x, bounds.y0), Point::new(x, bounds.y1))
+        } else {
+            let y = bounds.y0 + bounds.height() * fraction;
+            (Point::new(bounds.x0, y), Point::new(bounds.x1, y))
+        };
+        match self.intersections_with_line(p0, p1, font)[..] {
+            [first, second, ..] => Some(first.distance(second)),
+            _ => None,
+        }
+    }
+}
+
+fn key_glyphs(stem: &FontStems) -> impl Iterator<Item = (&'static str, f64)> + '_ {
+    KEY_GLYPHS
+        .iter()
+        .filter_map(|&(name, vertical, horizontal)| {
+            Some((
+                name,
+                if stem.horizontal {
+                    horizontal
+                } else {
+                    vertical
+                }?,
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Glyph, NodeType, Path, Shape};
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64) -> Shape {
+        let mut path = Path::new(true);
+        for (x, y) in [(x1, y0), (x1, y1), (x0, y1), (x0, y0)] {
+            path.add((x, y), NodeType::Line);
+        }
+        Shape::Path(Box::new(path))
+    }
+
+    #[test]
+    fn check_stems() {
+        let mut font = Font::default();
+        let mut h = Glyph::new(norad::Name::new("H").unwrap(), None);
+        let mut layer = Layer::new("m01");
+        layer.shapes = vec![
+            rect(0.0, 0.0, 90.0, 700.0),
+            rect(90.0, 320.0, 410.0, 380.0),
+            rect(410.0, 0.0, 500.0, 700.0),
+        ];
+        h.layers.push(layer);
+        font.glyphs.push(h);
+        font.stems = Some(vec![
+            FontStems {
+                name: "Vertical".into(),
+                filter: None,
+                horizontal: false,
+            },
+            FontStems {
+                name: "Horizontal".into(),
+                filter: None,
+                horizontal: true,
+            },
+            FontStems {
+                name: "Numbers".into(),
+                filter: Some("category == \"Number\"".into()),
+                horizontal: false,
+            },
+        ]);
+        font.font_master[0].stem_values = Some(vec![91.0, 50.0, 10.0]);
+
+        assert_eq!(
+            font.check_stems(2.0),
+            [StemMismatch {
+                master_id: "m01".into(),
+                stem: "Horizontal".into(),
+                glyph: "H".into(),
+                expected: 50.0,
+                measured: 60.0,
+            }]
+        );
+        assert_eq!(font.check_stems(0.5).len(), 2);
+    }
+}
@@ -0,0 +1,181 @@
+//! Proptest strategies generating the model types, for fuzzing code that
+//! works with fonts, such as saving and loading them.
+//!
+//! Numbers are generated in hundredths of a unit, which a Glyphs file holds
+//! exactly, so that the generated values survive a round trip through a file
+//! unchanged. Paths may be empty and glyphs may have no layer shapes, as
+//! fonts in the wild do.
+
+use kurbo::Point;
+use proptest::collection::{hash_map, hash_set, vec};
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::{Anchor, Component, Font, FontMaster, Glyph, Layer, MasterMetric, Node, NodeType};
+use crate::{Path, Plist, Shape};
+
+/// A number in hundredths of a unit.
+pub fn number() -> impl Strategy<Value = f64> {
+    (-1_000_000i64..1_000_000).prop_map(|n| n as f64 / 100.0)
+}
+
+pub fn point() -> impl Strategy<Value = Point> {
+    (number(), number()).prop_map(|(x, y)| Point::new(x, y))
+}
+
+/// Any plist, nested a few levels deep.
+///
+/// Floats always have a fractional part, as whole ones are written, and read
+/// back, as integers.
+pub fn plist() -> impl Strategy<Value = Plist> {
+    let leaf = prop_oneof![
+        "\\PC*".prop_map(Plist::String),
+        any::<i64>().prop_map(Plist::Integer),
+        number()
+            .prop_filter("whole numbers are integers", |n| n.fract() != 0.0)
+            .prop_map(Plist::Float),
+    ];
+    leaf.prop_recursive(3, 32, 4, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..4).prop_map(Plist::Array),
+            hash_map("\\PC*", inner, 0..4).prop_map(Plist::Dictionary),
+        ]
+    })
+}
+
+pub fn glyph_name() -> impl Strategy<Value = norad::Name> {
+    "[A-Za-z][A-Za-z0-9._-]{0,15}".prop_map(|name| norad::Name::new(&name).unwrap())
+}
+
+pub fn node_type() -> impl Strategy<Value = NodeType> {
+    prop_oneof![
+        Just(NodeType::Line),
+        Just(NodeType::LineSmooth),
+        Just(NodeType::OffCurve),
+        Just(NodeType::Curve),
+        Just(NodeType::CurveSmooth),
+        Just(NodeType::QCurve),
+        Just(NodeType::QCurveSmooth),
+    ]
+}
+
+pub fn node() -> impl Strategy<Value = Node> {
+    (point(), node_type()).prop_map(|(pt, node_type)| Node { pt, node_type })
+}
+
+/// A path of up to eight nodes, possibly none.
+pub fn path() -> impl Strategy<Value = Path> {
+    (any::<bool>(), vec(node(), 0..8)).prop_map(|(closed, nodes)| Path {
+        attr: None,
+        closed,
+        nodes,
+    })
+}
+
+pub fn component() -> impl Strategy<Value = Component> {
+    (glyph_name(), option::of(point()), option::of(number())).prop_map(
+        |(reference, pos, rotation)| Component {
+            reference: reference.to_string(),
+            rotation,
+            pos,
+            scale: None,
+            slant: None,
+            alignment: None,
+            anchor: None,
+            other_stuff: Default::default(),
+        },
+    )
+}
+
+pub fn shape() -> impl Strategy<Value = Shape> {
+    prop_oneof![
+        path().prop_map(|path| Shape::Path(Box::new(path))),
+        component().prop_map(Shape::Component),
+    ]
+}
+
+pub fn anchor() -> impl Strategy<Value = Anchor> {
+    ("[a-z_]{1,8}", point()).prop_map(|(name, pos)| Anchor {
+        name,
+        orientation: None,
+        pos,
+        user_data: Default::default(),
+    })
+}
+
+/// A master layer for the master with the given ID.
+pub fn layer(master_id: String) -> impl Strategy<Value = Layer> {
+    (
+        number(),
+        vec(shape(), 0..4),
+        option::of(vec(anchor(), 1..3)),
+    )
+        .prop_map(move |(width, shapes, anchors)| Layer {
+            width,
+            shapes,
+            anchors,
+            ..Layer::new(master_id.clone())
+        })
+}
+
+/// A glyph with a master layer for each of the masters with the given IDs.
+pub fn glyph(master_ids: Vec<String>) -> impl Strategy<Value = Glyph> {
+    glyph_name().prop_flat_map(move |name| named_glyph(name, &master_ids))
+}
+
+fn named_glyph(name: norad::Name, master_ids: &[String]) -> impl Strategy<Value = Glyph> {
+    let codepoints = hash_set(any::<char>(), 1..3);
+    let layers: Vec<_> = master_ids.iter().cloned().map(layer).collect();
+    (option::of(codepoints), layers).prop_map(move |(codepoints, layers)| Glyph {
+        layers,
+        ..Glyph::new(name.clone(), codepoints.map(norad::Codepoints::new))
+    })
+}
+
+/// A master with values for the metrics of [`Font::default`].
+pub fn master(id: String) -> impl Strategy<Value = FontMaster> {
+    ("[A-Za-z ]{1,12}", vec((number(), number()), 3)).prop_map(move |(name, metrics)| FontMaster {
+        metric_values: metrics
+            .into_iter()
+            .map(|(pos, over)| MasterMetric { pos, over })
+            .collect(),
+        ..FontMaster::new(id.clone(), name)
+    })
+}
+
+/// A font with up to three masters and eight glyphs.
+pub fn font() -> impl Strategy<Value = Font> {
+    (1..4usize, hash_set(glyph_name(), 0..8))
+        .prop_flat_map(|(masters, names)| {
+            let ids: Vec<String> = (1..=masters).map(|i| format!("m{i:02}")).collect();
+            let masters: Vec<_> = ids.iter().cloned().map(master).collect();
+            let glyphs: Vec<_> = names
+                .into_iter()
+                .map(|name| named_glyph(name, &ids))
+                .collect();
+            (masters, glyphs)
+        })
+        .prop_map(|(font_master, glyphs)| Font {
+            font_master,
+            glyphs: glyphs.into(),
+            ..Font::default()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn plist_round_trip(plist in plist()) {
+            prop_assert_eq!(Plist::parse(&plist.to_string()).unwrap(), plist);
+        }
+
+        #[test]
+        fn font_round_trip(font in font()) {
+            let saved = font.to_plist_string();
+            prop_assert_eq!(saved.parse::<Font>().unwrap(), font);
+        }
+    }
+}
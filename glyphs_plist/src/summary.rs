@@ -0,0 +1,105 @@
+//! One-line descriptions of fonts, glyphs and layers, for logs and test
+//! failures where their `Debug` output is far too long.
+
+use crate::{Font, Glyph, Layer, Shape};
+
+impl Font {
+    /// A one-line description of the font, like
+    /// `Font 'Family', 2 masters, 250 glyphs, 3 instances`.
+    pub fn summary(&self) -> String {
+        format!(
+            "Font '{}', {}, {}, {}",
+            self.family_name,
+            count(self.font_master.len(), "master"),
+            count(self.glyphs.len(), "glyph"),
+            count(self.instances.as_ref().map_or(0, Vec::len), "instance"),
+        )
+    }
+}
+
+impl Glyph {
+    /// A one-line description of the glyph, like
+    /// `Glyph 'A' U+0041, 3 layers, 2 paths/1 component in m01`, with the
+    /// shapes of its first layer.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Glyph '{}'", self.glyphname);
+        for codepoint in self.unicode.iter().flat_map(|u| u.iter()) {
+            summary.push_str(&format!(" U+{:04X}", codepoint as u32));
+        }
+        summary.push_str(&format!(", {}", count(self.layers.len(), "layer")));
+        if let Some(layer) = self.layers.first() {
+            summary.push_str(&format!(", {} in {}", shapes(layer), layer_name(layer)));
+        }
+        summary
+    }
+}
+
+impl Layer {
+    /// A one-line description of the layer, like
+    /// `Layer 'Bold' (L1) of m01, 2 paths/1 component, width 600`.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Layer {}", layer_name(self));
+        if let Some(master_id) = &self.associated_master_id {
+            summary.push_str(&format!(" of {master_id}"));
+        }
+        summary.push_str(&format!(", {}, width {}", shapes(self), self.width));
+        summary
+    }
+}
+
+fn layer_name(layer: &Layer) -> String {
+    match &layer.name {
+        Some(name) => format!("'{name}' ({})", layer.layer_id),
+        None => layer.layer_id.to_string(),
+    }
+}
+
+fn shapes(layer: &Layer) -> String {
+    let paths = layer
+        .shapes
+        .iter()
+        .filter(|s| matches!(s, Shape::Path(_)))
+        .count();
+    let components = layer.shapes.len() - paths;
+    format!(
+        "{}/{}",
+        count(paths, "path"),
+        count(components, "component")
+    )
+}
+
+fn count(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {noun}"),
+        n => format!("{n} {noun}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Font;
+
+    #[test]
+    fn summaries() {
+        let font = Font::default();
+        assert_eq!(
+            font.summary(),
+            "Font 'New Font', 1 master, 1 glyph, 0 instances"
+        );
+
+        let glyph = &font.glyphs[0];
+        assert_eq!(
+            glyph.summary(),
+            "Glyph 'space' U+0020, 1 layer, 0 paths/0 components in m01"
+        );
+
+        let mut layer = glyph.layers[0].clone();
+        layer.name = Some("Bold".into());
+        layer.layer_id = "L1".into();
+        layer.associated_master_id = Some("m01".into());
+        assert_eq!(
+            layer.summary(),
+            "Layer 'Bold' (L1) of m01, 0 paths/0 components, width 200"
+        );
+    }
+}
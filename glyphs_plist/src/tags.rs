@@ -0,0 +1,158 @@
+//! Editing the tags and notes of many glyphs at once.
+
+use crate::{Font, Glyph, Predicate};
+
+/// The glyphs a batch edit applies to: given by name, or those matching a
+/// predicate.
+#[derive(Clone, Copy, Debug)]
+pub enum GlyphSelection<'a> {
+    Names(&'a [&'a str]),
+    Predicate(&'a Predicate),
+}
+
+impl GlyphSelection<'_> {
+    fn contains(&self, glyph: &Glyph) -> bool {
+        match self {
+            GlyphSelection::Names(names) => names.contains(&glyph.glyphname.as_str()),
+            GlyphSelection::Predicate(predicate) => predicate.matches(glyph),
+        }
+    }
+}
+
+impl<'a> From<&'a [&'a str]> for GlyphSelection<'a> {
+    fn from(names: &'a [&'a str]) -> Self {
+        GlyphSelection::Names(names)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [&'a str; N]> for GlyphSelection<'a> {
+    fn from(names: &'a [&'a str; N]) -> Self {
+        GlyphSelection::Names(names)
+    }
+}
+
+impl<'a> From<&'a Predicate> for GlyphSelection<'a> {
+    fn from(predicate: &'a Predicate) -> Self {
+        GlyphSelection::Predicate(predicate)
+    }
+}
+
+impl Font {
+    /// Add a tag to the selected glyphs, keeping the tags of each sorted and
+    /// without duplicates. Returns the number of glyphs that didn't have the
+    /// tag yet.
+    pub fn add_tag_to<'a>(&mut self, glyphs: impl Into<GlyphSelection<'a>>, tag: &str) -> usize {
+        let glyphs = glyphs.into();
+        let mut changed = 0;
+        for glyph in self.glyphs.iter_mut().filter(|g| glyphs.contains(g)) {
+            if !glyph.tags.iter().any(|t| t == tag) {
+                glyph.tags.push(tag.to_string());
+                changed += 1;
+            }
+            glyph.tags.sort();
+            glyph.tags.dedup();
+        }
+        changed
+    }
+
+    /// Remove a tag from the selected glyphs. Returns the number of glyphs
+    /// that had it.
+    pub fn remove_tag<'a>(&mut self, glyphs: impl Into<GlyphSelection<'a>>, tag: &str) -> usize {
+        let glyphs = glyphs.into();
+        let mut changed = 0;
+        for glyph in self.glyphs.iter_mut().filter(|g| glyphs.contains(g)) {
+            let count = glyph.tags.len();
+            glyph.tags.retain(|t| t != tag);
+            if glyph.tags.len() != count {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Replace the notes of the selected glyphs, or clear them with `None`.
+    /// Returns the number of glyphs selected.
+    pub fn set_notes<'a>(
+        &mut self,
+        glyphs: impl Into<GlyphSelection<'a>>,
+        note: Option<&str>,
+    ) -> usize {
+        let glyphs = glyphs.into();
+        let mut count = 0;
+        for glyph in self.glyphs.iter_mut().filter(|g| glyphs.contains(g)) {
+            glyph.note = note.map(str::to_string);
+            count += 1;
+        }
+        count
+    }
+
+    /// Add a line to the notes of the selected glyphs. Returns the number of
+    /// glyphs selected.
+    pub fn append_to_notes<'a>(
+        &mut self,
+        glyphs: impl Into<GlyphSelection<'a>>,
+        line: &str,
+    ) -> usize {
+        let glyphs = glyphs.into();
+        let mut count = 0;
+        for glyph in self.glyphs.iter_mut().filter(|g| glyphs.contains(g)) {
+            match &mut glyph.note {
+                Some(note) if !note.is_empty() => {
+                    note.push('\n');
+                    note.push_str(line);
+                }
+                note => *note = Some(line.to_string()),
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        for glyph in &mut font.glyphs {
+            glyph.tags.clear();
+        }
+        font.get_glyph_mut("B").unwrap().tags = vec!["review".into(), "done".into(), "done".into()];
+
+        assert_eq!(font.add_tag_to(&["A", "B", "missing"], "done"), 1);
+        assert_eq!(font.get_glyph("A").unwrap().tags, ["done"]);
+        assert_eq!(font.get_glyph("B").unwrap().tags, ["done", "review"]);
+
+        let predicate = Predicate::parse("tags CONTAINS \"review\"").unwrap();
+        assert_eq!(font.add_tag_to(&predicate, "alpha"), 1);
+        assert_eq!(
+            font.get_glyph("B").unwrap().tags,
+            ["alpha", "done", "review"]
+        );
+
+        assert_eq!(font.remove_tag(&Predicate::True, "done"), 2);
+        assert_eq!(font.get_glyph("B").unwrap().tags, ["alpha", "review"]);
+        assert!(font.get_glyph("A").unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn notes() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        assert_eq!(font.set_notes(&["A", "B"], Some("Check spacing")), 2);
+        assert_eq!(font.append_to_notes(&["B", "C"], "Redraw"), 2);
+        assert_eq!(
+            font.get_glyph("A").unwrap().note.as_deref(),
+            Some("Check spacing")
+        );
+        assert_eq!(
+            font.get_glyph("B").unwrap().note.as_deref(),
+            Some("Check spacing\nRedraw")
+        );
+        assert_eq!(font.get_glyph("C").unwrap().note.as_deref(), Some("Redraw"));
+
+        font.set_notes(&Predicate::True, None);
+        assert!(font.glyphs.iter().all(|g| g.note.is_none()));
+    }
+}
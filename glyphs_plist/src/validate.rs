@@ -0,0 +1,358 @@
+//! Checks of the structural invariants that the model doesn't enforce by
+//! itself, for linting fonts before building them.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{Font, Glyph, Layer, Shape};
+
+/// How bad a problem found by [`Font::validate`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Glyphs.app copes with it, but it is likely a mistake.
+    Warning,
+    /// The font is inconsistent and tools will fail on it or misread it.
+    Error,
+}
+
+/// A problem found by [`Font::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where in the font the problem is, like
+    /// `glyphs["A"].layers["m01"].shapes[1]`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.path, self.message)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Diagnostics(pub(crate) Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub(crate) fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::Error, path.into(), message.into());
+    }
+
+    pub(crate) fn warning(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::Warning, path.into(), message.into());
+    }
+
+    pub(crate) fn push(&mut self, severity: Severity, path: String, message: String) {
+        self.0.push(Diagnostic {
+            severity,
+            path,
+            message,
+        });
+    }
+
+    /// Check that a list has one value per item it belongs to.
+    fn count(&mut self, path: String, what: &str, found: usize, expected: usize) {
+        if found != expected {
+            self.error(
+                path,
+                format!("{found} {what} values, but the font has {expected}"),
+            );
+        }
+    }
+}
+
+impl Font {
+    /// Check the structural invariants of the font, returning the problems
+    /// found in font order.
+    ///
+    /// Errors are inconsistencies: master metric, stem, number and axis
+    /// values that don't line up with the font's lists of them, instance and
+    /// special layer locations that don't either, duplicate or empty master
+    /// IDs, duplicate glyph names and layer IDs, layers of masters that don't
+    /// exist and components of glyphs that don't exist. Warnings are glyphs
+    /// missing a master layer, Unicode values used by more than one glyph,
+    /// and kerning of masters, glyphs or groups that don't exist.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostics::default();
+        self.validate_masters(&mut diagnostics);
+        self.validate_instances(&mut diagnostics);
+        self.validate_glyphs(&mut diagnostics);
+        self.validate_kerning(&mut diagnostics);
+        diagnostics.0
+    }
+
+    fn validate_masters(&self, diagnostics: &mut Diagnostics) {
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let mut ids = HashSet::new();
+        for (i, master) in self.font_master.iter().enumerate() {
+            let path = format!("fontMaster[{i}]");
+            if master.id.is_empty() {
+                diagnostics.error(&path, "empty master ID");
+            } else if !ids.insert(master.id.as_str()) {
+                diagnostics.error(&path, format!("duplicate master ID {:?}", master.id));
+            }
+
+            diagnostics.count(
+                format!("{path}.metricValues"),
+                "metric",
+                master.metric_values.len(),
+                self.metrics.len(),
+            );
+            let lists = [
+                ("axesValues", "axis", &master.axes_values, axis_count),
+                (
+                    "numberValues",
+                    "number",
+                    &master.number_values,
+                    self.numbers.as_ref().map_or(0, Vec::len),
+                ),
+                (
+                    "stemValues",
+                    "stem",
+                    &master.stem_values,
+                    self.stems.as_ref().map_or(0, Vec::len),
+                ),
+            ];
+            for (key, what, values, expected) in lists {
+                let found = values.as_ref().map_or(0, Vec::len);
+                diagnostics.count(format!("{path}.{key}"), what, found, expected);
+            }
+        }
+    }
+
+    fn validate_instances(&self, diagnostics: &mut Diagnostics) {
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        for (i, instance) in self.instances.iter().flatten().enumerate() {
+            if let Some(values) = &instance.axes_values {
+                let path = format!("instances[{i}].axesValues");
+                diagnostics.count(path, "axis", values.len(), axis_count);
+            }
+        }
+    }
+
+    fn validate_glyphs(&self, diagnostics: &mut Diagnostics) {
+        let master_ids: HashSet<&str> = self.font_master.iter().map(|m| m.id.as_str()).collect();
+        let glyph_names: HashSet<&str> = self.glyphs.iter().map(|g| g.glyphname.as_str()).collect();
+        let mut seen_names = HashSet::new();
+        let mut codepoints: HashMap<char, &str> = HashMap::new();
+        for glyph in &self.glyphs {
+            let path = format!("glyphs[{:?}]", glyph.glyphname.as_str());
+            if !seen_names.insert(glyph.glyphname.as_str()) {
+                diagnostics.error(&path, "duplicate glyph name");
+            }
+            for codepoint in glyph.unicode.iter().flat_map(|u| u.iter()) {
+                match codepoints.get(&codepoint) {
+                    Some(first) => diagnostics.warning(
+                        &path,
+                        format!("U+{:04X} is also used by {first:?}", codepoint as u32),
+                    ),
+                    None => {
+                        codepoints.insert(codepoint, glyph.glyphname.as_str());
+                    }
+                }
+            }
+            for master in &self.font_master {
+                if !master.id.is_empty() && glyph.master_layer(&master.id).is_none() {
+                    diagnostics.warning(&path, format!("no layer for master {:?}", master.id));
+                }
+            }
+            self.validate_layers(glyph, &path, &master_ids, &glyph_names, diagnostics);
+        }
+    }
+
+    fn validate_layers(
+        &self,
+        glyph: &Glyph,
+        glyph_path: &str,
+        master_ids: &HashSet<&str>,
+        glyph_names: &HashSet<&str>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let mut layer_ids = HashSet::new();
+        for (i, layer) in glyph.layers.iter().enumerate() {
+            let path = match layer.layer_id.as_str() {
+                "" => format!("{glyph_path}.layers[{i}]"),
+                id => format!("{glyph_path}.layers[{id:?}]"),
+            };
+            if layer.layer_id.is_empty() {
+                diagnostics.error(&path, "empty layer ID");
+            } else if !layer_ids.insert(layer.layer_id.as_str()) {
+                diagnostics.error(&path, "duplicate layer ID");
+            }
+            match &layer.associated_master_id {
+                Some(master_id) if !master_ids.contains(master_id.as_str()) => diagnostics.error(
+                    &path,
+                    format!("associated with unknown master {master_id:?}"),
+                ),
+                None if !master_ids.contains(layer.layer_id.as_str()) => {
+                    diagnostics.error(&path, "master layer of an unknown master")
+                }
+                _ => (),
+            }
+
+            if let Some(attr) = &layer.attr {
+                if let Some(coordinates) = &attr.coordinates {
+                    let path = format!("{path}.attr.coordinates");
+                    diagnostics.count(path, "axis", coordinates.len(), axis_count);
+                }
+                // Bracket layers may leave out trailing axes.
+                let rule_count = attr.axis_rules.as_ref().map_or(0, Vec::len);
+                if rule_count > axis_count {
+                    let path = format!("{path}.attr.axisRules");
+                    diagnostics.count(path, "axis", rule_count, axis_count);
+                }
+            }
+
+            validate_components(layer, &path, glyph_names, diagnostics);
+        }
+    }
+
+    fn validate_kerning(&self, diagnostics: &mut Diagnostics) {
+        let master_ids: HashSet<&str> = self.font_master.iter().map(|m| m.id.as_str()).collect();
+        let glyph_names: HashSet<&str> = self.glyphs.iter().map(|g| g.glyphname.as_str()).collect();
+        let groups = |side: fn(&Glyph) -> Option<&str>| -> HashSet<&str> {
+            self.glyphs.iter().filter_map(side).collect()
+        };
+        let first_groups = groups(|g| g.kern_right.as_deref());
+        let second_groups = groups(|g| g.kern_left.as_deref());
+        let is_known =
+            |name: &str, prefix: &str, groups: &HashSet<&str>| match name.strip_prefix(prefix) {
+                Some(group) => groups.contains(group),
+                None => name.starts_with('@') || glyph_names.contains(name),
+            };
+
+        let directions = [
+            ("kerningLTR", &self.kerning_ltr),
+            ("kerningRTL", &self.kerning_rtl),
+            ("kerningVertical", &self.kerning_vertical),
+        ];
+        for (key, kerning) in directions {
+            let mut masters: Vec<_> = kerning.iter().flatten().collect();
+            masters.sort_by_key(|(master_id, _)| *master_id);
+            for (master_id, pairs) in masters {
+                let path = format!("{key}[{master_id:?}]");
+                if !master_ids.contains(master_id.as_str()) {
+                    diagnostics.warning(&path, "kerning of an unknown master");
+                    continue;
+                }
+                for (first, seconds) in pairs {
+                    let first_path = format!("{path}[{:?}]", first.as_str());
+                    if !is_known(first, "@MMK_L_", &first_groups) {
+                        diagnostics.warning(&first_path, "unknown glyph or group");
+                    }
+                    for second in seconds.keys() {
+                        if !is_known(second, "@MMK_R_", &second_groups) {
+                            let path = format!("{first_path}[{:?}]", second.as_str());
+                            diagnostics.warning(path, "unknown glyph or group");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check that the components of a layer and its background refer to glyphs
+/// in the font.
+fn validate_components(
+    layer: &Layer,
+    layer_path: &str,
+    glyph_names: &HashSet<&str>,
+    diagnostics: &mut Diagnostics,
+) {
+    let background_shapes = layer.background.iter().flat_map(|b| &b.shapes);
+    let shapes = layer
+        .shapes
+        .iter()
+        .enumerate()
+        .map(|(i, shape)| (format!("{layer_path}.shapes[{i}]"), shape))
+        .chain(
+            background_shapes
+                .enumerate()
+                .map(|(i, shape)| (format!("{layer_path}.background.shapes[{i}]"), shape)),
+        );
+    for (path, shape) in shapes {
+        if let Shape::Component(component) = shape {
+            if !glyph_names.contains(component.reference.as_str()) {
+                diagnostics.error(
+                    path,
+                    format!("component of unknown glyph {:?}", component.reference),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Font, Layer, Severity};
+
+    #[test]
+    fn valid_fonts() {
+        for path in [
+            "testdata/GlyphsFileFormatv3.glyphs",
+            "testdata/NewFontG3.glyphs",
+            "testdata/NewFont.glyphs",
+        ] {
+            let font = Font::load(path).unwrap();
+            let errors: Vec<_> = font
+                .validate()
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .collect();
+            assert!(errors.is_empty(), "{path}: {errors:#?}");
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let baseline = font.validate();
+
+        font.font_master[1].id = font.font_master[0].id.clone();
+        font.font_master[0].metric_values.pop();
+        font.instances.as_mut().unwrap()[0].axes_values = Some(vec![1.0]);
+        let a = font.get_glyph_mut("A").unwrap();
+        a.layers.push(Layer::new(""));
+        a.layers.push(Layer::new_associated("orphan", "nope"));
+        let a_dieresis = font.get_glyph_mut("Ä").unwrap();
+        let dieresis = a_dieresis.layers[0].components_mut().nth(1).unwrap();
+        dieresis.reference = "missing".into();
+        font.set_kerning("m01", "@MMK_L_nogroup", "A", -10.0)
+            .unwrap();
+
+        let new: Vec<String> = font
+            .validate()
+            .into_iter()
+            .filter(|d| !baseline.contains(d))
+            .map(|d| d.to_string())
+            .collect();
+        let expected = [
+            r#"error: fontMaster[0].metricValues: 7 metric values, but the font has 8"#,
+            r#"error: fontMaster[1]: duplicate master ID "m01""#,
+            r#"error: instances[0].axesValues: 1 axis values, but the font has 3"#,
+            r#"error: glyphs["A"].layers[6]: empty layer ID"#,
+            r#"error: glyphs["A"].layers[6]: master layer of an unknown master"#,
+            r#"error: glyphs["A"].layers["orphan"]: associated with unknown master "nope""#,
+            r#"error: glyphs["Ä"].layers["m01"].shapes[1]: component of unknown glyph "missing""#,
+            r#"warning: kerningLTR["m01"]["@MMK_L_nogroup"]: unknown glyph or group"#,
+        ];
+        for diagnostic in expected {
+            assert!(
+                new.contains(&diagnostic.to_string()),
+                "{diagnostic}\n{new:#?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! The settings of the variable fonts a font is exported as.
+
+use crate::ParameterValue;
+use crate::{Axis, Font, FontMaster, Id, Instance, InstanceType, Plist};
+
+/// An axis with the extent of the masters on it, in design coordinates and
+/// in the user coordinates of the compiled font.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisIr {
+    pub name: String,
+    pub tag: String,
+    pub min: f64,
+    pub default: f64,
+    pub max: f64,
+    pub hidden: bool,
+    /// `min`, `default` and `max` in user coordinates.
+    pub user_min: f64,
+    pub user_default: f64,
+    pub user_max: f64,
+    /// Pairs of user and design coordinates, sorted by user coordinate, from
+    /// the font's "Axis Mappings" custom parameter or else the "Axis
+    /// Location" custom parameters of the masters. Empty if neither maps the
+    /// axis, as user and design coordinates are then the same.
+    pub mapping: Vec<(f64, f64)>,
+}
+
+/// A master and where it is in the design space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceIr {
+    pub id: Id,
+    pub name: String,
+    pub location: Vec<f64>,
+}
+
+impl AxisIr {
+    /// Convert a design coordinate on the axis to a user coordinate.
+    pub fn to_user(&self, design: f64) -> f64 {
+        piecewise_linear(
+            self.mapping.iter().map(|&(user, design)| (design, user)),
+            design,
+        )
+    }
+
+    /// Convert a user coordinate on the axis to a design coordinate.
+    pub fn to_design(&self, user: f64) -> f64 {
+        piecewise_linear(self.mapping.iter().copied(), user)
+    }
+}
+
+/// What a VF build needs to know beyond the masters and glyphs, from
+/// [`Font::variable_font_config`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableFontConfig {
+    /// The id of the master the default location of the font is at.
+    pub origin: Id,
+    /// The axes with their extent over the masters, in axis order.
+    pub axes: Vec<AxisIr>,
+    /// The variable font settings of the font, in instance order.
+    pub exports: Vec<VariableFontExport>,
+}
+
+/// A variable font setting: an instance of type variable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableFontExport {
+    pub name: String,
+    pub family_name: String,
+    /// The file name without extension, from the "fileName" custom parameter
+    /// or else the family name without spaces and with a `VF` suffix, like
+    /// Glyphs.app exports it.
+    pub file_name: String,
+    pub exports: bool,
+}
+
+impl Font {
+    /// The masters as sources, the index of the default one and the axes with
+    /// the extent of the masters on them. Returns `None` if the font has no
+    /// masters.
+    pub(crate) fn design_space(&self) -> Option<(Vec<SourceIr>, usize, Vec<AxisIr>)> {
+        let axis_count = self.axes.as_ref().map_or(0, Vec::len);
+        let sources: Vec<SourceIr> = self
+            .font_master
+            .iter()
+            .map(|master| {
+                let mut location = master.axes_values.clone().unwrap_or_default();
+                location.resize(axis_count, 0.0);
+                SourceIr {
+                    id: master.id.clone(),
+                    name: master.name.clone(),
+                    location,
+                }
+            })
+            .collect();
+        if sources.is_empty() {
+            return None;
+        }
+        let default = self.origin_master_index();
+
+        let axes = self
+            .axes
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, axis)| {
+                let values = sources.iter().map(|s| s.location[i]);
+                let mut axis = AxisIr {
+                    name: axis.name.clone(),
+                    tag: axis.tag.clone(),
+                    min: values.clone().fold(f64::INFINITY, f64::min),
+                    default: sources[default].location[i],
+                    max: values.fold(f64::NEG_INFINITY, f64::max),
+                    hidden: axis.hidden,
+                    user_min: 0.0,
+                    user_default: 0.0,
+                    user_max: 0.0,
+                    mapping: self.axis_mapping(axis, i),
+                };
+                axis.user_min = axis.to_user(axis.min);
+                axis.user_default = axis.to_user(axis.default);
+                axis.user_max = axis.to_user(axis.max);
+                axis
+            })
+            .collect();
+        Some((sources, default, axes))
+    }
+
+    /// The user and design coordinate pairs of an axis, see
+    /// [`AxisIr::mapping`].
+    fn axis_mapping(&self, axis: &Axis, index: usize) -> Vec<(f64, f64)> {
+        let mut mapping: Vec<(f64, f64)> = match self
+            .custom_parameter_as::<&Plist>("Axis Mappings")
+            .and_then(|mappings| mappings.get(&axis.tag)?.as_dict())
+        {
+            Some(mapping) => mapping
+                .iter()
+                .filter_map(|(user, design)| {
+                    Some((user.parse().ok()?, f64::from_parameter(design)?))
+                })
+                .collect(),
+            None => self
+                .font_master
+                .iter()
+                .filter_map(|master| {
+                    let design = *master.axes_values.as_ref()?.get(index)?;
+                    let locations = master.custom_parameter_as::<&[Plist]>("Axis Location")?;
+                    let user = locations
+                        .iter()
+                        .find(|l| l.get("Axis").and_then(Plist::as_str) == Some(&axis.name))?
+                        .get("Location")
+                        .and_then(f64::from_parameter)?;
+                    Some((user, design))
+                })
+                .collect(),
+        };
+        mapping.sort_by(|a, b| a.0.total_cmp(&b.0));
+        mapping.dedup_by(|a, b| a.0 == b.0);
+        mapping
+    }
+
+    /// The master named by the "Variable Font Origin" custom parameter, or
+    /// else the first master.
+    pub fn variable_font_origin(&self) -> Option<&FontMaster> {
+        self.font_master.get(self.origin_master_index())
+    }
+
+    /// The index of the master returned by [`Font::variable_font_origin`],
+    /// which is 0 if there are no masters.
+    pub(crate) fn origin_master_index(&self) -> usize {
+        let origin_id = self.custom_parameter_as::<&str>("Variable Font Origin");
+        self.font_master
+            .iter()
+            .position(|m| Some(m.id.as_str()) == origin_id)
+            .unwrap_or(0)
+    }
+
+    /// The variable font settings of the font: its origin master, axes and
+    /// variable instances. Returns `None` if the font has no masters.
+    pub fn variable_font_config(&self) -> Option<VariableFontConfig> {
+        let (_, _, axes) = self.design_space()?;
+        let origin = self.variable_font_origin()?.id.clone();
+        let exports = self
+            .instances
+            .iter()
+            .flatten()
+            .filter(|instance| instance.is_variable())
+            .map(|instance| {
+                let family_name = instance.resolved_names(self).family_name;
+                let file_name = instance
+                    .custom_parameter_as::<&str>("fileName")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{}VF", family_name.replace(' ', "")));
+                VariableFontExport {
+                    name: instance.name.clone(),
+                    family_name,
+                    file_name,
+                    exports: instance.exports,
+                }
+            })
+            .collect();
+        Some(VariableFontConfig {
+            origin,
+            axes,
+            exports,
+        })
+    }
+}
+
+impl Instance {
+    /// Whether the instance is a variable font setting rather than a static
+    /// instance.
+    pub fn is_variable(&self) -> bool {
+        self.r#type == Some(InstanceType::Variable)
+    }
+}
+
+/// Map a value through the line segments between points sorted by their
+/// input, extending the first and last segment's offset beyond them.
+fn piecewise_linear(points: impl Iterator<Item = (f64, f64)>, value: f64) -> f64 {
+    let mut points: Vec<(f64, f64)> = points.collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return value;
+    };
+    if value <= first.0 {
+        return value + first.1 - first.0;
+    }
+    if value >= last.0 {
+        return value + last.1 - last.0;
+    }
+    let i = points.partition_point(|p| p.0 <= value);
+    let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+    y0 + (y1 - y0) * (value - x0) / (x1 - x0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CustomParameter, Plist};
+
+    #[test]
+    fn config() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        // The file has a variable font setting named Regular.
+        let mut named = Instance::new("Named");
+        named.r#type = Some(InstanceType::Variable);
+        named.other_stuff.insert(
+            "customParameters".into(),
+            Plist::Array(vec![Plist::Dictionary(
+                [
+                    ("name".to_string(), Plist::String("fileName".into())),
+                    ("value".to_string(), Plist::String("Custom-VF".into())),
+                ]
+                .into(),
+            )]),
+        );
+        named.exports = false;
+        font.instances.get_or_insert_with(Vec::new).push(named);
+
+        let config = font.variable_font_config().unwrap();
+        assert_eq!(config.origin, font.font_master[0].id);
+        assert_eq!(config.axes.len(), font.axes.as_ref().unwrap().len());
+        assert_eq!(config.exports.len(), 2);
+        assert_eq!(config.exports[0].name, "Regular");
+        assert_eq!(
+            config.exports[0].file_name,
+            format!("{}VF", font.family_name.replace(' ', ""))
+        );
+        assert_eq!(config.exports[1].file_name, "Custom-VF");
+        assert!(config.exports[0].exports && !config.exports[1].exports);
+
+        let origin = font.font_master[1].id.clone();
+        font.custom_parameters
+            .get_or_insert_with(Vec::new)
+            .push(CustomParameter {
+                name: "Variable Font Origin".into(),
+                value: Plist::String(origin.to_string()),
+                disabled: false,
+            });
+        assert_eq!(font.variable_font_origin().unwrap().id, origin);
+
+        font.font_master.clear();
+        assert_eq!(font.variable_font_config(), None);
+    }
+
+    fn axis_location(axis: &str, location: f64) -> Plist {
+        let value = Plist::Array(vec![Plist::Dictionary(
+            [
+                ("Axis".to_string(), Plist::String(axis.into())),
+                ("Location".to_string(), Plist::Float(location)),
+            ]
+            .into(),
+        )]);
+        Plist::Array(vec![Plist::Dictionary(
+            [
+                ("name".to_string(), Plist::String("Axis Location".into())),
+                ("value".to_string(), value),
+            ]
+            .into(),
+        )])
+    }
+
+    #[test]
+    fn user_coordinates() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let (_, _, axes) = font.design_space().unwrap();
+        assert!(axes.iter().all(|a| a.mapping.is_empty()));
+        assert_eq!(axes[0].user_max, axes[0].max);
+
+        // The masters are at 100 and 900 on the first axis.
+        let weight = axes[0].name.clone();
+        for (master, user) in font.font_master.iter_mut().zip([300.0, 700.0]) {
+            master
+                .other_stuff
+                .insert("customParameters".into(), axis_location(&weight, user));
+        }
+        let (_, _, axes) = font.design_space().unwrap();
+        assert_eq!(axes[0].mapping, [(300.0, 100.0), (700.0, 900.0)]);
+        assert_eq!((axes[0].user_min, axes[0].user_max), (300.0, 700.0));
+        assert_eq!(axes[0].to_design(500.0), 500.0);
+        assert_eq!(axes[0].to_user(500.0), 500.0);
+        assert_eq!(axes[0].to_user(100.0), 300.0);
+
+        // The font's mappings win over the masters' locations.
+        let tag = axes[0].tag.clone();
+        let mapping = [("100", 100.0), ("400", 700.0), ("900", 900.0)]
+            .into_iter()
+            .map(|(user, design)| (user.to_string(), Plist::Float(design)))
+            .collect();
+        font.custom_parameters
+            .get_or_insert_with(Vec::new)
+            .push(CustomParameter {
+                name: "Axis Mappings".into(),
+                value: Plist::Dictionary([(tag, Plist::Dictionary(mapping))].into()),
+                disabled: false,
+            });
+        let (_, _, axes) = font.design_space().unwrap();
+        assert_eq!(axes[0].mapping.len(), 3);
+        assert_eq!(axes[0].to_user(700.0), 400.0);
+        assert_eq!(axes[0].to_design(250.0), 400.0);
+    }
+}
@@ -0,0 +1,174 @@
+//! The vertical metrics of the OS/2 and hhea tables, derived for each master
+//! like glyphsLib and ufo2ft do when building fonts.
+
+use crate::{Font, FontMaster, Id};
+
+/// The vertical metrics of a master, in font units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerticalMetrics {
+    pub master_id: Id,
+    pub typo_ascender: i64,
+    pub typo_descender: i64,
+    pub typo_line_gap: i64,
+    pub hhea_ascender: i64,
+    pub hhea_descender: i64,
+    pub hhea_line_gap: i64,
+    pub win_ascent: i64,
+    pub win_descent: i64,
+    /// Whether the "Use Typo Metrics" flag of the OS/2 table is set.
+    pub use_typo_metrics: bool,
+}
+
+impl Font {
+    /// The vertical metrics of every master, in master order.
+    ///
+    /// Each value comes from the custom parameter of the same name on the
+    /// master, or else on the font, like `typoAscender` or `winDescent`.
+    /// Without one, it falls back like ufo2ft:
+    ///
+    /// - the typo ascender and descender are the master's ascender and
+    ///   descender metrics, or 80% and -20% of the em;
+    /// - the typo line gap makes the line 120% of the em, and isn't negative;
+    /// - the hhea ascender is the typo ascender plus the typo line gap, the
+    ///   hhea descender the typo descender and the hhea line gap zero;
+    /// - the win ascent and descent cover the bounds of the exported glyphs
+    ///   of the master and the hhea ascender and descender.
+    pub fn vertical_metrics(&self) -> Vec<VerticalMetrics> {
+        self.font_master
+            .iter()
+            .map(|master| self.master_vertical_metrics(master))
+            .collect()
+    }
+
+    fn master_vertical_metrics(&self, master: &FontMaster) -> VerticalMetrics {
+        let parameter = |name: &str| {
+            master
+                .custom_parameter_as(name)
+                .or_else(|| self.custom_parameter_as(name))
+        };
+        let upm = f64::from(self.units_per_em);
+        let metrics = master.resolved_metrics(self);
+
+        let typo_ascender = parameter("typoAscender")
+            .or(metrics.ascender())
+            .unwrap_or(upm * 0.8)
+            .round();
+        let typo_descender = parameter("typoDescender")
+            .or(metrics.descender())
+            .unwrap_or(upm * -0.2)
+            .round();
+        let typo_line_gap = parameter("typoLineGap")
+            .unwrap_or_else(|| ((upm * 1.2).round() - typo_ascender + typo_descender).max(0.0))
+            .round();
+        let hhea_ascender = parameter("hheaAscender")
+            .unwrap_or(typo_ascender + typo_line_gap)
+            .round();
+        let hhea_descender = parameter("hheaDescender").unwrap_or(typo_descender).round();
+        let hhea_line_gap = parameter("hheaLineGap").unwrap_or(0.0).round();
+
+        let bounds = self
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.export)
+            .filter_map(|glyph| glyph.master_layer(&master.id)?.bounds(self))
+            .reduce(|a, b| a.union(b));
+        let (y_min, y_max) = bounds.map_or((0.0, 0.0), |b| (b.y0, b.y1));
+        let win_ascent = parameter("winAscent")
+            .unwrap_or_else(|| y_max.ceil().max(hhea_ascender).max(0.0))
+            .round();
+        let win_descent = parameter("winDescent")
+            .unwrap_or_else(|| (-y_min.floor()).max(-hhea_descender).max(0.0))
+            .round();
+
+        let use_typo_metrics = master
+            .custom_parameter_as("Use Typo Metrics")
+            .or_else(|| self.custom_parameter_as("Use Typo Metrics"))
+            .unwrap_or(false);
+
+        VerticalMetrics {
+            master_id: master.id.clone(),
+            typo_ascender: typo_ascender as i64,
+            typo_descender: typo_descender as i64,
+            typo_line_gap: typo_line_gap as i64,
+            hhea_ascender: hhea_ascender as i64,
+            hhea_descender: hhea_descender as i64,
+            hhea_line_gap: hhea_line_gap as i64,
+            win_ascent: win_ascent as i64,
+            win_descent: win_descent as i64,
+            use_typo_metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CustomParameter, NodeType, Path, Plist, Shape};
+
+    #[test]
+    fn fallbacks() {
+        let mut font = Font::load("testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        font.custom_parameters = None;
+        let master = &font.font_master[0];
+        let metrics = master.resolved_metrics(&font);
+        let (ascender, descender) = (metrics.ascender().unwrap(), metrics.descender().unwrap());
+
+        let vertical = &font.vertical_metrics()[0];
+        assert_eq!(vertical.master_id, master.id);
+        assert_eq!(vertical.typo_ascender, ascender as i64);
+        assert_eq!(vertical.typo_descender, descender as i64);
+        assert_eq!(
+            vertical.typo_line_gap,
+            (1200 - ascender as i64 + descender as i64).max(0)
+        );
+        assert_eq!(
+            vertical.hhea_ascender,
+            vertical.typo_ascender + vertical.typo_line_gap
+        );
+        assert_eq!(vertical.hhea_line_gap, 0);
+        assert!(vertical.win_ascent >= vertical.hhea_ascender);
+        assert!(vertical.win_descent >= -vertical.hhea_descender);
+        assert!(!vertical.use_typo_metrics);
+    }
+
+    #[test]
+    fn parameters_and_bounds() {
+        let mut font = Font::default();
+        let mut path = Path::new(true);
+        for (x, y) in [(0.0, -300.0), (100.0, -300.0), (100.0, 1100.0)] {
+            path.add((x, y), NodeType::Line);
+        }
+        font.glyphs[0].layers[0]
+            .shapes
+            .push(Shape::Path(Box::new(path)));
+        font.custom_parameters = Some(vec![
+            CustomParameter {
+                name: "typoLineGap".into(),
+                value: Plist::Integer(100),
+                disabled: false,
+            },
+            CustomParameter {
+                name: "Use Typo Metrics".into(),
+                value: Plist::Integer(1),
+                disabled: false,
+            },
+        ]);
+        font.font_master[0].other_stuff.insert(
+            "customParameters".into(),
+            Plist::Array(vec![Plist::Dictionary(
+                [
+                    ("name".to_string(), Plist::String("hheaDescender".into())),
+                    ("value".to_string(), Plist::String("-250".into())),
+                ]
+                .into(),
+            )]),
+        );
+
+        let vertical = &font.vertical_metrics()[0];
+        assert_eq!(vertical.typo_line_gap, 100);
+        assert_eq!(vertical.hhea_descender, -250);
+        assert_eq!(vertical.win_ascent, 1100);
+        assert_eq!(vertical.win_descent, 300);
+        assert!(vertical.use_typo_metrics);
+    }
+}
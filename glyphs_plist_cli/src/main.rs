@@ -0,0 +1,285 @@
+//! Command line access to the glyphs_plist library, for converting fonts
+//! between file formats, normalizing, comparing, validating and inspecting
+//! them without writing Rust.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use glyphs_plist::{Font, FontDiff, LoadMode, LoadOptions, Severity, ToPlist};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Convert a font to another file format.
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        /// The format to write, by default the one of the output's extension.
+        #[arg(long)]
+        to: Option<Format>,
+    },
+    /// Normalize a font for committing to version control, in place or into
+    /// another file.
+    Normalize {
+        input: PathBuf,
+        output: Option<PathBuf>,
+    },
+    /// Show the differences between two versions of a font.
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// How far coordinates and metrics may move and still count as
+        /// unchanged.
+        #[arg(long, default_value_t = 0.0)]
+        tolerance: f64,
+    },
+    /// Check a font for problems, failing if there are errors.
+    Validate { input: PathBuf },
+    /// Describe a font, or show one of its glyphs as written in the file.
+    Dump {
+        input: PathBuf,
+        #[arg(long)]
+        glyph: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Format {
+    /// A `.glyphs` file in the Glyphs 3 format.
+    Glyphs,
+    /// A `.glyphs` file in the Glyphs 2 format.
+    Glyphs2,
+    /// A `.glyphspackage` directory.
+    Glyphspackage,
+}
+
+impl Format {
+    /// The format a path is in, by its extension.
+    fn of(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("glyphspackage") => Format::Glyphspackage,
+            _ => Format::Glyphs,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode> {
+    match cli.command {
+        Command::Convert { input, output, to } => {
+            let font = load(&input)?;
+            save(&font, &output, to.unwrap_or_else(|| Format::of(&output)))?;
+        }
+        Command::Normalize { input, output } => {
+            let mut font = load(&input)?;
+            font.normalize()
+                .with_context(|| format!("failed to normalize {}", input.display()))?;
+            let output = output.as_ref().unwrap_or(&input);
+            save(&font, output, Format::of(output))?;
+        }
+        Command::Diff {
+            old,
+            new,
+            tolerance,
+        } => {
+            let diff = load(&old)?.diff(&load(&new)?, tolerance);
+            for line in diff_lines(&diff) {
+                println!("{line}");
+            }
+            if !diff.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Validate { input } => {
+            let options = LoadOptions {
+                mode: LoadMode::Lenient,
+                ..Default::default()
+            };
+            let (_, diagnostics) = Font::load_with(&input, &options)?;
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+            if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Dump { input, glyph } => {
+            let font = load(&input)?;
+            match glyph {
+                Some(name) => {
+                    let glyph = font
+                        .get_glyph(&name)
+                        .with_context(|| format!("no glyph {name:?} in {}", input.display()))?;
+                    print!("{}", glyph.to_plist());
+                }
+                None => {
+                    println!("{}", font.summary());
+                    for master in &font.font_master {
+                        println!("master {} {:?}", master.id, master.name);
+                    }
+                }
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn load(path: &Path) -> Result<Font> {
+    Ok(Font::load(path)?)
+}
+
+fn save(font: &Font, path: &Path, format: Format) -> Result<()> {
+    let context = || format!("failed to save {}", path.display());
+    match format {
+        Format::Glyphs => font.save(path).with_context(context)?,
+        Format::Glyphs2 => {
+            let report = font.save_v2(path).with_context(context)?;
+            for dropped in &report.dropped {
+                eprintln!("warning: left out {dropped}");
+            }
+        }
+        Format::Glyphspackage => {
+            font.save_package(path).with_context(context)?;
+        }
+    }
+    Ok(())
+}
+
+/// The differences between two fonts, one per line.
+fn diff_lines(diff: &FontDiff) -> Vec<String> {
+    let mut lines = Vec::new();
+    lines.extend(diff.added_masters.iter().map(|id| format!("+ master {id}")));
+    lines.extend(
+        diff.removed_masters
+            .iter()
+            .map(|id| format!("- master {id}")),
+    );
+    lines.extend(
+        diff.added_glyphs
+            .iter()
+            .map(|name| format!("+ glyph {name}")),
+    );
+    lines.extend(
+        diff.removed_glyphs
+            .iter()
+            .map(|name| format!("- glyph {name}")),
+    );
+    for glyph in &diff.changed_glyphs {
+        lines.push(format!("~ glyph {}", glyph.name));
+        if !glyph.attributes.is_empty() {
+            lines.push(format!("    {}", glyph.attributes.join(", ")));
+        }
+        lines.extend(
+            glyph
+                .added_layers
+                .iter()
+                .map(|id| format!("  + layer {id}")),
+        );
+        lines.extend(
+            glyph
+                .removed_layers
+                .iter()
+                .map(|id| format!("  - layer {id}")),
+        );
+        for layer in &glyph.changed_layers {
+            lines.push(format!("  ~ layer {}", layer.layer_id));
+            if let Some((old, new)) = layer.width {
+                lines.push(format!("    width {old} -> {new}"));
+            }
+            if !layer.shapes.is_empty() {
+                lines.push(format!("    {} shapes changed", layer.shapes.len()));
+            }
+            if !layer.anchors.is_empty() {
+                lines.push(format!("    anchors {}", layer.anchors.join(", ")));
+            }
+            if !layer.attributes.is_empty() {
+                lines.push(format!("    {}", layer.attributes.join(", ")));
+            }
+        }
+    }
+    for change in &diff.metrics {
+        let value = |m: &Option<glyphs_plist::MasterMetric>| match m {
+            Some(m) => format!("{}", m.pos),
+            None => "none".to_string(),
+        };
+        lines.push(format!(
+            "~ metric {} in {}: {} -> {}",
+            change.metric,
+            change.master_id,
+            value(&change.old),
+            value(&change.new)
+        ));
+    }
+    for change in &diff.kerning {
+        let value = |v: Option<f64>| v.map_or("none".to_string(), |v| v.to_string());
+        lines.push(format!(
+            "~ {} {} {} in {}: {} -> {}",
+            change.direction,
+            change.first,
+            change.second,
+            change.master_id,
+            value(change.old),
+            value(change.new)
+        ));
+    }
+    lines.extend(diff.custom_data.iter().map(|path| format!("~ {path}")));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_of_path() {
+        assert_eq!(
+            Format::of(Path::new("a/Font.glyphspackage")),
+            Format::Glyphspackage
+        );
+        assert_eq!(Format::of(Path::new("Font.glyphs")), Format::Glyphs);
+    }
+
+    #[test]
+    fn diff() {
+        let old = Font::load("../glyphs_plist/testdata/GlyphsFileFormatv3.glyphs").unwrap();
+        let mut new = old.clone();
+        new.get_glyph_mut("A").unwrap().layers[0].width += 10.0;
+        let removed = new.glyphs.pop().unwrap();
+
+        let lines = diff_lines(&old.diff(&new, 0.0));
+        assert!(lines.contains(&format!("- glyph {}", removed.glyphname)));
+        assert!(lines.contains(&"~ glyph A".to_string()));
+        assert!(lines.iter().any(|l| l.starts_with("    width ")));
+        assert!(diff_lines(&old.diff(&old, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn cli() {
+        Cli::parse_from([
+            "glyphs-plist",
+            "convert",
+            "a.glyphs",
+            "b.glyphs",
+            "--to",
+            "glyphs2",
+        ]);
+        Cli::parse_from(["glyphs-plist", "dump", "a.glyphs", "--glyph", "A"]);
+    }
+}
@@ -12,6 +12,8 @@ use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr, Path,
 enum PlistAttribute {
     Standard(PlistAttributeInner),
     Rest,
+    /// Not read from or written to the plist, filled with `Default::default()`.
+    Skip,
     None,
 }
 
@@ -47,6 +49,7 @@ impl From<&[Attribute]> for PlistAttribute {
             return PlistAttribute::None;
         };
         let mut rest = false;
+        let mut skip = false;
         let mut inner = PlistAttributeInner::default();
         plist_attr
             .parse_nested_meta(|meta| {
@@ -54,6 +57,10 @@ impl From<&[Attribute]> for PlistAttribute {
                     rest = true;
                     return Ok(());
                 }
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    return Ok(());
+                }
                 if meta.path.is_ident("rename") {
                     let name = meta.value()?.parse::<LitStr>()?;
                     inner.serialised_name = Some(name.value());
@@ -90,6 +97,12 @@ impl From<&[Attribute]> for PlistAttribute {
                 "plist(rest) should not be used with other attributes",
             );
             PlistAttribute::Rest
+        } else if skip {
+            debug_assert!(
+                inner.unused(),
+                "plist(skip) should not be used with other attributes",
+            );
+            PlistAttribute::Skip
         } else if !inner.unused() {
             PlistAttribute::Standard(inner)
         } else {
@@ -154,7 +167,7 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             impl TryFrom<crate::plist::Plist> for #name {
                 type Error = crate::GlyphsFromPlistError;
 
-                #[allow(clippy::unnecessary_fallible_conversions)]
+                #[allow(clippy::unnecessary_fallible_conversions, clippy::useless_conversion)]
                 fn try_from(plist: crate::plist::Plist) -> Result<Self, Self::Error> {
                     let mut hashmap = plist.into_hashmap();
                     Ok(#name {
@@ -168,7 +181,7 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             impl TryFrom<crate::plist::Plist> for #name {
                 type Error = crate::GlyphsFromPlistError;
 
-                #[allow(clippy::unnecessary_fallible_conversions)]
+                #[allow(clippy::unnecessary_fallible_conversions, clippy::useless_conversion)]
                 fn try_from(plist: crate::plist::Plist) -> Result<Self, Self::Error> {
                     let mut hashmap = plist.into_hashmap();
                     let result = #name {
@@ -200,7 +213,7 @@ pub fn derive_to(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let expanded = quote! {
         impl crate::to_plist::ToPlist for #name {
             #[allow(clippy::bool_comparison)]
-            fn to_plist(self) -> crate::plist::Plist {
+            fn to_plist(&self) -> crate::plist::Plist {
                 #ser_rest
                 #ser
                 hashmap.into()
@@ -296,6 +309,9 @@ fn add_deser(data: &Data) -> DeserialisedFields {
                         },
                     })
                 }
+                PlistAttribute::Skip => Some(quote_spanned! {field.span()=>
+                    #field_name: Default::default(),
+                }),
                 PlistAttribute::Rest => None,
             }
         });
@@ -344,7 +360,7 @@ fn add_ser(data: &Data) -> TokenStream {
         .iter()
         .map(|field| (field, PlistAttribute::from(field.attrs.as_slice())))
         .filter_map(|(field, mut options)| {
-            if matches!(options, PlistAttribute::Rest) {
+            if matches!(options, PlistAttribute::Rest | PlistAttribute::Skip) {
                 return None;
             }
             let field_name = field.ident.as_ref().unwrap();
@@ -355,7 +371,7 @@ fn add_ser(data: &Data) -> TokenStream {
             // Simple base case, no conditions to handle
             if options.always_serialise() {
                 Some(quote_spanned! {field.span()=>
-                    if let Some(plist) = crate::to_plist::ToPlistOpt::to_plist(self.#field_name) {
+                    if let Some(plist) = crate::to_plist::ToPlistOpt::to_plist(&self.#field_name) {
                         hashmap.insert(String::from(#plist_name), plist);
                     }
                 })
@@ -373,7 +389,7 @@ fn add_ser(data: &Data) -> TokenStream {
                             .unwrap_or(quote_spanned! {field.span()=> <#path>::default() });
                         Some(quote_spanned! {field.span()=>
                             let #field_name = PartialEq::ne(&self.#field_name, &#default_value)
-                                .then(|| crate::to_plist::ToPlistOpt::to_plist(self.#field_name))
+                                .then(|| crate::to_plist::ToPlistOpt::to_plist(&self.#field_name))
                                 .flatten();
                             if let Some(plist) = #field_name {
                                 hashmap.insert(String::from(#plist_name), plist);
@@ -386,7 +402,7 @@ fn add_ser(data: &Data) -> TokenStream {
                             .unwrap_or(quote_spanned! {field.span()=> <#path>::default() });
                         Some(quote_spanned! {field.span()=>
                             let #field_name = (self.#field_name != #default_value)
-                                .then(|| crate::to_plist::ToPlistOpt::to_plist(self.#field_name))
+                                .then(|| crate::to_plist::ToPlistOpt::to_plist(&self.#field_name))
                                 .flatten();
                             if let Some(plist) = #field_name {
                                 hashmap.insert(String::from(#plist_name), plist);
@@ -420,6 +436,6 @@ fn add_ser_rest(data: &Data) -> TokenStream {
         })
         .map_or(quote! { let mut hashmap = HashMap::new(); }, |field| {
             let name = field.ident.as_ref().unwrap();
-            quote_spanned! { field.span()=> let mut hashmap = self.#name; }
+            quote_spanned! { field.span()=> let mut hashmap = self.#name.clone(); }
         })
 }